@@ -0,0 +1,47 @@
+-- A confidential transfer is five transactions sent in sequence. Each job records the step
+-- it has reached, the signature of every step and the proof context accounts it allocated,
+-- so an interrupted transfer can be resumed (or rolled back) when the API restarts.
+-- Whoever drives a job holds a lease on it, renewed before every step. A job whose lease
+-- ran out (a dropped request, an RPC error, a crash) is picked up by the recovery loop.
+CREATE TABLE IF NOT EXISTS transfer_jobs (
+    id BIGSERIAL PRIMARY KEY,
+    sender_wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    sender pubkey NOT NULL,
+    recipient pubkey NOT NULL,
+    mint pubkey NOT NULL,
+    amount u64 NOT NULL,
+    decimals SMALLINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'created' CHECK (status IN (
+        'created',
+        'proofs_allocated',
+        'range_proof_verified',
+        'proofs_verified',
+        'transferred',
+        'completed',
+        'rolling_back',
+        'rolled_back',
+        'failed'
+    )),
+    equality_proof_account pubkey,
+    ciphertext_validity_proof_account pubkey,
+    range_proof_account pubkey,
+    -- serialized proof data and sender balance snapshot, see `solana::transfer::TransferProofs`
+    proofs BYTEA,
+    allocate_signature TEXT,
+    range_proof_signature TEXT,
+    remaining_proofs_signature TEXT,
+    transfer_signature TEXT,
+    close_signature TEXT,
+    -- transaction sent for the current step whose outcome has not been observed yet
+    pending_signature TEXT,
+    pending_blockhash TEXT,
+    error TEXT,
+    lease_id UUID,
+    leased_until TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_transfer_jobs_sender_wallet_id ON transfer_jobs(sender_wallet_id);
+CREATE INDEX idx_transfer_jobs_unfinished ON transfer_jobs(status)
+    WHERE status NOT IN ('completed', 'rolled_back', 'failed');
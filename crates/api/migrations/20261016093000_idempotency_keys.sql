@@ -0,0 +1,21 @@
+-- Responses to POSTs sent with an `Idempotency-Key` header, scoped per user. A row is
+-- inserted before the handler runs; `response_status` stays NULL until it finishes.
+-- A claimed key is locked while its request runs. A request that never stored a response
+-- (a crash, a dropped connection) leaves the key to be claimed again once the lock is old
+-- enough. Keys are kept for a day.
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    id BIGSERIAL PRIMARY KEY,
+    telegram_user_id BIGINT NOT NULL,
+    idempotency_key TEXT NOT NULL,
+    -- sha256 of the request method, path and body
+    request_hash TEXT NOT NULL,
+    response_status SMALLINT,
+    response_content_type TEXT,
+    response_body BYTEA,
+    locked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (telegram_user_id, idempotency_key)
+);
+
+CREATE INDEX idx_idempotency_keys_created_at ON idempotency_keys(created_at);
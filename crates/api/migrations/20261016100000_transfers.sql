@@ -0,0 +1,20 @@
+-- Ledger of transfers made through the API, used for transaction history. Transfers
+-- executed as a `transfer_jobs` job are kept in sync with the job as it finishes.
+CREATE TABLE IF NOT EXISTS transfers (
+    id BIGSERIAL PRIMARY KEY,
+    transfer_job_id BIGINT UNIQUE REFERENCES transfer_jobs(id),
+    sender pubkey NOT NULL,
+    recipient pubkey NOT NULL,
+    mint pubkey NOT NULL,
+    amount u64 NOT NULL,
+    decimals SMALLINT NOT NULL,
+    memo TEXT,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'completed', 'failed')),
+    -- signatures of every transaction sent for the transfer, in order
+    signatures TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_transfers_sender ON transfers(sender, id DESC);
+CREATE INDEX idx_transfers_recipient ON transfers(recipient, id DESC);
@@ -0,0 +1,31 @@
+-- Confidential transfers, mints and burns on a mint, with amounts decrypted by the
+-- mint's auditor key. `amount` is NULL when the auditor ciphertext could not be decrypted.
+CREATE TABLE IF NOT EXISTS audit_entries (
+    id BIGSERIAL PRIMARY KEY,
+    mint pubkey NOT NULL,
+    signature TEXT NOT NULL,
+    instruction_index SMALLINT NOT NULL,
+    slot BIGINT NOT NULL,
+    block_time TIMESTAMPTZ,
+    kind TEXT NOT NULL CHECK (kind IN ('transfer', 'mint', 'burn')),
+    -- token accounts; source is NULL for mints and destination is NULL for burns
+    source pubkey,
+    destination pubkey,
+    amount u64,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (signature, instruction_index)
+);
+
+CREATE INDEX idx_audit_entries_mint_slot ON audit_entries(mint, slot);
+
+-- Newest signature of each mint already scanned into `audit_entries`. Scans cover a bounded
+-- number of signatures per request. A scan that has not yet reached `last_signature`
+-- records where it started (`pass_newest`) and the oldest signature it got to
+-- (`pass_before`), the next request carries on from there.
+CREATE TABLE IF NOT EXISTS audit_scans (
+    mint pubkey PRIMARY KEY,
+    last_signature TEXT,
+    pass_newest TEXT,
+    pass_before TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- Wallet secrets are sealed by the configured key store. Envelopes are longer
+-- than a base58 keypair, so the column no longer uses the `keypair` domain.
+ALTER TABLE wallets ALTER COLUMN keypair TYPE TEXT;
@@ -0,0 +1,5 @@
+-- Version of the master key that wrapped the wallet secret, NULL for
+-- plaintext secrets. Envelopes written before versioning used version 1.
+ALTER TABLE wallets ADD COLUMN kek_version INTEGER;
+
+UPDATE wallets SET kek_version = 1 WHERE keypair LIKE 'envelope:%';
@@ -0,0 +1,11 @@
+-- Non-custodial wallets are registered by pubkey only; their keypair never
+-- reaches the server, so only custodial wallets store a secret.
+ALTER TABLE wallets
+    ADD COLUMN custody TEXT NOT NULL DEFAULT 'custodial'
+        CHECK (custody IN ('custodial', 'external'));
+
+ALTER TABLE wallets ALTER COLUMN keypair DROP NOT NULL;
+
+ALTER TABLE wallets
+    ADD CONSTRAINT wallets_custody_secret
+        CHECK ((custody = 'custodial') = (keypair IS NOT NULL));
@@ -0,0 +1,18 @@
+-- Pull payments: a user asks for an amount of a mint to be paid into one of their wallets.
+-- `payer` is set while a payment is in flight so a request can't be paid twice.
+CREATE TABLE IF NOT EXISTS payment_requests (
+    id BIGSERIAL PRIMARY KEY,
+    wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    recipient pubkey NOT NULL,
+    mint pubkey NOT NULL,
+    amount u64 NOT NULL,
+    memo TEXT,
+    status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'paid', 'expired', 'cancelled')),
+    expires_at TIMESTAMPTZ,
+    payer pubkey,
+    transfer_id BIGINT REFERENCES transfers(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_payment_requests_wallet ON payment_requests(wallet_id, id DESC);
@@ -0,0 +1,33 @@
+-- "Send by link": funds are moved into a per-link escrow wallet and whoever presents the
+-- claim secret gets them transferred to their own wallet. Only a SHA-256 hash of the
+-- secret is stored. Links expire (`CLAIM_LINK_TTL_DAYS`): an open link past its expiry is
+-- `expired` and its funds are sent back to the sender from the escrow wallet, `refunded`
+-- once that lands.
+CREATE TABLE IF NOT EXISTS claim_links (
+    id BIGSERIAL PRIMARY KEY,
+    sender_wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    escrow_wallet_id BIGINT NOT NULL UNIQUE REFERENCES wallets(id),
+    mint pubkey NOT NULL,
+    amount u64 NOT NULL,
+    memo TEXT,
+    secret_hash TEXT NOT NULL UNIQUE,
+    status TEXT NOT NULL DEFAULT 'funding' CHECK (status IN (
+        'funding',
+        'open',
+        'claiming',
+        'claimed',
+        'failed',
+        'expired',
+        'refunded'
+    )),
+    funding_transfer_id BIGINT REFERENCES transfers(id),
+    claim_transfer_id BIGINT REFERENCES transfers(id),
+    claimed_by_wallet_id BIGINT REFERENCES wallets(id),
+    refund_transfer_id BIGINT REFERENCES transfers(id),
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_claim_links_sender_wallet ON claim_links(sender_wallet_id, id DESC);
+CREATE INDEX idx_claim_links_status_expires_at ON claim_links(status, expires_at);
@@ -0,0 +1,19 @@
+-- Reserved wallets (created for Telegram usernames that never logged in) expire some time
+-- after their last incoming transfer. Once expired, a background sweeper refunds every
+-- transfer they received to its sender.
+ALTER TABLE wallets ADD COLUMN reserved_until TIMESTAMPTZ;
+ALTER TABLE wallets ADD COLUMN reservation_expired_at TIMESTAMPTZ;
+
+-- Incoming transfers of reserved wallets, so each one can be refunded to its sender
+CREATE TABLE IF NOT EXISTS reserved_wallet_credits (
+    id BIGSERIAL PRIMARY KEY,
+    wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    transfer_id BIGINT NOT NULL UNIQUE REFERENCES transfers(id),
+    refund_transfer_id BIGINT REFERENCES transfers(id),
+    -- refunds started so far, the sweeper gives up on a credit after a few failed ones
+    refund_attempts INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_reserved_wallet_credits_wallet ON reserved_wallet_credits(wallet_id);
@@ -0,0 +1,6 @@
+-- Memo written on chain with the transfer instruction, already encrypted if the
+-- sender asked for it. Stored on the job so a resumed job sends the same memo.
+ALTER TABLE transfer_jobs ADD COLUMN memo TEXT;
+
+-- Whether the on-chain copy of `transfers.memo` is encrypted
+ALTER TABLE transfers ADD COLUMN memo_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,24 @@
+-- Batch payouts run in the background: each entry of a batch is recorded with its transfer
+-- (or why it couldn't be made) and the client polls the batch for the results.
+CREATE TABLE IF NOT EXISTS transfer_batches (
+    id BIGSERIAL PRIMARY KEY,
+    sender_wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    mint pubkey NOT NULL,
+    total_amount u64 NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS transfer_batch_entries (
+    batch_id BIGINT NOT NULL REFERENCES transfer_batches(id),
+    position SMALLINT NOT NULL,
+    recipient pubkey,
+    telegram_username TEXT,
+    amount u64 NOT NULL,
+    transfer_id BIGINT REFERENCES transfers(id),
+    error TEXT,
+    PRIMARY KEY (batch_id, position)
+);
+
+-- The sender's balance was topped up for the whole batch before its jobs were recorded, so
+-- they don't top it up again one by one.
+ALTER TABLE transfer_jobs ADD COLUMN balance_funded BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,40 @@
+-- Recurring transfers from a custodial wallet, on a fixed interval or a cron
+-- expression (UTC). The worker runs entries whose `next_run_at` has passed.
+CREATE TABLE IF NOT EXISTS scheduled_transfers (
+    id BIGSERIAL PRIMARY KEY,
+    wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    sender pubkey NOT NULL,
+    recipient pubkey NOT NULL,
+    mint pubkey NOT NULL,
+    amount u64 NOT NULL,
+    decimals SMALLINT NOT NULL,
+    memo TEXT,
+    encrypt_memo BOOLEAN NOT NULL DEFAULT FALSE,
+    interval_secs BIGINT,
+    cron TEXT,
+    status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'paused', 'cancelled')),
+    next_run_at TIMESTAMPTZ NOT NULL,
+    last_run_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((interval_secs IS NULL) <> (cron IS NULL))
+);
+
+CREATE INDEX idx_scheduled_transfers_wallet ON scheduled_transfers(wallet_id, id DESC);
+CREATE INDEX idx_scheduled_transfers_due ON scheduled_transfers(next_run_at) WHERE status = 'active';
+
+-- Outcome of every run of a schedule. A run that sent a transfer is pending
+-- until the transfer's job ends, and then takes its outcome.
+CREATE TABLE IF NOT EXISTS scheduled_transfer_runs (
+    id BIGSERIAL PRIMARY KEY,
+    scheduled_transfer_id BIGINT NOT NULL REFERENCES scheduled_transfers(id),
+    scheduled_for TIMESTAMPTZ NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'completed', 'failed', 'skipped')),
+    transfer_id BIGINT REFERENCES transfers(id),
+    transfer_job_id BIGINT REFERENCES transfer_jobs(id),
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_scheduled_transfer_runs_schedule ON scheduled_transfer_runs(scheduled_transfer_id, id DESC);
+CREATE INDEX idx_scheduled_transfer_runs_transfer ON scheduled_transfer_runs(transfer_id) WHERE status = 'pending';
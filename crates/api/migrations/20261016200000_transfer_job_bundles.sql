@@ -0,0 +1,3 @@
+-- Signatures of a bundle sent for every remaining step of a job at once, in step order.
+-- `pending_signature` holds the last one; the bundle lands as a whole or not at all.
+ALTER TABLE transfer_jobs ADD COLUMN pending_bundle TEXT[];
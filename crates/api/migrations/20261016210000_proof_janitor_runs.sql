@@ -0,0 +1,29 @@
+-- Every run of the janitor that closes proof context state accounts left behind by
+-- failed transfers, withdraws and mints, with the rent it got back.
+CREATE TABLE IF NOT EXISTS proof_janitor_runs (
+    id BIGSERIAL PRIMARY KEY,
+    accounts_found INTEGER NOT NULL DEFAULT 0,
+    accounts_closed INTEGER NOT NULL DEFAULT 0,
+    -- withdraw proof accounts left unverified, which the run verified so they can be closed
+    accounts_unverified INTEGER NOT NULL DEFAULT 0,
+    lamports_reclaimed u64 NOT NULL DEFAULT 0,
+    error TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+-- Proof data of custodial withdraws that have not landed yet. A withdraw that fails after
+-- allocating its proof context accounts leaves them unverified, and an unverified account
+-- has no authority the janitor could find it by. The janitor verifies those accounts from
+-- the stored proofs so they can be closed.
+CREATE TABLE IF NOT EXISTS withdraw_proofs (
+    id BIGSERIAL PRIMARY KEY,
+    wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    equality_proof_account pubkey NOT NULL,
+    range_proof_account pubkey NOT NULL,
+    -- serialized proof data, see `solana::withdraw::WithdrawProofs`
+    proofs BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_withdraw_proofs_created_at ON withdraw_proofs(created_at);
@@ -0,0 +1,9 @@
+-- Transaction fees the fee payer sponsored for each user, reset once a day.
+CREATE TABLE IF NOT EXISTS fee_budgets (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id),
+    -- Overrides FEE_BUDGET_LAMPORTS for this user when set
+    budget_lamports u64,
+    spent_lamports u64 NOT NULL DEFAULT 0,
+    period_start TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
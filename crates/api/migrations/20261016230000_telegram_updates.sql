@@ -0,0 +1,8 @@
+-- Bot updates already received through the webhook. Telegram redelivers an
+-- update until it sees a 200, and a redelivered /send must not pay twice.
+CREATE TABLE IF NOT EXISTS telegram_updates (
+    update_id BIGINT PRIMARY KEY,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_telegram_updates_received_at ON telegram_updates(received_at);
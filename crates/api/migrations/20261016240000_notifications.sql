@@ -0,0 +1,32 @@
+-- Outbox of Telegram messages to users, delivered by the notification worker.
+-- Messages to a reserved user wait until the user signs up and has a
+-- telegram_user_id to send to. Messages about a transfer into a reserved wallet are
+-- cancelled when the wallet expires and the transfer is refunded.
+CREATE TABLE IF NOT EXISTS notifications (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    kind TEXT NOT NULL CHECK (kind IN ('incoming_payment')),
+    text TEXT NOT NULL,
+    transfer_id BIGINT REFERENCES transfers(id),
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN (
+        'pending',
+        'sent',
+        'failed',
+        'skipped',
+        'cancelled'
+    )),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_notifications_pending ON notifications(next_attempt_at) WHERE status = 'pending';
+
+-- Which notifications a user wants, everything is on without a row.
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id BIGINT PRIMARY KEY REFERENCES users(id),
+    incoming_payments BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,25 @@
+-- Payments offered with the bot's inline mode: the sender posts an offer in any chat and
+-- whoever taps its claim button first gets the amount transferred from the sender's wallet.
+-- Nothing moves until then, so an unclaimed offer simply expires.
+CREATE TABLE IF NOT EXISTS inline_payments (
+    id BIGSERIAL PRIMARY KEY,
+    -- inline result id, also the claim button's callback data
+    token TEXT NOT NULL UNIQUE,
+    sender_wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    sender_telegram_user_id BIGINT NOT NULL,
+    -- how the offer names the sender, kept to restore it if a claim fails
+    sender_name TEXT NOT NULL,
+    mint pubkey NOT NULL,
+    amount u64 NOT NULL,
+    inline_message_id TEXT,
+    status TEXT NOT NULL DEFAULT 'open' CHECK (status IN (
+        'open',
+        'claiming',
+        'claimed'
+    )),
+    claimed_by_telegram_user_id BIGINT,
+    transfer_id BIGINT REFERENCES transfers(id),
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
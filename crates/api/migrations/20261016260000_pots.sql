@@ -0,0 +1,39 @@
+-- Group pots: a user collects money from the members of a Telegram chat towards a target.
+-- Contributions are confidential transfers into a per-pot escrow wallet. The creator closes
+-- the pot to withdraw what was collected, or every contribution is refunded once it expires.
+CREATE TABLE IF NOT EXISTS pots (
+    id BIGSERIAL PRIMARY KEY,
+    chat_id BIGINT NOT NULL,
+    creator_wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    escrow_wallet_id BIGINT NOT NULL UNIQUE REFERENCES wallets(id),
+    title TEXT NOT NULL,
+    mint pubkey NOT NULL,
+    target_amount u64 NOT NULL,
+    status TEXT NOT NULL DEFAULT 'open' CHECK (status IN (
+        'open',
+        'closing',
+        'closed',
+        'expired'
+    )),
+    withdraw_transfer_id BIGINT REFERENCES transfers(id),
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_pots_chat ON pots(chat_id, id DESC);
+CREATE INDEX idx_pots_open_expiry ON pots(expires_at) WHERE status = 'open';
+
+-- A contribution is recorded together with the transfer paying it, so a pot can't be closed
+-- while one is still in flight. It only counts once the transfer has completed.
+CREATE TABLE IF NOT EXISTS pot_contributions (
+    id BIGSERIAL PRIMARY KEY,
+    pot_id BIGINT NOT NULL REFERENCES pots(id),
+    wallet_id BIGINT NOT NULL REFERENCES wallets(id),
+    transfer_id BIGINT NOT NULL UNIQUE REFERENCES transfers(id),
+    refund_transfer_id BIGINT REFERENCES transfers(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_pot_contributions_pot ON pot_contributions(pot_id);
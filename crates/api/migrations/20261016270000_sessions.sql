@@ -0,0 +1,26 @@
+-- Login sessions. Access tokens are short-lived JWTs carrying the session id as `jti`, and
+-- are only accepted while their session is active. A session is kept alive with a refresh
+-- token, stored as a SHA-256 hash and replaced on every refresh. Sessions are deleted some
+-- time after they expired or were revoked.
+CREATE TABLE IF NOT EXISTS sessions (
+    id UUID PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    refresh_token_hash TEXT NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_sessions_user ON sessions(user_id) WHERE revoked_at IS NULL;
+CREATE INDEX idx_sessions_ended ON sessions(COALESCE(revoked_at, expires_at));
+
+-- Every refresh token a session has replaced, so presenting any of them again is caught as a
+-- leak and revokes the session. Rows go with their session.
+CREATE TABLE IF NOT EXISTS used_refresh_tokens (
+    refresh_token_hash TEXT PRIMARY KEY,
+    session_id UUID NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    used_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_used_refresh_tokens_session ON used_refresh_tokens(session_id);
@@ -1,16 +1,39 @@
-use crate::AppState;
+//! Authentication of API requests.
+//!
+//! Logging in starts a session (see [`start_session`]) and hands out a
+//! short-lived HS256 access token plus a refresh token. Access tokens carry
+//! the session id as their `jti` claim, and [`AuthUser`] only accepts them
+//! while the session is active. The refresh token is exchanged for a new pair
+//! with [`refresh_session`]; it is stored hashed and replaced every time, and
+//! using any replaced one again revokes the session, since it must have
+//! leaked. Sessions that ended a while ago are deleted when a new one starts.
+
+use crate::handlers::AppError;
+use crate::{AppState, db};
 use axum::{
     extract::FromRequestParts,
     http::{StatusCode, header::AUTHORIZATION, request::Parts},
 };
-use jsonwebtoken::{DecodingKey, Validation, decode};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use tracing::{error, info, warn};
+use uuid::Uuid;
 
 const DEV_MOCK_TOKEN: &str = "dev_mock_token_for_local_testing";
 const DEV_MOCK_USER_ID: i64 = 123456789;
 
+const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+
+/// Sessions end this long after their last refresh.
+const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+/// Ended sessions are deleted this long after they expired or were revoked.
+const ENDED_SESSION_RETENTION_DAYS: i64 = 7;
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct AuthClaims {
     pub sub: String,
@@ -18,6 +41,9 @@ pub struct AuthClaims {
     pub username: Option<String>,
     pub exp: usize,
     pub iat: usize,
+    /// Id of the session the token belongs to.
+    #[serde(default)]
+    pub jti: Option<Uuid>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +51,8 @@ pub struct AuthUser {
     pub telegram_user_id: i64,
     #[allow(dead_code)]
     pub username: Option<String>,
+    /// `None` for the dev mode and bypass tokens.
+    pub session_id: Option<Uuid>,
 }
 
 impl From<AuthClaims> for AuthUser {
@@ -32,10 +60,134 @@ impl From<AuthClaims> for AuthUser {
         Self {
             telegram_user_id: claims.telegram_user_id,
             username: claims.username,
+            session_id: claims.jti,
         }
     }
 }
 
+/// Tokens handed out when a session starts or is refreshed.
+#[derive(Debug)]
+pub struct SessionTokens {
+    pub access_token: String,
+    pub access_token_expires_at: DateTime<Utc>,
+    pub refresh_token: String,
+    pub refresh_token_expires_at: DateTime<Utc>,
+}
+
+/// Start a session for a user who just logged in.
+pub async fn start_session(
+    state: &AppState,
+    telegram_user_id: i64,
+    username: Option<&str>,
+) -> Result<SessionTokens, AppError> {
+    db::delete_ended_sessions(
+        &state.db,
+        Duration::days(ENDED_SESSION_RETENTION_DAYS).num_seconds(),
+    )
+    .await?;
+
+    let session_id = Uuid::new_v4();
+    let refresh_token = generate_refresh_token();
+    let refresh_token_expires_at = db::create_session(
+        &state.db,
+        session_id,
+        telegram_user_id,
+        &hash_refresh_token(&refresh_token),
+        Duration::days(REFRESH_TOKEN_TTL_DAYS).num_seconds(),
+    )
+    .await?;
+
+    let (access_token, access_token_expires_at) = issue_access_token(
+        session_id,
+        telegram_user_id,
+        username,
+        state.config.jwt_secret.expose(),
+    )?;
+    Ok(SessionTokens {
+        access_token,
+        access_token_expires_at,
+        refresh_token,
+        refresh_token_expires_at,
+    })
+}
+
+/// Exchange a refresh token for a new access token and refresh token.
+pub async fn refresh_session(
+    state: &AppState,
+    refresh_token: &str,
+) -> Result<SessionTokens, AppError> {
+    let refresh_token_hash = hash_refresh_token(refresh_token);
+    let new_refresh_token = generate_refresh_token();
+    let Some(session) = db::rotate_session(
+        &state.db,
+        &refresh_token_hash,
+        &hash_refresh_token(&new_refresh_token),
+        Duration::days(REFRESH_TOKEN_TTL_DAYS).num_seconds(),
+    )
+    .await?
+    else {
+        if db::revoke_session_by_used_refresh_token(&state.db, &refresh_token_hash).await? {
+            warn!("replaced refresh token used again, session revoked");
+        }
+        return Err(AppError::new(
+            anyhow::anyhow!("Invalid or expired refresh token"),
+            StatusCode::UNAUTHORIZED,
+        ));
+    };
+
+    let (access_token, access_token_expires_at) = issue_access_token(
+        session.id,
+        session.telegram_user_id,
+        session.username.as_deref(),
+        state.config.jwt_secret.expose(),
+    )?;
+    Ok(SessionTokens {
+        access_token,
+        access_token_expires_at,
+        refresh_token: new_refresh_token,
+        refresh_token_expires_at: session.expires_at,
+    })
+}
+
+fn issue_access_token(
+    session_id: Uuid,
+    telegram_user_id: i64,
+    username: Option<&str>,
+    jwt_secret: &str,
+) -> Result<(String, DateTime<Utc>), AppError> {
+    let now = Utc::now();
+    let expires_at = now + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES);
+
+    let claims = AuthClaims {
+        sub: telegram_user_id.to_string(),
+        telegram_user_id,
+        username: username.map(str::to_string),
+        exp: expires_at.timestamp() as usize,
+        iat: now.timestamp() as usize,
+        jti: Some(session_id),
+    };
+
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(jwt_secret.as_bytes()),
+    )
+    .map_err(|e| AppError::internal_server_error(anyhow::anyhow!("JWT encoding failed: {}", e)))?;
+
+    Ok((token, expires_at))
+}
+
+fn generate_refresh_token() -> String {
+    let mut token = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut token);
+    bs58::encode(token).into_string()
+}
+
+/// Hex encoded SHA-256 of a refresh token, the only form it is stored in.
+fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
 pub struct AuthError {
     pub message: String,
     pub status: StatusCode,
@@ -48,6 +200,13 @@ impl AuthError {
             status: StatusCode::UNAUTHORIZED,
         }
     }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            status: StatusCode::FORBIDDEN,
+        }
+    }
 }
 
 impl axum::response::IntoResponse for AuthError {
@@ -80,22 +239,24 @@ impl FromRequestParts<Arc<AppState>> for AuthUser {
         })?;
 
         // Dev mode bypass: accept mock token for local development
-        if state.dev_mode && token == DEV_MOCK_TOKEN {
+        if state.config.dev_mode && token == DEV_MOCK_TOKEN {
             info!("dev mode: accepting mock token");
             return Ok(AuthUser {
                 telegram_user_id: DEV_MOCK_USER_ID,
                 username: Some("dev_user".to_string()),
+                session_id: None,
             });
         }
 
-        let bypass_token = std::env::var("BYPASS_AUTH_TOKEN").unwrap_or_default();
-        if !bypass_token.is_empty() && token == bypass_token {
+        if let Some(bypass_token) = &state.config.bypass_auth_token
+            && token == bypass_token.expose()
+        {
             return Ok(AuthUser::from(AuthClaims::default()));
         }
 
         let token_data = decode::<AuthClaims>(
             token,
-            &DecodingKey::from_secret(state.jwt_secret.as_bytes()),
+            &DecodingKey::from_secret(state.config.jwt_secret.expose().as_bytes()),
             &Validation::default(),
         )
         .map_err(|e| {
@@ -103,6 +264,92 @@ impl FromRequestParts<Arc<AppState>> for AuthUser {
             AuthError::unauthorized(format!("Invalid token: {}", e))
         })?;
 
+        let Some(session_id) = token_data.claims.jti else {
+            warn!("token without a session for path: {}", path);
+            return Err(AuthError::unauthorized(
+                "Token has no session, log in again",
+            ));
+        };
+        match db::is_session_active(&state.db, session_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return Err(AuthError::unauthorized(
+                    "Session has been revoked or has expired",
+                ));
+            }
+            Err(e) => {
+                error!("failed to check session {}: {:?}", session_id, e);
+                return Err(AuthError {
+                    message: "Failed to check session".to_string(),
+                    status: StatusCode::INTERNAL_SERVER_ERROR,
+                });
+            }
+        }
+
         Ok(AuthUser::from(token_data.claims))
     }
 }
+
+/// An authenticated user listed in `ADMIN_TELEGRAM_USER_IDS`.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub AuthUser);
+
+impl FromRequestParts<Arc<AppState>> for AdminUser {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let auth_user = AuthUser::from_request_parts(parts, state).await?;
+
+        if !state
+            .config
+            .admin_telegram_user_ids
+            .contains(&auth_user.telegram_user_id)
+        {
+            warn!(
+                "non-admin user {} attempted to access {}",
+                auth_user.telegram_user_id,
+                parts.uri.path()
+            );
+            return Err(AuthError::forbidden("Admin access required"));
+        }
+
+        Ok(AdminUser(auth_user))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_token_carries_session() {
+        let secret = "test-secret-that-is-at-least-32-bytes";
+        let session_id = Uuid::new_v4();
+        let (token, expires_at) =
+            issue_access_token(session_id, 42, Some("alice"), secret).unwrap();
+        assert!(expires_at <= Utc::now() + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES));
+
+        let claims = decode::<AuthClaims>(
+            &token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &Validation::default(),
+        )
+        .unwrap()
+        .claims;
+        assert_eq!(claims.jti, Some(session_id));
+        assert_eq!(claims.telegram_user_id, 42);
+        assert_eq!(claims.username.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn test_refresh_tokens() {
+        let token = generate_refresh_token();
+        assert_ne!(token, generate_refresh_token());
+        assert_eq!(hash_refresh_token(&token), hash_refresh_token(&token));
+        assert_ne!(hash_refresh_token(&token), token);
+        assert_eq!(hash_refresh_token(&token).len(), 64);
+    }
+}
@@ -0,0 +1,291 @@
+//! Cluster profiles.
+//!
+//! `CLUSTER` picks the defaults for the cluster the API runs against:
+//!
+//! | cluster              | airdrops | fee sponsorship | commitment | confirmation timeout |
+//! |----------------------|----------|-----------------|------------|----------------------|
+//! | `localnet`/`surfpool`| on       | on              | confirmed  | 5s                   |
+//! | `devnet`             | off      | on              | confirmed  | 30s                  |
+//! | `mainnet`            | off      | on              | finalized  | 60s                  |
+//! | `custom`             | off      | on              | confirmed  | 30s                  |
+//!
+//! Devnet has a faucet too, but it is rate limited, so airdrops are opt-in
+//! there. Each setting can be overridden (`AIRDROPS`, `FEE_SPONSORSHIP`,
+//! `COMMITMENT`, `CONFIRMATION_TIMEOUT_SECS`, `EXPLORER_TX_URL`,
+//! `EXPLORER_ADDRESS_URL`, `CONVERT_ROUTE`), but [`Cluster::validate`] refuses
+//! to start on mainnet with anything meant for local development.
+
+use anyhow::Result;
+use serde::Serialize;
+use solana_client::rpc_config::{CommitmentConfig, CommitmentLevel};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterKind {
+    Localnet,
+    Devnet,
+    Mainnet,
+    Custom,
+}
+
+impl ClusterKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ClusterKind::Localnet => "localnet",
+            ClusterKind::Devnet => "devnet",
+            ClusterKind::Mainnet => "mainnet",
+            ClusterKind::Custom => "custom",
+        }
+    }
+}
+
+impl std::str::FromStr for ClusterKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "localnet" | "surfpool" => Ok(ClusterKind::Localnet),
+            "devnet" => Ok(ClusterKind::Devnet),
+            "mainnet" | "mainnet-beta" => Ok(ClusterKind::Mainnet),
+            "custom" => Ok(ClusterKind::Custom),
+            other => anyhow::bail!("Unknown CLUSTER: {}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    pub kind: ClusterKind,
+    /// Fund the authority, the fee payer and wallets that pay their own fees
+    /// with airdrops.
+    pub airdrops: bool,
+    /// Pay the fees of custodial wallets from the fee payer, see
+    /// [`crate::fees`]. Without it every wallet pays its own fees.
+    pub fee_sponsorship: bool,
+    /// Explorer link for a transaction, `{signature}` is replaced.
+    pub explorer_tx_url: String,
+    /// Explorer link for an account, `{address}` is replaced.
+    pub explorer_address_url: String,
+    pub commitment: CommitmentConfig,
+    /// How long to wait for a sent transaction to show up before giving up.
+    pub confirmation_timeout: Duration,
+    /// Serve the keypair conversion utility at `/api/convert`.
+    pub convert_route: bool,
+}
+
+impl Cluster {
+    /// The profile for `kind`, before any overrides.
+    pub fn profile(kind: ClusterKind, rpc_url: &str) -> Self {
+        let explorer_query = match kind {
+            ClusterKind::Localnet | ClusterKind::Custom => {
+                format!("?cluster=custom&customUrl={}", rpc_url)
+            }
+            ClusterKind::Devnet => "?cluster=devnet".to_string(),
+            ClusterKind::Mainnet => String::new(),
+        };
+        let (commitment, confirmation_timeout_secs) = match kind {
+            ClusterKind::Localnet => (CommitmentConfig::confirmed(), 5),
+            ClusterKind::Devnet | ClusterKind::Custom => (CommitmentConfig::confirmed(), 30),
+            ClusterKind::Mainnet => (CommitmentConfig::finalized(), 60),
+        };
+
+        Self {
+            kind,
+            airdrops: kind == ClusterKind::Localnet,
+            fee_sponsorship: true,
+            explorer_tx_url: format!(
+                "https://explorer.solana.com/tx/{{signature}}{explorer_query}"
+            ),
+            explorer_address_url: format!(
+                "https://explorer.solana.com/address/{{address}}{explorer_query}"
+            ),
+            commitment,
+            confirmation_timeout: Duration::from_secs(confirmation_timeout_secs),
+            convert_route: kind != ClusterKind::Mainnet,
+        }
+    }
+
+    /// The profile picked by `CLUSTER`, with the overrides applied. `var`
+    /// looks up a setting, see [`crate::config`].
+    pub fn from_vars(rpc_url: &str, mut var: impl FnMut(&str) -> Option<String>) -> Result<Self> {
+        let kind = match var("CLUSTER") {
+            Some(kind) => kind.parse()?,
+            None => ClusterKind::Localnet,
+        };
+        let mut cluster = Self::profile(kind, rpc_url);
+
+        let flag = |name: &str, value: Option<String>| -> Result<Option<bool>> {
+            value
+                .map(|value| match value.as_str() {
+                    "true" => Ok(true),
+                    "false" => Ok(false),
+                    other => anyhow::bail!("Invalid {} {}: expected true or false", name, other),
+                })
+                .transpose()
+        };
+        if let Some(airdrops) = flag("AIRDROPS", var("AIRDROPS"))? {
+            cluster.airdrops = airdrops;
+        }
+        if let Some(fee_sponsorship) = flag("FEE_SPONSORSHIP", var("FEE_SPONSORSHIP"))? {
+            cluster.fee_sponsorship = fee_sponsorship;
+        }
+        if let Some(convert_route) = flag("CONVERT_ROUTE", var("CONVERT_ROUTE"))? {
+            cluster.convert_route = convert_route;
+        }
+        if let Some(url) = var("EXPLORER_TX_URL") {
+            cluster.explorer_tx_url = url;
+        }
+        if let Some(url) = var("EXPLORER_ADDRESS_URL") {
+            cluster.explorer_address_url = url;
+        }
+        if let Some(commitment) = var("COMMITMENT") {
+            cluster.commitment = CommitmentConfig {
+                commitment: match commitment.as_str() {
+                    "processed" => CommitmentLevel::Processed,
+                    "confirmed" => CommitmentLevel::Confirmed,
+                    "finalized" => CommitmentLevel::Finalized,
+                    other => anyhow::bail!("Invalid COMMITMENT: {}", other),
+                },
+            };
+        }
+        if let Some(secs) = var("CONFIRMATION_TIMEOUT_SECS") {
+            let secs = secs.parse::<u64>().map_err(|e| {
+                anyhow::anyhow!("Invalid CONFIRMATION_TIMEOUT_SECS {}: {}", secs, e)
+            })?;
+            cluster.confirmation_timeout = Duration::from_secs(secs);
+        }
+
+        Ok(cluster)
+    }
+
+    /// Refuse settings that are only safe for local development on mainnet,
+    /// reporting all of them at once.
+    pub fn validate(&self, dev_mode: bool, bypass_auth_token: bool) -> Result<()> {
+        if self.kind != ClusterKind::Mainnet {
+            return Ok(());
+        }
+
+        let mut problems = vec![];
+        if dev_mode {
+            problems.push("DEV_MODE=true accepts a mock auth token");
+        }
+        if bypass_auth_token {
+            problems.push("BYPASS_AUTH_TOKEN lets anyone authenticate");
+        }
+        if self.convert_route {
+            problems.push("CONVERT_ROUTE=true serves /api/convert, which logs keypairs");
+        }
+        if self.airdrops {
+            problems.push("AIRDROPS=true, mainnet has no faucet");
+        }
+        if self.commitment.commitment == CommitmentLevel::Processed {
+            problems.push("COMMITMENT=processed can report transactions that are rolled back");
+        }
+
+        if !problems.is_empty() {
+            anyhow::bail!("Refusing to start on mainnet: {}", problems.join("; "));
+        }
+        Ok(())
+    }
+
+    pub fn explorer_tx_url(&self, signature: &str) -> String {
+        self.explorer_tx_url.replace("{signature}", signature)
+    }
+
+    pub fn explorer_address_url(&self, address: &str) -> String {
+        self.explorer_address_url.replace("{address}", address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<Cluster> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Cluster::from_vars("http://localhost:8899", |name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_profiles() {
+        let localnet = from_vars(&[]).unwrap();
+        assert_eq!(localnet.kind, ClusterKind::Localnet);
+        assert!(localnet.airdrops);
+        assert!(localnet.convert_route);
+        assert_eq!(
+            localnet.explorer_tx_url("abc"),
+            "https://explorer.solana.com/tx/abc?cluster=custom&customUrl=http://localhost:8899"
+        );
+
+        let surfpool = from_vars(&[("CLUSTER", "surfpool")]).unwrap();
+        assert_eq!(surfpool.kind, ClusterKind::Localnet);
+
+        let mainnet = from_vars(&[("CLUSTER", "mainnet")]).unwrap();
+        assert!(!mainnet.airdrops);
+        assert!(!mainnet.convert_route);
+        assert!(mainnet.fee_sponsorship);
+        assert_eq!(mainnet.commitment, CommitmentConfig::finalized());
+        assert_eq!(
+            mainnet.explorer_address_url("xyz"),
+            "https://explorer.solana.com/address/xyz"
+        );
+
+        assert!(from_vars(&[("CLUSTER", "testnet")]).is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let cluster = from_vars(&[
+            ("CLUSTER", "devnet"),
+            ("AIRDROPS", "true"),
+            ("FEE_SPONSORSHIP", "false"),
+            ("COMMITMENT", "processed"),
+            ("CONFIRMATION_TIMEOUT_SECS", "12"),
+            (
+                "EXPLORER_TX_URL",
+                "https://solscan.io/tx/{signature}?cluster=devnet",
+            ),
+        ])
+        .unwrap();
+        assert!(cluster.airdrops);
+        assert!(!cluster.fee_sponsorship);
+        assert_eq!(cluster.commitment, CommitmentConfig::processed());
+        assert_eq!(cluster.confirmation_timeout, Duration::from_secs(12));
+        assert_eq!(
+            cluster.explorer_tx_url("abc"),
+            "https://solscan.io/tx/abc?cluster=devnet"
+        );
+
+        assert!(from_vars(&[("AIRDROPS", "yes")]).is_err());
+        assert!(from_vars(&[("COMMITMENT", "max")]).is_err());
+    }
+
+    #[test]
+    fn test_validate_refuses_dev_settings_on_mainnet() {
+        let mainnet = from_vars(&[("CLUSTER", "mainnet")]).unwrap();
+        assert!(mainnet.validate(false, false).is_ok());
+        assert!(mainnet.validate(true, false).is_err());
+        assert!(mainnet.validate(false, true).is_err());
+
+        let error = from_vars(&[
+            ("CLUSTER", "mainnet"),
+            ("CONVERT_ROUTE", "true"),
+            ("AIRDROPS", "true"),
+        ])
+        .unwrap()
+        .validate(true, false)
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("DEV_MODE"));
+        assert!(error.contains("/api/convert"));
+        assert!(error.contains("AIRDROPS"));
+
+        let localnet = from_vars(&[]).unwrap();
+        assert!(localnet.validate(true, true).is_ok());
+    }
+}
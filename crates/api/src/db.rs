@@ -1,19 +1,31 @@
-use crate::models::Wallet;
+use crate::keystore::SealedSecret;
+use crate::models::{
+    AbandonedReservedWalletRefund, ClaimLink, ClaimLinkRefund, InlinePayment, Notification,
+    NotificationKind, NotificationPreferences, NotificationStatus, PaymentRequest,
+    PendingWithdrawProofs, Pot, PotContributor, PotRefund, ProofJanitorRun, ReservedWalletRefund,
+    ScheduledTransfer, ScheduledTransferRun, ScheduledTransferRunStatus, Session, Transfer,
+    TransferBatch, TransferBatchEntry, TransferJob, TransferJobStatus, TransferStatus, Wallet,
+    WalletCustody,
+};
+use crate::solana::audit::{AuditPosition, AuditRecord, AuditScanCursor};
+use crate::solana::transfer::ProofAccounts;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
-use solana_keypair::Keypair;
+use solana_hash::Hash;
+use solana_keypair::Signature;
 use solana_pubkey::Pubkey;
-use solana_signer::Signer;
 use sqlx::{PgConnection, PgPool, prelude::FromRow};
-use std::{str::FromStr, sync::Arc};
+use std::str::FromStr;
 use tracing::{debug, error, info};
+use uuid::Uuid;
 
 pub async fn create_wallet(
     tx: &mut PgConnection,
     user_id: i64,
     pubkey: &Pubkey,
-    keypair: &Keypair,
+    secret: &SealedSecret,
 ) -> Result<i64> {
     let wallet_id = sqlx::query_scalar::<_, i64>(
         r#"
@@ -21,16 +33,18 @@ pub async fn create_wallet(
             user_id,
             pubkey,
             keypair,
+            kek_version,
             created_at,
             updated_at
         )
-        VALUES ($1, $2, $3, NOW(), NOW())
+        VALUES ($1, $2, $3, $4, NOW(), NOW())
         RETURNING id
         "#,
     )
     .bind(user_id)
     .bind(pubkey.to_string())
-    .bind(keypair.to_base58_string())
+    .bind(&secret.secret)
+    .bind(secret.kek_version)
     .fetch_one(tx.as_mut())
     .await?;
 
@@ -130,6 +144,8 @@ pub async fn upsert_telegram_user(
         .await?;
 
         if claimed.is_some() {
+            // notifications queued for the reserved user can be delivered now
+            // that it has a telegram_user_id, see `crate::notifications`
             info!(
                 "claiming reserved user for telegram username: {}, telegram_user_id: {}",
                 username, telegram_user_id
@@ -194,7 +210,7 @@ pub async fn create_wallet_for_telegram_user(
     pool: &PgPool,
     telegram_user_id: i64,
     pubkey: &Pubkey,
-    keypair: &Keypair,
+    secret: &SealedSecret,
 ) -> Result<i64> {
     let user_id_str = format!("tg:{}", telegram_user_id);
     info!(
@@ -212,8 +228,8 @@ pub async fn create_wallet_for_telegram_user(
                 updated_at = NOW()
             RETURNING id
         )
-        INSERT INTO wallets (user_id, pubkey, keypair, created_at, updated_at)
-        SELECT upsert_user.id, $3, $4, NOW(), NOW()
+        INSERT INTO wallets (user_id, pubkey, keypair, kek_version, created_at, updated_at)
+        SELECT upsert_user.id, $3, $4, $5, NOW(), NOW()
         FROM upsert_user
         RETURNING id
         "#,
@@ -221,7 +237,8 @@ pub async fn create_wallet_for_telegram_user(
     .bind(&user_id_str)
     .bind(telegram_user_id)
     .bind(pubkey.to_string())
-    .bind(keypair.to_base58_string())
+    .bind(&secret.secret)
+    .bind(secret.kek_version)
     .fetch_one(pool)
     .await
     .map_err(|e| {
@@ -236,17 +253,56 @@ pub async fn create_wallet_for_telegram_user(
     Ok(wallet_id)
 }
 
+/// Register a non-custodial wallet for a Telegram user. No secret is stored,
+/// the user signs for the wallet themselves.
+pub async fn create_external_wallet_for_telegram_user(
+    pool: &PgPool,
+    telegram_user_id: i64,
+    pubkey: &Pubkey,
+) -> Result<i64> {
+    let user_id_str = format!("tg:{}", telegram_user_id);
+
+    let wallet_id = sqlx::query_scalar::<_, i64>(
+        r#"
+        WITH upsert_user AS (
+            INSERT INTO users (user_id, telegram_user_id, created_at, updated_at)
+            VALUES ($1, $2, NOW(), NOW())
+            ON CONFLICT (telegram_user_id) DO UPDATE SET
+                user_id = EXCLUDED.user_id,
+                updated_at = NOW()
+            RETURNING id
+        )
+        INSERT INTO wallets (user_id, pubkey, custody, created_at, updated_at)
+        SELECT upsert_user.id, $3, $4, NOW(), NOW()
+        FROM upsert_user
+        RETURNING id
+        "#,
+    )
+    .bind(&user_id_str)
+    .bind(telegram_user_id)
+    .bind(pubkey.to_string())
+    .bind(WalletCustody::External.as_str())
+    .fetch_one(pool)
+    .await?;
+
+    info!(
+        "registered external wallet for telegram_user_id: {}, wallet_id: {}",
+        telegram_user_id, wallet_id
+    );
+    Ok(wallet_id)
+}
+
 #[allow(dead_code)]
 pub async fn create_user_and_wallet(
     pool: PgPool,
     external_user_id: &str,
     pubkey: &Pubkey,
-    keypair: &Keypair,
+    secret: &SealedSecret,
 ) -> Result<(i64, i64)> {
     let mut tx = pool.begin().await?;
 
-    let user_id = create_user(&mut *tx, external_user_id).await?;
-    let wallet_id = create_wallet(&mut *tx, user_id, pubkey, keypair).await?;
+    let user_id = create_user(&mut tx, external_user_id).await?;
+    let wallet_id = create_wallet(&mut tx, user_id, pubkey, secret).await?;
 
     tx.commit().await?;
 
@@ -258,7 +314,7 @@ pub struct WalletRow {
     pub id: i64,
     pub user_id: i64,
     pub pubkey: String,
-    pub keypair: String,
+    pub custody: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -272,7 +328,7 @@ impl TryFrom<WalletRow> for Wallet {
             user_id: wallet.user_id,
             pubkey: Pubkey::from_str(&wallet.pubkey)
                 .map_err(|e| anyhow::anyhow!("Failed to parse pubkey: {}", e))?,
-            keypair: Arc::new(Keypair::from_base58_string(&wallet.keypair)),
+            custody: wallet.custody.parse()?,
             created_at: wallet.created_at,
             updated_at: wallet.updated_at,
         })
@@ -399,12 +455,12 @@ pub async fn get_wallets_for_telegram_user(
         debug!("[DB] wallet[{}]: {}", i, w.0);
     }
 
-    Ok(wallets
+    wallets
         .into_iter()
         .map(|w| {
             Pubkey::from_str(&w.0).map_err(|e| anyhow::anyhow!("Failed to parse pubkey: {}", e))
         })
-        .collect::<Result<Vec<_>>>()?)
+        .collect::<Result<Vec<_>>>()
 }
 
 /// Get wallet for a telegram username. Returns the wallet if the user exists and has one.
@@ -436,7 +492,7 @@ pub async fn create_reserved_wallet_for_username(
     pool: &PgPool,
     username: &str,
     pubkey: &Pubkey,
-    keypair: &Keypair,
+    secret: &SealedSecret,
 ) -> Result<i64> {
     // Use a placeholder user_id that indicates this is a reserved/unclaimed account
     let user_id_str = format!("tg:reserved:{}", username.to_lowercase());
@@ -452,8 +508,8 @@ pub async fn create_reserved_wallet_for_username(
             VALUES ($1, $2, NOW(), NOW())
             RETURNING id
         )
-        INSERT INTO wallets (user_id, pubkey, keypair, created_at, updated_at)
-        SELECT insert_user.id, $3, $4, NOW(), NOW()
+        INSERT INTO wallets (user_id, pubkey, keypair, kek_version, created_at, updated_at)
+        SELECT insert_user.id, $3, $4, $5, NOW(), NOW()
         FROM insert_user
         RETURNING id
         "#,
@@ -461,7 +517,8 @@ pub async fn create_reserved_wallet_for_username(
     .bind(&user_id_str)
     .bind(username)
     .bind(pubkey.to_string())
-    .bind(keypair.to_base58_string())
+    .bind(&secret.secret)
+    .bind(secret.kek_version)
     .fetch_one(pool)
     .await
     .map_err(|e| {
@@ -484,17 +541,4254 @@ pub async fn create_reserved_wallet_for_username(
 pub async fn get_or_create_wallet_for_username(
     pool: &PgPool,
     username: &str,
-    keypair: &Keypair,
+    pubkey: &Pubkey,
+    secret: &SealedSecret,
 ) -> Result<Wallet> {
     if let Some(wallet) = get_wallet_by_telegram_username(pool, username).await? {
         info!("found existing wallet for telegram username: {}", username);
         return Ok(wallet);
     }
 
-    let pubkey = keypair.pubkey();
-    create_reserved_wallet_for_username(pool, username, &pubkey, keypair).await?;
+    create_reserved_wallet_for_username(pool, username, pubkey, secret).await?;
 
     get_wallet_by_telegram_username(pool, username)
         .await?
         .ok_or_else(|| anyhow::anyhow!("Failed to fetch newly created wallet"))
 }
+
+pub async fn get_wallet_by_id(pool: &PgPool, wallet_id: i64) -> Result<Option<Wallet>> {
+    let wallet = sqlx::query_as::<_, WalletRow>(
+        r#"
+        SELECT *
+        FROM wallets w
+        WHERE w.id = $1
+        "#,
+    )
+    .bind(wallet_id)
+    .fetch_optional(pool)
+    .await?;
+
+    wallet
+        .map(|w| Wallet::try_from(w).map_err(|e| anyhow::anyhow!("Failed to parse wallet: {}", e)))
+        .transpose()
+}
+
+/// Pubkey and stored secret of a wallet. Only the key store should call this.
+pub async fn get_wallet_secret(
+    pool: &PgPool,
+    wallet_id: i64,
+) -> Result<Option<(Pubkey, SealedSecret)>> {
+    let row = sqlx::query_as::<_, (String, Option<String>, Option<i32>)>(
+        r#"
+        SELECT pubkey, keypair, kek_version
+        FROM wallets
+        WHERE id = $1
+        "#,
+    )
+    .bind(wallet_id)
+    .fetch_optional(pool)
+    .await?;
+
+    row.map(|(pubkey, secret, kek_version)| {
+        let secret =
+            secret.ok_or_else(|| anyhow::anyhow!("Wallet {} is not custodial", wallet_id))?;
+        Ok((
+            parse_pubkey(&pubkey)?,
+            SealedSecret {
+                secret,
+                kek_version,
+            },
+        ))
+    })
+    .transpose()
+}
+
+/// Page through stored wallet secrets by id, for re-encryption and master key
+/// rotation.
+pub async fn list_wallet_secrets(
+    pool: &PgPool,
+    after_id: i64,
+    limit: i64,
+) -> Result<Vec<(i64, SealedSecret)>> {
+    let rows = sqlx::query_as::<_, (i64, String, Option<i32>)>(
+        r#"
+        SELECT id, keypair, kek_version
+        FROM wallets
+        WHERE id > $1 AND keypair IS NOT NULL
+        ORDER BY id
+        LIMIT $2
+        "#,
+    )
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, secret, kek_version)| {
+            (
+                id,
+                SealedSecret {
+                    secret,
+                    kek_version,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Id and pubkey of every wallet the key store can sign for.
+pub async fn list_custodial_wallets(pool: &PgPool) -> Result<Vec<(i64, Pubkey)>> {
+    let rows = sqlx::query_as::<_, (i64, String)>(
+        r#"
+        SELECT id, pubkey
+        FROM wallets
+        WHERE custody = 'custodial'
+        ORDER BY id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|(id, pubkey)| Ok((id, parse_pubkey(&pubkey)?)))
+        .collect()
+}
+
+/// Swap a wallet's stored secret, only if it still holds `current`.
+/// Returns whether the row was updated.
+pub async fn replace_wallet_secret(
+    pool: &PgPool,
+    wallet_id: i64,
+    current: &str,
+    replacement: &SealedSecret,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE wallets
+        SET keypair = $3, kek_version = $4, updated_at = NOW()
+        WHERE id = $1 AND keypair = $2
+        "#,
+    )
+    .bind(wallet_id)
+    .bind(current)
+    .bind(&replacement.secret)
+    .bind(replacement.kek_version)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[derive(Debug, FromRow)]
+pub struct TransferJobRow {
+    pub id: i64,
+    pub sender_wallet_id: i64,
+    pub sender: String,
+    pub recipient: String,
+    pub mint: String,
+    pub amount: String,
+    pub decimals: i16,
+    pub memo: Option<String>,
+    pub status: String,
+    pub equality_proof_account: Option<String>,
+    pub ciphertext_validity_proof_account: Option<String>,
+    pub range_proof_account: Option<String>,
+    pub proofs: Option<Vec<u8>>,
+    pub allocate_signature: Option<String>,
+    pub range_proof_signature: Option<String>,
+    pub remaining_proofs_signature: Option<String>,
+    pub transfer_signature: Option<String>,
+    pub close_signature: Option<String>,
+    pub pending_signature: Option<String>,
+    pub pending_blockhash: Option<String>,
+    pub pending_bundle: Option<Vec<String>>,
+    pub error: Option<String>,
+    pub balance_funded: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const TRANSFER_JOB_COLUMNS: &str = r#"
+    id,
+    sender_wallet_id,
+    sender,
+    recipient,
+    mint,
+    amount::TEXT AS amount,
+    decimals,
+    memo,
+    status,
+    equality_proof_account,
+    ciphertext_validity_proof_account,
+    range_proof_account,
+    proofs,
+    allocate_signature,
+    range_proof_signature,
+    remaining_proofs_signature,
+    transfer_signature,
+    close_signature,
+    pending_signature,
+    pending_blockhash,
+    pending_bundle,
+    error,
+    balance_funded,
+    created_at,
+    updated_at
+"#;
+
+fn parse_pubkey(value: &str) -> Result<Pubkey> {
+    Pubkey::from_str(value).map_err(|e| anyhow::anyhow!("Failed to parse pubkey: {}", e))
+}
+
+fn parse_signature(value: Option<String>) -> Result<Option<Signature>> {
+    value
+        .map(|s| {
+            Signature::from_str(&s).map_err(|e| anyhow::anyhow!("Failed to parse signature: {}", e))
+        })
+        .transpose()
+}
+
+impl TryFrom<TransferJobRow> for TransferJob {
+    type Error = anyhow::Error;
+
+    fn try_from(job: TransferJobRow) -> Result<Self, Self::Error> {
+        let proof_accounts = match (
+            job.equality_proof_account.as_deref(),
+            job.ciphertext_validity_proof_account.as_deref(),
+            job.range_proof_account.as_deref(),
+        ) {
+            (Some(equality), Some(ciphertext_validity), Some(range)) => Some(ProofAccounts {
+                equality: parse_pubkey(equality)?,
+                ciphertext_validity: parse_pubkey(ciphertext_validity)?,
+                range: parse_pubkey(range)?,
+            }),
+            _ => None,
+        };
+
+        Ok(TransferJob {
+            id: job.id,
+            sender_wallet_id: job.sender_wallet_id,
+            sender: parse_pubkey(&job.sender)?,
+            recipient: parse_pubkey(&job.recipient)?,
+            mint: parse_pubkey(&job.mint)?,
+            amount: job
+                .amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+            decimals: u8::try_from(job.decimals)
+                .map_err(|e| anyhow::anyhow!("Failed to parse decimals: {}", e))?,
+            memo: job.memo,
+            status: job.status.parse()?,
+            proof_accounts,
+            proofs: job.proofs,
+            allocate_signature: parse_signature(job.allocate_signature)?,
+            range_proof_signature: parse_signature(job.range_proof_signature)?,
+            remaining_proofs_signature: parse_signature(job.remaining_proofs_signature)?,
+            transfer_signature: parse_signature(job.transfer_signature)?,
+            close_signature: parse_signature(job.close_signature)?,
+            pending_signature: parse_signature(job.pending_signature)?,
+            pending_blockhash: job
+                .pending_blockhash
+                .map(|h| {
+                    Hash::from_str(&h).map_err(|e| anyhow::anyhow!("Failed to parse hash: {}", e))
+                })
+                .transpose()?,
+            pending_bundle: job
+                .pending_bundle
+                .map(|signatures| {
+                    signatures
+                        .iter()
+                        .map(|s| {
+                            Signature::from_str(s)
+                                .map_err(|e| anyhow::anyhow!("Failed to parse signature: {}", e))
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+                .transpose()?,
+            error: job.error,
+            balance_funded: job.balance_funded,
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        })
+    }
+}
+
+/// A transfer about to be recorded: a job to run it and its ledger entry.
+#[derive(Debug, Clone, Copy)]
+pub struct NewTransfer<'a> {
+    pub sender_wallet: &'a Wallet,
+    pub recipient: &'a Pubkey,
+    pub mint: &'a Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    /// Memo sent on chain, encrypted if the sender asked for it.
+    pub onchain_memo: Option<&'a str>,
+    /// Plaintext memo kept in the ledger.
+    pub memo: Option<&'a str>,
+    pub memo_encrypted: bool,
+    /// The sender's balance was already topped up for this transfer, see
+    /// [`TransferJob::balance_funded`].
+    pub balance_funded: bool,
+}
+
+/// Record a transfer job and its ledger entry together. Returns the ledger id
+/// and the job id.
+pub async fn create_transfer(pool: &PgPool, transfer: &NewTransfer<'_>) -> Result<(i64, i64)> {
+    let mut tx = pool.begin().await?;
+    let ids = insert_transfer(&mut tx, transfer).await?;
+    tx.commit().await?;
+
+    Ok(ids)
+}
+
+/// [`create_transfer`] inside the caller's transaction, so whatever the
+/// transfer is made for can be linked to it atomically.
+async fn insert_transfer(
+    conn: &mut PgConnection,
+    transfer: &NewTransfer<'_>,
+) -> Result<(i64, i64)> {
+    let job_id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO transfer_jobs (
+            sender_wallet_id,
+            sender,
+            recipient,
+            mint,
+            amount,
+            decimals,
+            memo,
+            balance_funded,
+            status,
+            created_at,
+            updated_at
+        )
+        VALUES ($1, $2, $3, $4, $5::NUMERIC, $6, $7, $8, 'created', NOW(), NOW())
+        RETURNING id
+        "#,
+    )
+    .bind(transfer.sender_wallet.id)
+    .bind(transfer.sender_wallet.pubkey.to_string())
+    .bind(transfer.recipient.to_string())
+    .bind(transfer.mint.to_string())
+    .bind(transfer.amount.to_string())
+    .bind(transfer.decimals as i16)
+    .bind(transfer.onchain_memo)
+    .bind(transfer.balance_funded)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let transfer_id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO transfers (
+            transfer_job_id,
+            sender,
+            recipient,
+            mint,
+            amount,
+            decimals,
+            memo,
+            memo_encrypted,
+            status,
+            created_at,
+            updated_at
+        )
+        VALUES ($1, $2, $3, $4, $5::NUMERIC, $6, $7, $8, 'pending', NOW(), NOW())
+        RETURNING id
+        "#,
+    )
+    .bind(job_id)
+    .bind(transfer.sender_wallet.pubkey.to_string())
+    .bind(transfer.recipient.to_string())
+    .bind(transfer.mint.to_string())
+    .bind(transfer.amount.to_string())
+    .bind(transfer.decimals as i16)
+    .bind(transfer.memo)
+    .bind(transfer.memo_encrypted)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok((transfer_id, job_id))
+}
+
+pub async fn get_transfer_job(pool: &PgPool, job_id: i64) -> Result<Option<TransferJob>> {
+    let job = sqlx::query_as::<_, TransferJobRow>(&format!(
+        "SELECT {TRANSFER_JOB_COLUMNS} FROM transfer_jobs WHERE id = $1"
+    ))
+    .bind(job_id)
+    .fetch_optional(pool)
+    .await?;
+
+    job.map(TransferJob::try_from).transpose()
+}
+
+/// Jobs that were interrupted before reaching a terminal status, oldest first.
+pub async fn get_unfinished_transfer_jobs(pool: &PgPool) -> Result<Vec<TransferJob>> {
+    let jobs = sqlx::query_as::<_, TransferJobRow>(&format!(
+        r#"
+        SELECT {TRANSFER_JOB_COLUMNS}
+        FROM transfer_jobs
+        WHERE status NOT IN ('completed', 'rolled_back', 'failed')
+        ORDER BY id ASC
+        "#
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    jobs.into_iter().map(TransferJob::try_from).collect()
+}
+
+/// Unfinished jobs nobody is driving: their lease ran out (or they never had
+/// one) and they haven't changed for `idle_secs`, oldest first.
+///
+/// Jobs whose sender has another unfinished job under a live lease are left
+/// alone, two transfers from the same account can't run at once.
+pub async fn get_stalled_transfer_jobs(pool: &PgPool, idle_secs: i64) -> Result<Vec<TransferJob>> {
+    let jobs = sqlx::query_as::<_, TransferJobRow>(&format!(
+        r#"
+        SELECT {TRANSFER_JOB_COLUMNS}
+        FROM transfer_jobs
+        WHERE status NOT IN ('completed', 'rolled_back', 'failed')
+          AND (leased_until IS NULL OR leased_until <= NOW())
+          AND updated_at <= NOW() - make_interval(secs => $1)
+          AND NOT EXISTS (
+            SELECT 1
+            FROM transfer_jobs running
+            WHERE running.sender_wallet_id = transfer_jobs.sender_wallet_id
+              AND running.id <> transfer_jobs.id
+              AND running.status NOT IN ('completed', 'rolled_back', 'failed')
+              AND running.leased_until > NOW()
+          )
+        ORDER BY id ASC
+        "#
+    ))
+    .bind(idle_secs as f64)
+    .fetch_all(pool)
+    .await?;
+
+    jobs.into_iter().map(TransferJob::try_from).collect()
+}
+
+/// Take or renew the lease on a job for `lease_secs`. Succeeds when the job
+/// isn't leased, its lease ran out, or `lease_id` already holds it.
+pub async fn lease_transfer_job(
+    pool: &PgPool,
+    job_id: i64,
+    lease_id: Uuid,
+    lease_secs: i64,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET lease_id = $2,
+            leased_until = NOW() + make_interval(secs => $3)
+        WHERE id = $1
+          AND (lease_id = $2 OR leased_until IS NULL OR leased_until <= NOW())
+        "#,
+    )
+    .bind(job_id)
+    .bind(lease_id)
+    .bind(lease_secs as f64)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// [`lease_transfer_job`] for several jobs at once, taking or renewing the
+/// lease on each job that is free or already held by `lease_id`.
+pub async fn lease_transfer_jobs(
+    pool: &PgPool,
+    job_ids: &[i64],
+    lease_id: Uuid,
+    lease_secs: i64,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET lease_id = $2,
+            leased_until = NOW() + make_interval(secs => $3)
+        WHERE id = ANY($1)
+          AND (lease_id = $2 OR leased_until IS NULL OR leased_until <= NOW())
+        "#,
+    )
+    .bind(job_ids)
+    .bind(lease_id)
+    .bind(lease_secs as f64)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn release_transfer_job(pool: &PgPool, job_id: i64, lease_id: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET lease_id = NULL,
+            leased_until = NULL
+        WHERE id = $1 AND lease_id = $2
+        "#,
+    )
+    .bind(job_id)
+    .bind(lease_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record the proof accounts and proof data generated for a job, replacing any
+/// previous (never allocated) attempt.
+pub async fn set_transfer_job_proofs(
+    pool: &PgPool,
+    job_id: i64,
+    proof_accounts: &ProofAccounts,
+    proofs: &[u8],
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET equality_proof_account = $2,
+            ciphertext_validity_proof_account = $3,
+            range_proof_account = $4,
+            proofs = $5,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job_id)
+    .bind(proof_accounts.equality.to_string())
+    .bind(proof_accounts.ciphertext_validity.to_string())
+    .bind(proof_accounts.range.to_string())
+    .bind(proofs)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record the transaction about to be sent for the job's current step. Written
+/// before sending so a restart can find out whether it landed.
+pub async fn set_transfer_job_pending(
+    pool: &PgPool,
+    job_id: i64,
+    signature: &Signature,
+    blockhash: &Hash,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET pending_signature = $2,
+            pending_blockhash = $3,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job_id)
+    .bind(signature.to_string())
+    .bind(blockhash.to_string())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record a bundle about to be sent for every remaining step of a job. Its
+/// last transaction is the pending one: the bundle lands as a whole or not at all.
+pub async fn set_transfer_job_pending_bundle(
+    pool: &PgPool,
+    job_id: i64,
+    signatures: &[Signature],
+    blockhash: &Hash,
+) -> Result<()> {
+    let last = signatures
+        .last()
+        .ok_or_else(|| anyhow::anyhow!("Bundle has no transactions"))?;
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET pending_signature = $2,
+            pending_blockhash = $3,
+            pending_bundle = $4,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job_id)
+    .bind(last.to_string())
+    .bind(blockhash.to_string())
+    .bind(signatures.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn clear_transfer_job_pending(pool: &PgPool, job_id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET pending_signature = NULL,
+            pending_blockhash = NULL,
+            pending_bundle = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Move a job to `status`, storing the signature of the step that got it there.
+pub async fn advance_transfer_job(
+    pool: &PgPool,
+    job_id: i64,
+    status: TransferJobStatus,
+    signature: &Signature,
+) -> Result<()> {
+    let signature_column = match status {
+        TransferJobStatus::ProofsAllocated => "allocate_signature",
+        TransferJobStatus::RangeProofVerified => "range_proof_signature",
+        TransferJobStatus::ProofsVerified => "remaining_proofs_signature",
+        TransferJobStatus::Transferred => "transfer_signature",
+        TransferJobStatus::Completed => "close_signature",
+        _ => anyhow::bail!("Transfer job status {:?} has no step signature", status),
+    };
+
+    sqlx::query(&format!(
+        r#"
+        UPDATE transfer_jobs
+        SET status = $2,
+            {signature_column} = $3,
+            pending_signature = NULL,
+            pending_blockhash = NULL,
+            pending_bundle = NULL,
+            error = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#
+    ))
+    .bind(job_id)
+    .bind(status.as_str())
+    .bind(signature.to_string())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Complete a job whose remaining steps landed together in one bundle,
+/// storing the signature of every step from allocation to close.
+pub async fn complete_transfer_job_bundle(
+    pool: &PgPool,
+    job_id: i64,
+    signatures: &[Signature],
+) -> Result<()> {
+    let [allocate, range_proof, remaining_proofs, transfer, close] = signatures else {
+        anyhow::bail!(
+            "Transfer bundle has {} signatures, expected 5",
+            signatures.len()
+        );
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET status = 'completed',
+            allocate_signature = $2,
+            range_proof_signature = $3,
+            remaining_proofs_signature = $4,
+            transfer_signature = $5,
+            close_signature = $6,
+            pending_signature = NULL,
+            pending_blockhash = NULL,
+            pending_bundle = NULL,
+            error = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job_id)
+    .bind(allocate.to_string())
+    .bind(range_proof.to_string())
+    .bind(remaining_proofs.to_string())
+    .bind(transfer.to_string())
+    .bind(close.to_string())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_transfer_job_status(
+    pool: &PgPool,
+    job_id: i64,
+    status: TransferJobStatus,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET status = $2,
+            error = COALESCE($3, error),
+            pending_signature = NULL,
+            pending_blockhash = NULL,
+            pending_bundle = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job_id)
+    .bind(status.as_str())
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record the last error seen while running a job without changing its status.
+pub async fn set_transfer_job_error(pool: &PgPool, job_id: i64, error: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transfer_jobs
+        SET error = $2,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(job_id)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+pub struct IdempotencyKeyRow {
+    pub request_hash: String,
+    pub response_status: Option<i16>,
+    pub response_content_type: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+}
+
+/// Claim an idempotency key for a request. Returns `None` when the key was
+/// claimed, or the existing row when the key has been used before.
+///
+/// A key whose request never stored a response is claimed again once it has
+/// been locked for `lock_secs`, as long as the request is the same. Keys
+/// older than `ttl_secs` are forgotten.
+pub async fn claim_idempotency_key(
+    pool: &PgPool,
+    telegram_user_id: i64,
+    idempotency_key: &str,
+    request_hash: &str,
+    lock_secs: i64,
+    ttl_secs: i64,
+) -> Result<Option<IdempotencyKeyRow>> {
+    sqlx::query(
+        "DELETE FROM idempotency_keys WHERE created_at < NOW() - make_interval(secs => $1)",
+    )
+    .bind(ttl_secs as f64)
+    .execute(pool)
+    .await?;
+
+    let claimed = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO idempotency_keys (
+            telegram_user_id,
+            idempotency_key,
+            request_hash,
+            locked_at,
+            created_at,
+            updated_at
+        )
+        VALUES ($1, $2, $3, NOW(), NOW(), NOW())
+        ON CONFLICT (telegram_user_id, idempotency_key) DO UPDATE
+        SET locked_at = NOW(),
+            updated_at = NOW()
+        WHERE idempotency_keys.response_status IS NULL
+          AND idempotency_keys.request_hash = EXCLUDED.request_hash
+          AND idempotency_keys.locked_at <= NOW() - make_interval(secs => $4)
+        RETURNING id
+        "#,
+    )
+    .bind(telegram_user_id)
+    .bind(idempotency_key)
+    .bind(request_hash)
+    .bind(lock_secs as f64)
+    .fetch_optional(pool)
+    .await?;
+
+    if claimed.is_some() {
+        return Ok(None);
+    }
+
+    let existing = sqlx::query_as::<_, IdempotencyKeyRow>(
+        r#"
+        SELECT request_hash, response_status, response_content_type, response_body
+        FROM idempotency_keys
+        WHERE telegram_user_id = $1 AND idempotency_key = $2
+        "#,
+    )
+    .bind(telegram_user_id)
+    .bind(idempotency_key)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Some(existing))
+}
+
+/// Give up a claimed key without a response, so the request can be retried.
+pub async fn release_idempotency_key(
+    pool: &PgPool,
+    telegram_user_id: i64,
+    idempotency_key: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        DELETE FROM idempotency_keys
+        WHERE telegram_user_id = $1 AND idempotency_key = $2 AND response_status IS NULL
+        "#,
+    )
+    .bind(telegram_user_id)
+    .bind(idempotency_key)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_idempotency_key_response(
+    pool: &PgPool,
+    telegram_user_id: i64,
+    idempotency_key: &str,
+    response_status: u16,
+    response_content_type: Option<&str>,
+    response_body: &[u8],
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE idempotency_keys
+        SET response_status = $3,
+            response_content_type = $4,
+            response_body = $5,
+            locked_at = NULL,
+            updated_at = NOW()
+        WHERE telegram_user_id = $1 AND idempotency_key = $2
+        "#,
+    )
+    .bind(telegram_user_id)
+    .bind(idempotency_key)
+    .bind(response_status as i16)
+    .bind(response_content_type)
+    .bind(response_body)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+pub struct TransferRow {
+    pub id: i64,
+    pub transfer_job_id: Option<i64>,
+    pub sender: String,
+    pub recipient: String,
+    pub mint: String,
+    pub amount: String,
+    pub decimals: i16,
+    pub memo: Option<String>,
+    pub memo_encrypted: bool,
+    pub status: String,
+    pub signatures: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const TRANSFER_COLUMNS: &str = r#"
+    id,
+    transfer_job_id,
+    sender,
+    recipient,
+    mint,
+    amount::TEXT AS amount,
+    decimals,
+    memo,
+    memo_encrypted,
+    status,
+    signatures,
+    created_at,
+    updated_at
+"#;
+
+impl TryFrom<TransferRow> for Transfer {
+    type Error = anyhow::Error;
+
+    fn try_from(transfer: TransferRow) -> Result<Self, Self::Error> {
+        Ok(Transfer {
+            id: transfer.id,
+            transfer_job_id: transfer.transfer_job_id,
+            sender: parse_pubkey(&transfer.sender)?,
+            recipient: parse_pubkey(&transfer.recipient)?,
+            mint: parse_pubkey(&transfer.mint)?,
+            amount: transfer
+                .amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+            decimals: u8::try_from(transfer.decimals)
+                .map_err(|e| anyhow::anyhow!("Failed to parse decimals: {}", e))?,
+            memo: transfer.memo,
+            memo_encrypted: transfer.memo_encrypted,
+            status: transfer.status.parse()?,
+            signatures: transfer
+                .signatures
+                .into_iter()
+                .map(|s| {
+                    Signature::from_str(&s)
+                        .map_err(|e| anyhow::anyhow!("Failed to parse signature: {}", e))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            created_at: transfer.created_at,
+            updated_at: transfer.updated_at,
+        })
+    }
+}
+
+/// An entry of a batch payout, with the transfer recorded for it or why there
+/// is none.
+#[derive(Debug, Clone)]
+pub struct NewBatchEntry<'a> {
+    pub recipient: Option<&'a Pubkey>,
+    pub telegram_username: Option<&'a str>,
+    pub amount: u64,
+    pub transfer_id: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// Record a batch payout and its entries, in request order. Returns its id.
+pub async fn create_transfer_batch(
+    pool: &PgPool,
+    sender_wallet_id: i64,
+    mint: &Pubkey,
+    total_amount: u64,
+    entries: &[NewBatchEntry<'_>],
+) -> Result<i64> {
+    let mut tx = pool.begin().await?;
+
+    let batch_id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO transfer_batches (sender_wallet_id, mint, total_amount)
+        VALUES ($1, $2, $3::NUMERIC)
+        RETURNING id
+        "#,
+    )
+    .bind(sender_wallet_id)
+    .bind(mint.to_string())
+    .bind(total_amount.to_string())
+    .fetch_one(tx.as_mut())
+    .await?;
+
+    for (position, entry) in entries.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO transfer_batch_entries (
+                batch_id,
+                position,
+                recipient,
+                telegram_username,
+                amount,
+                transfer_id,
+                error
+            )
+            VALUES ($1, $2, $3, $4, $5::NUMERIC, $6, $7)
+            "#,
+        )
+        .bind(batch_id)
+        .bind(position as i16)
+        .bind(entry.recipient.map(|r| r.to_string()))
+        .bind(entry.telegram_username)
+        .bind(entry.amount.to_string())
+        .bind(entry.transfer_id)
+        .bind(entry.error.as_deref())
+        .execute(tx.as_mut())
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(batch_id)
+}
+
+#[derive(Debug, FromRow)]
+struct TransferBatchRow {
+    id: i64,
+    mint: String,
+    total_amount: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+struct TransferBatchEntryRow {
+    position: i16,
+    recipient: Option<String>,
+    telegram_username: Option<String>,
+    amount: String,
+    transfer_id: Option<i64>,
+    job_id: Option<i64>,
+    status: Option<String>,
+    signatures: Option<Vec<String>>,
+    error: Option<String>,
+}
+
+/// A batch payout with the current status of each of its transfers, if it was
+/// sent from one of the wallets of `telegram_user_id`.
+pub async fn get_user_transfer_batch(
+    pool: &PgPool,
+    id: i64,
+    telegram_user_id: i64,
+) -> Result<Option<TransferBatch>> {
+    let Some(batch) = sqlx::query_as::<_, TransferBatchRow>(
+        r#"
+        SELECT id, mint, total_amount::TEXT AS total_amount, created_at
+        FROM transfer_batches
+        WHERE id = $1
+            AND sender_wallet_id IN (
+                SELECT w.id
+                FROM wallets w
+                JOIN users u ON w.user_id = u.id
+                WHERE u.user_id = $2
+            )
+        "#,
+    )
+    .bind(id)
+    .bind(format!("tg:{}", telegram_user_id))
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let entries = sqlx::query_as::<_, TransferBatchEntryRow>(
+        r#"
+        SELECT
+            e.position,
+            e.recipient,
+            e.telegram_username,
+            e.amount::TEXT AS amount,
+            e.transfer_id,
+            t.transfer_job_id AS job_id,
+            t.status,
+            t.signatures,
+            e.error
+        FROM transfer_batch_entries e
+        LEFT JOIN transfers t ON t.id = e.transfer_id
+        WHERE e.batch_id = $1
+        ORDER BY e.position
+        "#,
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|entry| {
+        Ok(TransferBatchEntry {
+            position: usize::try_from(entry.position)
+                .map_err(|e| anyhow::anyhow!("Failed to parse position: {}", e))?,
+            recipient: entry.recipient.as_deref().map(parse_pubkey).transpose()?,
+            telegram_username: entry.telegram_username,
+            amount: entry
+                .amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+            transfer_id: entry.transfer_id,
+            job_id: entry.job_id,
+            status: entry.status.map(|s| s.parse()).transpose()?,
+            signatures: entry
+                .signatures
+                .unwrap_or_default()
+                .into_iter()
+                .map(|s| {
+                    Signature::from_str(&s)
+                        .map_err(|e| anyhow::anyhow!("Failed to parse signature: {}", e))
+                })
+                .collect::<Result<_>>()?,
+            error: entry.error,
+        })
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+    Ok(Some(TransferBatch {
+        id: batch.id,
+        mint: parse_pubkey(&batch.mint)?,
+        total_amount: batch
+            .total_amount
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+        entries,
+        created_at: batch.created_at,
+    }))
+}
+
+/// Reacts to a transfer in the ledger reaching a final status, in the same
+/// database transaction that records it. Hooks are given every settled
+/// transfer and only touch the rows of their own feature that refer to it.
+type TransferSettledHook =
+    for<'c> fn(&'c mut PgConnection, i64, TransferStatus) -> BoxFuture<'c, Result<()>>;
+
+/// Features that make transfers on behalf of something, settled with them.
+const TRANSFER_SETTLED_HOOKS: &[TransferSettledHook] = &[
+    |conn, transfer_id, status| Box::pin(settle_payment_request(conn, transfer_id, status)),
+    |conn, transfer_id, status| Box::pin(settle_claim_link(conn, transfer_id, status)),
+    |conn, transfer_id, status| Box::pin(settle_inline_payment(conn, transfer_id, status)),
+    |conn, transfer_id, status| Box::pin(settle_pot_withdrawal(conn, transfer_id, status)),
+    |conn, transfer_id, status| Box::pin(settle_scheduled_transfer_run(conn, transfer_id, status)),
+];
+
+/// Copy the status and signatures of a job onto its ledger entry, if it has
+/// one, and once the transfer is final run [`TRANSFER_SETTLED_HOOKS`].
+pub async fn sync_transfer_with_job(pool: &PgPool, job: &TransferJob) -> Result<()> {
+    let status = TransferStatus::from_job(job);
+    let signatures = job
+        .signatures()
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+
+    let mut tx = pool.begin().await?;
+    let transfer_id = sqlx::query_scalar::<_, i64>(
+        r#"
+        UPDATE transfers
+        SET status = $2,
+            signatures = $3,
+            updated_at = NOW()
+        WHERE transfer_job_id = $1
+        RETURNING id
+        "#,
+    )
+    .bind(job.id)
+    .bind(status.as_str())
+    .bind(signatures)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(transfer_id) = transfer_id
+        && status != TransferStatus::Pending
+    {
+        for hook in TRANSFER_SETTLED_HOOKS {
+            hook(&mut tx, transfer_id, status).await?;
+        }
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Transfers sent or received by any of `wallets`, newest first. Pass the id of
+/// the last transfer of the previous page as `before_id` to get the next page.
+pub async fn list_transfers_for_wallets(
+    pool: &PgPool,
+    wallets: &[Pubkey],
+    before_id: Option<i64>,
+    limit: i64,
+) -> Result<Vec<Transfer>> {
+    let wallets = wallets.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+
+    let transfers = sqlx::query_as::<_, TransferRow>(&format!(
+        r#"
+        SELECT {TRANSFER_COLUMNS}
+        FROM transfers
+        WHERE (sender = ANY($1::TEXT[]) OR recipient = ANY($1::TEXT[]))
+            AND ($2::BIGINT IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#
+    ))
+    .bind(wallets)
+    .bind(before_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    transfers.into_iter().map(Transfer::try_from).collect()
+}
+
+#[derive(Debug, FromRow)]
+pub struct AuditEntryRow {
+    pub signature: String,
+    pub instruction_index: i16,
+    pub slot: i64,
+    pub block_time: Option<DateTime<Utc>>,
+    pub kind: String,
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub amount: Option<String>,
+}
+
+impl TryFrom<AuditEntryRow> for AuditRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(entry: AuditEntryRow) -> Result<Self, Self::Error> {
+        Ok(AuditRecord {
+            signature: Signature::from_str(&entry.signature)
+                .map_err(|e| anyhow::anyhow!("Failed to parse signature: {}", e))?,
+            instruction_index: u8::try_from(entry.instruction_index)
+                .map_err(|e| anyhow::anyhow!("Failed to parse instruction index: {}", e))?,
+            slot: u64::try_from(entry.slot)
+                .map_err(|e| anyhow::anyhow!("Failed to parse slot: {}", e))?,
+            block_time: entry.block_time.map(|t| t.timestamp()),
+            kind: entry.kind.parse()?,
+            source: entry.source.as_deref().map(parse_pubkey).transpose()?,
+            destination: entry.destination.as_deref().map(parse_pubkey).transpose()?,
+            amount: entry
+                .amount
+                .map(|a| a.parse())
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+        })
+    }
+}
+
+pub async fn get_audit_scan_cursor(pool: &PgPool, mint: &Pubkey) -> Result<AuditScanCursor> {
+    read_audit_scan_cursor(&mut *pool.acquire().await?, mint).await
+}
+
+async fn read_audit_scan_cursor(conn: &mut PgConnection, mint: &Pubkey) -> Result<AuditScanCursor> {
+    let row = sqlx::query_as::<_, (Option<String>, Option<String>, Option<String>)>(
+        r#"
+        SELECT last_signature, pass_newest, pass_before
+        FROM audit_scans
+        WHERE mint = $1
+        "#,
+    )
+    .bind(mint.to_string())
+    .fetch_optional(conn)
+    .await?;
+
+    let Some((last_signature, pass_newest, pass_before)) = row else {
+        return Ok(AuditScanCursor::default());
+    };
+    let in_progress = match (parse_signature(pass_newest)?, parse_signature(pass_before)?) {
+        (Some(newest), Some(before)) => Some((newest, before)),
+        _ => None,
+    };
+    Ok(AuditScanCursor {
+        scanned_until: parse_signature(last_signature)?,
+        in_progress,
+    })
+}
+
+/// Store scanned audit records and advance the mint's scan cursor atomically,
+/// provided the cursor is still at `previous`, where the scan started. Saves of
+/// the same mint are serialized, so of two scans that started from the same
+/// cursor only the first is stored. Returns whether the scan was stored.
+pub async fn save_audit_scan(
+    pool: &PgPool,
+    mint: &Pubkey,
+    records: &[AuditRecord],
+    previous: &AuditScanCursor,
+    cursor: &AuditScanCursor,
+) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('audit_scan:' || $1))")
+        .bind(mint.to_string())
+        .execute(tx.as_mut())
+        .await?;
+    if read_audit_scan_cursor(&mut tx, mint).await? != *previous {
+        return Ok(false);
+    }
+
+    for record in records {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_entries (
+                mint,
+                signature,
+                instruction_index,
+                slot,
+                block_time,
+                kind,
+                source,
+                destination,
+                amount,
+                created_at
+            )
+            VALUES ($1, $2, $3, $4, TO_TIMESTAMP($5), $6, $7, $8, $9::NUMERIC, NOW())
+            ON CONFLICT (signature, instruction_index) DO NOTHING
+            "#,
+        )
+        .bind(mint.to_string())
+        .bind(record.signature.to_string())
+        .bind(record.instruction_index as i16)
+        .bind(record.slot as i64)
+        .bind(record.block_time.map(|t| t as f64))
+        .bind(record.kind.as_str())
+        .bind(record.source.map(|s| s.to_string()))
+        .bind(record.destination.map(|d| d.to_string()))
+        .bind(record.amount.map(|a| a.to_string()))
+        .execute(tx.as_mut())
+        .await?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO audit_scans (mint, last_signature, pass_newest, pass_before, updated_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (mint) DO UPDATE
+        SET last_signature = EXCLUDED.last_signature,
+            pass_newest = EXCLUDED.pass_newest,
+            pass_before = EXCLUDED.pass_before,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(mint.to_string())
+    .bind(cursor.scanned_until.map(|s| s.to_string()))
+    .bind(cursor.in_progress.map(|(newest, _)| newest.to_string()))
+    .bind(cursor.in_progress.map(|(_, before)| before.to_string()))
+    .execute(tx.as_mut())
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Audit entries for a mint in chain order, optionally limited to a block time
+/// range and to entries touching `account` (as source or destination). Pass
+/// the position of the last entry of the previous page as `after` to get the
+/// next page.
+pub async fn list_audit_entries(
+    pool: &PgPool,
+    mint: &Pubkey,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    account: Option<&Pubkey>,
+    after: Option<&AuditPosition>,
+    limit: i64,
+) -> Result<Vec<AuditRecord>> {
+    let entries = sqlx::query_as::<_, AuditEntryRow>(
+        r#"
+        SELECT
+            signature,
+            instruction_index,
+            slot,
+            block_time,
+            kind,
+            source,
+            destination,
+            amount::TEXT AS amount
+        FROM audit_entries
+        WHERE mint = $1
+            AND ($2::TIMESTAMPTZ IS NULL OR block_time >= $2)
+            AND ($3::TIMESTAMPTZ IS NULL OR block_time < $3)
+            AND ($4::TEXT IS NULL OR source = $4 OR destination = $4)
+            AND (
+                $5::BIGINT IS NULL
+                OR (slot, signature, instruction_index) > ($5, $6::TEXT, $7::SMALLINT)
+            )
+        ORDER BY slot ASC, signature ASC, instruction_index ASC
+        LIMIT $8
+        "#,
+    )
+    .bind(mint.to_string())
+    .bind(from)
+    .bind(to)
+    .bind(account.map(|a| a.to_string()))
+    .bind(after.map(|a| a.slot as i64))
+    .bind(after.map(|a| a.signature.to_string()))
+    .bind(after.map(|a| a.instruction_index as i16))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    entries.into_iter().map(AuditRecord::try_from).collect()
+}
+
+#[derive(Debug, FromRow)]
+pub struct PaymentRequestRow {
+    pub id: i64,
+    pub wallet_id: i64,
+    pub recipient: String,
+    pub mint: String,
+    pub amount: String,
+    pub memo: Option<String>,
+    pub status: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub payer: Option<String>,
+    pub transfer_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const PAYMENT_REQUEST_COLUMNS: &str = r#"
+    id,
+    wallet_id,
+    recipient,
+    mint,
+    amount::TEXT AS amount,
+    memo,
+    status,
+    expires_at,
+    payer,
+    transfer_id,
+    created_at,
+    updated_at
+"#;
+
+impl TryFrom<PaymentRequestRow> for PaymentRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(request: PaymentRequestRow) -> Result<Self, Self::Error> {
+        Ok(PaymentRequest {
+            id: request.id,
+            wallet_id: request.wallet_id,
+            recipient: parse_pubkey(&request.recipient)?,
+            mint: parse_pubkey(&request.mint)?,
+            amount: request
+                .amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+            memo: request.memo,
+            status: request.status.parse()?,
+            expires_at: request.expires_at,
+            payer: request.payer.as_deref().map(parse_pubkey).transpose()?,
+            transfer_id: request.transfer_id,
+            created_at: request.created_at,
+            updated_at: request.updated_at,
+        })
+    }
+}
+
+pub async fn create_payment_request(
+    pool: &PgPool,
+    wallet: &Wallet,
+    mint: &Pubkey,
+    amount: u64,
+    memo: Option<&str>,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<PaymentRequest> {
+    let request = sqlx::query_as::<_, PaymentRequestRow>(&format!(
+        r#"
+        INSERT INTO payment_requests (wallet_id, recipient, mint, amount, memo, expires_at)
+        VALUES ($1, $2, $3, $4::NUMERIC, $5, $6)
+        RETURNING {PAYMENT_REQUEST_COLUMNS}
+        "#
+    ))
+    .bind(wallet.id)
+    .bind(wallet.pubkey.to_string())
+    .bind(mint.to_string())
+    .bind(amount.to_string())
+    .bind(memo)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+
+    request.try_into()
+}
+
+/// Fetch a payment request, moving it to `expired` first if it is still open
+/// past its expiry.
+pub async fn get_payment_request(pool: &PgPool, id: i64) -> Result<Option<PaymentRequest>> {
+    sqlx::query(
+        r#"
+        UPDATE payment_requests
+        SET status = 'expired',
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'open' AND payer IS NULL AND expires_at <= NOW()
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    let request = sqlx::query_as::<_, PaymentRequestRow>(&format!(
+        r#"
+        SELECT {PAYMENT_REQUEST_COLUMNS}
+        FROM payment_requests
+        WHERE id = $1
+        "#
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    request.map(PaymentRequest::try_from).transpose()
+}
+
+/// Reserve an open, unexpired request for the sender of `transfer` and record
+/// the transfer paying it, in one transaction. Returns the ledger id and the
+/// job id, or None if the request can't be paid, including when another
+/// payment for it is already in flight.
+pub async fn pay_payment_request(
+    pool: &PgPool,
+    id: i64,
+    transfer: &NewTransfer<'_>,
+) -> Result<Option<(i64, i64)>> {
+    let mut tx = pool.begin().await?;
+
+    let claimed = sqlx::query(
+        r#"
+        UPDATE payment_requests
+        SET payer = $2,
+            updated_at = NOW()
+        WHERE id = $1
+            AND status = 'open'
+            AND payer IS NULL
+            AND (expires_at IS NULL OR expires_at > NOW())
+        "#,
+    )
+    .bind(id)
+    .bind(transfer.sender_wallet.pubkey.to_string())
+    .execute(tx.as_mut())
+    .await?;
+    if claimed.rows_affected() != 1 {
+        return Ok(None);
+    }
+
+    let (transfer_id, job_id) = insert_transfer(&mut tx, transfer).await?;
+    sqlx::query(
+        r#"
+        UPDATE payment_requests
+        SET transfer_id = $2,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(transfer_id)
+    .execute(tx.as_mut())
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some((transfer_id, job_id)))
+}
+
+/// Mark a request paid once its transfer lands, or free it for another payer
+/// if the transfer failed.
+async fn settle_payment_request(
+    conn: &mut PgConnection,
+    transfer_id: i64,
+    status: TransferStatus,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE payment_requests
+        SET status = CASE WHEN $2 = 'completed' THEN 'paid' ELSE status END,
+            payer = CASE WHEN $2 = 'failed' THEN NULL ELSE payer END,
+            transfer_id = CASE WHEN $2 = 'failed' THEN NULL ELSE transfer_id END,
+            updated_at = NOW()
+        WHERE transfer_id = $1 AND status = 'open'
+        "#,
+    )
+    .bind(transfer_id)
+    .bind(status.as_str())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Cancel an open request that has no payment in flight. Returns false if
+/// the request could not be cancelled.
+pub async fn cancel_payment_request(pool: &PgPool, id: i64) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE payment_requests
+        SET status = 'cancelled',
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'open' AND payer IS NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Create a custodial wallet that holds the funds of a claim link. It belongs to
+/// a placeholder user, like reserved wallets, so it never shows up as anyone's
+/// own wallet.
+pub async fn create_escrow_wallet(
+    pool: &PgPool,
+    pubkey: &Pubkey,
+    secret: &SealedSecret,
+) -> Result<Wallet> {
+    let wallet = sqlx::query_as::<_, WalletRow>(
+        r#"
+        WITH insert_user AS (
+            INSERT INTO users (user_id, created_at, updated_at)
+            VALUES ($1, NOW(), NOW())
+            RETURNING id
+        )
+        INSERT INTO wallets (user_id, pubkey, keypair, kek_version, created_at, updated_at)
+        SELECT insert_user.id, $2, $3, $4, NOW(), NOW()
+        FROM insert_user
+        RETURNING *
+        "#,
+    )
+    .bind(format!("escrow:{}", pubkey))
+    .bind(pubkey.to_string())
+    .bind(&secret.secret)
+    .bind(secret.kek_version)
+    .fetch_one(pool)
+    .await?;
+
+    wallet.try_into()
+}
+
+#[derive(Debug, FromRow)]
+pub struct ClaimLinkRow {
+    pub id: i64,
+    pub sender_wallet_id: i64,
+    pub escrow_wallet_id: i64,
+    pub mint: String,
+    pub amount: String,
+    pub memo: Option<String>,
+    pub status: String,
+    pub funding_transfer_id: Option<i64>,
+    pub claim_transfer_id: Option<i64>,
+    pub claimed_by_wallet_id: Option<i64>,
+    pub refund_transfer_id: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const CLAIM_LINK_COLUMNS: &str = r#"
+    id,
+    sender_wallet_id,
+    escrow_wallet_id,
+    mint,
+    amount::TEXT AS amount,
+    memo,
+    status,
+    funding_transfer_id,
+    claim_transfer_id,
+    claimed_by_wallet_id,
+    refund_transfer_id,
+    expires_at,
+    created_at,
+    updated_at
+"#;
+
+impl TryFrom<ClaimLinkRow> for ClaimLink {
+    type Error = anyhow::Error;
+
+    fn try_from(link: ClaimLinkRow) -> Result<Self, Self::Error> {
+        Ok(ClaimLink {
+            id: link.id,
+            sender_wallet_id: link.sender_wallet_id,
+            escrow_wallet_id: link.escrow_wallet_id,
+            mint: parse_pubkey(&link.mint)?,
+            amount: link
+                .amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+            memo: link.memo,
+            status: link.status.parse()?,
+            funding_transfer_id: link.funding_transfer_id,
+            claim_transfer_id: link.claim_transfer_id,
+            claimed_by_wallet_id: link.claimed_by_wallet_id,
+            refund_transfer_id: link.refund_transfer_id,
+            expires_at: link.expires_at,
+            created_at: link.created_at,
+            updated_at: link.updated_at,
+        })
+    }
+}
+
+/// Record a claim link along with the transfer funding its escrow wallet,
+/// the recipient of `funding`. Returns the link and the ledger id and job id
+/// of the funding transfer.
+pub async fn create_claim_link(
+    pool: &PgPool,
+    escrow_wallet_id: i64,
+    secret_hash: &str,
+    ttl_secs: i64,
+    funding: &NewTransfer<'_>,
+) -> Result<(ClaimLink, i64, i64)> {
+    let mut tx = pool.begin().await?;
+    let (transfer_id, job_id) = insert_transfer(&mut tx, funding).await?;
+
+    let link = sqlx::query_as::<_, ClaimLinkRow>(&format!(
+        r#"
+        INSERT INTO claim_links (
+            sender_wallet_id,
+            escrow_wallet_id,
+            mint,
+            amount,
+            memo,
+            secret_hash,
+            funding_transfer_id,
+            expires_at
+        )
+        VALUES ($1, $2, $3, $4::NUMERIC, $5, $6, $7, NOW() + make_interval(secs => $8))
+        RETURNING {CLAIM_LINK_COLUMNS}
+        "#
+    ))
+    .bind(funding.sender_wallet.id)
+    .bind(escrow_wallet_id)
+    .bind(funding.mint.to_string())
+    .bind(funding.amount.to_string())
+    .bind(funding.memo)
+    .bind(secret_hash)
+    .bind(transfer_id)
+    .bind(ttl_secs as f64)
+    .fetch_one(tx.as_mut())
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((link.try_into()?, transfer_id, job_id))
+}
+
+/// A claim link, if it was sent from one of the wallets of `telegram_user_id`.
+pub async fn get_user_claim_link(
+    pool: &PgPool,
+    id: i64,
+    telegram_user_id: i64,
+) -> Result<Option<ClaimLink>> {
+    let link = sqlx::query_as::<_, ClaimLinkRow>(&format!(
+        r#"
+        SELECT {CLAIM_LINK_COLUMNS}
+        FROM claim_links
+        WHERE id = $1
+            AND sender_wallet_id IN (
+                SELECT w.id
+                FROM wallets w
+                JOIN users u ON w.user_id = u.id
+                WHERE u.user_id = $2
+            )
+        "#
+    ))
+    .bind(id)
+    .bind(format!("tg:{}", telegram_user_id))
+    .fetch_optional(pool)
+    .await?;
+
+    link.map(ClaimLink::try_from).transpose()
+}
+
+pub async fn get_claim_link_by_secret_hash(
+    pool: &PgPool,
+    secret_hash: &str,
+) -> Result<Option<ClaimLink>> {
+    let link = sqlx::query_as::<_, ClaimLinkRow>(&format!(
+        r#"
+        SELECT {CLAIM_LINK_COLUMNS}
+        FROM claim_links
+        WHERE secret_hash = $1
+        "#
+    ))
+    .bind(secret_hash)
+    .fetch_optional(pool)
+    .await?;
+
+    link.map(ClaimLink::try_from).transpose()
+}
+
+/// Reserve an open, unexpired claim link for `wallet_id` and record the
+/// transfer paying it out, in one transaction. Returns the ledger id and the
+/// job id, or None if the link can't be claimed, including when another claim
+/// for it is in flight.
+pub async fn reserve_claim_link(
+    pool: &PgPool,
+    id: i64,
+    wallet_id: i64,
+    transfer: &NewTransfer<'_>,
+) -> Result<Option<(i64, i64)>> {
+    let mut tx = pool.begin().await?;
+
+    let reserved = sqlx::query(
+        r#"
+        UPDATE claim_links
+        SET status = 'claiming',
+            claimed_by_wallet_id = $2,
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'open' AND expires_at > NOW()
+        "#,
+    )
+    .bind(id)
+    .bind(wallet_id)
+    .execute(tx.as_mut())
+    .await?;
+    if reserved.rows_affected() != 1 {
+        return Ok(None);
+    }
+
+    let (transfer_id, job_id) = insert_transfer(&mut tx, transfer).await?;
+    sqlx::query(
+        r#"
+        UPDATE claim_links
+        SET claim_transfer_id = $2,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(transfer_id)
+    .execute(tx.as_mut())
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some((transfer_id, job_id)))
+}
+
+/// Move open claim links past their expiry to `expired`, so they can no
+/// longer be claimed and get refunded. Returns their ids.
+pub async fn expire_claim_links(pool: &PgPool) -> Result<Vec<i64>> {
+    let link_ids = sqlx::query_scalar::<_, i64>(
+        r#"
+        UPDATE claim_links
+        SET status = 'expired',
+            updated_at = NOW()
+        WHERE status = 'open' AND expires_at <= NOW()
+        RETURNING id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(link_ids)
+}
+
+#[derive(Debug, FromRow)]
+struct ClaimLinkRefundRow {
+    link_id: i64,
+    escrow_wallet_id: i64,
+    sender: String,
+    mint: String,
+    amount: String,
+    decimals: i16,
+}
+
+/// Expired claim links that have not been refunded yet, or whose refund
+/// failed.
+pub async fn get_pending_claim_link_refunds(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<ClaimLinkRefund>> {
+    let rows = sqlx::query_as::<_, ClaimLinkRefundRow>(
+        r#"
+        SELECT
+            cl.id AS link_id,
+            cl.escrow_wallet_id,
+            t.sender,
+            t.mint,
+            t.amount::TEXT AS amount,
+            t.decimals
+        FROM claim_links cl
+        JOIN transfers t ON t.id = cl.funding_transfer_id
+        LEFT JOIN transfers r ON r.id = cl.refund_transfer_id
+        WHERE cl.status = 'expired'
+            AND (cl.refund_transfer_id IS NULL OR r.status = 'failed')
+        ORDER BY cl.id
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ClaimLinkRefund {
+                link_id: row.link_id,
+                escrow_wallet_id: row.escrow_wallet_id,
+                sender: parse_pubkey(&row.sender)?,
+                mint: parse_pubkey(&row.mint)?,
+                amount: row
+                    .amount
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+                decimals: u8::try_from(row.decimals)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse decimals: {}", e))?,
+            })
+        })
+        .collect()
+}
+
+/// Record the transfer refunding an expired claim link, unless a refund that
+/// has not failed is already recorded for it. Returns the job id, or None if
+/// another sweep got to the link first.
+pub async fn start_claim_link_refund(
+    pool: &PgPool,
+    link_id: i64,
+    refund: &NewTransfer<'_>,
+) -> Result<Option<i64>> {
+    let mut tx = pool.begin().await?;
+    let (transfer_id, job_id) = insert_transfer(&mut tx, refund).await?;
+
+    let claimed = sqlx::query(
+        r#"
+        UPDATE claim_links
+        SET refund_transfer_id = $2,
+            updated_at = NOW()
+        WHERE id = $1
+            AND status = 'expired'
+            AND (
+                refund_transfer_id IS NULL
+                OR refund_transfer_id IN (SELECT id FROM transfers WHERE status = 'failed')
+            )
+        "#,
+    )
+    .bind(link_id)
+    .bind(transfer_id)
+    .execute(tx.as_mut())
+    .await?;
+    if claimed.rows_affected() != 1 {
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(job_id))
+}
+
+/// Open a link once its funding transfer lands (or fail it), mark it claimed
+/// once its claim transfer lands (or open it up again), and refunded once its
+/// refund lands.
+async fn settle_claim_link(
+    conn: &mut PgConnection,
+    transfer_id: i64,
+    status: TransferStatus,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE claim_links
+        SET status = CASE WHEN $2 = 'completed' THEN 'open' ELSE 'failed' END,
+            updated_at = NOW()
+        WHERE funding_transfer_id = $1 AND status = 'funding'
+        "#,
+    )
+    .bind(transfer_id)
+    .bind(status.as_str())
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE claim_links
+        SET status = CASE WHEN $2 = 'completed' THEN 'claimed' ELSE 'open' END,
+            claim_transfer_id = CASE WHEN $2 = 'completed' THEN claim_transfer_id END,
+            claimed_by_wallet_id = CASE WHEN $2 = 'completed' THEN claimed_by_wallet_id END,
+            updated_at = NOW()
+        WHERE claim_transfer_id = $1 AND status = 'claiming'
+        "#,
+    )
+    .bind(transfer_id)
+    .bind(status.as_str())
+    .execute(&mut *conn)
+    .await?;
+
+    // a failed refund is picked up again by the next sweep
+    sqlx::query(
+        r#"
+        UPDATE claim_links
+        SET status = 'refunded',
+            updated_at = NOW()
+        WHERE refund_transfer_id = $1 AND status = 'expired' AND $2 = 'completed'
+        "#,
+    )
+    .bind(transfer_id)
+    .bind(status.as_str())
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Record a transfer into a reserved wallet so it can be refunded if the wallet
+/// is never claimed, and push the wallet's expiry back to `ttl_secs` from now.
+/// Does nothing and returns false if the wallet isn't an unclaimed reserved wallet.
+pub async fn record_reserved_wallet_credit(
+    pool: &PgPool,
+    wallet_id: i64,
+    transfer_id: i64,
+    ttl_secs: i64,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        WITH reserved AS (
+            UPDATE wallets
+            SET reserved_until = GREATEST(
+                    COALESCE(wallets.reserved_until, NOW()),
+                    NOW() + $3 * INTERVAL '1 second'
+                ),
+                reservation_expired_at = NULL,
+                updated_at = NOW()
+            FROM users
+            WHERE wallets.id = $1
+                AND wallets.user_id = users.id
+                AND users.telegram_user_id IS NULL
+                AND users.user_id LIKE 'tg:reserved:%'
+            RETURNING wallets.id
+        )
+        INSERT INTO reserved_wallet_credits (wallet_id, transfer_id)
+        SELECT id, $2 FROM reserved
+        "#,
+    )
+    .bind(wallet_id)
+    .bind(transfer_id)
+    .bind(ttl_secs)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Mark unclaimed reserved wallets whose expiry has passed as expired, and
+/// cancel the notifications still waiting to tell their users about the
+/// transfers that are now refunded. Returns the ids of the wallets that just
+/// expired.
+pub async fn expire_reserved_wallets(pool: &PgPool) -> Result<Vec<i64>> {
+    let wallet_ids = sqlx::query_scalar::<_, i64>(
+        r#"
+        WITH expired AS (
+            UPDATE wallets
+            SET reservation_expired_at = NOW(),
+                updated_at = NOW()
+            FROM users
+            WHERE wallets.user_id = users.id
+                AND users.telegram_user_id IS NULL
+                AND users.user_id LIKE 'tg:reserved:%'
+                AND wallets.reserved_until <= NOW()
+                AND wallets.reservation_expired_at IS NULL
+            RETURNING wallets.id
+        ),
+        cancelled AS (
+            UPDATE notifications
+            SET status = 'cancelled'
+            WHERE status = 'pending'
+                AND transfer_id IN (
+                    SELECT c.transfer_id
+                    FROM reserved_wallet_credits c
+                    JOIN expired e ON e.id = c.wallet_id
+                )
+        )
+        SELECT id FROM expired
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(wallet_ids)
+}
+
+#[derive(Debug, FromRow)]
+struct ReservedWalletRefundRow {
+    credit_id: i64,
+    wallet_id: i64,
+    sender: String,
+    mint: String,
+    amount: String,
+    decimals: i16,
+    refund_attempts: i32,
+    stalled_job_id: Option<i64>,
+}
+
+/// Completed transfers into expired, still unclaimed reserved wallets that have
+/// not been refunded yet, whose refund job has not moved for `idle_secs` and
+/// isn't being run, or whose refund failed. A failed refund is retried
+/// `retry_delay_secs` after it failed, doubled after every further attempt, and
+/// not at all once it was attempted `max_attempts` times.
+pub async fn get_pending_reserved_wallet_refunds(
+    pool: &PgPool,
+    limit: i64,
+    idle_secs: i64,
+    max_attempts: i32,
+    retry_delay_secs: i64,
+) -> Result<Vec<ReservedWalletRefund>> {
+    let rows = sqlx::query_as::<_, ReservedWalletRefundRow>(
+        r#"
+        SELECT
+            c.id AS credit_id,
+            c.wallet_id,
+            t.sender,
+            t.mint,
+            t.amount::TEXT AS amount,
+            t.decimals,
+            c.refund_attempts,
+            CASE WHEN r.status = 'pending' THEN j.id END AS stalled_job_id
+        FROM reserved_wallet_credits c
+        JOIN transfers t ON t.id = c.transfer_id
+        JOIN wallets w ON w.id = c.wallet_id
+        JOIN users u ON u.id = w.user_id
+        LEFT JOIN transfers r ON r.id = c.refund_transfer_id
+        LEFT JOIN transfer_jobs j ON j.id = r.transfer_job_id
+        WHERE w.reservation_expired_at IS NOT NULL
+            AND u.telegram_user_id IS NULL
+            AND t.status = 'completed'
+            AND (
+                c.refund_transfer_id IS NULL
+                OR (
+                    r.status = 'failed'
+                    AND c.refund_attempts < $3
+                    AND r.updated_at
+                        <= NOW() - make_interval(secs => $4 * power(2, c.refund_attempts - 1))
+                )
+                OR (
+                    r.status = 'pending'
+                    AND (j.leased_until IS NULL OR j.leased_until <= NOW())
+                    AND j.updated_at <= NOW() - make_interval(secs => $2)
+                )
+            )
+        ORDER BY c.id
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .bind(idle_secs as f64)
+    .bind(max_attempts)
+    .bind(retry_delay_secs as f64)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ReservedWalletRefund {
+                credit_id: row.credit_id,
+                wallet_id: row.wallet_id,
+                sender: parse_pubkey(&row.sender)?,
+                mint: parse_pubkey(&row.mint)?,
+                amount: row
+                    .amount
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+                decimals: u8::try_from(row.decimals)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse decimals: {}", e))?,
+                refund_attempts: row.refund_attempts,
+                stalled_job_id: row.stalled_job_id,
+            })
+        })
+        .collect()
+}
+
+/// Record the transfer refunding a reserved wallet credit and count the
+/// attempt, unless a refund that has not failed is already recorded for it.
+/// Returns the job id, or None if another sweep got to the credit first.
+pub async fn start_reserved_wallet_refund(
+    pool: &PgPool,
+    credit_id: i64,
+    refund: &NewTransfer<'_>,
+) -> Result<Option<i64>> {
+    let mut tx = pool.begin().await?;
+    let (transfer_id, job_id) = insert_transfer(&mut tx, refund).await?;
+
+    let claimed = sqlx::query(
+        r#"
+        UPDATE reserved_wallet_credits
+        SET refund_transfer_id = $2,
+            refund_attempts = refund_attempts + 1,
+            updated_at = NOW()
+        WHERE id = $1
+            AND (
+                refund_transfer_id IS NULL
+                OR refund_transfer_id IN (SELECT id FROM transfers WHERE status = 'failed')
+            )
+        "#,
+    )
+    .bind(credit_id)
+    .bind(transfer_id)
+    .execute(tx.as_mut())
+    .await?;
+    if claimed.rows_affected() != 1 {
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(job_id))
+}
+
+#[derive(Debug, FromRow)]
+struct AbandonedReservedWalletRefundRow {
+    credit_id: i64,
+    wallet_id: i64,
+    sender: String,
+    mint: String,
+    amount: String,
+    decimals: i16,
+    refund_attempts: i32,
+    error: Option<String>,
+    failed_at: DateTime<Utc>,
+}
+
+/// Reserved wallet credits whose last refund failed after `max_attempts`
+/// attempts, which the sweeper no longer retries. Most recently failed first.
+pub async fn list_abandoned_reserved_wallet_refunds(
+    pool: &PgPool,
+    max_attempts: i32,
+    limit: i64,
+) -> Result<Vec<AbandonedReservedWalletRefund>> {
+    let rows = sqlx::query_as::<_, AbandonedReservedWalletRefundRow>(
+        r#"
+        SELECT
+            c.id AS credit_id,
+            c.wallet_id,
+            t.sender,
+            t.mint,
+            t.amount::TEXT AS amount,
+            t.decimals,
+            c.refund_attempts,
+            j.error,
+            r.updated_at AS failed_at
+        FROM reserved_wallet_credits c
+        JOIN transfers t ON t.id = c.transfer_id
+        JOIN transfers r ON r.id = c.refund_transfer_id
+        LEFT JOIN transfer_jobs j ON j.id = r.transfer_job_id
+        WHERE r.status = 'failed'
+            AND c.refund_attempts >= $1
+        ORDER BY r.updated_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(max_attempts)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(AbandonedReservedWalletRefund {
+                credit_id: row.credit_id,
+                wallet_id: row.wallet_id,
+                sender: parse_pubkey(&row.sender)?,
+                mint: parse_pubkey(&row.mint)?,
+                amount: row
+                    .amount
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+                decimals: u8::try_from(row.decimals)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse decimals: {}", e))?,
+                attempts: row.refund_attempts,
+                error: row.error,
+                failed_at: row.failed_at,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, FromRow)]
+pub struct ScheduledTransferRow {
+    pub id: i64,
+    pub wallet_id: i64,
+    pub sender: String,
+    pub recipient: String,
+    pub mint: String,
+    pub amount: String,
+    pub decimals: i16,
+    pub memo: Option<String>,
+    pub encrypt_memo: bool,
+    pub interval_secs: Option<i64>,
+    pub cron: Option<String>,
+    pub status: String,
+    pub next_run_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+const SCHEDULED_TRANSFER_COLUMNS: &str = r#"
+    s.id,
+    s.wallet_id,
+    s.sender,
+    s.recipient,
+    s.mint,
+    s.amount::TEXT AS amount,
+    s.decimals,
+    s.memo,
+    s.encrypt_memo,
+    s.interval_secs,
+    s.cron,
+    s.status,
+    s.next_run_at,
+    s.last_run_at,
+    s.created_at,
+    s.updated_at
+"#;
+
+impl TryFrom<ScheduledTransferRow> for ScheduledTransfer {
+    type Error = anyhow::Error;
+
+    fn try_from(scheduled: ScheduledTransferRow) -> Result<Self, Self::Error> {
+        Ok(ScheduledTransfer {
+            id: scheduled.id,
+            wallet_id: scheduled.wallet_id,
+            sender: parse_pubkey(&scheduled.sender)?,
+            recipient: parse_pubkey(&scheduled.recipient)?,
+            mint: parse_pubkey(&scheduled.mint)?,
+            amount: scheduled
+                .amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+            decimals: u8::try_from(scheduled.decimals)
+                .map_err(|e| anyhow::anyhow!("Failed to parse decimals: {}", e))?,
+            memo: scheduled.memo,
+            encrypt_memo: scheduled.encrypt_memo,
+            interval_secs: scheduled.interval_secs,
+            cron: scheduled.cron,
+            status: scheduled.status.parse()?,
+            next_run_at: scheduled.next_run_at,
+            last_run_at: scheduled.last_run_at,
+            created_at: scheduled.created_at,
+            updated_at: scheduled.updated_at,
+        })
+    }
+}
+
+/// Fields of a scheduled transfer chosen by its owner.
+pub struct NewScheduledTransfer<'a> {
+    pub wallet: &'a Wallet,
+    pub recipient: &'a Pubkey,
+    pub mint: &'a Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    pub memo: Option<&'a str>,
+    pub encrypt_memo: bool,
+    pub interval_secs: Option<i64>,
+    pub cron: Option<&'a str>,
+    pub next_run_at: DateTime<Utc>,
+}
+
+pub async fn create_scheduled_transfer(
+    pool: &PgPool,
+    new: &NewScheduledTransfer<'_>,
+) -> Result<ScheduledTransfer> {
+    let scheduled = sqlx::query_as::<_, ScheduledTransferRow>(&format!(
+        r#"
+        INSERT INTO scheduled_transfers AS s (
+            wallet_id,
+            sender,
+            recipient,
+            mint,
+            amount,
+            decimals,
+            memo,
+            encrypt_memo,
+            interval_secs,
+            cron,
+            next_run_at
+        )
+        VALUES ($1, $2, $3, $4, $5::NUMERIC, $6, $7, $8, $9, $10, $11)
+        RETURNING {SCHEDULED_TRANSFER_COLUMNS}
+        "#
+    ))
+    .bind(new.wallet.id)
+    .bind(new.wallet.pubkey.to_string())
+    .bind(new.recipient.to_string())
+    .bind(new.mint.to_string())
+    .bind(new.amount.to_string())
+    .bind(new.decimals as i16)
+    .bind(new.memo)
+    .bind(new.encrypt_memo)
+    .bind(new.interval_secs)
+    .bind(new.cron)
+    .bind(new.next_run_at)
+    .fetch_one(pool)
+    .await?;
+
+    scheduled.try_into()
+}
+
+/// A scheduled transfer, if it is paid from one of the telegram user's wallets.
+pub async fn get_scheduled_transfer_for_user(
+    pool: &PgPool,
+    id: i64,
+    telegram_user_id: i64,
+) -> Result<Option<ScheduledTransfer>> {
+    let scheduled = sqlx::query_as::<_, ScheduledTransferRow>(&format!(
+        r#"
+        SELECT {SCHEDULED_TRANSFER_COLUMNS}
+        FROM scheduled_transfers s
+        JOIN wallets w ON w.id = s.wallet_id
+        JOIN users u ON u.id = w.user_id
+        WHERE s.id = $1 AND u.user_id = $2
+        "#
+    ))
+    .bind(id)
+    .bind(format!("tg:{}", telegram_user_id))
+    .fetch_optional(pool)
+    .await?;
+
+    scheduled.map(ScheduledTransfer::try_from).transpose()
+}
+
+/// Scheduled transfers paid from any of the telegram user's wallets, newest first.
+pub async fn list_scheduled_transfers_for_user(
+    pool: &PgPool,
+    telegram_user_id: i64,
+) -> Result<Vec<ScheduledTransfer>> {
+    let scheduled = sqlx::query_as::<_, ScheduledTransferRow>(&format!(
+        r#"
+        SELECT {SCHEDULED_TRANSFER_COLUMNS}
+        FROM scheduled_transfers s
+        JOIN wallets w ON w.id = s.wallet_id
+        JOIN users u ON u.id = w.user_id
+        WHERE u.user_id = $1
+        ORDER BY s.id DESC
+        "#
+    ))
+    .bind(format!("tg:{}", telegram_user_id))
+    .fetch_all(pool)
+    .await?;
+
+    scheduled
+        .into_iter()
+        .map(ScheduledTransfer::try_from)
+        .collect()
+}
+
+/// Active scheduled transfers whose next run is due, most overdue first.
+pub async fn get_due_scheduled_transfers(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<ScheduledTransfer>> {
+    let scheduled = sqlx::query_as::<_, ScheduledTransferRow>(&format!(
+        r#"
+        SELECT {SCHEDULED_TRANSFER_COLUMNS}
+        FROM scheduled_transfers s
+        WHERE s.status = 'active' AND s.next_run_at <= NOW()
+        ORDER BY s.next_run_at ASC
+        LIMIT $1
+        "#
+    ))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    scheduled
+        .into_iter()
+        .map(ScheduledTransfer::try_from)
+        .collect()
+}
+
+/// Take the run of a scheduled transfer that was due at `due_at`, moving the
+/// schedule on to `next_run_at`. Returns false if the schedule is no longer
+/// active or the run was already taken.
+pub async fn claim_scheduled_transfer_run(
+    pool: &PgPool,
+    id: i64,
+    due_at: DateTime<Utc>,
+    next_run_at: DateTime<Utc>,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE scheduled_transfers
+        SET next_run_at = $3,
+            last_run_at = NOW(),
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'active' AND next_run_at = $2
+        "#,
+    )
+    .bind(id)
+    .bind(due_at)
+    .bind(next_run_at)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Stop an active scheduled transfer from running until it is resumed.
+pub async fn pause_scheduled_transfer(pool: &PgPool, id: i64) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE scheduled_transfers
+        SET status = 'paused',
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'active'
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn cancel_scheduled_transfer(pool: &PgPool, id: i64) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE scheduled_transfers
+        SET status = 'cancelled',
+            updated_at = NOW()
+        WHERE id = $1 AND status IN ('active', 'paused')
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Reactivate a paused scheduled transfer, next running at `next_run_at`.
+pub async fn resume_scheduled_transfer(
+    pool: &PgPool,
+    id: i64,
+    next_run_at: DateTime<Utc>,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE scheduled_transfers
+        SET status = 'active',
+            next_run_at = $2,
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'paused'
+        "#,
+    )
+    .bind(id)
+    .bind(next_run_at)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Record a run of a schedule that didn't send anything, see
+/// [`create_scheduled_transfer_run`] for one that did.
+pub async fn record_scheduled_transfer_run(
+    pool: &PgPool,
+    scheduled_transfer_id: i64,
+    scheduled_for: DateTime<Utc>,
+    status: ScheduledTransferRunStatus,
+    reason: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO scheduled_transfer_runs (
+            scheduled_transfer_id,
+            scheduled_for,
+            status,
+            reason
+        )
+        VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(scheduled_transfer_id)
+    .bind(scheduled_for)
+    .bind(status.as_str())
+    .bind(reason)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record a run of a schedule that sends a transfer, together with the
+/// transfer. The run stays pending until the transfer's job ends (see
+/// [`sync_transfer_with_job`]). Returns the ledger id and the job id.
+pub async fn create_scheduled_transfer_run(
+    pool: &PgPool,
+    scheduled_transfer_id: i64,
+    scheduled_for: DateTime<Utc>,
+    transfer: &NewTransfer<'_>,
+) -> Result<(i64, i64)> {
+    let mut tx = pool.begin().await?;
+    let (transfer_id, job_id) = insert_transfer(&mut tx, transfer).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO scheduled_transfer_runs (
+            scheduled_transfer_id,
+            scheduled_for,
+            status,
+            transfer_id,
+            transfer_job_id
+        )
+        VALUES ($1, $2, 'pending', $3, $4)
+        "#,
+    )
+    .bind(scheduled_transfer_id)
+    .bind(scheduled_for)
+    .bind(transfer_id)
+    .bind(job_id)
+    .execute(tx.as_mut())
+    .await?;
+    tx.commit().await?;
+
+    Ok((transfer_id, job_id))
+}
+
+/// Give a pending run the outcome of its transfer, with the job's error if
+/// it failed.
+async fn settle_scheduled_transfer_run(
+    conn: &mut PgConnection,
+    transfer_id: i64,
+    status: TransferStatus,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE scheduled_transfer_runs r
+        SET status = $2,
+            reason = CASE WHEN $2 = 'failed' THEN COALESCE(j.error, 'Transfer failed') END
+        FROM transfer_jobs j
+        WHERE r.transfer_id = $1
+            AND r.status = 'pending'
+            AND j.id = r.transfer_job_id
+        "#,
+    )
+    .bind(transfer_id)
+    .bind(status.as_str())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+struct ScheduledTransferRunRow {
+    id: i64,
+    scheduled_for: DateTime<Utc>,
+    status: String,
+    transfer_id: Option<i64>,
+    transfer_job_id: Option<i64>,
+    reason: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// Latest runs of a scheduled transfer, newest first.
+pub async fn list_scheduled_transfer_runs(
+    pool: &PgPool,
+    scheduled_transfer_id: i64,
+    limit: i64,
+) -> Result<Vec<ScheduledTransferRun>> {
+    let runs = sqlx::query_as::<_, ScheduledTransferRunRow>(
+        r#"
+        SELECT id, scheduled_for, status, transfer_id, transfer_job_id, reason, created_at
+        FROM scheduled_transfer_runs
+        WHERE scheduled_transfer_id = $1
+        ORDER BY id DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(scheduled_transfer_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    runs.into_iter()
+        .map(|run| {
+            Ok(ScheduledTransferRun {
+                id: run.id,
+                scheduled_for: run.scheduled_for,
+                status: run.status.parse()?,
+                transfer_id: run.transfer_id,
+                job_id: run.transfer_job_id,
+                reason: run.reason,
+                created_at: run.created_at,
+            })
+        })
+        .collect()
+}
+
+/// Store the proofs of a custodial withdraw before its transactions are sent.
+pub async fn create_withdraw_proofs(
+    pool: &PgPool,
+    wallet_id: i64,
+    equality_proof_account: &Pubkey,
+    range_proof_account: &Pubkey,
+    proofs: &[u8],
+) -> Result<i64> {
+    let id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO withdraw_proofs (wallet_id, equality_proof_account, range_proof_account, proofs)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(wallet_id)
+    .bind(equality_proof_account.to_string())
+    .bind(range_proof_account.to_string())
+    .bind(proofs)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id)
+}
+
+pub async fn delete_withdraw_proofs(pool: &PgPool, id: i64) -> Result<()> {
+    sqlx::query("DELETE FROM withdraw_proofs WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+struct PendingWithdrawProofsRow {
+    id: i64,
+    wallet_id: i64,
+    equality_proof_account: String,
+    range_proof_account: String,
+    proofs: Vec<u8>,
+}
+
+/// Withdraw proofs stored more than `idle_secs` ago, oldest first. A withdraw
+/// still in flight removes its own row once it lands.
+pub async fn get_stale_withdraw_proofs(
+    pool: &PgPool,
+    idle_secs: f64,
+    limit: i64,
+) -> Result<Vec<PendingWithdrawProofs>> {
+    let rows = sqlx::query_as::<_, PendingWithdrawProofsRow>(
+        r#"
+        SELECT
+            id,
+            wallet_id,
+            equality_proof_account,
+            range_proof_account,
+            proofs
+        FROM withdraw_proofs
+        WHERE created_at <= NOW() - make_interval(secs => $1)
+        ORDER BY created_at
+        LIMIT $2
+        "#,
+    )
+    .bind(idle_secs)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(PendingWithdrawProofs {
+                id: row.id,
+                wallet_id: row.wallet_id,
+                equality_proof_account: parse_pubkey(&row.equality_proof_account)?,
+                range_proof_account: parse_pubkey(&row.range_proof_account)?,
+                proofs: row.proofs,
+            })
+        })
+        .collect()
+}
+
+pub async fn start_proof_janitor_run(pool: &PgPool) -> Result<i64> {
+    let id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO proof_janitor_runs (started_at)
+        VALUES (NOW())
+        RETURNING id
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(id)
+}
+
+pub async fn finish_proof_janitor_run(
+    pool: &PgPool,
+    id: i64,
+    accounts_found: usize,
+    accounts_closed: usize,
+    accounts_unverified: usize,
+    lamports_reclaimed: u64,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE proof_janitor_runs
+        SET accounts_found = $2,
+            accounts_closed = $3,
+            accounts_unverified = $4,
+            lamports_reclaimed = $5::NUMERIC,
+            error = $6,
+            finished_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(accounts_found as i32)
+    .bind(accounts_closed as i32)
+    .bind(accounts_unverified as i32)
+    .bind(lamports_reclaimed.to_string())
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+struct ProofJanitorRunRow {
+    id: i64,
+    accounts_found: i32,
+    accounts_closed: i32,
+    accounts_unverified: i32,
+    lamports_reclaimed: String,
+    error: Option<String>,
+    started_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+/// Latest janitor runs, newest first.
+pub async fn list_proof_janitor_runs(pool: &PgPool, limit: i64) -> Result<Vec<ProofJanitorRun>> {
+    let runs = sqlx::query_as::<_, ProofJanitorRunRow>(
+        r#"
+        SELECT
+            id,
+            accounts_found,
+            accounts_closed,
+            accounts_unverified,
+            lamports_reclaimed::TEXT AS lamports_reclaimed,
+            error,
+            started_at,
+            finished_at
+        FROM proof_janitor_runs
+        ORDER BY id DESC
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    runs.into_iter()
+        .map(|run| {
+            Ok(ProofJanitorRun {
+                id: run.id,
+                accounts_found: run.accounts_found,
+                accounts_closed: run.accounts_closed,
+                accounts_unverified: run.accounts_unverified,
+                lamports_reclaimed: run
+                    .lamports_reclaimed
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Failed to parse lamports: {}", e))?,
+                error: run.error,
+                started_at: run.started_at,
+                finished_at: run.finished_at,
+            })
+        })
+        .collect()
+}
+
+/// Accounts closed and lamports reclaimed over every janitor run.
+pub async fn get_proof_janitor_totals(pool: &PgPool) -> Result<(i64, u64)> {
+    let (accounts_closed, lamports_reclaimed) = sqlx::query_as::<_, (i64, String)>(
+        r#"
+        SELECT
+            COALESCE(SUM(accounts_closed), 0)::BIGINT,
+            COALESCE(SUM(lamports_reclaimed), 0)::TEXT
+        FROM proof_janitor_runs
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((
+        accounts_closed,
+        lamports_reclaimed
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Failed to parse lamports: {}", e))?,
+    ))
+}
+
+/// The fee budget override and the fees sponsored in the current period for
+/// the user owning a wallet. `None` if nothing was sponsored for them yet.
+pub async fn get_fee_budget(pool: &PgPool, wallet_id: i64) -> Result<Option<(Option<u64>, u64)>> {
+    let row = sqlx::query_as::<_, (Option<String>, String)>(
+        r#"
+        SELECT
+            fb.budget_lamports::TEXT,
+            (CASE WHEN fb.period_start > NOW() - INTERVAL '1 day'
+                THEN fb.spent_lamports ELSE 0 END)::TEXT
+        FROM fee_budgets fb
+        JOIN wallets w ON w.user_id = fb.user_id
+        WHERE w.id = $1
+        "#,
+    )
+    .bind(wallet_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let parse = |lamports: String| {
+        lamports
+            .parse::<u64>()
+            .map_err(|e| anyhow::anyhow!("Failed to parse lamports: {}", e))
+    };
+    row.map(|(budget, spent)| Ok((budget.map(parse).transpose()?, parse(spent)?)))
+        .transpose()
+}
+
+/// Charge sponsored fees to the user owning a wallet, starting a new period
+/// if the current one is over a day old. Wallets without an owner, like claim
+/// link escrows, have no budget.
+pub async fn charge_fee_budget(pool: &PgPool, wallet_id: i64, lamports: u64) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO fee_budgets (user_id, spent_lamports)
+        SELECT user_id, $2::NUMERIC FROM wallets WHERE id = $1 AND user_id IS NOT NULL
+        ON CONFLICT (user_id) DO UPDATE SET
+            spent_lamports = CASE WHEN fee_budgets.period_start > NOW() - INTERVAL '1 day'
+                THEN fee_budgets.spent_lamports + EXCLUDED.spent_lamports
+                ELSE EXCLUDED.spent_lamports END,
+            period_start = CASE WHEN fee_budgets.period_start > NOW() - INTERVAL '1 day'
+                THEN fee_budgets.period_start
+                ELSE NOW() END,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(wallet_id)
+    .bind(lamports.to_string())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// The custodial wallet a Telegram user acts with from the bot, their oldest
+/// one.
+pub async fn get_custodial_wallet_for_telegram_user(
+    pool: &PgPool,
+    telegram_user_id: i64,
+) -> Result<Option<Wallet>> {
+    let wallet = sqlx::query_as::<_, WalletRow>(
+        r#"
+        SELECT w.*
+        FROM wallets w
+        JOIN users u ON w.user_id = u.id
+        WHERE u.telegram_user_id = $1 AND w.custody = 'custodial'
+        ORDER BY w.id
+        LIMIT 1
+        "#,
+    )
+    .bind(telegram_user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    wallet
+        .map(|w| Wallet::try_from(w).map_err(|e| anyhow::anyhow!("Failed to parse wallet: {}", e)))
+        .transpose()
+}
+
+/// Record a bot update, returning false if it was already received. Updates
+/// older than Telegram keeps redelivering (a day) are forgotten.
+pub async fn claim_telegram_update(pool: &PgPool, update_id: i64) -> Result<bool> {
+    sqlx::query("DELETE FROM telegram_updates WHERE received_at < NOW() - INTERVAL '2 days'")
+        .execute(pool)
+        .await?;
+
+    let claimed = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO telegram_updates (update_id)
+        VALUES ($1)
+        ON CONFLICT (update_id) DO NOTHING
+        RETURNING update_id
+        "#,
+    )
+    .bind(update_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(claimed.is_some())
+}
+
+/// Telegram username of the user owning a wallet, if they have one.
+pub async fn get_telegram_username_for_wallet(
+    pool: &PgPool,
+    wallet_id: i64,
+) -> Result<Option<String>> {
+    let username = sqlx::query_scalar::<_, Option<String>>(
+        r#"
+        SELECT u.telegram_username
+        FROM wallets w
+        JOIN users u ON w.user_id = u.id
+        WHERE w.id = $1
+        "#,
+    )
+    .bind(wallet_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(username.flatten())
+}
+
+/// Queue a notification to the user owning a wallet. Returns `None` for
+/// wallets without an owner, like claim link escrows.
+pub async fn enqueue_notification(
+    pool: &PgPool,
+    wallet_id: i64,
+    kind: NotificationKind,
+    text: &str,
+    transfer_id: Option<i64>,
+) -> Result<Option<i64>> {
+    let id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO notifications (user_id, kind, text, transfer_id)
+        SELECT user_id, $2, $3, $4 FROM wallets WHERE id = $1 AND user_id IS NOT NULL
+        RETURNING id
+        "#,
+    )
+    .bind(wallet_id)
+    .bind(kind.as_str())
+    .bind(text)
+    .bind(transfer_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(id)
+}
+
+#[derive(Debug, FromRow)]
+struct NotificationRow {
+    id: i64,
+    kind: String,
+    telegram_user_id: i64,
+    text: String,
+    attempts: i32,
+    wanted: bool,
+}
+
+/// Claim up to `limit` pending notifications that are due, to users who can
+/// be reached. Each one is leased for `lease_secs`, so it is retried if the
+/// process dies before recording the outcome.
+pub async fn claim_due_notifications(
+    pool: &PgPool,
+    limit: i64,
+    lease_secs: i64,
+) -> Result<Vec<Notification>> {
+    let rows = sqlx::query_as::<_, NotificationRow>(
+        r#"
+        UPDATE notifications n
+        SET attempts = n.attempts + 1,
+            next_attempt_at = NOW() + make_interval(secs => $2)
+        FROM users u
+        LEFT JOIN notification_preferences p ON p.user_id = u.id
+        WHERE u.id = n.user_id
+          AND n.id IN (
+            SELECT pending.id
+            FROM notifications pending
+            JOIN users reachable ON reachable.id = pending.user_id
+            WHERE pending.status = 'pending'
+              AND pending.next_attempt_at <= NOW()
+              AND reachable.telegram_user_id IS NOT NULL
+            ORDER BY pending.next_attempt_at
+            LIMIT $1
+            FOR UPDATE OF pending SKIP LOCKED
+          )
+        RETURNING
+            n.id,
+            n.kind,
+            u.telegram_user_id,
+            n.text,
+            n.attempts,
+            CASE n.kind
+                WHEN 'incoming_payment' THEN COALESCE(p.incoming_payments, TRUE)
+                ELSE TRUE
+            END AS wanted
+        "#,
+    )
+    .bind(limit)
+    .bind(lease_secs as f64)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(Notification {
+                id: row.id,
+                kind: row.kind.parse()?,
+                telegram_user_id: row.telegram_user_id,
+                text: row.text,
+                attempts: row.attempts,
+                wanted: row.wanted,
+            })
+        })
+        .collect()
+}
+
+/// Record the final outcome of a notification.
+pub async fn finish_notification(
+    pool: &PgPool,
+    id: i64,
+    status: NotificationStatus,
+    error: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE notifications
+        SET status = $2,
+            last_error = COALESCE($3, last_error),
+            sent_at = CASE WHEN $2 = 'sent' THEN NOW() ELSE sent_at END
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(status.as_str())
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Try a notification again in `delay_secs`.
+pub async fn retry_notification(
+    pool: &PgPool,
+    id: i64,
+    error: &str,
+    delay_secs: i64,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE notifications
+        SET last_error = $2,
+            next_attempt_at = NOW() + make_interval(secs => $3)
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(error)
+    .bind(delay_secs as f64)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Notification preferences of a Telegram user, the defaults if they never
+/// changed them.
+pub async fn get_notification_preferences(
+    pool: &PgPool,
+    telegram_user_id: i64,
+) -> Result<NotificationPreferences> {
+    let incoming_payments = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT p.incoming_payments
+        FROM notification_preferences p
+        JOIN users u ON u.id = p.user_id
+        WHERE u.telegram_user_id = $1
+        "#,
+    )
+    .bind(telegram_user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(incoming_payments
+        .map(|incoming_payments| NotificationPreferences { incoming_payments })
+        .unwrap_or_default())
+}
+
+/// Save the notification preferences of a Telegram user. Returns false if
+/// there is no such user.
+pub async fn set_notification_preferences(
+    pool: &PgPool,
+    telegram_user_id: i64,
+    preferences: NotificationPreferences,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO notification_preferences (user_id, incoming_payments)
+        SELECT id, $2 FROM users WHERE telegram_user_id = $1
+        ON CONFLICT (user_id) DO UPDATE SET
+            incoming_payments = EXCLUDED.incoming_payments,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(telegram_user_id)
+    .bind(preferences.incoming_payments)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[derive(Debug, FromRow)]
+pub struct InlinePaymentRow {
+    pub id: i64,
+    pub token: String,
+    pub sender_wallet_id: i64,
+    pub sender_telegram_user_id: i64,
+    pub sender_name: String,
+    pub mint: String,
+    pub amount: String,
+    pub inline_message_id: Option<String>,
+    pub status: String,
+    pub claimed_by_telegram_user_id: Option<i64>,
+    pub transfer_id: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+const INLINE_PAYMENT_COLUMNS: &str = r#"
+    id,
+    token,
+    sender_wallet_id,
+    sender_telegram_user_id,
+    sender_name,
+    mint,
+    amount::TEXT AS amount,
+    inline_message_id,
+    status,
+    claimed_by_telegram_user_id,
+    transfer_id,
+    expires_at,
+    created_at
+"#;
+
+impl TryFrom<InlinePaymentRow> for InlinePayment {
+    type Error = anyhow::Error;
+
+    fn try_from(payment: InlinePaymentRow) -> Result<Self, Self::Error> {
+        Ok(InlinePayment {
+            id: payment.id,
+            token: payment.token,
+            sender_wallet_id: payment.sender_wallet_id,
+            sender_telegram_user_id: payment.sender_telegram_user_id,
+            sender_name: payment.sender_name,
+            mint: parse_pubkey(&payment.mint)?,
+            amount: payment
+                .amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+            inline_message_id: payment.inline_message_id,
+            status: payment.status.parse()?,
+            claimed_by_telegram_user_id: payment.claimed_by_telegram_user_id,
+            transfer_id: payment.transfer_id,
+            expires_at: payment.expires_at,
+            created_at: payment.created_at,
+        })
+    }
+}
+
+/// Record an inline payment the sender posted in a chat, open for claims for
+/// `ttl_secs`. Telegram may report the same inline result more than once, a
+/// repeat is ignored.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_inline_payment(
+    pool: &PgPool,
+    token: &str,
+    sender_wallet: &Wallet,
+    sender_telegram_user_id: i64,
+    sender_name: &str,
+    mint: &Pubkey,
+    amount: u64,
+    inline_message_id: Option<&str>,
+    ttl_secs: i64,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO inline_payments (
+            token,
+            sender_wallet_id,
+            sender_telegram_user_id,
+            sender_name,
+            mint,
+            amount,
+            inline_message_id,
+            expires_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6::NUMERIC, $7, NOW() + $8 * INTERVAL '1 second')
+        ON CONFLICT (token) DO NOTHING
+        "#,
+    )
+    .bind(token)
+    .bind(sender_wallet.id)
+    .bind(sender_telegram_user_id)
+    .bind(sender_name)
+    .bind(mint.to_string())
+    .bind(amount.to_string())
+    .bind(inline_message_id)
+    .bind(ttl_secs)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_inline_payment_by_token(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<InlinePayment>> {
+    let payment = sqlx::query_as::<_, InlinePaymentRow>(&format!(
+        r#"
+        SELECT {INLINE_PAYMENT_COLUMNS}
+        FROM inline_payments
+        WHERE token = $1
+        "#
+    ))
+    .bind(token)
+    .fetch_optional(pool)
+    .await?;
+
+    payment.map(InlinePayment::try_from).transpose()
+}
+
+/// Mark an open, unexpired inline payment as being claimed by a Telegram user.
+/// Returns false if it was claimed by someone else first or has expired.
+pub async fn reserve_inline_payment(
+    pool: &PgPool,
+    id: i64,
+    claimed_by_telegram_user_id: i64,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE inline_payments
+        SET status = 'claiming',
+            claimed_by_telegram_user_id = $2,
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'open' AND expires_at > NOW()
+        "#,
+    )
+    .bind(id)
+    .bind(claimed_by_telegram_user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Link the transfer paying out a reserved inline payment.
+pub async fn set_inline_payment_transfer(pool: &PgPool, id: i64, transfer_id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE inline_payments
+        SET transfer_id = $2,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(transfer_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Open a reserved inline payment up again when its transfer couldn't be
+/// started. Once started, the transfer's outcome decides (see
+/// [`sync_transfer_with_job`]).
+pub async fn release_inline_payment(pool: &PgPool, id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE inline_payments
+        SET status = 'open',
+            claimed_by_telegram_user_id = NULL,
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'claiming' AND transfer_id IS NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Mark a payment claimed once its transfer lands, or put the offer back up
+/// if the transfer failed.
+async fn settle_inline_payment(
+    conn: &mut PgConnection,
+    transfer_id: i64,
+    status: TransferStatus,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE inline_payments
+        SET status = CASE WHEN $2 = 'completed' THEN 'claimed' ELSE 'open' END,
+            transfer_id = CASE WHEN $2 = 'completed' THEN transfer_id END,
+            claimed_by_telegram_user_id = CASE
+                WHEN $2 = 'completed' THEN claimed_by_telegram_user_id
+            END,
+            updated_at = NOW()
+        WHERE transfer_id = $1 AND status = 'claiming'
+        "#,
+    )
+    .bind(transfer_id)
+    .bind(status.as_str())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+pub struct PotRow {
+    pub id: i64,
+    pub chat_id: i64,
+    pub creator_wallet_id: i64,
+    pub creator_telegram_user_id: Option<i64>,
+    pub escrow_wallet_id: i64,
+    pub title: String,
+    pub mint: String,
+    pub target_amount: String,
+    pub collected_amount: String,
+    pub contributor_count: i64,
+    pub status: String,
+    pub withdraw_transfer_id: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Pots along with their creator and what they collected so far, filter on
+/// `p`.
+const POT_SELECT: &str = r#"
+    SELECT
+        p.id,
+        p.chat_id,
+        p.creator_wallet_id,
+        u.telegram_user_id AS creator_telegram_user_id,
+        p.escrow_wallet_id,
+        p.title,
+        p.mint,
+        p.target_amount::TEXT AS target_amount,
+        COALESCE(c.collected, 0)::TEXT AS collected_amount,
+        COALESCE(c.contributors, 0) AS contributor_count,
+        p.status,
+        p.withdraw_transfer_id,
+        p.expires_at,
+        p.created_at
+    FROM pots p
+    JOIN wallets w ON w.id = p.creator_wallet_id
+    JOIN users u ON u.id = w.user_id
+    LEFT JOIN LATERAL (
+        SELECT SUM(t.amount) AS collected, COUNT(DISTINCT pc.wallet_id) AS contributors
+        FROM pot_contributions pc
+        JOIN transfers t ON t.id = pc.transfer_id
+        WHERE pc.pot_id = p.id AND t.status = 'completed'
+    ) c ON TRUE
+"#;
+
+impl TryFrom<PotRow> for Pot {
+    type Error = anyhow::Error;
+
+    fn try_from(pot: PotRow) -> Result<Self, Self::Error> {
+        Ok(Pot {
+            id: pot.id,
+            chat_id: pot.chat_id,
+            creator_wallet_id: pot.creator_wallet_id,
+            creator_telegram_user_id: pot.creator_telegram_user_id,
+            escrow_wallet_id: pot.escrow_wallet_id,
+            title: pot.title,
+            mint: parse_pubkey(&pot.mint)?,
+            target_amount: pot
+                .target_amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse target amount: {}", e))?,
+            collected_amount: pot
+                .collected_amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Failed to parse collected amount: {}", e))?,
+            contributor_count: pot.contributor_count,
+            status: pot.status.parse()?,
+            withdraw_transfer_id: pot.withdraw_transfer_id,
+            expires_at: pot.expires_at,
+            created_at: pot.created_at,
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn create_pot(
+    pool: &PgPool,
+    chat_id: i64,
+    creator_wallet: &Wallet,
+    escrow_wallet: &Wallet,
+    title: &str,
+    mint: &Pubkey,
+    target_amount: u64,
+    expires_at: DateTime<Utc>,
+) -> Result<Pot> {
+    let id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO pots (
+            chat_id,
+            creator_wallet_id,
+            escrow_wallet_id,
+            title,
+            mint,
+            target_amount,
+            expires_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6::NUMERIC, $7)
+        RETURNING id
+        "#,
+    )
+    .bind(chat_id)
+    .bind(creator_wallet.id)
+    .bind(escrow_wallet.id)
+    .bind(title)
+    .bind(mint.to_string())
+    .bind(target_amount.to_string())
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await?;
+
+    get_pot(pool, id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Pot {} not found", id))
+}
+
+pub async fn get_pot(pool: &PgPool, id: i64) -> Result<Option<Pot>> {
+    let pot = sqlx::query_as::<_, PotRow>(&format!("{POT_SELECT} WHERE p.id = $1"))
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    pot.map(Pot::try_from).transpose()
+}
+
+/// Pots of a Telegram chat, newest first.
+pub async fn list_pots_for_chat(pool: &PgPool, chat_id: i64, limit: i64) -> Result<Vec<Pot>> {
+    let pots = sqlx::query_as::<_, PotRow>(&format!(
+        "{POT_SELECT} WHERE p.chat_id = $1 ORDER BY p.id DESC LIMIT $2"
+    ))
+    .bind(chat_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    pots.into_iter().map(Pot::try_from).collect()
+}
+
+#[derive(Debug, FromRow)]
+struct PotContributorRow {
+    wallet: String,
+    telegram_username: Option<String>,
+    amount: String,
+}
+
+/// Completed contributions to a pot, summed per wallet, largest first.
+pub async fn get_pot_contributors(pool: &PgPool, pot_id: i64) -> Result<Vec<PotContributor>> {
+    let rows = sqlx::query_as::<_, PotContributorRow>(
+        r#"
+        SELECT
+            w.pubkey AS wallet,
+            u.telegram_username,
+            SUM(t.amount)::TEXT AS amount
+        FROM pot_contributions pc
+        JOIN transfers t ON t.id = pc.transfer_id
+        JOIN wallets w ON w.id = pc.wallet_id
+        JOIN users u ON u.id = w.user_id
+        WHERE pc.pot_id = $1 AND t.status = 'completed'
+        GROUP BY w.pubkey, u.telegram_username
+        ORDER BY SUM(t.amount) DESC, w.pubkey
+        "#,
+    )
+    .bind(pot_id)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(PotContributor {
+                wallet: parse_pubkey(&row.wallet)?,
+                telegram_username: row.telegram_username,
+                amount: row
+                    .amount
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+            })
+        })
+        .collect()
+}
+
+/// Record a contribution to an open, unexpired pot together with the transfer
+/// paying it, returning the ids of the transfer and its job. Returns `None`,
+/// recording nothing, if the pot isn't open anymore. The pot's row stays
+/// locked until the contribution is recorded, so [`start_pot_close`] sees it.
+pub async fn create_pot_contribution(
+    pool: &PgPool,
+    pot_id: i64,
+    contribution: &NewTransfer<'_>,
+) -> Result<Option<(i64, i64)>> {
+    let mut tx = pool.begin().await?;
+    let (transfer_id, job_id) = insert_transfer(&mut tx, contribution).await?;
+
+    let id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO pot_contributions (pot_id, wallet_id, transfer_id)
+        SELECT id, $2, $3
+        FROM pots
+        WHERE id = $1 AND status = 'open' AND expires_at > NOW()
+        FOR SHARE
+        RETURNING id
+        "#,
+    )
+    .bind(pot_id)
+    .bind(contribution.sender_wallet.id)
+    .bind(transfer_id)
+    .fetch_optional(tx.as_mut())
+    .await?;
+    if id.is_none() {
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+
+    Ok(Some((transfer_id, job_id)))
+}
+
+/// Mark an open pot as closing, before its collected amount is withdrawn.
+/// Returns false if it isn't open or a contribution is still in flight, which
+/// would be left behind in the escrow wallet.
+pub async fn start_pot_close(pool: &PgPool, id: i64) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+
+    // waits for contributions being recorded, so the check below sees them
+    let status =
+        sqlx::query_scalar::<_, String>("SELECT status FROM pots WHERE id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    if status.as_deref() != Some("open") {
+        return Ok(false);
+    }
+
+    let in_flight = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM pot_contributions pc
+            JOIN transfers t ON t.id = pc.transfer_id
+            WHERE pc.pot_id = $1 AND t.status = 'pending'
+        )
+        "#,
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if in_flight {
+        return Ok(false);
+    }
+
+    sqlx::query("UPDATE pots SET status = 'closing', updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Link the transfer withdrawing a closing pot.
+pub async fn set_pot_withdraw_transfer(pool: &PgPool, id: i64, transfer_id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE pots
+        SET withdraw_transfer_id = $2,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(transfer_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Close a closing pot that has nothing to withdraw.
+pub async fn set_pot_closed(pool: &PgPool, id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE pots
+        SET status = 'closed',
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'closing'
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Open a closing pot up again when its withdraw couldn't be started. Once
+/// started, the transfer's outcome decides (see [`sync_transfer_with_job`]).
+pub async fn reopen_pot(pool: &PgPool, id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE pots
+        SET status = 'open',
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'closing' AND withdraw_transfer_id IS NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Close a pot once its withdraw lands, or open it up again if the withdraw
+/// failed.
+async fn settle_pot_withdrawal(
+    conn: &mut PgConnection,
+    transfer_id: i64,
+    status: TransferStatus,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE pots
+        SET status = CASE WHEN $2 = 'completed' THEN 'closed' ELSE 'open' END,
+            withdraw_transfer_id = CASE WHEN $2 = 'completed' THEN withdraw_transfer_id END,
+            updated_at = NOW()
+        WHERE withdraw_transfer_id = $1 AND status = 'closing'
+        "#,
+    )
+    .bind(transfer_id)
+    .bind(status.as_str())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Mark open pots whose expiry has passed as expired. Returns the ids of the
+/// pots that just expired.
+pub async fn expire_pots(pool: &PgPool) -> Result<Vec<i64>> {
+    let pot_ids = sqlx::query_scalar::<_, i64>(
+        r#"
+        UPDATE pots
+        SET status = 'expired',
+            updated_at = NOW()
+        WHERE status = 'open' AND expires_at <= NOW()
+        RETURNING id
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(pot_ids)
+}
+
+#[derive(Debug, FromRow)]
+struct PotRefundRow {
+    contribution_id: i64,
+    escrow_wallet_id: i64,
+    contributor: String,
+    mint: String,
+    amount: String,
+    decimals: i16,
+}
+
+/// Completed contributions to expired pots that have not been refunded yet,
+/// or whose refund failed.
+pub async fn get_pending_pot_refunds(pool: &PgPool, limit: i64) -> Result<Vec<PotRefund>> {
+    let rows = sqlx::query_as::<_, PotRefundRow>(
+        r#"
+        SELECT
+            pc.id AS contribution_id,
+            p.escrow_wallet_id,
+            t.sender AS contributor,
+            t.mint,
+            t.amount::TEXT AS amount,
+            t.decimals
+        FROM pot_contributions pc
+        JOIN pots p ON p.id = pc.pot_id
+        JOIN transfers t ON t.id = pc.transfer_id
+        LEFT JOIN transfers r ON r.id = pc.refund_transfer_id
+        WHERE p.status = 'expired'
+            AND t.status = 'completed'
+            AND (pc.refund_transfer_id IS NULL OR r.status = 'failed')
+        ORDER BY pc.id
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(PotRefund {
+                contribution_id: row.contribution_id,
+                escrow_wallet_id: row.escrow_wallet_id,
+                contributor: parse_pubkey(&row.contributor)?,
+                mint: parse_pubkey(&row.mint)?,
+                amount: row
+                    .amount
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Failed to parse amount: {}", e))?,
+                decimals: u8::try_from(row.decimals)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse decimals: {}", e))?,
+            })
+        })
+        .collect()
+}
+
+/// Record the refund of a contribution to an expired pot, claiming the
+/// contribution in the same transaction. Returns the refund's job id, or None
+/// if the contribution is already being refunded or was refunded.
+pub async fn start_pot_refund(
+    pool: &PgPool,
+    contribution_id: i64,
+    refund: &NewTransfer<'_>,
+) -> Result<Option<i64>> {
+    let mut tx = pool.begin().await?;
+    let (transfer_id, job_id) = insert_transfer(&mut tx, refund).await?;
+
+    let claimed = sqlx::query(
+        r#"
+        UPDATE pot_contributions
+        SET refund_transfer_id = $2,
+            updated_at = NOW()
+        WHERE id = $1
+            AND pot_id IN (SELECT id FROM pots WHERE status = 'expired')
+            AND (
+                refund_transfer_id IS NULL
+                OR refund_transfer_id IN (SELECT id FROM transfers WHERE status = 'failed')
+            )
+        "#,
+    )
+    .bind(contribution_id)
+    .bind(transfer_id)
+    .execute(tx.as_mut())
+    .await?;
+    if claimed.rows_affected() != 1 {
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+
+    Ok(Some(job_id))
+}
+
+/// Start a session for a Telegram user that lasts `ttl_secs` unless it is
+/// refreshed. Returns when it expires.
+pub async fn create_session(
+    pool: &PgPool,
+    id: Uuid,
+    telegram_user_id: i64,
+    refresh_token_hash: &str,
+    ttl_secs: i64,
+) -> Result<DateTime<Utc>> {
+    let expires_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+        r#"
+        INSERT INTO sessions (id, user_id, refresh_token_hash, expires_at)
+        SELECT $1, id, $3, NOW() + $4 * INTERVAL '1 second'
+        FROM users
+        WHERE telegram_user_id = $2
+        RETURNING expires_at
+        "#,
+    )
+    .bind(id)
+    .bind(telegram_user_id)
+    .bind(refresh_token_hash)
+    .bind(ttl_secs)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("User {} not found", telegram_user_id))?;
+
+    Ok(expires_at)
+}
+
+#[derive(Debug, FromRow)]
+struct SessionRow {
+    id: Uuid,
+    telegram_user_id: i64,
+    username: Option<String>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Delete sessions that expired or were revoked more than `retention_secs`
+/// ago, along with the refresh tokens they used.
+pub async fn delete_ended_sessions(pool: &PgPool, retention_secs: i64) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM sessions
+        WHERE COALESCE(revoked_at, expires_at) < NOW() - make_interval(secs => $1)
+        "#,
+    )
+    .bind(retention_secs as f64)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Replace the refresh token of the active session it belongs to, remembering
+/// the old one as used, and extend the session to `ttl_secs` from now.
+/// Returns `None` if no active session has this refresh token.
+pub async fn rotate_session(
+    pool: &PgPool,
+    refresh_token_hash: &str,
+    new_refresh_token_hash: &str,
+    ttl_secs: i64,
+) -> Result<Option<Session>> {
+    let mut tx = pool.begin().await?;
+    let session = sqlx::query_as::<_, SessionRow>(
+        r#"
+        UPDATE sessions s
+        SET refresh_token_hash = $2,
+            expires_at = NOW() + $3 * INTERVAL '1 second',
+            updated_at = NOW()
+        FROM users u
+        WHERE u.id = s.user_id
+            AND s.refresh_token_hash = $1
+            AND s.revoked_at IS NULL
+            AND s.expires_at > NOW()
+        RETURNING s.id, u.telegram_user_id, u.telegram_username AS username, s.expires_at
+        "#,
+    )
+    .bind(refresh_token_hash)
+    .bind(new_refresh_token_hash)
+    .bind(ttl_secs)
+    .fetch_optional(tx.as_mut())
+    .await?;
+    let Some(session) = session else {
+        return Ok(None);
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO used_refresh_tokens (refresh_token_hash, session_id)
+        VALUES ($1, $2)
+        "#,
+    )
+    .bind(refresh_token_hash)
+    .bind(session.id)
+    .execute(tx.as_mut())
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some(Session {
+        id: session.id,
+        telegram_user_id: session.telegram_user_id,
+        username: session.username,
+        expires_at: session.expires_at,
+    }))
+}
+
+/// Revoke the session that already replaced this refresh token: it was used
+/// again, so someone else has a copy. Returns false if it isn't a used
+/// refresh token of an active session.
+pub async fn revoke_session_by_used_refresh_token(
+    pool: &PgPool,
+    refresh_token_hash: &str,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sessions
+        SET revoked_at = NOW(),
+            updated_at = NOW()
+        WHERE id IN (
+                SELECT session_id FROM used_refresh_tokens WHERE refresh_token_hash = $1
+            )
+            AND revoked_at IS NULL
+        "#,
+    )
+    .bind(refresh_token_hash)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether access tokens of a session are still accepted.
+pub async fn is_session_active(pool: &PgPool, id: Uuid) -> Result<bool> {
+    let active = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM sessions
+            WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        )
+        "#,
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(active)
+}
+
+/// Revoke one of a Telegram user's sessions. Returns false if it was already
+/// revoked or isn't theirs.
+pub async fn revoke_session(pool: &PgPool, id: Uuid, telegram_user_id: i64) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sessions s
+        SET revoked_at = NOW(),
+            updated_at = NOW()
+        FROM users u
+        WHERE u.id = s.user_id
+            AND s.id = $1
+            AND u.telegram_user_id = $2
+            AND s.revoked_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .bind(telegram_user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Revoke every active session of a Telegram user. Returns how many there
+/// were.
+pub async fn revoke_sessions_for_telegram_user(
+    pool: &PgPool,
+    telegram_user_id: i64,
+) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sessions s
+        SET revoked_at = NOW(),
+            updated_at = NOW()
+        FROM users u
+        WHERE u.id = s.user_id
+            AND u.telegram_user_id = $1
+            AND s.revoked_at IS NULL
+        "#,
+    )
+    .bind(telegram_user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_signer::Signer;
+
+    /// A user with a custodial wallet, for tests against a database.
+    async fn test_wallet(pool: &PgPool) -> Result<Wallet> {
+        // unique across runs, unlike `Pubkey::new_unique`
+        let pubkey = solana_keypair::Keypair::new().pubkey();
+        let secret = SealedSecret {
+            secret: pubkey.to_string(),
+            kek_version: None,
+        };
+        let (_, wallet_id) =
+            create_user_and_wallet(pool.clone(), &pubkey.to_string(), &pubkey, &secret).await?;
+        Ok(get_wallet_by_id(pool, wallet_id).await?.unwrap())
+    }
+
+    fn new_transfer<'a>(
+        wallet: &'a Wallet,
+        recipient: &'a Pubkey,
+        mint: &'a Pubkey,
+    ) -> NewTransfer<'a> {
+        NewTransfer {
+            sender_wallet: wallet,
+            recipient,
+            mint,
+            amount: 1,
+            decimals: 2,
+            onchain_memo: None,
+            memo: None,
+            memo_encrypted: false,
+            balance_funded: true,
+        }
+    }
+
+    async fn test_job(pool: &PgPool, wallet: &Wallet) -> Result<i64> {
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (_, job_id) = create_transfer(pool, &new_transfer(wallet, &recipient, &mint)).await?;
+        Ok(job_id)
+    }
+
+    // needs a migrated database at DATABASE_URL
+    #[tokio::test]
+    #[ignore]
+    async fn test_stalled_jobs_wait_for_leased_jobs_of_their_sender() -> Result<()> {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+        let wallet = test_wallet(&pool).await?;
+        let job_ids = [
+            test_job(&pool, &wallet).await?,
+            test_job(&pool, &wallet).await?,
+            test_job(&pool, &wallet).await?,
+        ];
+        let stalled = |jobs: Vec<TransferJob>| {
+            jobs.into_iter()
+                .map(|job| job.id)
+                .filter(|id| job_ids.contains(id))
+                .collect::<Vec<_>>()
+        };
+
+        // a batch leases its jobs up front, then runs and releases them in order
+        let batch_lease = Uuid::new_v4();
+        lease_transfer_jobs(&pool, &job_ids, batch_lease, 600).await?;
+        assert!(stalled(get_stalled_transfer_jobs(&pool, 0).await?).is_empty());
+
+        // the first job paused on an error while the rest of the batch waits
+        release_transfer_job(&pool, job_ids[0], batch_lease).await?;
+        assert!(stalled(get_stalled_transfer_jobs(&pool, 0).await?).is_empty());
+
+        // someone else can't take over a job the batch holds
+        assert!(!lease_transfer_job(&pool, job_ids[1], Uuid::new_v4(), 600).await?);
+
+        release_transfer_job(&pool, job_ids[1], batch_lease).await?;
+        release_transfer_job(&pool, job_ids[2], batch_lease).await?;
+        assert_eq!(
+            stalled(get_stalled_transfer_jobs(&pool, 0).await?),
+            job_ids.to_vec()
+        );
+
+        for job_id in job_ids {
+            set_transfer_job_status(&pool, job_id, TransferJobStatus::Failed, None).await?;
+        }
+        Ok(())
+    }
+
+    // needs a migrated database at DATABASE_URL
+    #[tokio::test]
+    #[ignore]
+    async fn test_scheduled_transfer_run_takes_the_outcome_of_its_job() -> Result<()> {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+        let wallet = test_wallet(&pool).await?;
+        let (recipient, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let scheduled = create_scheduled_transfer(
+            &pool,
+            &NewScheduledTransfer {
+                wallet: &wallet,
+                recipient: &recipient,
+                mint: &mint,
+                amount: 1,
+                decimals: 2,
+                memo: None,
+                encrypt_memo: false,
+                interval_secs: Some(60),
+                cron: None,
+                next_run_at: Utc::now(),
+            },
+        )
+        .await?;
+        let (transfer_id, job_id) = create_scheduled_transfer_run(
+            &pool,
+            scheduled.id,
+            scheduled.next_run_at,
+            &new_transfer(&wallet, &recipient, &mint),
+        )
+        .await?;
+
+        let runs = list_scheduled_transfer_runs(&pool, scheduled.id, 10).await?;
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, ScheduledTransferRunStatus::Pending);
+        assert_eq!(runs[0].transfer_id, Some(transfer_id));
+        assert_eq!(runs[0].job_id, Some(job_id));
+
+        // the job paused on an error and the recovery loop later rolled it back
+        set_transfer_job_status(
+            &pool,
+            job_id,
+            TransferJobStatus::Failed,
+            Some("Proof rejected"),
+        )
+        .await?;
+        sync_transfer_with_job(&pool, &get_transfer_job(&pool, job_id).await?.unwrap()).await?;
+
+        let runs = list_scheduled_transfer_runs(&pool, scheduled.id, 10).await?;
+        assert_eq!(runs[0].status, ScheduledTransferRunStatus::Failed);
+        assert_eq!(runs[0].reason.as_deref(), Some("Proof rejected"));
+        Ok(())
+    }
+
+    // needs a migrated database at DATABASE_URL
+    #[tokio::test]
+    #[ignore]
+    async fn test_failed_reserved_wallet_refunds_back_off_and_give_up() -> Result<()> {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+        let sender = test_wallet(&pool).await?;
+        let keypair = solana_keypair::Keypair::new();
+        let reserved = get_or_create_wallet_for_username(
+            &pool,
+            &keypair.pubkey().to_string(),
+            &keypair.pubkey(),
+            &SealedSecret {
+                secret: keypair.pubkey().to_string(),
+                kek_version: None,
+            },
+        )
+        .await?;
+        let mint = Pubkey::new_unique();
+        let (transfer_id, job_id) =
+            create_transfer(&pool, &new_transfer(&sender, &reserved.pubkey, &mint)).await?;
+        complete_transfer_job_bundle(&pool, job_id, &[Signature::default(); 5]).await?;
+        sync_transfer_with_job(&pool, &get_transfer_job(&pool, job_id).await?.unwrap()).await?;
+        assert!(record_reserved_wallet_credit(&pool, reserved.id, transfer_id, 0).await?);
+        expire_reserved_wallets(&pool).await?;
+
+        let pending = |retry_delay_secs: i64| {
+            let pool = pool.clone();
+            async move {
+                let refunds =
+                    get_pending_reserved_wallet_refunds(&pool, 1000, 0, 2, retry_delay_secs)
+                        .await?;
+                anyhow::Ok(refunds.into_iter().find(|r| r.wallet_id == reserved.id))
+            }
+        };
+        let credit_id = pending(0).await?.unwrap().credit_id;
+
+        for attempt in 1..=2 {
+            let refund = new_transfer(&reserved, &sender.pubkey, &mint);
+            let job_id = start_reserved_wallet_refund(&pool, credit_id, &refund)
+                .await?
+                .unwrap();
+            set_transfer_job_status(&pool, job_id, TransferJobStatus::Failed, Some("No funds"))
+                .await?;
+            sync_transfer_with_job(&pool, &get_transfer_job(&pool, job_id).await?.unwrap()).await?;
+
+            if attempt == 1 {
+                // retried once the backoff has passed
+                assert!(pending(3600).await?.is_none());
+                assert_eq!(pending(0).await?.unwrap().refund_attempts, 1);
+            }
+        }
+
+        // out of attempts, left for an admin
+        assert!(pending(0).await?.is_none());
+        let abandoned = list_abandoned_reserved_wallet_refunds(&pool, 2, 1000).await?;
+        let abandoned = abandoned.iter().find(|r| r.credit_id == credit_id).unwrap();
+        assert_eq!(abandoned.attempts, 2);
+        assert_eq!(abandoned.error.as_deref(), Some("No funds"));
+        Ok(())
+    }
+
+    // needs a migrated database at DATABASE_URL
+    #[tokio::test]
+    #[ignore]
+    async fn test_audit_scans_of_a_mint_are_stored_once_and_listed_in_pages() -> Result<()> {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL")?).await?;
+        let mint = solana_keypair::Keypair::new().pubkey();
+        let records = (0..3u8)
+            .map(|i| AuditRecord {
+                signature: solana_keypair::Keypair::new().sign_message(&[i]),
+                instruction_index: i,
+                slot: 100 + u64::from(i),
+                block_time: None,
+                kind: crate::solana::audit::AuditKind::Transfer,
+                source: None,
+                destination: None,
+                amount: Some(1),
+            })
+            .collect::<Vec<_>>();
+        let start = get_audit_scan_cursor(&pool, &mint).await?;
+        let scanned = AuditScanCursor {
+            scanned_until: Some(records[2].signature),
+            in_progress: None,
+        };
+
+        // two requests scanned the same batch, only the first one is stored
+        assert!(save_audit_scan(&pool, &mint, &records, &start, &scanned).await?);
+        assert!(!save_audit_scan(&pool, &mint, &records[..1], &start, &scanned).await?);
+        assert_eq!(get_audit_scan_cursor(&pool, &mint).await?, scanned);
+
+        let first = list_audit_entries(&pool, &mint, None, None, None, None, 2).await?;
+        assert_eq!(first, records[..2]);
+        let after = first[1].position();
+        let second = list_audit_entries(&pool, &mint, None, None, None, Some(&after), 2).await?;
+        assert_eq!(second, records[2..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+//! Fee sponsorship for custodial wallets.
+//!
+//! Custodial wallets never hold SOL. Every transaction the API sends for one
+//! is paid for by the fee payer (`FEE_PAYER_KP`, the global authority if
+//! unset), which also funds the rent of the proof context state accounts and
+//! gets it back when they are closed. The wallet only signs as the owner.
+//!
+//! What the fee payer spends on a user's behalf is charged to their daily
+//! budget in `fee_budgets`: `FEE_BUDGET_LAMPORTS`, unless overridden for that
+//! user. An operation is only started while some of the budget is left, so a
+//! user can go over it by at most one operation.
+//!
+//! Clusters can turn sponsorship off (see [`crate::cluster`]), in which case
+//! every wallet pays for its own transactions and has no budget.
+
+use crate::handlers::AppError;
+use crate::solana::airdrop::request_airdrop_and_confirm;
+use crate::solana::transaction::{build_transaction, required_signers};
+use crate::{AppState, db};
+use anyhow::Result;
+use axum::http::StatusCode;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use solana_transaction::versioned::VersionedTransaction;
+use std::sync::Arc;
+use tracing::error;
+
+type SharedSigner = Arc<dyn Signer + Send + Sync>;
+
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+pub const DEFAULT_FEE_BUDGET_LAMPORTS: u64 = 10_000_000;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Daily fee budget of {budget} lamports is used up, try again tomorrow")]
+pub struct FeeBudgetExceeded {
+    pub budget: u64,
+}
+
+/// Base fee of a transaction, without priority fees.
+pub fn transaction_fee(transaction: &VersionedTransaction) -> u64 {
+    u64::from(transaction.message.header().num_required_signatures) * LAMPORTS_PER_SIGNATURE
+}
+
+/// Who pays for the transactions of `owner`.
+pub fn payer(state: &AppState, owner: &SharedSigner) -> SharedSigner {
+    if state.config.cluster.fee_sponsorship {
+        state.fee_payer.clone()
+    } else {
+        owner.clone()
+    }
+}
+
+/// Build a transaction for `owner`, paid for by [`payer`] and signed by
+/// whichever of the owner and `signers` the instructions need.
+pub async fn build_sponsored_transaction(
+    state: &AppState,
+    owner: SharedSigner,
+    instructions: Vec<Instruction>,
+    mut signers: Vec<SharedSigner>,
+) -> Result<VersionedTransaction> {
+    let payer = payer(state, &owner);
+    signers.push(owner);
+    let signers = required_signers(&instructions, &signers);
+    build_transaction(state.rpc_client.clone(), None, instructions, payer, signers).await
+}
+
+/// Without sponsorship a new custodial wallet pays its own fees, so it is
+/// funded with an airdrop where the cluster has them.
+pub async fn fund_new_wallet(state: &AppState, pubkey: &Pubkey) -> Result<()> {
+    if state.config.cluster.fee_sponsorship || !state.config.cluster.airdrops {
+        return Ok(());
+    }
+    request_airdrop_and_confirm(state.rpc_client.clone(), pubkey, 10_u64.pow(9)).await?;
+    Ok(())
+}
+
+fn check_budget(budget: u64, spent: u64) -> Result<(), FeeBudgetExceeded> {
+    if spent >= budget {
+        return Err(FeeBudgetExceeded { budget });
+    }
+    Ok(())
+}
+
+/// Check whether the owner of a wallet has any fee budget left.
+pub async fn check(state: &AppState, wallet_id: i64) -> Result<Result<(), FeeBudgetExceeded>> {
+    if !state.config.cluster.fee_sponsorship {
+        return Ok(Ok(()));
+    }
+    let (budget, spent) = match db::get_fee_budget(&state.db, wallet_id).await? {
+        Some((budget, spent)) => (budget.unwrap_or(state.config.fee_budget_lamports), spent),
+        None => (state.config.fee_budget_lamports, 0),
+    };
+    Ok(check_budget(budget, spent))
+}
+
+/// [`check`] for handlers, an exhausted budget is a 429.
+pub async fn ensure_budget(state: &AppState, wallet_id: i64) -> Result<(), AppError> {
+    check(state, wallet_id)
+        .await?
+        .map_err(|e| AppError::new(e, StatusCode::TOO_MANY_REQUESTS))
+}
+
+/// Charge fees the fee payer already paid to the owner of a wallet. The
+/// transactions have landed by now, so a failure is only logged.
+pub async fn charge(state: &AppState, wallet_id: i64, lamports: u64) {
+    if lamports == 0 || !state.config.cluster.fee_sponsorship {
+        return;
+    }
+    if let Err(e) = db::charge_fee_budget(&state.db, wallet_id, lamports).await {
+        error!(
+            wallet_id,
+            lamports, "Failed to charge sponsored fees: {:?}", e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_budget() {
+        assert!(check_budget(10_000, 0).is_ok());
+        assert!(check_budget(10_000, 9_999).is_ok());
+        assert_eq!(check_budget(10_000, 10_000).unwrap_err().budget, 10_000);
+        assert!(check_budget(10_000, 15_000).is_err());
+        assert!(check_budget(0, 0).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use crate::AppState;
+use crate::auth::AdminUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use crate::models::ProofJanitorRun;
+use axum::extract::State;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use std::sync::Arc;
+
+/// Runs listed in the report, newest first.
+const RECENT_RUNS: i64 = 20;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JanitorRunResponse {
+    pub id: i64,
+    pub accounts_found: i32,
+    pub accounts_closed: i32,
+    pub accounts_unverified: i32,
+    #[serde_as(as = "DisplayFromStr")]
+    pub lamports_reclaimed: u64,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JanitorReportResponse {
+    pub total_accounts_closed: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub total_lamports_reclaimed: u64,
+    pub runs: Vec<JanitorRunResponse>,
+}
+
+// handler is at GET /api/admin/janitor, reports the rent reclaimed from
+// orphaned proof context accounts
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminUser,
+) -> Result<ApiResponse<JanitorReportResponse>, AppError> {
+    let (total_accounts_closed, total_lamports_reclaimed) =
+        db::get_proof_janitor_totals(&state.db).await?;
+    let runs = db::list_proof_janitor_runs(&state.db, RECENT_RUNS).await?;
+
+    Ok(ApiResponse::new(JanitorReportResponse {
+        total_accounts_closed,
+        total_lamports_reclaimed,
+        runs: runs.into_iter().map(to_run_response).collect(),
+    }))
+}
+
+fn to_run_response(run: ProofJanitorRun) -> JanitorRunResponse {
+    JanitorRunResponse {
+        id: run.id,
+        accounts_found: run.accounts_found,
+        accounts_closed: run.accounts_closed,
+        accounts_unverified: run.accounts_unverified,
+        lamports_reclaimed: run.lamports_reclaimed,
+        error: run.error,
+        started_at: run.started_at,
+        finished_at: run.finished_at,
+    }
+}
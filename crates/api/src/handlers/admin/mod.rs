@@ -0,0 +1,15 @@
+use axum::{Router, routing::get};
+use std::sync::Arc;
+
+use crate::AppState;
+
+pub mod janitor;
+pub mod refunds;
+
+/// nested within /admin prefix
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/janitor", get(janitor::handler))
+        .route("/refunds", get(refunds::handler))
+        .with_state(state)
+}
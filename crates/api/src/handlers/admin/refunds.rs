@@ -0,0 +1,68 @@
+use crate::AppState;
+use crate::auth::AdminUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use crate::jobs::reservations::MAX_REFUND_ATTEMPTS;
+use crate::models::AbandonedReservedWalletRefund;
+use axum::extract::State;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+/// Refunds listed in the report, most recently failed first.
+const RECENT_REFUNDS: i64 = 100;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbandonedRefundResponse {
+    pub credit_id: i64,
+    pub wallet_id: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub sender: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub decimals: u8,
+    pub attempts: i32,
+    pub error: Option<String>,
+    pub failed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefundsReportResponse {
+    pub reserved_wallet_credits: Vec<AbandonedRefundResponse>,
+}
+
+// handler is at GET /api/admin/refunds, lists the refunds of expired reserved
+// wallets the sweeper gave up on after too many failed attempts
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminUser,
+) -> Result<ApiResponse<RefundsReportResponse>, AppError> {
+    let refunds =
+        db::list_abandoned_reserved_wallet_refunds(&state.db, MAX_REFUND_ATTEMPTS, RECENT_REFUNDS)
+            .await?;
+
+    Ok(ApiResponse::new(RefundsReportResponse {
+        reserved_wallet_credits: refunds.into_iter().map(to_refund_response).collect(),
+    }))
+}
+
+fn to_refund_response(refund: AbandonedReservedWalletRefund) -> AbandonedRefundResponse {
+    AbandonedRefundResponse {
+        credit_id: refund.credit_id,
+        wallet_id: refund.wallet_id,
+        sender: refund.sender,
+        mint: refund.mint,
+        amount: refund.amount,
+        decimals: refund.decimals,
+        attempts: refund.attempts,
+        error: refund.error,
+        failed_at: refund.failed_at,
+    }
+}
@@ -0,0 +1,226 @@
+use crate::AppState;
+use crate::auth::AdminUser;
+use crate::db;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::solana::audit::{AuditPosition, AuditRecord, scan_mint};
+use axum::extract::Path;
+use axum::extract::{Query, State};
+use axum::http::HeaderValue;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use std::sync::Arc;
+use tracing::info;
+
+const DEFAULT_PAGE_SIZE: u32 = 500;
+const MAX_PAGE_SIZE: u32 = 5_000;
+
+/// Carries `nextCursor` on CSV responses.
+const NEXT_CURSOR_HEADER: &str = "next-cursor";
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditPath {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditQuery {
+    /// Only entries with a block time at or after this instant.
+    pub from: Option<DateTime<Utc>>,
+    /// Only entries with a block time before this instant.
+    pub to: Option<DateTime<Utc>>,
+    /// Only entries where this token account is the source or destination.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub account: Option<Pubkey>,
+    /// `nextCursor` from the previous page.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub cursor: Option<AuditPosition>,
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub format: AuditFormat,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    #[serde_as(as = "DisplayFromStr")]
+    pub signature: Signature,
+    pub instruction_index: u8,
+    pub slot: u64,
+    pub block_time: Option<DateTime<Utc>>,
+    pub kind: String,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub source: Option<Pubkey>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub destination: Option<Pubkey>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub amount: Option<u64>,
+}
+
+impl From<AuditRecord> for AuditEntry {
+    fn from(record: AuditRecord) -> Self {
+        Self {
+            signature: record.signature,
+            instruction_index: record.instruction_index,
+            slot: record.slot,
+            block_time: record
+                .block_time
+                .and_then(|t| DateTime::<Utc>::from_timestamp(t, 0)),
+            kind: record.kind.as_str().to_string(),
+            source: record.source,
+            destination: record.destination,
+            amount: record.amount,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditResponse {
+    pub entries: Vec<AuditEntry>,
+    /// Set when there are more entries, pass it as `cursor` to get them.
+    pub next_cursor: Option<String>,
+    /// False while older activity of the mint is still to be scanned, request
+    /// again to continue.
+    pub scan_complete: bool,
+}
+
+// handler is at GET /api/audit/{mint}, admin only. Scans a batch of the mint's
+// new confidential activity, then returns a page of the entries stored so far
+// decrypted with the auditor key, in chain order. CSV pages carry the next
+// cursor in the `next-cursor` header.
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path(path): Path<AuditPath>,
+    Query(params): Query<AuditQuery>,
+    AdminUser(admin): AdminUser,
+) -> Result<Response, AppError> {
+    info!(
+        "audit of mint {} requested by {}",
+        path.mint, admin.telegram_user_id
+    );
+    let scan_complete = sync_audit_entries(&state, &path.mint).await?;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    // fetch one extra row to know whether there is another page
+    let mut records = db::list_audit_entries(
+        &state.db,
+        &path.mint,
+        params.from,
+        params.to,
+        params.account.as_ref(),
+        params.cursor.as_ref(),
+        limit as i64 + 1,
+    )
+    .await?;
+    let next_cursor = if records.len() > limit as usize {
+        records.truncate(limit as usize);
+        records.last().map(|record| record.position().to_string())
+    } else {
+        None
+    };
+    let entries = records
+        .into_iter()
+        .map(AuditEntry::from)
+        .collect::<Vec<_>>();
+
+    match params.format {
+        AuditFormat::Json => Ok(ApiResponse::new(AuditResponse {
+            entries,
+            next_cursor,
+            scan_complete,
+        })
+        .into_response()),
+        AuditFormat::Csv => {
+            let mut response = (
+                [
+                    (CONTENT_TYPE, "text/csv".to_string()),
+                    (
+                        CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"audit-{}.csv\"", path.mint),
+                    ),
+                ],
+                to_csv(&entries),
+            )
+                .into_response();
+            if let Some(cursor) = next_cursor.and_then(|c| HeaderValue::from_str(&c).ok()) {
+                response.headers_mut().insert(NEXT_CURSOR_HEADER, cursor);
+            }
+            Ok(response)
+        }
+    }
+}
+
+/// Scan the next batch of the mint's activity since the last scan and store
+/// it. Returns whether the scan caught up.
+async fn sync_audit_entries(state: &AppState, mint: &Pubkey) -> Result<bool, AppError> {
+    let cursor = db::get_audit_scan_cursor(&state.db, mint).await?;
+    let (records, next_cursor) = scan_mint(
+        state.rpc_client.clone(),
+        mint,
+        state.elgamal_keypair.secret(),
+        &cursor,
+    )
+    .await
+    .map_err(|e| {
+        AppError::internal_server_error(anyhow::anyhow!("Failed to scan mint: {:?}", e))
+    })?;
+
+    if next_cursor == cursor {
+        return Ok(cursor.is_complete());
+    }
+    if !db::save_audit_scan(&state.db, mint, &records, &cursor, &next_cursor).await? {
+        // another request scanned the same batch first, leave its cursor
+        info!("audit scan of {} overtaken by a concurrent scan", mint);
+        return Ok(false);
+    }
+    info!("audit scan of {} found {} new entries", mint, records.len());
+
+    Ok(next_cursor.is_complete())
+}
+
+fn to_csv(entries: &[AuditEntry]) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_default();
+
+    let mut csv = String::from(
+        "signature,instruction_index,slot,block_time,kind,source,destination,amount\n",
+    );
+    for entry in entries {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            entry.signature,
+            entry.instruction_index,
+            entry.slot,
+            optional(entry.block_time.map(|t| t.to_rfc3339())),
+            entry.kind,
+            optional(entry.source.map(|s| s.to_string())),
+            optional(entry.destination.map(|d| d.to_string())),
+            optional(entry.amount.map(|a| a.to_string())),
+        ));
+    }
+    csv
+}
@@ -0,0 +1,13 @@
+use axum::{Router, routing::get};
+use std::sync::Arc;
+
+use crate::AppState;
+
+pub mod list;
+
+/// nested within /audit prefix
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/{mint}", get(list::handler))
+        .with_state(state)
+}
@@ -0,0 +1,32 @@
+use crate::AppState;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterResponse {
+    pub cluster: String,
+    pub airdrops: bool,
+    pub fee_sponsorship: bool,
+    /// `{signature}` is replaced with the transaction signature.
+    pub explorer_tx_url: String,
+    /// `{address}` is replaced with the account address.
+    pub explorer_address_url: String,
+}
+
+// handler is at GET /api/cluster, tells clients which cluster the API runs
+// against and how to link to its explorer
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<ApiResponse<ClusterResponse>, AppError> {
+    let cluster = &state.config.cluster;
+    Ok(ApiResponse::new(ClusterResponse {
+        cluster: cluster.kind.as_str().to_string(),
+        airdrops: cluster.airdrops,
+        fee_sponsorship: cluster.fee_sponsorship,
+        explorer_tx_url: cluster.explorer_tx_url.clone(),
+        explorer_address_url: cluster.explorer_address_url.clone(),
+    }))
+}
@@ -1,8 +1,18 @@
+pub mod admin;
+pub mod audit;
+pub mod cluster;
 pub mod convert;
 pub mod health;
+pub mod noncustodial;
+pub mod notifications;
+pub mod pots;
+pub mod requests;
+pub mod schedules;
+pub mod sessions;
 pub mod telegram;
 pub mod tokens;
 pub mod transfers;
+pub mod tx;
 pub mod wallets;
 
 use axum::{
@@ -40,6 +50,11 @@ pub type ApiResponse<T> = BaseApiResponse<T>;
 pub struct AppError {
     inner: anyhow::Error,
     status: StatusCode,
+    /// Sent as the response's `data` instead of the error message.
+    data: Option<serde_json::Value>,
+    /// The request was recorded before it failed, see
+    /// [`crate::idempotency::Recorded`].
+    recorded: bool,
 }
 
 impl AppError {
@@ -47,9 +62,26 @@ impl AppError {
         Self {
             inner: error.into(),
             status,
+            data: None,
+            recorded: false,
         }
     }
 
+    /// A 202 carrying `data`, for a request that was recorded but whose
+    /// outcome isn't known yet when the handler returns.
+    pub fn accepted(message: impl Into<anyhow::Error>, data: impl Serialize) -> Self {
+        let mut error = Self::new(message, StatusCode::ACCEPTED);
+        error.data = serde_json::to_value(data).ok();
+        error
+    }
+
+    /// Mark the error as having happened after the request was recorded, so
+    /// a retry with the same `Idempotency-Key` doesn't run it again.
+    pub fn recorded(mut self) -> Self {
+        self.recorded = true;
+        self
+    }
+
     pub fn internal_server_error(error: impl Into<anyhow::Error>) -> Self {
         Self::new(error, StatusCode::INTERNAL_SERVER_ERROR)
     }
@@ -61,17 +93,33 @@ impl AppError {
     pub fn not_found(error: impl Into<anyhow::Error>) -> Self {
         Self::new(error, StatusCode::NOT_FOUND)
     }
+
+    pub fn conflict(error: impl Into<anyhow::Error>) -> Self {
+        Self::new(error, StatusCode::CONFLICT)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let error_message = self.inner.to_string();
 
-        if error_message.is_empty() {
+        let mut response = if let Some(data) = self.data {
+            (self.status, Json(BaseApiResponse { data })).into_response()
+        } else if error_message.is_empty() {
             (self.status, "server error").into_response()
         } else {
             (self.status, error_message).into_response()
+        };
+        if self.recorded {
+            response
+                .extensions_mut()
+                .insert(crate::idempotency::Recorded);
         }
+        response
     }
 }
 
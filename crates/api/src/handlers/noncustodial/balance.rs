@@ -0,0 +1,80 @@
+use super::{WalletPath, keys_from_signature, validate_external_wallet};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::handlers::wallets::balance::{BalanceResponse, EncryptedBalance};
+use crate::solana::balance::get_confidential_balances_with_keys;
+use crate::solana::tokens::get_maybe_ata;
+use axum::extract::Path;
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    /// Owner's signature over the token account address.
+    #[serde_as(as = "DisplayFromStr")]
+    pub key_signature: Signature,
+}
+
+// handler is at POST /api/noncustodial/{address}/balance, decrypts the
+// confidential balances with keys derived from the client's signature
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<WalletPath>,
+    Json(payload): Json<BalanceRequest>,
+) -> Result<ApiResponse<BalanceResponse>, AppError> {
+    validate_external_wallet(&state, &path.address, auth_user.telegram_user_id).await?;
+    let confidential_keys =
+        keys_from_signature(&path.address, &payload.mint, &payload.key_signature)?;
+
+    let (ata, maybe_ata_account) =
+        get_maybe_ata(state.rpc_client.clone(), &path.address, &payload.mint).await?;
+    if maybe_ata_account.is_none() {
+        return Err(AppError::not_found(anyhow::anyhow!(
+            "Token account not found"
+        )));
+    }
+
+    let (pending_balance, available_balance) = get_confidential_balances_with_keys(
+        state.rpc_client.clone(),
+        &path.address,
+        &payload.mint,
+        &confidential_keys,
+    )
+    .await?;
+
+    let public_balance = state
+        .rpc_client
+        .get_token_account_balance(&ata)
+        .await
+        .map_err(|e| {
+            AppError::internal_server_error(anyhow::anyhow!(
+                "Failed to get token account balance: {}",
+                e
+            ))
+        })?
+        .amount
+        .parse::<u64>()
+        .map_err(|err| anyhow::anyhow!("Failed to parse ATA balance: {}", err))?;
+
+    Ok(ApiResponse::new(BalanceResponse {
+        owner: path.address,
+        mint: payload.mint,
+        token_account: ata,
+        public_balance,
+        encrypted_balance: EncryptedBalance {
+            pending: pending_balance,
+            available: available_balance,
+        },
+    }))
+}
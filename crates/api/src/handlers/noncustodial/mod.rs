@@ -0,0 +1,69 @@
+//! Non-custodial wallets: the keypair stays on the client.
+//!
+//! Confidential keys are derived from the owner's signature over their token
+//! account address (see [`confidential_keys_from_signature`]), which the
+//! client sends as `keySignature` with every request that needs them.
+//! Transactions for these wallets are built by the `/api/tx` endpoints and
+//! signed on the client.
+
+use crate::handlers::AppError;
+use crate::models::{Wallet, WalletCustody};
+use crate::solana::confidential_keys::ConfidentialKeys;
+use crate::solana::utils::confidential_keys_from_signature;
+use crate::{AppState, db};
+use axum::{Router, routing::post};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use std::sync::Arc;
+
+pub mod balance;
+pub mod register;
+
+/// nested within /noncustodial prefix
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/wallets", post(register::handler))
+        .route("/{address}/balance", post(balance::handler))
+        .with_state(state)
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletPath {
+    #[serde_as(as = "DisplayFromStr")]
+    pub address: Pubkey,
+}
+
+/// Load a wallet of the user and make sure it is a non-custodial one.
+pub async fn validate_external_wallet(
+    state: &AppState,
+    address: &Pubkey,
+    telegram_user_id: i64,
+) -> Result<Wallet, AppError> {
+    let Some(wallet) = db::get_user_wallet_by_pubkey(&state.db, address, telegram_user_id).await?
+    else {
+        return Err(AppError::not_found(anyhow::anyhow!(
+            "Wallet not found or not authorized"
+        )));
+    };
+
+    if wallet.custody != WalletCustody::External {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Wallet {} is custodial, use the /api/wallets endpoints",
+            address
+        )));
+    }
+
+    Ok(wallet)
+}
+
+pub fn keys_from_signature(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    key_signature: &Signature,
+) -> Result<ConfidentialKeys, AppError> {
+    confidential_keys_from_signature(owner, mint, key_signature).map_err(AppError::bad_request)
+}
@@ -0,0 +1,59 @@
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::solana::airdrop::request_airdrop_and_confirm;
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterWalletRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub pubkey: Pubkey,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterWalletResponse {
+    #[serde_as(as = "DisplayFromStr")]
+    pub pubkey: Pubkey,
+}
+
+// handler is at POST /api/noncustodial/wallets, links a wallet the user holds
+// the keypair for to their account
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<RegisterWalletRequest>,
+) -> Result<ApiResponse<RegisterWalletResponse>, AppError> {
+    if db::wallet_exists(&state.db, &payload.pubkey).await? {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Wallet {} is already registered",
+            payload.pubkey
+        )));
+    }
+
+    if state.config.cluster.airdrops {
+        request_airdrop_and_confirm(state.rpc_client.clone(), &payload.pubkey, 10_u64.pow(9))
+            .await
+            .map_err(AppError::from)?;
+    }
+
+    db::create_external_wallet_for_telegram_user(
+        &state.db,
+        auth_user.telegram_user_id,
+        &payload.pubkey,
+    )
+    .await?;
+
+    Ok(ApiResponse::new(RegisterWalletResponse {
+        pubkey: payload.pubkey,
+    }))
+}
@@ -0,0 +1,45 @@
+use crate::AppState;
+use crate::models::NotificationPreferences;
+use axum::{Router, routing::get};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+pub mod preferences;
+
+/// nested within /notifications prefix
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/preferences",
+            get(preferences::get_handler).put(preferences::put_handler),
+        )
+        .with_state(state)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPreferencesBody {
+    pub incoming_payments: bool,
+}
+
+impl Default for NotificationPreferencesBody {
+    fn default() -> Self {
+        NotificationPreferences::default().into()
+    }
+}
+
+impl From<NotificationPreferences> for NotificationPreferencesBody {
+    fn from(preferences: NotificationPreferences) -> Self {
+        Self {
+            incoming_payments: preferences.incoming_payments,
+        }
+    }
+}
+
+impl From<NotificationPreferencesBody> for NotificationPreferences {
+    fn from(body: NotificationPreferencesBody) -> Self {
+        Self {
+            incoming_payments: body.incoming_payments,
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use super::NotificationPreferencesBody;
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use axum::{Json, extract::State};
+use std::sync::Arc;
+
+// handler is at GET /api/notifications/preferences, users who never saved
+// any get the defaults
+pub async fn get_handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<NotificationPreferencesBody>, AppError> {
+    let preferences =
+        db::get_notification_preferences(&state.db, auth_user.telegram_user_id).await?;
+    Ok(ApiResponse::new(preferences.into()))
+}
+
+// handler is at PUT /api/notifications/preferences, notifications already
+// queued follow the new preferences too
+pub async fn put_handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(body): Json<NotificationPreferencesBody>,
+) -> Result<ApiResponse<NotificationPreferencesBody>, AppError> {
+    if !db::set_notification_preferences(&state.db, auth_user.telegram_user_id, body.clone().into())
+        .await?
+    {
+        return Err(AppError::not_found(anyhow::anyhow!("User not found")));
+    }
+    Ok(ApiResponse::new(body))
+}
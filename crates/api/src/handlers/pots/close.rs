@@ -0,0 +1,107 @@
+use super::{PotPath, PotResponse, get_pot, reload_pot, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::transfers::{
+    ensure_recipient_confidential_account, finish_transfer, format_transfer_results,
+    get_mint_decimals, pending_transfer, start_transfer,
+};
+use crate::handlers::wallets::deposit::TransactionResult;
+use crate::handlers::{ApiResponse, AppError};
+use crate::models::PotStatus;
+use axum::extract::{Path, State};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::error;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClosePotResponse {
+    /// The withdraw, unless nothing was collected.
+    pub transfer_id: Option<i64>,
+    pub job_id: Option<i64>,
+    pub transactions: Vec<TransactionResult>,
+    pub pot: PotResponse,
+}
+
+// handler is at POST /api/pots/{id}/close, only for the creator, who gets
+// everything collected so far
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<PotPath>,
+) -> Result<ApiResponse<ClosePotResponse>, AppError> {
+    let pot = get_pot(&state, path.id, auth_user.telegram_user_id).await?;
+    if pot.creator_telegram_user_id != Some(auth_user.telegram_user_id) {
+        return Err(AppError::new(
+            anyhow::anyhow!("Only the creator can close a pot"),
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    if pot.status != PotStatus::Open {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Pot is {}",
+            pot.status.as_str()
+        )));
+    }
+
+    let creator_wallet = db::get_wallet_by_id(&state.db, pot.creator_wallet_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Wallet {} not found", pot.creator_wallet_id))?;
+    let escrow_wallet = db::get_wallet_by_id(&state.db, pot.escrow_wallet_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Escrow wallet {} not found", pot.escrow_wallet_id))?;
+    ensure_recipient_confidential_account(&state, &creator_wallet, &pot.mint, creator_wallet.id)
+        .await?;
+    let mint_decimals = get_mint_decimals(&state, &pot.mint).await?;
+
+    if !db::start_pot_close(&state.db, pot.id).await? {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Contributions to this pot are still being sent, try again shortly"
+        )));
+    }
+    // contributions can't change anymore, so this is what the escrow holds
+    let pot = reload_pot(&state, pot.id).await?;
+
+    let mut response = ClosePotResponse::default();
+    if pot.collected_amount == 0 {
+        db::set_pot_closed(&state.db, pot.id).await?;
+    } else {
+        let (transfer_id, job_id) = match start_transfer(
+            &state,
+            &escrow_wallet,
+            &creator_wallet.pubkey,
+            pot.collected_amount,
+            pot.mint,
+            mint_decimals,
+            None,
+        )
+        .await
+        {
+            Ok(ids) => ids,
+            Err(e) => {
+                if let Err(reopen_error) = db::reopen_pot(&state.db, pot.id).await {
+                    error!("failed to reopen pot {}: {:?}", pot.id, reopen_error);
+                }
+                return Err(e);
+            }
+        };
+
+        // the pot is marked closed once the withdraw lands, or opened up
+        // again if it fails (see `db::sync_transfer_with_job`)
+        if let Err(e) = db::set_pot_withdraw_transfer(&state.db, pot.id, transfer_id).await {
+            // the job is recorded and goes through with the recovery loop anyway
+            error!("failed to link pot {} to its withdraw: {:?}", pot.id, e);
+            return Err(pending_transfer(transfer_id, job_id));
+        }
+        let job = finish_transfer(&state, transfer_id, job_id).await?;
+        response.transfer_id = Some(transfer_id);
+        response.job_id = Some(job.id);
+        response.transactions = format_transfer_results(&job.signatures());
+    }
+
+    let pot = reload_pot(&state, pot.id).await?;
+    response.pot = to_response(&state, pot, auth_user.telegram_user_id).await?;
+    Ok(ApiResponse::new(response))
+}
@@ -0,0 +1,93 @@
+use super::{PotPath, PotResponse, get_pot, reload_pot, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::transfers::{
+    finish_transfer, format_transfer_results, get_mint_decimals, prepare_transfer,
+    validate_sender_wallet,
+};
+use crate::handlers::wallets::deposit::TransactionResult;
+use crate::handlers::{ApiResponse, AppError};
+use crate::models::PotStatus;
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContributeRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub source: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContributeResponse {
+    pub transfer_id: i64,
+    pub job_id: i64,
+    pub transactions: Vec<TransactionResult>,
+    pub pot: PotResponse,
+}
+
+// handler is at POST /api/pots/{id}/contribute, confidentially transfers into
+// the pot's escrow wallet
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<PotPath>,
+    Json(payload): Json<ContributeRequest>,
+) -> Result<ApiResponse<ContributeResponse>, AppError> {
+    let pot = get_pot(&state, path.id, auth_user.telegram_user_id).await?;
+    if pot.status != PotStatus::Open {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Pot is {}",
+            pot.status.as_str()
+        )));
+    }
+    if payload.amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Contribution must be greater than 0"
+        )));
+    }
+    let sender_wallet =
+        validate_sender_wallet(&state, &payload.source, auth_user.telegram_user_id).await?;
+    let mint_decimals = get_mint_decimals(&state, &pot.mint).await?;
+    let escrow_wallet = db::get_wallet_by_id(&state.db, pot.escrow_wallet_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Escrow wallet {} not found", pot.escrow_wallet_id))?;
+
+    let transfer = prepare_transfer(
+        &state,
+        &sender_wallet,
+        escrow_wallet.pubkey,
+        payload.amount,
+        pot.mint,
+        mint_decimals,
+        None,
+    )
+    .await?;
+    // recorded with its transfer, so the pot can't be closed while the
+    // transfer is in flight
+    let Some((transfer_id, job_id)) =
+        db::create_pot_contribution(&state.db, pot.id, &transfer.ledger_entry()).await?
+    else {
+        return Err(AppError::conflict(anyhow::anyhow!("Pot is no longer open")));
+    };
+    let job = finish_transfer(&state, transfer_id, job_id).await?;
+
+    let pot = reload_pot(&state, pot.id).await?;
+    Ok(ApiResponse::new(ContributeResponse {
+        transfer_id,
+        job_id: job.id,
+        transactions: format_transfer_results(&job.signatures()),
+        pot: to_response(&state, pot, auth_user.telegram_user_id).await?,
+    }))
+}
@@ -0,0 +1,109 @@
+use super::{PotResponse, ensure_chat_member, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::transfers::{
+    ensure_recipient_confidential_account, validate_confidential_mint,
+};
+use crate::handlers::{ApiResponse, AppError};
+use crate::{db, fees};
+use axum::{Json, extract::State};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use std::sync::Arc;
+
+const MAX_TITLE_LENGTH: usize = 64;
+
+const DEFAULT_POT_DAYS: i64 = 7;
+
+const MAX_POT_DAYS: i64 = 90;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePotRequest {
+    pub chat_id: i64,
+    /// Wallet of the authenticated user that receives the pot when it is
+    /// closed.
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    pub title: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub target_amount: u64,
+    /// Defaults to a week from now, at most 90 days.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// handler is at POST /api/pots, starts collecting towards a target in a chat
+// the authenticated user is in
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreatePotRequest>,
+) -> Result<ApiResponse<PotResponse>, AppError> {
+    let Some(wallet) =
+        db::get_user_wallet_by_pubkey(&state.db, &payload.wallet, auth_user.telegram_user_id)
+            .await?
+    else {
+        return Err(AppError::not_found(anyhow::anyhow!(
+            "Wallet not found or not authorized"
+        )));
+    };
+
+    let title = payload.title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Title must be 1 to {} characters",
+            MAX_TITLE_LENGTH
+        )));
+    }
+    if payload.target_amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Target amount must be greater than 0"
+        )));
+    }
+    let now = Utc::now();
+    let expires_at = payload
+        .expires_at
+        .unwrap_or(now + Duration::days(DEFAULT_POT_DAYS));
+    if expires_at <= now || expires_at > now + Duration::days(MAX_POT_DAYS) {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Expiry must be in the next {} days",
+            MAX_POT_DAYS
+        )));
+    }
+
+    ensure_chat_member(&state, payload.chat_id, auth_user.telegram_user_id).await?;
+    validate_confidential_mint(&state, &payload.mint).await?;
+
+    // the escrow wallet sends the withdraw and the refunds, so it gets its
+    // confidential account up front
+    let keypair = Keypair::new();
+    let secret = state.key_store.seal(&keypair)?;
+    let escrow_wallet = db::create_escrow_wallet(&state.db, &keypair.pubkey(), &secret).await?;
+    fees::fund_new_wallet(&state, &escrow_wallet.pubkey)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to fund escrow wallet: {}", e))?;
+    ensure_recipient_confidential_account(&state, &escrow_wallet, &payload.mint, wallet.id).await?;
+
+    let pot = db::create_pot(
+        &state.db,
+        payload.chat_id,
+        &wallet,
+        &escrow_wallet,
+        title,
+        &payload.mint,
+        payload.target_amount,
+        expires_at,
+    )
+    .await?;
+
+    Ok(ApiResponse::new(
+        to_response(&state, pot, auth_user.telegram_user_id).await?,
+    ))
+}
@@ -0,0 +1,18 @@
+use super::{PotPath, PotResponse, get_pot, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::{Path, State};
+use std::sync::Arc;
+
+// handler is at GET /api/pots/{id}
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<PotPath>,
+) -> Result<ApiResponse<PotResponse>, AppError> {
+    let pot = get_pot(&state, path.id, auth_user.telegram_user_id).await?;
+    Ok(ApiResponse::new(
+        to_response(&state, pot, auth_user.telegram_user_id).await?,
+    ))
+}
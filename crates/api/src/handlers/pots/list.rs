@@ -0,0 +1,33 @@
+use super::{PotResponse, ensure_chat_member, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::{Query, State};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const MAX_POTS: i64 = 50;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPotsQuery {
+    pub chat_id: i64,
+}
+
+// handler is at GET /api/pots?chatId=..., lists the latest pots of a chat the
+// authenticated user is in
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Query(query): Query<ListPotsQuery>,
+) -> Result<ApiResponse<Vec<PotResponse>>, AppError> {
+    ensure_chat_member(&state, query.chat_id, auth_user.telegram_user_id).await?;
+
+    let pots = db::list_pots_for_chat(&state.db, query.chat_id, MAX_POTS).await?;
+    let mut responses = Vec::with_capacity(pots.len());
+    for pot in pots {
+        responses.push(to_response(&state, pot, auth_user.telegram_user_id).await?);
+    }
+    Ok(ApiResponse::new(responses))
+}
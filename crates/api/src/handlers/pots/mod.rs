@@ -0,0 +1,171 @@
+use crate::handlers::AppError;
+use crate::models::Pot;
+use crate::telegram::api::BotApiError;
+use crate::{AppState, db, idempotency};
+use axum::{
+    Router,
+    middleware::from_fn_with_state,
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+pub mod close;
+pub mod contribute;
+pub mod create;
+pub mod get;
+pub mod list;
+
+/// nested within /pots prefix
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list::handler))
+        .route(
+            "/",
+            post(create::handler).layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
+        .route("/{id}", get(get::handler))
+        .route(
+            "/{id}/contribute",
+            post(contribute::handler)
+                .layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
+        .route("/{id}/close", post(close::handler))
+        .with_state(state)
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PotPath {
+    pub id: i64,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PotResponse {
+    pub id: i64,
+    pub chat_id: i64,
+    pub title: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub target_amount: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub collected_amount: u64,
+    pub contributor_count: i64,
+    pub status: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub is_creator: bool,
+    /// What the authenticated user contributed.
+    #[serde_as(as = "DisplayFromStr")]
+    pub my_contribution: u64,
+    /// Per-contributor amounts, only shown to the creator.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contributions: Option<Vec<PotContributionResponse>>,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PotContributionResponse {
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    pub telegram_username: Option<String>,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+}
+
+/// Pots are shared with the members of their chat, as far as the bot can
+/// tell, so the bot has to be in the chat.
+pub async fn ensure_chat_member(
+    state: &AppState,
+    chat_id: i64,
+    telegram_user_id: i64,
+) -> Result<(), AppError> {
+    // the mock user of dev mode isn't in any chat
+    if state.config.dev_mode {
+        return Ok(());
+    }
+
+    let member = state
+        .telegram
+        .get_chat_member(chat_id, telegram_user_id)
+        .await
+        .map_err(|e| match e.downcast_ref::<BotApiError>() {
+            Some(api_error) if api_error.is_permanent() => AppError::bad_request(anyhow::anyhow!(
+                "Can't check who is in this chat, add the bot to it first"
+            )),
+            _ => AppError::from(e),
+        })?;
+    if !member.is_in_chat() {
+        return Err(AppError::new(
+            anyhow::anyhow!("You are not a member of this chat"),
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    Ok(())
+}
+
+/// A pot of a chat the authenticated user is in.
+pub async fn get_pot(state: &AppState, id: i64, telegram_user_id: i64) -> Result<Pot, AppError> {
+    let pot = reload_pot(state, id).await?;
+    ensure_chat_member(state, pot.chat_id, telegram_user_id).await?;
+    Ok(pot)
+}
+
+/// A pot already checked with [`get_pot`], as it is now.
+async fn reload_pot(state: &AppState, id: i64) -> Result<Pot, AppError> {
+    db::get_pot(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::not_found(anyhow::anyhow!("Pot not found")))
+}
+
+/// Describe a pot to a user. Only its creator sees who contributed how much,
+/// everyone else only their own contribution.
+pub async fn to_response(
+    state: &AppState,
+    pot: Pot,
+    telegram_user_id: i64,
+) -> Result<PotResponse, AppError> {
+    let contributors = db::get_pot_contributors(&state.db, pot.id).await?;
+    let own_wallets = db::get_wallets_for_telegram_user(&state.db, telegram_user_id).await?;
+    let my_contribution = contributors
+        .iter()
+        .filter(|contributor| own_wallets.contains(&contributor.wallet))
+        .map(|contributor| contributor.amount)
+        .sum();
+
+    let is_creator = pot.creator_telegram_user_id == Some(telegram_user_id);
+    let contributions = is_creator.then(|| {
+        contributors
+            .into_iter()
+            .map(|contributor| PotContributionResponse {
+                wallet: contributor.wallet,
+                telegram_username: contributor.telegram_username,
+                amount: contributor.amount,
+            })
+            .collect()
+    });
+
+    Ok(PotResponse {
+        id: pot.id,
+        chat_id: pot.chat_id,
+        title: pot.title,
+        mint: pot.mint,
+        target_amount: pot.target_amount,
+        collected_amount: pot.collected_amount,
+        contributor_count: pot.contributor_count,
+        status: pot.status.as_str().to_string(),
+        expires_at: pot.expires_at,
+        created_at: pot.created_at,
+        is_creator,
+        my_contribution,
+        contributions,
+    })
+}
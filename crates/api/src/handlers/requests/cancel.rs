@@ -0,0 +1,39 @@
+use super::{PaymentRequestPath, PaymentRequestResponse, get_payment_request, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::transfers::get_mint_decimals;
+use crate::handlers::{ApiResponse, AppError};
+use crate::models::PaymentRequestStatus;
+use axum::extract::{Path, State};
+use std::sync::Arc;
+
+// handler is at POST /api/requests/{id}/cancel, only the requester can cancel
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<PaymentRequestPath>,
+) -> Result<ApiResponse<PaymentRequestResponse>, AppError> {
+    let request = get_payment_request(&state, path.id).await?;
+    let is_requester =
+        db::get_user_wallet_by_pubkey(&state.db, &request.recipient, auth_user.telegram_user_id)
+            .await?
+            .is_some();
+    if !is_requester {
+        return Err(AppError::not_found(anyhow::anyhow!(
+            "Payment request not found"
+        )));
+    }
+
+    if !db::cancel_payment_request(&state.db, request.id).await? {
+        return Err(AppError::conflict(match request.status {
+            PaymentRequestStatus::Open => anyhow::anyhow!("Payment request is being paid"),
+            status => anyhow::anyhow!("Payment request is already {}", status.as_str()),
+        }));
+    }
+
+    let request = get_payment_request(&state, path.id).await?;
+    let decimals = get_mint_decimals(&state, &request.mint).await?;
+
+    Ok(ApiResponse::new(to_response(request, decimals)?))
+}
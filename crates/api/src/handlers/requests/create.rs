@@ -0,0 +1,74 @@
+use super::{PaymentRequestResponse, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::transfers::{get_mint_decimals, normalize_memo, validate_confidential_mint};
+use crate::handlers::{ApiResponse, AppError};
+use axum::{Json, extract::State};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePaymentRequestRequest {
+    /// Wallet of the authenticated user that receives the payment.
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub memo: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// handler is at POST /api/requests, asks for a payment into one of the
+// authenticated user's wallets
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreatePaymentRequestRequest>,
+) -> Result<ApiResponse<PaymentRequestResponse>, AppError> {
+    let Some(wallet) =
+        db::get_user_wallet_by_pubkey(&state.db, &payload.wallet, auth_user.telegram_user_id)
+            .await?
+    else {
+        return Err(AppError::not_found(anyhow::anyhow!(
+            "Wallet not found or not authorized"
+        )));
+    };
+
+    if payload.amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Requested amount must be greater than 0"
+        )));
+    }
+    let memo = normalize_memo(payload.memo.as_deref())?;
+    if payload
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Expiry must be in the future"
+        )));
+    }
+
+    validate_confidential_mint(&state, &payload.mint).await?;
+    let decimals = get_mint_decimals(&state, &payload.mint).await?;
+
+    let request = db::create_payment_request(
+        &state.db,
+        &wallet,
+        &payload.mint,
+        payload.amount,
+        memo,
+        payload.expires_at,
+    )
+    .await?;
+
+    Ok(ApiResponse::new(to_response(request, decimals)?))
+}
@@ -0,0 +1,20 @@
+use super::{PaymentRequestPath, PaymentRequestResponse, get_payment_request, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::transfers::get_mint_decimals;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::{Path, State};
+use std::sync::Arc;
+
+// handler is at GET /api/requests/{id}. Any authenticated user can look up a
+// request, the same way anyone holding the link can pay it.
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    _auth_user: AuthUser,
+    Path(path): Path<PaymentRequestPath>,
+) -> Result<ApiResponse<PaymentRequestResponse>, AppError> {
+    let request = get_payment_request(&state, path.id).await?;
+    let decimals = get_mint_decimals(&state, &request.mint).await?;
+
+    Ok(ApiResponse::new(to_response(request, decimals)?))
+}
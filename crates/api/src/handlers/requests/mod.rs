@@ -0,0 +1,99 @@
+use crate::handlers::AppError;
+use crate::models::PaymentRequest;
+use crate::solana::pay::{qr_code_png, transfer_request_url};
+use crate::{AppState, idempotency};
+use axum::{
+    Router,
+    middleware::from_fn_with_state,
+    routing::{get, post},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+pub mod cancel;
+pub mod create;
+pub mod get;
+pub mod pay;
+
+/// nested within /requests prefix
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", post(create::handler))
+        .route("/{id}", get(get::handler))
+        .route(
+            "/{id}/pay",
+            post(pay::handler).layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
+        .route("/{id}/cancel", post(cancel::handler))
+        .with_state(state)
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequestPath {
+    pub id: i64,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaymentRequestResponse {
+    pub id: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub recipient: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub decimals: u8,
+    pub memo: Option<String>,
+    pub status: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub transfer_id: Option<i64>,
+    /// Solana Pay-style `solana:` transfer request URL.
+    pub link: String,
+    /// Base64 encoded PNG of `link` as a QR code.
+    pub qr_code: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn get_payment_request(state: &AppState, id: i64) -> Result<PaymentRequest, AppError> {
+    crate::db::get_payment_request(&state.db, id)
+        .await?
+        .ok_or_else(|| AppError::not_found(anyhow::anyhow!("Payment request not found")))
+}
+
+pub fn to_response(
+    request: PaymentRequest,
+    decimals: u8,
+) -> Result<PaymentRequestResponse, AppError> {
+    let message = format!("Payment request #{}", request.id);
+    let link = transfer_request_url(
+        &request.recipient,
+        &request.mint,
+        request.amount,
+        decimals,
+        Some(&message),
+        request.memo.as_deref(),
+    );
+    let qr_code = BASE64_STANDARD.encode(qr_code_png(&link)?);
+
+    Ok(PaymentRequestResponse {
+        id: request.id,
+        recipient: request.recipient,
+        mint: request.mint,
+        amount: request.amount,
+        decimals,
+        memo: request.memo,
+        status: request.status.as_str().to_string(),
+        expires_at: request.expires_at,
+        transfer_id: request.transfer_id,
+        link,
+        qr_code,
+        created_at: request.created_at,
+    })
+}
@@ -0,0 +1,93 @@
+use super::{PaymentRequestPath, PaymentRequestResponse, get_payment_request, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::transfers::{
+    TransferMemo, finish_transfer, format_transfer_results, get_mint_decimals, prepare_transfer,
+    validate_recipient_account, validate_sender_wallet,
+};
+use crate::handlers::wallets::deposit::TransactionResult;
+use crate::handlers::{ApiResponse, AppError};
+use crate::models::PaymentRequestStatus;
+use axum::Json;
+use axum::extract::{Path, State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayRequestRequest {
+    /// Wallet of the authenticated user the payment is sent from.
+    #[serde_as(as = "DisplayFromStr")]
+    pub source: Pubkey,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayRequestResponse {
+    pub request: PaymentRequestResponse,
+    pub transfer_id: i64,
+    pub job_id: i64,
+    pub transactions: Vec<TransactionResult>,
+}
+
+// handler is at POST /api/requests/{id}/pay, pays an open request with a
+// confidential transfer from one of the authenticated user's wallets
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<PaymentRequestPath>,
+    Json(payload): Json<PayRequestRequest>,
+) -> Result<ApiResponse<PayRequestResponse>, AppError> {
+    let request = get_payment_request(&state, path.id).await?;
+    if request.status != PaymentRequestStatus::Open {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Payment request is {}",
+            request.status.as_str()
+        )));
+    }
+
+    let sender_wallet =
+        validate_sender_wallet(&state, &payload.source, auth_user.telegram_user_id).await?;
+    if sender_wallet.pubkey == request.recipient {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Payment request can't be paid from the requesting wallet"
+        )));
+    }
+    validate_recipient_account(&state, &request.recipient, &request.mint).await?;
+    let decimals = get_mint_decimals(&state, &request.mint).await?;
+
+    let transfer = prepare_transfer(
+        &state,
+        &sender_wallet,
+        request.recipient,
+        request.amount,
+        request.mint,
+        decimals,
+        request.memo.as_deref().map(TransferMemo::plain),
+    )
+    .await?;
+
+    // from here on the request follows the transfer: it is marked paid once
+    // the transfer lands, or opened up again if it fails (see
+    // `db::sync_transfer_with_job`), including when the job is resumed later
+    let Some((transfer_id, job_id)) =
+        db::pay_payment_request(&state.db, request.id, &transfer.ledger_entry()).await?
+    else {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Payment request is already being paid or no longer open"
+        )));
+    };
+    let job = finish_transfer(&state, transfer_id, job_id).await?;
+
+    let request = get_payment_request(&state, request.id).await?;
+    Ok(ApiResponse::new(PayRequestResponse {
+        request: to_response(request, decimals)?,
+        transfer_id,
+        job_id: job.id,
+        transactions: format_transfer_results(&job.signatures()),
+    }))
+}
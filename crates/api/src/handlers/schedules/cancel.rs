@@ -0,0 +1,27 @@
+use super::{
+    ScheduledTransferPath, ScheduledTransferResponse, get_scheduled_transfer, to_response,
+};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::{Path, State};
+use std::sync::Arc;
+
+// handler is at POST /api/schedules/{id}/cancel, cancelled schedules can't be
+// resumed
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<ScheduledTransferPath>,
+) -> Result<ApiResponse<ScheduledTransferResponse>, AppError> {
+    let scheduled = get_scheduled_transfer(&state, path.id, auth_user.telegram_user_id).await?;
+    if !db::cancel_scheduled_transfer(&state.db, scheduled.id).await? {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Scheduled transfer is already cancelled"
+        )));
+    }
+
+    let scheduled = get_scheduled_transfer(&state, path.id, auth_user.telegram_user_id).await?;
+    Ok(ApiResponse::new(to_response(scheduled)))
+}
@@ -0,0 +1,102 @@
+use super::{ScheduledTransferResponse, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db::{self, NewScheduledTransfer};
+use crate::handlers::transfers::{
+    get_mint_decimals, normalize_memo, validate_confidential_mint, validate_recipient_account,
+    validate_sender_wallet,
+};
+use crate::handlers::{ApiResponse, AppError};
+use crate::schedule::Schedule;
+use axum::{Json, extract::State};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateScheduledTransferRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub source: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub recipient: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub encrypt_memo: bool,
+    /// Run every `interval_secs` seconds. Set either this or `cron`.
+    pub interval_secs: Option<i64>,
+    /// Five field cron expression, evaluated in UTC.
+    pub cron: Option<String>,
+    /// First run, defaults to the first time the schedule is due from now.
+    pub start_at: Option<DateTime<Utc>>,
+}
+
+// handler is at POST /api/schedules, sets up a recurring transfer from one of
+// the authenticated user's custodial wallets
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateScheduledTransferRequest>,
+) -> Result<ApiResponse<ScheduledTransferResponse>, AppError> {
+    let sender_wallet =
+        validate_sender_wallet(&state, &payload.source, auth_user.telegram_user_id).await?;
+    if payload.amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Transfer amount must be greater than 0"
+        )));
+    }
+    let memo = normalize_memo(payload.memo.as_deref())?;
+
+    let cron = payload
+        .cron
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    let schedule = match (payload.interval_secs, cron) {
+        (Some(secs), None) => Schedule::interval(secs),
+        (None, Some(cron)) => cron.parse().map(Schedule::Cron),
+        _ => Err(anyhow::anyhow!("Set exactly one of intervalSecs or cron")),
+    }
+    .map_err(AppError::bad_request)?;
+
+    let now = Utc::now();
+    let next_run_at = match payload.start_at {
+        Some(start_at) if start_at <= now => {
+            return Err(AppError::bad_request(anyhow::anyhow!(
+                "Start must be in the future"
+            )));
+        }
+        Some(start_at) => start_at,
+        None => schedule.next_after(now).map_err(AppError::bad_request)?,
+    };
+
+    validate_confidential_mint(&state, &payload.mint).await?;
+    validate_recipient_account(&state, &payload.recipient, &payload.mint).await?;
+    let decimals = get_mint_decimals(&state, &payload.mint).await?;
+
+    let scheduled = db::create_scheduled_transfer(
+        &state.db,
+        &NewScheduledTransfer {
+            wallet: &sender_wallet,
+            recipient: &payload.recipient,
+            mint: &payload.mint,
+            amount: payload.amount,
+            decimals,
+            memo,
+            encrypt_memo: payload.encrypt_memo && memo.is_some(),
+            interval_secs: payload.interval_secs,
+            cron,
+            next_run_at,
+        },
+    )
+    .await?;
+
+    Ok(ApiResponse::new(to_response(scheduled)))
+}
@@ -0,0 +1,38 @@
+use super::{
+    ScheduledTransferPath, ScheduledTransferResponse, ScheduledTransferRunResponse,
+    get_scheduled_transfer, to_response, to_run_response,
+};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::{Path, State};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Runs returned along with a schedule.
+const RECENT_RUNS: i64 = 20;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledTransferDetailResponse {
+    #[serde(flatten)]
+    pub scheduled_transfer: ScheduledTransferResponse,
+    /// Latest runs, newest first.
+    pub runs: Vec<ScheduledTransferRunResponse>,
+}
+
+// handler is at GET /api/schedules/{id}
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<ScheduledTransferPath>,
+) -> Result<ApiResponse<ScheduledTransferDetailResponse>, AppError> {
+    let scheduled = get_scheduled_transfer(&state, path.id, auth_user.telegram_user_id).await?;
+    let runs = db::list_scheduled_transfer_runs(&state.db, scheduled.id, RECENT_RUNS).await?;
+
+    Ok(ApiResponse::new(ScheduledTransferDetailResponse {
+        scheduled_transfer: to_response(scheduled),
+        runs: runs.into_iter().map(to_run_response).collect(),
+    }))
+}
@@ -0,0 +1,21 @@
+use super::{ScheduledTransferResponse, to_response};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::State;
+use std::sync::Arc;
+
+// handler is at GET /api/schedules, lists the schedules of all of the
+// authenticated user's wallets
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<Vec<ScheduledTransferResponse>>, AppError> {
+    let scheduled =
+        db::list_scheduled_transfers_for_user(&state.db, auth_user.telegram_user_id).await?;
+
+    Ok(ApiResponse::new(
+        scheduled.into_iter().map(to_response).collect(),
+    ))
+}
@@ -0,0 +1,120 @@
+use crate::handlers::AppError;
+use crate::models::{ScheduledTransfer, ScheduledTransferRun};
+use crate::{AppState, idempotency};
+use axum::{
+    Router,
+    middleware::from_fn_with_state,
+    routing::{get, post},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+pub mod cancel;
+pub mod create;
+pub mod get;
+pub mod list;
+pub mod pause;
+pub mod resume;
+
+/// nested within /schedules prefix
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list::handler))
+        .route(
+            "/",
+            post(create::handler).layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
+        .route("/{id}", get(get::handler))
+        .route("/{id}/pause", post(pause::handler))
+        .route("/{id}/resume", post(resume::handler))
+        .route("/{id}/cancel", post(cancel::handler))
+        .with_state(state)
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledTransferPath {
+    pub id: i64,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledTransferResponse {
+    pub id: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub source: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub recipient: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub decimals: u8,
+    pub memo: Option<String>,
+    pub encrypt_memo: bool,
+    pub interval_secs: Option<i64>,
+    pub cron: Option<String>,
+    pub status: String,
+    pub next_run_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledTransferRunResponse {
+    pub id: i64,
+    pub scheduled_for: DateTime<Utc>,
+    pub status: String,
+    pub transfer_id: Option<i64>,
+    pub job_id: Option<i64>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A scheduled transfer of the authenticated user, other users' schedules are
+/// reported as not found.
+pub async fn get_scheduled_transfer(
+    state: &AppState,
+    id: i64,
+    telegram_user_id: i64,
+) -> Result<ScheduledTransfer, AppError> {
+    crate::db::get_scheduled_transfer_for_user(&state.db, id, telegram_user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found(anyhow::anyhow!("Scheduled transfer not found")))
+}
+
+pub fn to_response(scheduled: ScheduledTransfer) -> ScheduledTransferResponse {
+    ScheduledTransferResponse {
+        id: scheduled.id,
+        source: scheduled.sender,
+        recipient: scheduled.recipient,
+        mint: scheduled.mint,
+        amount: scheduled.amount,
+        decimals: scheduled.decimals,
+        memo: scheduled.memo,
+        encrypt_memo: scheduled.encrypt_memo,
+        interval_secs: scheduled.interval_secs,
+        cron: scheduled.cron,
+        status: scheduled.status.as_str().to_string(),
+        next_run_at: scheduled.next_run_at,
+        last_run_at: scheduled.last_run_at,
+        created_at: scheduled.created_at,
+    }
+}
+
+pub fn to_run_response(run: ScheduledTransferRun) -> ScheduledTransferRunResponse {
+    ScheduledTransferRunResponse {
+        id: run.id,
+        scheduled_for: run.scheduled_for,
+        status: run.status.as_str().to_string(),
+        transfer_id: run.transfer_id,
+        job_id: run.job_id,
+        reason: run.reason,
+        created_at: run.created_at,
+    }
+}
@@ -0,0 +1,28 @@
+use super::{
+    ScheduledTransferPath, ScheduledTransferResponse, get_scheduled_transfer, to_response,
+};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::{Path, State};
+use std::sync::Arc;
+
+// handler is at POST /api/schedules/{id}/pause, a run already in progress
+// still finishes
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<ScheduledTransferPath>,
+) -> Result<ApiResponse<ScheduledTransferResponse>, AppError> {
+    let scheduled = get_scheduled_transfer(&state, path.id, auth_user.telegram_user_id).await?;
+    if !db::pause_scheduled_transfer(&state.db, scheduled.id).await? {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Scheduled transfer is {}",
+            scheduled.status.as_str()
+        )));
+    }
+
+    let scheduled = get_scheduled_transfer(&state, path.id, auth_user.telegram_user_id).await?;
+    Ok(ApiResponse::new(to_response(scheduled)))
+}
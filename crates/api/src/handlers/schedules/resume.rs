@@ -0,0 +1,36 @@
+use super::{
+    ScheduledTransferPath, ScheduledTransferResponse, get_scheduled_transfer, to_response,
+};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::{Path, State};
+use chrono::Utc;
+use std::sync::Arc;
+
+// handler is at POST /api/schedules/{id}/resume. Runs missed while the
+// schedule was paused are not made up for, it continues with the next one due.
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<ScheduledTransferPath>,
+) -> Result<ApiResponse<ScheduledTransferResponse>, AppError> {
+    let scheduled = get_scheduled_transfer(&state, path.id, auth_user.telegram_user_id).await?;
+
+    let now = Utc::now();
+    let next_run_at = if scheduled.next_run_at > now {
+        scheduled.next_run_at
+    } else {
+        scheduled.schedule()?.next_after(now)?
+    };
+    if !db::resume_scheduled_transfer(&state.db, scheduled.id, next_run_at).await? {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Scheduled transfer is {}",
+            scheduled.status.as_str()
+        )));
+    }
+
+    let scheduled = get_scheduled_transfer(&state, path.id, auth_user.telegram_user_id).await?;
+    Ok(ApiResponse::new(to_response(scheduled)))
+}
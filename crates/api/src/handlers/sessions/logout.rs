@@ -0,0 +1,48 @@
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::{ApiResponse, AppError};
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogoutResponse {
+    pub revoked_sessions: u64,
+}
+
+// handler is at POST /api/auth/logout, revokes the session of the access token
+// used, along with its refresh token
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<LogoutResponse>, AppError> {
+    let revoked = match auth_user.session_id {
+        Some(session_id) => {
+            db::revoke_session(&state.db, session_id, auth_user.telegram_user_id).await?
+        }
+        None => false,
+    };
+
+    Ok(ApiResponse::new(LogoutResponse {
+        revoked_sessions: revoked.into(),
+    }))
+}
+
+// handler is at POST /api/auth/logout-all, revokes every session of the
+// authenticated user, logging out all their devices
+pub async fn all_handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<LogoutResponse>, AppError> {
+    let revoked_sessions =
+        db::revoke_sessions_for_telegram_user(&state.db, auth_user.telegram_user_id).await?;
+    info!(
+        telegram_user_id = auth_user.telegram_user_id,
+        revoked_sessions, "logged out of all sessions"
+    );
+
+    Ok(ApiResponse::new(LogoutResponse { revoked_sessions }))
+}
@@ -0,0 +1,15 @@
+use crate::AppState;
+use axum::{Router, routing::post};
+use std::sync::Arc;
+
+pub mod logout;
+pub mod refresh;
+
+/// nested within /auth prefix, next to the Telegram login at /auth/telegram
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/refresh", post(refresh::handler))
+        .route("/logout", post(logout::handler))
+        .route("/logout-all", post(logout::all_handler))
+        .with_state(state)
+}
@@ -0,0 +1,37 @@
+use crate::AppState;
+use crate::handlers::{ApiResponse, AppError};
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshResponse {
+    pub token: String,
+    pub expires_at: String,
+    /// Replaces the refresh token that was sent, which stops working.
+    pub refresh_token: String,
+    pub refresh_expires_at: String,
+}
+
+// handler is at POST /api/auth/refresh, needs no access token so an expired
+// one can be replaced
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<ApiResponse<RefreshResponse>, AppError> {
+    let tokens = crate::auth::refresh_session(&state, payload.refresh_token.trim()).await?;
+
+    Ok(ApiResponse::new(RefreshResponse {
+        token: tokens.access_token,
+        expires_at: tokens.access_token_expires_at.to_rfc3339(),
+        refresh_token: tokens.refresh_token,
+        refresh_expires_at: tokens.refresh_token_expires_at.to_rfc3339(),
+    }))
+}
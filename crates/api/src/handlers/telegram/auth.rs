@@ -2,9 +2,8 @@ use crate::AppState;
 use crate::db::upsert_telegram_user;
 use crate::handlers::{ApiResponse, AppError};
 use axum::{Json, extract::State};
-use chrono::{Duration, Utc};
+use chrono::Utc;
 use hmac::{Hmac, Mac};
-use jsonwebtoken::{EncodingKey, Header, encode};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
@@ -34,23 +33,20 @@ pub struct TelegramUser {
 
 #[derive(Debug, Default, Serialize)]
 pub struct TelegramAuthResponse {
+    /// Access token, short-lived.
     pub token: String,
     pub user: TelegramUser,
     #[serde(rename = "expiresAt")]
     pub expires_at: String,
+    /// Exchanged for new tokens at `POST /api/auth/refresh`.
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+    #[serde(rename = "refreshExpiresAt")]
+    pub refresh_expires_at: String,
     #[serde(rename = "hasReservedWallet")]
     pub has_reserved_wallet: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct JwtClaims {
-    sub: String,
-    telegram_user_id: i64,
-    username: Option<String>,
-    exp: usize,
-    iat: usize,
-}
-
 #[derive(Debug, Deserialize)]
 struct TelegramInitDataUser {
     id: i64,
@@ -107,18 +103,18 @@ fn verify_init_data(init_data: &str, bot_token: &str) -> Result<TelegramUser, Ap
     }
 
     // Optionally check auth_date to prevent replay attacks (e.g., within last hour)
-    if let Some(auth_date_str) = params.get("auth_date") {
-        if let Ok(auth_date) = auth_date_str.parse::<i64>() {
-            let now = Utc::now().timestamp();
-            let age_seconds = now - auth_date;
-            let max_age_seconds = 3600; // 1 hour
-
-            if age_seconds > max_age_seconds {
-                return Err(AppError::new(
-                    anyhow::anyhow!("initData expired"),
-                    StatusCode::UNAUTHORIZED,
-                ));
-            }
+    if let Some(auth_date_str) = params.get("auth_date")
+        && let Ok(auth_date) = auth_date_str.parse::<i64>()
+    {
+        let now = Utc::now().timestamp();
+        let age_seconds = now - auth_date;
+        let max_age_seconds = 3600; // 1 hour
+
+        if age_seconds > max_age_seconds {
+            return Err(AppError::new(
+                anyhow::anyhow!("initData expired"),
+                StatusCode::UNAUTHORIZED,
+            ));
         }
     }
 
@@ -142,33 +138,11 @@ fn verify_init_data(init_data: &str, bot_token: &str) -> Result<TelegramUser, Ap
     })
 }
 
-fn generate_jwt(user: &TelegramUser, jwt_secret: &str) -> Result<(String, String), AppError> {
-    let now = Utc::now();
-    let expires_at = now + Duration::hours(24);
-
-    let claims = JwtClaims {
-        sub: user.telegram_user_id.to_string(),
-        telegram_user_id: user.telegram_user_id,
-        username: user.username.clone(),
-        exp: expires_at.timestamp() as usize,
-        iat: now.timestamp() as usize,
-    };
-
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(jwt_secret.as_bytes()),
-    )
-    .map_err(|e| AppError::internal_server_error(anyhow::anyhow!("JWT encoding failed: {}", e)))?;
-
-    Ok((token, expires_at.to_rfc3339()))
-}
-
 pub async fn handler(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<TelegramAuthRequest>,
 ) -> Result<ApiResponse<TelegramAuthResponse>, AppError> {
-    let user = if state.dev_mode {
+    let user = if state.config.dev_mode {
         info!("dev mode enabled, using mock user");
         TelegramUser {
             telegram_user_id: 123456789,
@@ -178,7 +152,7 @@ pub async fn handler(
             language_code: Some("en".to_string()),
         }
     } else {
-        verify_init_data(&payload.init_data, &state.telegram_bot_token)?
+        verify_init_data(&payload.init_data, state.config.telegram_bot_token.expose())?
     };
 
     info!(
@@ -200,12 +174,15 @@ pub async fn handler(
         AppError::internal_server_error(anyhow::anyhow!("Failed to save user: {}", e))
     })?;
 
-    let (token, expires_at) = generate_jwt(&user, &state.jwt_secret)?;
+    let tokens =
+        crate::auth::start_session(&state, user.telegram_user_id, user.username.as_deref()).await?;
 
     Ok(ApiResponse::new(TelegramAuthResponse {
-        token,
+        token: tokens.access_token,
         user,
-        expires_at,
+        expires_at: tokens.access_token_expires_at.to_rfc3339(),
+        refresh_token: tokens.refresh_token,
+        refresh_expires_at: tokens.refresh_token_expires_at.to_rfc3339(),
         has_reserved_wallet: upsert_result.claimed_reserved_wallet,
     }))
 }
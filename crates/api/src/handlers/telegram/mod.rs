@@ -1,4 +1,5 @@
 pub mod auth;
+pub mod webhook;
 
 use crate::AppState;
 use axum::{Router, routing::post};
@@ -0,0 +1,65 @@
+use crate::handlers::AppError;
+use crate::telegram::api::Update;
+use crate::{AppState, db, telegram};
+use axum::{Json, extract::State, http::HeaderMap};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Header Telegram sends the secret token registered with `setWebhook` in.
+pub const SECRET_TOKEN_HEADER: &str = "x-telegram-bot-api-secret-token";
+
+// handler is at POST /api/telegram/webhook, Telegram delivers bot updates to
+// it. Commands are answered in the background, Telegram only needs a quick 200.
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(update): Json<Update>,
+) -> Result<StatusCode, AppError> {
+    let Some(bot) = &state.config.bot else {
+        return Err(AppError::not_found(anyhow::anyhow!("Bot is not enabled")));
+    };
+    let secret_token = headers
+        .get(SECRET_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !secret_token_matches(secret_token, bot.webhook_secret.expose()) {
+        return Err(AppError::new(
+            anyhow::anyhow!("Invalid secret token"),
+            StatusCode::UNAUTHORIZED,
+        ));
+    }
+
+    if !db::claim_telegram_update(&state.db, update.update_id).await? {
+        info!(update_id = update.update_id, "ignoring redelivered update");
+        return Ok(StatusCode::OK);
+    }
+
+    let update_id = update.update_id;
+    tokio::spawn(async move {
+        if let Err(e) = telegram::bot::handle_update(&state, update).await {
+            error!(update_id, "failed to handle bot update: {:?}", e);
+        }
+    });
+
+    Ok(StatusCode::OK)
+}
+
+/// Compare digests, so the time taken doesn't depend on how much of the
+/// secret was guessed.
+fn secret_token_matches(given: &str, secret: &str) -> bool {
+    Sha256::digest(given.as_bytes()) == Sha256::digest(secret.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_token_matches() {
+        assert!(secret_token_matches("webhook-secret", "webhook-secret"));
+        assert!(!secret_token_matches("webhook-secreT", "webhook-secret"));
+        assert!(!secret_token_matches("", "webhook-secret"));
+    }
+}
@@ -1,4 +1,6 @@
 use crate::solana;
+use crate::solana::GeneratedInstructions;
+use crate::solana::submit::submit_sequence;
 use crate::solana::tokens::setup_token_account_with_keys;
 use crate::solana::utils::confidential_keys_for_mint;
 use crate::{
@@ -12,7 +14,6 @@ use axum::{Json, extract::State};
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_keypair::Keypair;
 use solana_keypair::Signature;
 use solana_pubkey::Pubkey;
 use solana_signer::Signer;
@@ -68,7 +69,7 @@ pub async fn handler(
     );
 
     // Validate recipient wallet
-    let ata_authority: Arc<Keypair> = validate_recipient_wallet(&state.db, &path.address).await?;
+    let ata_authority = validate_recipient_wallet(&state, &path.address).await?;
 
     // Prepare confidential keys and parameters
     let confidential_keys = confidential_keys_for_mint(ata_authority.clone(), &payload.mint)?;
@@ -137,8 +138,11 @@ fn scale_decimal_amount(amount: f64) -> u64 {
     (amount * DECIMAL_MULTIPLIER) as u64
 }
 
-async fn validate_recipient_wallet(db: &sqlx::PgPool, address: &Pubkey) -> Result<Arc<Keypair>> {
-    let wallet = db::get_wallet_by_pubkey(db, address)
+async fn validate_recipient_wallet(
+    state: &AppState,
+    address: &Pubkey,
+) -> Result<Arc<dyn Signer + Send + Sync>> {
+    let wallet = db::get_wallet_by_pubkey(&state.db, address)
         .await?
         .ok_or_else(|| AppError::not_found(anyhow::anyhow!("Recipient wallet not found")))?;
 
@@ -148,7 +152,7 @@ async fn validate_recipient_wallet(db: &sqlx::PgPool, address: &Pubkey) -> Resul
         ));
     }
 
-    Ok(wallet.keypair)
+    state.key_store.signer(wallet.id).await
 }
 
 /// Sends a transaction and confirms it, with consistent error handling.
@@ -239,20 +243,28 @@ async fn execute_confidential_mint(
     )
     .await?;
 
-    let mut last_signature = Signature::default();
-    for pending in pending_txs {
-        let transaction = build_transaction(
-            state.rpc_client.clone(),
-            None,
-            pending.instructions,
-            global_authority.clone(),
-            pending.additional_signers,
-        )
-        .await?;
-
-        last_signature =
-            send_transaction(state.rpc_client.clone(), &transaction, "confidential mint").await?;
-    }
+    let steps = pending_txs
+        .into_iter()
+        .map(|pending| GeneratedInstructions {
+            instructions: pending.instructions,
+            additional_signers: pending.additional_signers,
+        })
+        .collect();
+    let signatures = submit_sequence(
+        state.rpc_client.clone(),
+        state.submitter.as_ref(),
+        global_authority,
+        vec![],
+        steps,
+    )
+    .await
+    .map_err(|e| {
+        AppError::internal_server_error(anyhow::anyhow!(
+            "Failed to send and confirm confidential mint transactions: {}",
+            e
+        ))
+    })?;
+    let last_signature = signatures.last().copied().unwrap_or_default();
 
     info!(
         "Completed confidential mint for mint={:?} with signature={:?}",
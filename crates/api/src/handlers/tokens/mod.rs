@@ -1,10 +1,11 @@
 use axum::{
     Router,
+    middleware::from_fn_with_state,
     routing::{get, post},
 };
 use std::sync::Arc;
 
-use crate::AppState;
+use crate::{AppState, idempotency};
 
 pub mod create;
 pub mod mint;
@@ -15,7 +16,10 @@ pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         // .route("/{address}", get(get_token::handler))
         .route("/", post(create::handler))
-        .route("/{address}/mint", post(mint::handler))
+        .route(
+            "/{address}/mint",
+            post(mint::handler).layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
         .route("/{mint}/supply", get(supply::handler))
         .with_state(state)
 }
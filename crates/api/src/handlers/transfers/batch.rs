@@ -0,0 +1,461 @@
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::wallets::deposit::TransactionResult;
+use crate::handlers::{ApiResponse, AppError};
+use crate::models::{TransferStatus, Wallet};
+use crate::solana::transfer::ensure_confidential_balance;
+use crate::{db, fees, jobs};
+use axum::Json;
+use axum::extract::{Path, State};
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Most recipients accepted in a single batch.
+pub const MAX_BATCH_RECIPIENTS: usize = 50;
+
+/// Recipients whose wallet and confidential account are prepared at once.
+const RECIPIENT_SETUP_CONCURRENCY: usize = 8;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTransferRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub source: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    pub recipients: Vec<BatchRecipient>,
+    /// Sent with every transfer of the batch, at most 256 bytes.
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub encrypt_memo: bool,
+}
+
+/// A payout to either a Solana address or a Telegram username.
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchRecipient {
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub recipient: Option<Pubkey>,
+    pub telegram_username: Option<String>,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTransferResult {
+    /// Position of the recipient in the request.
+    pub index: usize,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub recipient: Option<Pubkey>,
+    pub telegram_username: Option<String>,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub status: String,
+    pub transfer_id: Option<i64>,
+    pub job_id: Option<i64>,
+    pub transactions: Vec<TransactionResult>,
+    pub error: Option<String>,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTransferResponse {
+    pub batch_id: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub total_amount: u64,
+    pub completed: usize,
+    pub failed: usize,
+    /// Transfers still running, poll `GET /api/transfers/batch/{id}` until none are.
+    pub pending: usize,
+    pub results: Vec<BatchTransferResult>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchPath {
+    pub id: i64,
+}
+
+/// Who a batch entry pays, usernames compared case-insensitively so a
+/// username listed twice gets a single reserved wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Target {
+    Address(Pubkey),
+    Username(String),
+}
+
+/// A recipient whose confidential account is ready to receive the mint.
+#[derive(Clone)]
+struct PreparedRecipient {
+    pubkey: Pubkey,
+    /// Our wallet for the recipient, for Telegram usernames.
+    wallet: Option<Wallet>,
+}
+
+// handler is at POST /api/transfers/batch, pays out to many recipients from
+// one source wallet. The sender balance is checked (and topped up from the
+// public balance, like a single transfer) once for the whole batch, and
+// recipient wallets are set up concurrently. Every transfer is recorded before
+// the response, then they run one after the other in the background: each one
+// proves against the source account's current available balance, so two
+// transfers from the same account can't overlap, and all of them are leased to
+// the background run so the recovery loop doesn't start one early. The client follows them with
+// `status_handler`. A failed recipient is reported in its result and doesn't
+// stop the batch.
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<BatchTransferRequest>,
+) -> Result<ApiResponse<BatchTransferResponse>, AppError> {
+    let sender_wallet =
+        super::validate_sender_wallet(&state, &payload.source, auth_user.telegram_user_id).await?;
+    super::validate_confidential_mint(&state, &payload.mint).await?;
+    let memo = super::normalize_memo(payload.memo.as_deref())?;
+    let targets = validate_recipients(&payload.recipients)?;
+    let total_amount = payload
+        .recipients
+        .iter()
+        .try_fold(0u64, |total, r| total.checked_add(r.amount))
+        .ok_or_else(|| AppError::bad_request(anyhow::anyhow!("Batch total overflows")))?;
+
+    let mint_decimals = super::get_mint_decimals(&state, &payload.mint).await?;
+
+    fees::ensure_budget(&state, sender_wallet.id).await?;
+
+    let sender = state.key_store.signer(sender_wallet.id).await?;
+    let balance_fees = ensure_confidential_balance(
+        state.rpc_client.clone(),
+        fees::payer(&state, &sender),
+        sender,
+        &payload.mint,
+        mint_decimals,
+        total_amount,
+    )
+    .await
+    .map_err(|e| {
+        AppError::bad_request(anyhow::anyhow!(
+            "Insufficient confidential balance for batch total of {}: {}",
+            total_amount,
+            e
+        ))
+    })?;
+    fees::charge(&state, sender_wallet.id, balance_fees).await;
+
+    let unique_targets = targets.iter().cloned().collect::<HashSet<_>>();
+    let prepared: HashMap<Target, Result<PreparedRecipient, String>> = stream::iter(unique_targets)
+        .map(|target| {
+            let state = &state;
+            let mint = payload.mint;
+            let sender_wallet_id = sender_wallet.id;
+            async move {
+                let prepared = prepare_recipient(state, &target, &mint, sender_wallet_id)
+                    .await
+                    .map_err(|e| e.to_string());
+                (target, prepared)
+            }
+        })
+        .buffer_unordered(RECIPIENT_SETUP_CONCURRENCY)
+        .collect()
+        .await;
+
+    let transfer_memo = memo.map(|text| super::TransferMemo {
+        text,
+        encrypt: payload.encrypt_memo,
+    });
+    let mut entries = Vec::with_capacity(payload.recipients.len());
+    let mut job_ids = vec![];
+    for (index, (entry, target)) in payload.recipients.iter().zip(&targets).enumerate() {
+        let prepared = &prepared[target];
+        let recorded = match prepared {
+            Ok(recipient) => {
+                record(
+                    &state,
+                    &sender_wallet,
+                    recipient,
+                    entry.amount,
+                    payload.mint,
+                    mint_decimals,
+                    transfer_memo,
+                )
+                .await
+            }
+            Err(e) => Err(e.clone()),
+        };
+        let (transfer_id, error) = match recorded {
+            Ok((transfer_id, job_id)) => {
+                job_ids.push(job_id);
+                (Some(transfer_id), None)
+            }
+            Err(e) => {
+                warn!(index, "batch transfer failed: {}", e);
+                (None, Some(e))
+            }
+        };
+        entries.push(db::NewBatchEntry {
+            recipient: match prepared {
+                Ok(recipient) => Some(&recipient.pubkey),
+                Err(_) => entry.recipient.as_ref(),
+            },
+            telegram_username: entry.telegram_username.as_deref(),
+            amount: entry.amount,
+            transfer_id,
+            error,
+        });
+    }
+
+    let batch_id = db::create_transfer_batch(
+        &state.db,
+        sender_wallet.id,
+        &payload.mint,
+        total_amount,
+        &entries,
+    )
+    .await?;
+    info!(
+        "batch {} from {}: {} transfers recorded, {} failed",
+        batch_id,
+        sender_wallet.pubkey,
+        job_ids.len(),
+        entries.len() - job_ids.len()
+    );
+
+    // an interrupted run is picked up by the recovery loop, job by job
+    let run_state = state.clone();
+    tokio::spawn(async move {
+        let results = jobs::transfer::run_in_order(&run_state, &job_ids).await;
+        for (job_id, result) in job_ids.iter().zip(results) {
+            if let Err(e) = result {
+                warn!(batch_id, job_id, "batch transfer failed: {:?}", e);
+            }
+        }
+    });
+
+    get_batch(&state, batch_id, auth_user.telegram_user_id).await
+}
+
+// handler is at GET /api/transfers/batch/{id}, the results of a batch sent by
+// the authenticated user so far
+pub async fn status_handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<BatchPath>,
+) -> Result<ApiResponse<BatchTransferResponse>, AppError> {
+    get_batch(&state, path.id, auth_user.telegram_user_id).await
+}
+
+async fn get_batch(
+    state: &AppState,
+    id: i64,
+    telegram_user_id: i64,
+) -> Result<ApiResponse<BatchTransferResponse>, AppError> {
+    let batch = db::get_user_transfer_batch(&state.db, id, telegram_user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found(anyhow::anyhow!("Batch not found")))?;
+
+    let results = batch
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let status = entry.status.unwrap_or(TransferStatus::Failed);
+            BatchTransferResult {
+                index: entry.position,
+                recipient: entry.recipient,
+                telegram_username: entry.telegram_username,
+                amount: entry.amount,
+                status: status.as_str().to_string(),
+                transfer_id: entry.transfer_id,
+                job_id: entry.job_id,
+                transactions: super::format_transfer_results(&entry.signatures),
+                error: entry.error,
+            }
+        })
+        .collect::<Vec<_>>();
+    let count = |status: TransferStatus| {
+        results
+            .iter()
+            .filter(|r| r.status == status.as_str())
+            .count()
+    };
+
+    Ok(ApiResponse::new(BatchTransferResponse {
+        batch_id: batch.id,
+        mint: batch.mint,
+        total_amount: batch.total_amount,
+        completed: count(TransferStatus::Completed),
+        failed: count(TransferStatus::Failed),
+        pending: count(TransferStatus::Pending),
+        results,
+        created_at: batch.created_at,
+    }))
+}
+
+fn validate_recipients(recipients: &[BatchRecipient]) -> Result<Vec<Target>, AppError> {
+    if recipients.is_empty() || recipients.len() > MAX_BATCH_RECIPIENTS {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "A batch takes between 1 and {} recipients",
+            MAX_BATCH_RECIPIENTS
+        )));
+    }
+
+    recipients
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            if entry.amount == 0 {
+                return Err(AppError::bad_request(anyhow::anyhow!(
+                    "Recipient {}: amount must be greater than 0",
+                    index
+                )));
+            }
+            let username = entry
+                .telegram_username
+                .as_deref()
+                .map(|u| u.trim().trim_start_matches('@'))
+                .filter(|u| !u.is_empty());
+            match (entry.recipient, username) {
+                (Some(pubkey), None) => Ok(Target::Address(pubkey)),
+                (None, Some(username)) => Ok(Target::Username(username.to_lowercase())),
+                _ => Err(AppError::bad_request(anyhow::anyhow!(
+                    "Recipient {}: set exactly one of recipient or telegramUsername",
+                    index
+                ))),
+            }
+        })
+        .collect()
+}
+
+async fn prepare_recipient(
+    state: &AppState,
+    target: &Target,
+    mint: &Pubkey,
+    sender_wallet_id: i64,
+) -> Result<PreparedRecipient, AppError> {
+    match target {
+        Target::Address(pubkey) => {
+            super::validate_recipient_account(state, pubkey, mint).await?;
+            Ok(PreparedRecipient {
+                pubkey: *pubkey,
+                wallet: None,
+            })
+        }
+        Target::Username(username) => {
+            let recipient_info = super::telegram::get_or_create_recipient_wallet(state, username)
+                .await
+                .map_err(AppError::from)?;
+            super::ensure_recipient_confidential_account(
+                state,
+                &recipient_info.wallet,
+                mint,
+                sender_wallet_id,
+            )
+            .await?;
+            Ok(PreparedRecipient {
+                pubkey: recipient_info.wallet.pubkey,
+                wallet: Some(recipient_info.wallet),
+            })
+        }
+    }
+}
+
+/// Record the transfer to a prepared recipient, already covered by the
+/// batch's balance top-up, remembering the sender if it goes to a reserved
+/// wallet (see the Telegram transfer handler).
+async fn record(
+    state: &AppState,
+    sender_wallet: &Wallet,
+    recipient: &PreparedRecipient,
+    amount: u64,
+    mint: Pubkey,
+    mint_decimals: u8,
+    memo: Option<super::TransferMemo<'_>>,
+) -> Result<(i64, i64), String> {
+    let recorded: Result<_, AppError> = async {
+        let transfer = super::prepare_transfer(
+            state,
+            sender_wallet,
+            recipient.pubkey,
+            amount,
+            mint,
+            mint_decimals,
+            memo,
+        )
+        .await?
+        .with_funded_balance();
+        let (transfer_id, job_id) =
+            db::create_transfer(&state.db, &transfer.ledger_entry()).await?;
+
+        if let Some(wallet) = &recipient.wallet {
+            db::record_reserved_wallet_credit(
+                &state.db,
+                wallet.id,
+                transfer_id,
+                state.config.reserved_wallet_ttl.num_seconds(),
+            )
+            .await?;
+        }
+
+        Ok((transfer_id, job_id))
+    }
+    .await;
+
+    recorded.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(recipient: Option<Pubkey>, username: Option<&str>, amount: u64) -> BatchRecipient {
+        BatchRecipient {
+            recipient,
+            telegram_username: username.map(str::to_string),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_validate_recipients() {
+        let pubkey = Pubkey::new_unique();
+        let targets = validate_recipients(&[
+            entry(Some(pubkey), None, 1),
+            entry(None, Some("@Alice "), 2),
+            entry(None, Some("alice"), 3),
+        ])
+        .unwrap();
+        assert_eq!(
+            targets,
+            vec![
+                Target::Address(pubkey),
+                Target::Username("alice".to_string()),
+                Target::Username("alice".to_string()),
+            ]
+        );
+
+        assert!(validate_recipients(&[]).is_err());
+        assert!(validate_recipients(&[entry(Some(pubkey), None, 0)]).is_err());
+        assert!(validate_recipients(&[entry(None, None, 1)]).is_err());
+        assert!(validate_recipients(&[entry(Some(pubkey), Some("alice"), 1)]).is_err());
+        assert!(validate_recipients(&[entry(None, Some("  "), 1)]).is_err());
+        assert!(
+            validate_recipients(&vec![
+                entry(Some(pubkey), None, 1);
+                MAX_BATCH_RECIPIENTS + 1
+            ])
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use super::link::hash_claim_secret;
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::wallets::deposit::TransactionResult;
+use crate::handlers::{ApiResponse, AppError};
+use crate::models::{ClaimLinkStatus, Wallet};
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimRequest {
+    pub secret: String,
+    /// Wallet to receive the funds, defaults to the user's first wallet.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub wallet: Option<Pubkey>,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimResponse {
+    pub link_id: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub recipient: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub memo: Option<String>,
+    pub transfer_id: i64,
+    pub job_id: i64,
+    pub transactions: Vec<TransactionResult>,
+}
+
+// handler is at POST /api/transfers/claim, pays out a claim link to the
+// authenticated user
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<ClaimRequest>,
+) -> Result<ApiResponse<ClaimResponse>, AppError> {
+    let Some(link) =
+        db::get_claim_link_by_secret_hash(&state.db, &hash_claim_secret(payload.secret.trim()))
+            .await?
+    else {
+        return Err(AppError::not_found(anyhow::anyhow!("Claim link not found")));
+    };
+
+    match link.status {
+        ClaimLinkStatus::Open => {}
+        ClaimLinkStatus::Funding => {
+            return Err(AppError::conflict(anyhow::anyhow!(
+                "Claim link is still being funded"
+            )));
+        }
+        ClaimLinkStatus::Claiming => {
+            return Err(AppError::conflict(anyhow::anyhow!(
+                "Claim link is already being claimed"
+            )));
+        }
+        ClaimLinkStatus::Claimed => {
+            return Err(AppError::conflict(anyhow::anyhow!(
+                "Claim link has already been claimed"
+            )));
+        }
+        ClaimLinkStatus::Failed => {
+            return Err(AppError::conflict(anyhow::anyhow!(
+                "Claim link was never funded"
+            )));
+        }
+        ClaimLinkStatus::Expired | ClaimLinkStatus::Refunded => {
+            return Err(AppError::conflict(anyhow::anyhow!(
+                "Claim link has expired"
+            )));
+        }
+    }
+
+    let recipient_wallet =
+        get_recipient_wallet(&state, &payload, auth_user.telegram_user_id).await?;
+    super::ensure_recipient_confidential_account(
+        &state,
+        &recipient_wallet,
+        &link.mint,
+        link.sender_wallet_id,
+    )
+    .await?;
+    let mint_decimals = super::get_mint_decimals(&state, &link.mint).await?;
+
+    let escrow_wallet = db::get_wallet_by_id(&state.db, link.escrow_wallet_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Escrow wallet {} not found", link.escrow_wallet_id))?;
+
+    let transfer = super::prepare_transfer(
+        &state,
+        &escrow_wallet,
+        recipient_wallet.pubkey,
+        link.amount,
+        link.mint,
+        mint_decimals,
+        link.memo.as_deref().map(super::TransferMemo::encrypted),
+    )
+    .await?;
+
+    // the link is marked claimed once the transfer lands, or opened up again
+    // if it fails (see `db::sync_transfer_with_job`)
+    let Some((transfer_id, job_id)) = db::reserve_claim_link(
+        &state.db,
+        link.id,
+        recipient_wallet.id,
+        &transfer.ledger_entry(),
+    )
+    .await?
+    else {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Claim link is already being claimed or has expired"
+        )));
+    };
+    let job = super::finish_transfer(&state, transfer_id, job_id).await?;
+
+    Ok(ApiResponse::new(ClaimResponse {
+        link_id: link.id,
+        recipient: recipient_wallet.pubkey,
+        mint: link.mint,
+        amount: link.amount,
+        memo: link.memo,
+        transfer_id,
+        job_id: job.id,
+        transactions: super::format_transfer_results(&job.signatures()),
+    }))
+}
+
+async fn get_recipient_wallet(
+    state: &AppState,
+    payload: &ClaimRequest,
+    telegram_user_id: i64,
+) -> Result<Wallet, AppError> {
+    let pubkey = match payload.wallet {
+        Some(pubkey) => pubkey,
+        None => db::get_wallets_for_telegram_user(&state.db, telegram_user_id)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                AppError::bad_request(anyhow::anyhow!("Create a wallet before claiming"))
+            })?,
+    };
+
+    db::get_user_wallet_by_pubkey(&state.db, &pubkey, telegram_user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found(anyhow::anyhow!("Wallet not found or not authorized")))
+}
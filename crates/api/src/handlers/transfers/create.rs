@@ -2,7 +2,6 @@ use crate::AppState;
 use crate::auth::AuthUser;
 use crate::handlers::wallets::deposit::TransactionResult;
 use crate::handlers::{ApiResponse, AppError};
-use crate::solana;
 use axum::{Json, extract::State};
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
@@ -21,13 +20,22 @@ pub struct CreateTransferRequest {
     pub mint: Pubkey,
     #[serde_as(as = "DisplayFromStr")]
     pub amount: u64,
+    /// Written on chain with the transfer, at most 256 bytes.
+    pub memo: Option<String>,
+    /// Encrypt the on-chain memo so only the sender and recipient can read it.
+    #[serde(default)]
+    pub encrypt_memo: bool,
 }
 
 #[serde_as]
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TransferResponse {
+    pub transfer_id: i64,
+    pub job_id: i64,
     pub transactions: Vec<TransactionResult>,
+    pub memo: Option<String>,
+    pub memo_encrypted: bool,
 }
 
 pub async fn handler(
@@ -38,42 +46,32 @@ pub async fn handler(
     let sender_wallet =
         super::validate_sender_wallet(&state, &payload.source, auth_user.telegram_user_id).await?;
     super::validate_confidential_mint(&state, &payload.mint).await?;
-
-    let (_, maybe_recipient_ata_account) = solana::tokens::get_maybe_ata(
-        state.rpc_client.clone(),
-        &payload.recipient,
-        &payload.mint,
-    )
-    .await?;
-    if maybe_recipient_ata_account.is_none() {
-        return Err(AppError::bad_request(anyhow::anyhow!(
-            "Recipient confidential token account not found",
-        )));
-    }
-    let requires_setup = solana::tokens::ata_has_confidential_transfer_extension(
-        maybe_recipient_ata_account,
-        &payload.recipient,
-        &payload.mint,
-    )?;
-    if requires_setup {
-        return Err(AppError::bad_request(anyhow::anyhow!(
-            "Recipient confidential token account is not configured"
-        )));
-    }
+    super::validate_recipient_account(&state, &payload.recipient, &payload.mint).await?;
+    let memo = super::normalize_memo(payload.memo.as_deref())?;
 
     let mint_decimals = super::get_mint_decimals(&state, &payload.mint).await?;
 
-    let transfer_signatures = super::execute_transfer(
-        state.rpc_client.clone(),
-        sender_wallet.keypair.clone(),
+    let (transfer_id, job) = super::execute_transfer(
+        &state,
+        &sender_wallet,
         &payload.recipient,
         payload.amount,
         payload.mint,
         mint_decimals,
+        memo.map(|text| super::TransferMemo {
+            text,
+            encrypt: payload.encrypt_memo,
+        }),
     )
     .await?;
 
-    let transactions = super::format_transfer_results(&transfer_signatures);
+    let transactions = super::format_transfer_results(&job.signatures());
 
-    Ok(ApiResponse::new(TransferResponse { transactions }))
+    Ok(ApiResponse::new(TransferResponse {
+        transfer_id,
+        job_id: job.id,
+        transactions,
+        memo: memo.map(str::to_string),
+        memo_encrypted: memo.is_some() && payload.encrypt_memo,
+    }))
 }
@@ -0,0 +1,184 @@
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::{ApiResponse, AppError};
+use crate::{db, fees, jobs};
+use axum::Json;
+use axum::extract::{Path, State};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use sha2::{Digest, Sha256};
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use std::sync::Arc;
+use tracing::error;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateClaimLinkRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub source: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateClaimLinkResponse {
+    pub link_id: i64,
+    /// One-time URL carrying the claim secret. It is not stored and can't be
+    /// shown again.
+    pub claim_url: String,
+    /// `funding` until the transfer into the escrow wallet lands, see
+    /// [`status_handler`].
+    pub status: String,
+    pub expires_at: DateTime<Utc>,
+    pub transfer_id: i64,
+    pub job_id: i64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimLinkPath {
+    pub id: i64,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimLinkResponse {
+    pub id: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub memo: Option<String>,
+    pub status: String,
+    pub expires_at: DateTime<Utc>,
+    pub funding_transfer_id: Option<i64>,
+    pub claim_transfer_id: Option<i64>,
+    pub refund_transfer_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// New random claim secret, base58 encoded so it can go in a Telegram
+/// `startapp` parameter.
+pub fn generate_claim_secret() -> String {
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    bs58::encode(secret).into_string()
+}
+
+/// Hex encoded SHA-256 of a claim secret, the only form it is stored in.
+pub fn hash_claim_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// handler is at POST /api/transfers/link, records a link anyone can use to
+// claim the amount and returns it right away. The funds move into the link's
+// escrow wallet in the background, follow it with `status_handler`.
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateClaimLinkRequest>,
+) -> Result<ApiResponse<CreateClaimLinkResponse>, AppError> {
+    let sender_wallet =
+        super::validate_sender_wallet(&state, &payload.source, auth_user.telegram_user_id).await?;
+    super::validate_confidential_mint(&state, &payload.mint).await?;
+
+    if payload.amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Transfer amount must be greater than 0"
+        )));
+    }
+    let memo = super::normalize_memo(payload.memo.as_deref())?;
+
+    let mint_decimals = super::get_mint_decimals(&state, &payload.mint).await?;
+
+    // the escrow wallet sends the claim transfer, so it gets its confidential
+    // account up front
+    let keypair = Keypair::new();
+    let secret = state.key_store.seal(&keypair)?;
+    let escrow_wallet = db::create_escrow_wallet(&state.db, &keypair.pubkey(), &secret).await?;
+    fees::fund_new_wallet(&state, &escrow_wallet.pubkey)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to fund escrow wallet: {}", e))?;
+    super::ensure_recipient_confidential_account(
+        &state,
+        &escrow_wallet,
+        &payload.mint,
+        sender_wallet.id,
+    )
+    .await?;
+
+    let funding = super::prepare_transfer(
+        &state,
+        &sender_wallet,
+        escrow_wallet.pubkey,
+        payload.amount,
+        payload.mint,
+        mint_decimals,
+        memo.map(super::TransferMemo::encrypted),
+    )
+    .await?;
+    let claim_secret = generate_claim_secret();
+    let (link, transfer_id, job_id) = db::create_claim_link(
+        &state.db,
+        escrow_wallet.id,
+        &hash_claim_secret(&claim_secret),
+        state.config.claim_link_ttl.num_seconds(),
+        &funding.ledger_entry(),
+    )
+    .await?;
+
+    // the secret is only in this response, so it goes out before anything is
+    // sent. The link opens up once the funding transfer lands, or fails with
+    // it (see `db::sync_transfer_with_job`), and an interrupted job is resumed
+    // by the recovery loop.
+    let job_state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = jobs::transfer::run(&job_state, job_id).await {
+            error!("funding of claim link {} failed: {:?}", link.id, e);
+        }
+    });
+
+    Ok(ApiResponse::new(CreateClaimLinkResponse {
+        link_id: link.id,
+        claim_url: format!("{}{}", state.config.claim_link_base_url, claim_secret),
+        status: link.status.as_str().to_string(),
+        expires_at: link.expires_at,
+        transfer_id,
+        job_id,
+    }))
+}
+
+// handler is at GET /api/transfers/link/{id}, the status of a link sent by the
+// authenticated user
+pub async fn status_handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(path): Path<ClaimLinkPath>,
+) -> Result<ApiResponse<ClaimLinkResponse>, AppError> {
+    let link = db::get_user_claim_link(&state.db, path.id, auth_user.telegram_user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found(anyhow::anyhow!("Claim link not found")))?;
+
+    Ok(ApiResponse::new(ClaimLinkResponse {
+        id: link.id,
+        mint: link.mint,
+        amount: link.amount,
+        memo: link.memo,
+        status: link.status.as_str().to_string(),
+        expires_at: link.expires_at,
+        funding_transfer_id: link.funding_transfer_id,
+        claim_transfer_id: link.claim_transfer_id,
+        refund_transfer_id: link.refund_transfer_id,
+        created_at: link.created_at,
+    }))
+}
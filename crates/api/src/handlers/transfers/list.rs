@@ -0,0 +1,141 @@
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::models::Transfer;
+use axum::extract::{Query, State};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_keypair::Signature;
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTransfersQuery {
+    /// `nextCursor` from the previous page.
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferDirection {
+    #[default]
+    Sent,
+    Received,
+    /// Between two wallets of the same user.
+    SelfTransfer,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferItem {
+    pub id: i64,
+    pub direction: TransferDirection,
+    #[serde_as(as = "DisplayFromStr")]
+    pub sender: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub recipient: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub decimals: u8,
+    pub memo: Option<String>,
+    pub memo_encrypted: bool,
+    pub status: String,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub signatures: Vec<Signature>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTransfersResponse {
+    pub transfers: Vec<TransferItem>,
+    pub next_cursor: Option<String>,
+}
+
+// handler is at GET /api/transfers, returns transfers sent or received by the
+// authenticated user's wallets, newest first
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Query(query): Query<ListTransfersQuery>,
+) -> Result<ApiResponse<ListTransfersResponse>, AppError> {
+    let before_id = query
+        .cursor
+        .as_deref()
+        .map(|cursor| {
+            cursor
+                .parse::<i64>()
+                .map_err(|_| AppError::bad_request(anyhow::anyhow!("Invalid cursor")))
+        })
+        .transpose()?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let wallets = db::get_wallets_for_telegram_user(&state.db, auth_user.telegram_user_id)
+        .await
+        .map_err(|e| {
+            AppError::internal_server_error(anyhow::anyhow!("Failed to get wallets: {}", e))
+        })?;
+    if wallets.is_empty() {
+        return Ok(ApiResponse::new(ListTransfersResponse::default()));
+    }
+
+    // fetch one extra row to know whether there is another page
+    let mut transfers =
+        db::list_transfers_for_wallets(&state.db, &wallets, before_id, limit as i64 + 1).await?;
+    let next_cursor = if transfers.len() > limit as usize {
+        transfers.truncate(limit as usize);
+        transfers.last().map(|transfer| transfer.id.to_string())
+    } else {
+        None
+    };
+
+    let transfers = transfers
+        .into_iter()
+        .map(|transfer| to_item(transfer, &wallets))
+        .collect();
+
+    Ok(ApiResponse::new(ListTransfersResponse {
+        transfers,
+        next_cursor,
+    }))
+}
+
+fn to_item(transfer: Transfer, wallets: &[Pubkey]) -> TransferItem {
+    let direction = match (
+        wallets.contains(&transfer.sender),
+        wallets.contains(&transfer.recipient),
+    ) {
+        (true, true) => TransferDirection::SelfTransfer,
+        (true, false) => TransferDirection::Sent,
+        _ => TransferDirection::Received,
+    };
+
+    TransferItem {
+        id: transfer.id,
+        direction,
+        sender: transfer.sender,
+        recipient: transfer.recipient,
+        mint: transfer.mint,
+        amount: transfer.amount,
+        decimals: transfer.decimals,
+        memo: transfer.memo,
+        memo_encrypted: transfer.memo_encrypted,
+        status: transfer.status.as_str().to_string(),
+        signatures: transfer.signatures,
+        created_at: transfer.created_at,
+    }
+}
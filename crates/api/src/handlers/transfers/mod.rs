@@ -1,19 +1,36 @@
 use crate::handlers::AppError;
 use crate::handlers::wallets::deposit::TransactionResult;
-use crate::solana::transfer;
-use crate::{AppState, db, solana};
-use axum::{Router, routing::post};
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_keypair::{Keypair, Signature};
+use crate::models::{TransferJob, Wallet, WalletCustody};
+use crate::solana::tokens::setup_token_account_with_keys;
+use crate::solana::transaction::build_transaction;
+use crate::solana::utils::confidential_keys_for_mint;
+use crate::{AppState, db, fees, idempotency, jobs, solana};
+use axum::{
+    Router,
+    middleware::from_fn_with_state,
+    routing::{get, post},
+};
+use serde::Serialize;
+use solana_keypair::Signature;
 use solana_pubkey::Pubkey;
+use solana_signer::Signer;
 use spl_token_2022::extension::ExtensionType;
 use spl_token_2022::{extension::StateWithExtensionsOwned, state::Mint};
 use std::sync::Arc;
-use tokio::task;
+use tracing::error;
 
+pub mod batch;
+pub mod claim;
 pub mod create;
+pub mod link;
+pub mod list;
 pub mod telegram;
 
+/// Longest memo accepted on anything that ends up in the transfer ledger.
+/// Kept well below what fits next to the transfer instruction, so the
+/// encrypted and base64 encoded form still fits in the transaction.
+pub const MAX_MEMO_LENGTH: usize = 256;
+
 pub const TRANSFER_TRANSACTION_LABELS: [&str; 5] = [
     "Create Proof Accounts",
     "Verify Proof Accounts: Range",
@@ -25,16 +42,72 @@ pub const TRANSFER_TRANSACTION_LABELS: [&str; 5] = [
 /// nested within /transfers prefix
 pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
-        .route("/", post(create::handler))
-        .route("/telegram", post(telegram::handler))
+        .route("/", get(list::handler))
+        .route(
+            "/",
+            post(create::handler).layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
+        .route(
+            "/telegram",
+            post(telegram::handler)
+                .layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
+        .route(
+            "/batch",
+            post(batch::handler).layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
+        .route("/batch/{id}", get(batch::status_handler))
+        // not behind the idempotency layer, which would store the claim secret
+        // with the response
+        .route("/link", post(link::handler))
+        .route("/link/{id}", get(link::status_handler))
+        .route("/claim", post(claim::handler))
         .with_state(state)
 }
 
+/// Memo sent with a transfer.
+#[derive(Debug, Clone, Copy)]
+pub struct TransferMemo<'a> {
+    pub text: &'a str,
+    /// Encrypt the on-chain copy with a key only the sender and recipient can
+    /// derive, the ledger always keeps the plaintext.
+    pub encrypt: bool,
+}
+
+impl<'a> TransferMemo<'a> {
+    pub fn plain(text: &'a str) -> Self {
+        Self {
+            text,
+            encrypt: false,
+        }
+    }
+
+    pub fn encrypted(text: &'a str) -> Self {
+        Self {
+            text,
+            encrypt: true,
+        }
+    }
+}
+
+/// Trim a user supplied memo, treating a blank one as no memo, and enforce
+/// [`MAX_MEMO_LENGTH`].
+pub fn normalize_memo(memo: Option<&str>) -> Result<Option<&str>, AppError> {
+    let memo = memo.map(str::trim).filter(|m| !m.is_empty());
+    if memo.is_some_and(|memo| memo.len() > MAX_MEMO_LENGTH) {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Memo must be at most {} bytes",
+            MAX_MEMO_LENGTH
+        )));
+    }
+    Ok(memo)
+}
+
 pub async fn validate_sender_wallet(
     state: &AppState,
     source: &Pubkey,
     telegram_user_id: i64,
-) -> Result<crate::models::Wallet, AppError> {
+) -> Result<Wallet, AppError> {
     let Some(wallet) = db::get_user_wallet_by_pubkey(&state.db, source, telegram_user_id).await?
     else {
         return Err(AppError::not_found(anyhow::anyhow!(
@@ -48,6 +121,13 @@ pub async fn validate_sender_wallet(
         )));
     }
 
+    if wallet.custody == WalletCustody::External {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Wallet {} is non-custodial, use /api/tx/transfer",
+            source
+        )));
+    }
+
     Ok(wallet)
 }
 
@@ -63,6 +143,143 @@ pub async fn validate_confidential_mint(state: &AppState, mint: &Pubkey) -> Resu
     Ok(())
 }
 
+/// Make sure `recipient` has a token account for `mint` that is configured
+/// for confidential transfers.
+pub async fn validate_recipient_account(
+    state: &AppState,
+    recipient: &Pubkey,
+    mint: &Pubkey,
+) -> Result<(), AppError> {
+    let (_, maybe_recipient_ata_account) =
+        solana::tokens::get_maybe_ata(state.rpc_client.clone(), recipient, mint).await?;
+    if maybe_recipient_ata_account.is_none() {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Recipient confidential token account not found",
+        )));
+    }
+    let requires_setup = solana::tokens::ata_has_confidential_transfer_extension(
+        maybe_recipient_ata_account,
+        recipient,
+        mint,
+    )?;
+    if requires_setup {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Recipient confidential token account is not configured"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Create and configure the recipient's confidential token account for `mint`
+/// if it doesn't have one yet. The fee payer pays for the setup, and its fee
+/// and the rent of a new account are charged to the budget of
+/// `charged_wallet_id`, usually the sender, which must have some left.
+pub async fn ensure_recipient_confidential_account(
+    state: &AppState,
+    recipient_wallet: &Wallet,
+    mint: &Pubkey,
+    charged_wallet_id: i64,
+) -> Result<(), AppError> {
+    let recipient_pubkey = &recipient_wallet.pubkey;
+    let (recipient_ata, maybe_recipient_ata_account) =
+        solana::tokens::get_maybe_ata(state.rpc_client.clone(), recipient_pubkey, mint).await?;
+    let creates_account = maybe_recipient_ata_account.is_none();
+    let requires_recipient_setup = creates_account
+        || solana::tokens::ata_has_confidential_transfer_extension(
+            maybe_recipient_ata_account,
+            recipient_pubkey,
+            mint,
+        )?;
+
+    if !requires_recipient_setup {
+        return Ok(());
+    }
+
+    // we can only configure the account for wallets we hold the keys of
+    if recipient_wallet.custody == WalletCustody::External {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Recipient has not set up a confidential token account for this mint"
+        )));
+    }
+
+    fees::ensure_budget(state, charged_wallet_id).await?;
+
+    let recipient_signer = state.key_store.signer(recipient_wallet.id).await?;
+    let confidential_keys =
+        confidential_keys_for_mint(recipient_signer.clone(), mint).map_err(|e| {
+            AppError::internal_server_error(anyhow::anyhow!(
+                "Failed to derive confidential keys: {}",
+                e
+            ))
+        })?;
+
+    let setup_instructions = setup_token_account_with_keys(
+        state.rpc_client.clone(),
+        &state.fee_payer.pubkey(),
+        recipient_pubkey,
+        mint,
+        &confidential_keys,
+    )
+    .await
+    .map_err(|e| {
+        AppError::internal_server_error(anyhow::anyhow!(
+            "Failed to setup recipient token account: {}",
+            e
+        ))
+    })?;
+
+    if setup_instructions.instructions.is_empty() {
+        return Ok(());
+    }
+
+    let mut additional_signers: Vec<Arc<dyn Signer + Send + Sync>> = vec![recipient_signer.clone()];
+    additional_signers.extend(setup_instructions.additional_signers);
+
+    let transaction = build_transaction(
+        state.rpc_client.clone(),
+        None,
+        setup_instructions.instructions,
+        state.fee_payer.clone(),
+        additional_signers,
+    )
+    .await
+    .map_err(|e| {
+        AppError::internal_server_error(anyhow::anyhow!("Failed to build setup transaction: {}", e))
+    })?;
+
+    state
+        .rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .await
+        .map_err(|e| {
+            AppError::internal_server_error(anyhow::anyhow!(
+                "Failed to setup recipient token account: {}",
+                e
+            ))
+        })?;
+
+    let rent = if creates_account {
+        match state.rpc_client.get_balance(&recipient_ata).await {
+            Ok(lamports) => lamports,
+            Err(e) => {
+                error!(%recipient_ata, "Failed to get recipient account rent: {:?}", e);
+                0
+            }
+        }
+    } else {
+        0
+    };
+    fees::charge(
+        state,
+        charged_wallet_id,
+        fees::transaction_fee(&transaction) + rent,
+    )
+    .await;
+
+    Ok(())
+}
+
 pub async fn get_mint_decimals(state: &AppState, mint: &Pubkey) -> Result<u8, AppError> {
     let mint_account = state.rpc_client.get_account(mint).await.map_err(|e| {
         AppError::internal_server_error(anyhow::anyhow!("Mint account not found: {:?}", e))
@@ -73,36 +290,200 @@ pub async fn get_mint_decimals(state: &AppState, mint: &Pubkey) -> Result<u8, Ap
     Ok(mint_state.base.decimals)
 }
 
+/// Run a confidential transfer as a persisted job and record it in the
+/// transfer ledger. Returns the ledger id along with the finished job.
+///
+/// The job is recorded before anything is sent so that, if the process dies
+/// mid-transfer, it is resumed (or rolled back) by the recovery loop.
 pub async fn execute_transfer(
-    rpc_client: Arc<RpcClient>,
-    sender_kp: Arc<Keypair>,
+    state: &AppState,
+    sender_wallet: &Wallet,
     recipient_pubkey: &Pubkey,
     amount: u64,
     mint: Pubkey,
     mint_decimals: u8,
-) -> Result<Vec<Signature>, AppError> {
-    let recipient_pubkey = *recipient_pubkey;
-
-    task::spawn_blocking(move || {
-        let handle = tokio::runtime::Handle::current();
-        handle.block_on(transfer::invoke_confidential_transfer(
-            rpc_client,
-            sender_kp,
-            &recipient_pubkey,
-            amount,
-            &mint,
-            mint_decimals,
-        ))
-    })
-    .await
-    .map_err(|e| {
-        AppError::internal_server_error(anyhow::anyhow!("Failed to join transfer task: {:?}", e))
-    })?
-    .map_err(|e| {
-        AppError::internal_server_error(anyhow::anyhow!("Failed to create transfer: {:?}", e))
+    memo: Option<TransferMemo<'_>>,
+) -> Result<(i64, TransferJob), AppError> {
+    let (transfer_id, job_id) = start_transfer(
+        state,
+        sender_wallet,
+        recipient_pubkey,
+        amount,
+        mint,
+        mint_decimals,
+        memo,
+    )
+    .await?;
+    let job = finish_transfer(state, transfer_id, job_id).await?;
+
+    Ok((transfer_id, job))
+}
+
+/// Record a transfer job and its ledger entry without sending anything,
+/// once the sender's fee budget is checked. Returns the ledger id and the job
+/// id.
+pub async fn start_transfer(
+    state: &AppState,
+    sender_wallet: &Wallet,
+    recipient_pubkey: &Pubkey,
+    amount: u64,
+    mint: Pubkey,
+    mint_decimals: u8,
+    memo: Option<TransferMemo<'_>>,
+) -> Result<(i64, i64), AppError> {
+    let transfer = prepare_transfer(
+        state,
+        sender_wallet,
+        *recipient_pubkey,
+        amount,
+        mint,
+        mint_decimals,
+        memo,
+    )
+    .await?;
+    let ids = db::create_transfer(&state.db, &transfer.ledger_entry()).await?;
+
+    Ok(ids)
+}
+
+/// A transfer whose sender has the fee budget for it, ready to be recorded.
+#[derive(Debug, Clone)]
+pub struct PreparedTransfer<'a> {
+    sender_wallet: &'a Wallet,
+    recipient: Pubkey,
+    mint: Pubkey,
+    amount: u64,
+    decimals: u8,
+    memo: Option<TransferMemo<'a>>,
+    onchain_memo: Option<String>,
+    balance_funded: bool,
+}
+
+impl PreparedTransfer<'_> {
+    /// Skip topping up the sender's balance when the job runs, because the
+    /// caller already covered this transfer.
+    pub fn with_funded_balance(mut self) -> Self {
+        self.balance_funded = true;
+        self
+    }
+
+    /// The job and ledger entry to record, by [`start_transfer`] or by a db
+    /// function that links it to what it pays for in the same transaction.
+    pub fn ledger_entry(&self) -> db::NewTransfer<'_> {
+        db::NewTransfer {
+            sender_wallet: self.sender_wallet,
+            recipient: &self.recipient,
+            mint: &self.mint,
+            amount: self.amount,
+            decimals: self.decimals,
+            onchain_memo: self.onchain_memo.as_deref(),
+            memo: self.memo.map(|memo| memo.text),
+            memo_encrypted: self.memo.is_some_and(|memo| memo.encrypt),
+            balance_funded: self.balance_funded,
+        }
+    }
+}
+
+/// Check the sender's fee budget and build the on-chain memo of a transfer.
+pub async fn prepare_transfer<'a>(
+    state: &AppState,
+    sender_wallet: &'a Wallet,
+    recipient_pubkey: Pubkey,
+    amount: u64,
+    mint: Pubkey,
+    mint_decimals: u8,
+    memo: Option<TransferMemo<'a>>,
+) -> Result<PreparedTransfer<'a>, AppError> {
+    fees::ensure_budget(state, sender_wallet.id).await?;
+
+    let onchain_memo = match memo {
+        Some(memo) if memo.encrypt => {
+            let key = state
+                .key_store
+                .memo_key(sender_wallet.id, &recipient_pubkey)
+                .await?;
+            Some(solana::memo::encrypt_memo(&key, memo.text)?)
+        }
+        memo => memo.map(|memo| memo.text.to_string()),
+    };
+
+    Ok(PreparedTransfer {
+        sender_wallet,
+        recipient: recipient_pubkey,
+        mint,
+        amount,
+        decimals: mint_decimals,
+        memo,
+        onchain_memo,
+        balance_funded: false,
     })
 }
 
+/// Drive a job recorded by [`start_transfer`] to the end, failing unless the
+/// transfer landed.
+///
+/// A job that paused on an error is left to the recovery loop and answered
+/// with a 202 carrying its ids, and a job that failed is a recorded error, so
+/// a retry with the same `Idempotency-Key` can't send the transfer again.
+pub async fn finish_transfer(
+    state: &AppState,
+    transfer_id: i64,
+    job_id: i64,
+) -> Result<TransferJob, AppError> {
+    finished_transfer(
+        transfer_id,
+        job_id,
+        jobs::transfer::run(state, job_id).await,
+    )
+}
+
+/// Response for a transfer whose job is still running.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTransferResponse {
+    pub transfer_id: i64,
+    pub job_id: i64,
+    pub status: &'static str,
+}
+
+/// The 202 answered for a recorded transfer whose job is still running.
+pub fn pending_transfer(transfer_id: i64, job_id: i64) -> AppError {
+    AppError::accepted(
+        anyhow::anyhow!("Transfer job {} is still running", job_id),
+        PendingTransferResponse {
+            transfer_id,
+            job_id,
+            status: "pending",
+        },
+    )
+}
+
+pub(crate) fn finished_transfer(
+    transfer_id: i64,
+    job_id: i64,
+    result: anyhow::Result<TransferJob>,
+) -> Result<TransferJob, AppError> {
+    let job = match result {
+        Ok(job) => job,
+        Err(e) => {
+            error!(job_id, "transfer job did not finish: {:?}", e);
+            return Err(pending_transfer(transfer_id, job_id));
+        }
+    };
+
+    // a job whose close was rejected still moved the funds
+    if job.transfer_signature.is_some() {
+        return Ok(job);
+    }
+    Err(AppError::internal_server_error(anyhow::anyhow!(
+        "Transfer job {} ended with status {}: {}",
+        job.id,
+        job.status.as_str(),
+        job.error.as_deref().unwrap_or("unknown error")
+    ))
+    .recorded())
+}
+
 pub fn format_transfer_results(transfer_signatures: &[Signature]) -> Vec<TransactionResult> {
     TRANSFER_TRANSACTION_LABELS
         .iter()
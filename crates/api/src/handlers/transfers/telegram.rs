@@ -1,12 +1,9 @@
 use crate::AppState;
 use crate::auth::AuthUser;
+use crate::fees;
 use crate::handlers::wallets::deposit::TransactionResult;
 use crate::handlers::{ApiResponse, AppError};
-use crate::solana;
-use crate::solana::airdrop::request_airdrop_and_confirm;
-use crate::solana::tokens::setup_token_account_with_keys;
-use crate::solana::transaction::build_transaction;
-use crate::solana::utils::confidential_keys_for_mint;
+use crate::models::{TransferJob, Wallet};
 use anyhow::Result;
 use axum::{Json, extract::State};
 use serde::{Deserialize, Serialize};
@@ -15,7 +12,7 @@ use solana_keypair::Keypair;
 use solana_pubkey::Pubkey;
 use solana_signer::Signer;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{error, info};
 
 #[serde_as]
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
@@ -28,6 +25,11 @@ pub struct TelegramTransferRequest {
     pub mint: Pubkey,
     #[serde_as(as = "DisplayFromStr")]
     pub amount: u64,
+    /// Written on chain with the transfer, at most 256 bytes.
+    pub memo: Option<String>,
+    /// Encrypt the on-chain memo so only the sender and recipient can read it.
+    #[serde(default)]
+    pub encrypt_memo: bool,
 }
 
 #[serde_as]
@@ -44,13 +46,17 @@ pub struct Recipient {
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TelegramTransferResponse {
+    pub transfer_id: i64,
+    pub job_id: i64,
     pub transactions: Vec<TransactionResult>,
+    pub memo: Option<String>,
+    pub memo_encrypted: bool,
     pub recipient: Recipient,
 }
 
-struct RecipientInfo {
-    wallet: crate::models::Wallet,
-    was_new_wallet: bool,
+pub struct RecipientInfo {
+    pub wallet: Wallet,
+    pub was_new_wallet: bool,
 }
 
 pub async fn handler(
@@ -61,51 +67,140 @@ pub async fn handler(
     let sender_wallet =
         super::validate_sender_wallet(&state, &payload.source, auth_user.telegram_user_id).await?;
     super::validate_confidential_mint(&state, &payload.mint).await?;
-
-    let recipient_info = get_or_create_recipient_wallet(&state, &payload.telegram_username)
-        .await
-        .map_err(AppError::from)?;
-
-    let recipient_pubkey = recipient_info.wallet.pubkey;
-    let recipient_keypair = recipient_info.wallet.keypair.clone();
-    info!(
-        "transfer: telegram username: {}, recipient pubkey: {}",
-        payload.telegram_username, recipient_pubkey
-    );
-
-    ensure_recipient_confidential_account(
-        &state,
-        &recipient_pubkey,
-        &payload.mint,
-        &recipient_keypair,
-    )
-    .await?;
+    let memo = super::normalize_memo(payload.memo.as_deref())?;
 
     let mint_decimals = super::get_mint_decimals(&state, &payload.mint).await?;
 
-    let transfer_signatures = super::execute_transfer(
-        state.rpc_client.clone(),
-        sender_wallet.keypair.clone(),
-        &recipient_pubkey,
+    let (transfer_id, job, recipient_info) = send_to_username(
+        &state,
+        &sender_wallet,
+        &payload.telegram_username,
         payload.amount,
         payload.mint,
         mint_decimals,
+        memo.map(|text| super::TransferMemo {
+            text,
+            encrypt: payload.encrypt_memo,
+        }),
     )
     .await?;
 
-    let transactions = super::format_transfer_results(&transfer_signatures);
+    let transactions = super::format_transfer_results(&job.signatures());
 
     Ok(ApiResponse::new(TelegramTransferResponse {
+        transfer_id,
+        job_id: job.id,
         transactions,
+        memo: memo.map(str::to_string),
+        memo_encrypted: memo.is_some() && payload.encrypt_memo,
         recipient: Recipient {
-            pubkey: recipient_pubkey,
+            pubkey: recipient_info.wallet.pubkey,
             username: payload.telegram_username,
             new_wallet: recipient_info.was_new_wallet,
         },
     }))
 }
 
-async fn get_or_create_recipient_wallet(
+/// Confidentially transfer `amount` of `mint` to a Telegram username, into a
+/// reserved wallet if they haven't signed up yet, and notify them. Returns the
+/// ledger id, the finished job and the recipient.
+pub async fn send_to_username(
+    state: &AppState,
+    sender_wallet: &Wallet,
+    telegram_username: &str,
+    amount: u64,
+    mint: Pubkey,
+    mint_decimals: u8,
+    memo: Option<super::TransferMemo<'_>>,
+) -> Result<(i64, TransferJob, RecipientInfo), AppError> {
+    let (transfer_id, job_id, recipient_info) = start_send_to_username(
+        state,
+        sender_wallet,
+        telegram_username,
+        amount,
+        mint,
+        mint_decimals,
+        memo,
+    )
+    .await?;
+    let job = super::finish_transfer(state, transfer_id, job_id).await?;
+    crate::notifications::notify_incoming_payment(
+        state,
+        sender_wallet,
+        &recipient_info.wallet,
+        transfer_id,
+        amount,
+        &mint,
+        mint_decimals,
+    )
+    .await;
+
+    Ok((transfer_id, job, recipient_info))
+}
+
+/// The first half of [`send_to_username`]: set up the recipient's wallet and
+/// record the transfer without sending anything, for callers that need the
+/// ledger id before the transfer runs. Returns the ledger id, the job id and
+/// the recipient.
+pub async fn start_send_to_username(
+    state: &AppState,
+    sender_wallet: &Wallet,
+    telegram_username: &str,
+    amount: u64,
+    mint: Pubkey,
+    mint_decimals: u8,
+    memo: Option<super::TransferMemo<'_>>,
+) -> Result<(i64, i64, RecipientInfo), AppError> {
+    let recipient_info = get_or_create_recipient_wallet(state, telegram_username)
+        .await
+        .map_err(AppError::from)?;
+
+    let recipient_pubkey = recipient_info.wallet.pubkey;
+    info!(
+        "transfer: telegram username: {}, recipient pubkey: {}",
+        telegram_username, recipient_pubkey
+    );
+
+    super::ensure_recipient_confidential_account(
+        state,
+        &recipient_info.wallet,
+        &mint,
+        sender_wallet.id,
+    )
+    .await?;
+
+    let (transfer_id, job_id) = super::start_transfer(
+        state,
+        sender_wallet,
+        &recipient_pubkey,
+        amount,
+        mint,
+        mint_decimals,
+        memo,
+    )
+    .await?;
+    // remember who sent what to a reserved wallet, so it can be refunded if the
+    // recipient never claims it
+    if let Err(e) = crate::db::record_reserved_wallet_credit(
+        &state.db,
+        recipient_info.wallet.id,
+        transfer_id,
+        state.config.reserved_wallet_ttl.num_seconds(),
+    )
+    .await
+    {
+        // the job is recorded and goes through with the recovery loop anyway
+        error!(
+            transfer_id,
+            "failed to record reserved wallet credit: {:?}", e
+        );
+        return Err(super::pending_transfer(transfer_id, job_id));
+    }
+
+    Ok((transfer_id, job_id, recipient_info))
+}
+
+pub(super) async fn get_or_create_recipient_wallet(
     state: &AppState,
     telegram_username: &str,
 ) -> Result<RecipientInfo> {
@@ -124,12 +219,17 @@ async fn get_or_create_recipient_wallet(
         telegram_username
     );
     let keypair = Keypair::new();
-    let wallet =
-        crate::db::get_or_create_wallet_for_username(&state.db, telegram_username, &keypair)
-            .await
-            .map_err(|e| anyhow::anyhow!("failed to create wallet for recipient: {}", e))?;
+    let secret = state.key_store.seal(&keypair)?;
+    let wallet = crate::db::get_or_create_wallet_for_username(
+        &state.db,
+        telegram_username,
+        &keypair.pubkey(),
+        &secret,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("failed to create wallet for recipient: {}", e))?;
 
-    request_airdrop_and_confirm(state.rpc_client.clone(), &wallet.pubkey, 1 * 10_u64.pow(9))
+    fees::fund_new_wallet(state, &wallet.pubkey)
         .await
         .map_err(|e| anyhow::anyhow!("failed to fund recipient wallet: {}", e))?;
 
@@ -138,85 +238,3 @@ async fn get_or_create_recipient_wallet(
         was_new_wallet: true,
     })
 }
-
-async fn ensure_recipient_confidential_account(
-    state: &AppState,
-    recipient_pubkey: &Pubkey,
-    mint: &Pubkey,
-    recipient_keypair: &Arc<Keypair>,
-) -> Result<(), AppError> {
-    let requires_recipient_setup = {
-        let (_, maybe_recipient_ata_account) =
-            solana::tokens::get_maybe_ata(state.rpc_client.clone(), recipient_pubkey, mint).await?;
-
-        if maybe_recipient_ata_account.is_none() {
-            true
-        } else {
-            solana::tokens::ata_has_confidential_transfer_extension(
-                maybe_recipient_ata_account,
-                recipient_pubkey,
-                mint,
-            )?
-        }
-    };
-
-    if !requires_recipient_setup {
-        return Ok(());
-    }
-
-    let confidential_keys =
-        confidential_keys_for_mint(recipient_keypair.clone(), mint).map_err(|e| {
-            AppError::internal_server_error(anyhow::anyhow!(
-                "Failed to derive confidential keys: {}",
-                e
-            ))
-        })?;
-
-    let setup_instructions = setup_token_account_with_keys(
-        state.rpc_client.clone(),
-        &state.global_authority.pubkey(),
-        recipient_pubkey,
-        mint,
-        &confidential_keys,
-    )
-    .await
-    .map_err(|e| {
-        AppError::internal_server_error(anyhow::anyhow!(
-            "Failed to setup recipient token account: {}",
-            e
-        ))
-    })?;
-
-    if setup_instructions.instructions.is_empty() {
-        return Ok(());
-    }
-
-    let mut additional_signers: Vec<Arc<dyn Signer + Send + Sync>> =
-        vec![recipient_keypair.clone()];
-    additional_signers.extend(setup_instructions.additional_signers);
-
-    let transaction = build_transaction(
-        state.rpc_client.clone(),
-        None,
-        setup_instructions.instructions,
-        state.global_authority.clone(),
-        additional_signers,
-    )
-    .await
-    .map_err(|e| {
-        AppError::internal_server_error(anyhow::anyhow!("Failed to build setup transaction: {}", e))
-    })?;
-
-    state
-        .rpc_client
-        .send_and_confirm_transaction(&transaction)
-        .await
-        .map_err(|e| {
-            AppError::internal_server_error(anyhow::anyhow!(
-                "Failed to setup recipient token account: {}",
-                e
-            ))
-        })?;
-
-    Ok(())
-}
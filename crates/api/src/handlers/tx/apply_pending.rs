@@ -0,0 +1,56 @@
+use super::{UnsignedTransactionsResponse, unsigned_transactions};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::handlers::noncustodial::{keys_from_signature, validate_external_wallet};
+use crate::solana::balance::apply_pending_balance_with_keys;
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyPendingBalanceRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub owner: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    /// Owner's signature over the token account address.
+    #[serde_as(as = "DisplayFromStr")]
+    pub key_signature: Signature,
+}
+
+// handler is at POST /api/tx/apply-pending, builds the transaction moving the
+// pending balance into the available balance. It reads the current pending
+// balance, so it has to be requested after the deposit/transfer landed.
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<ApplyPendingBalanceRequest>,
+) -> Result<ApiResponse<UnsignedTransactionsResponse>, AppError> {
+    validate_external_wallet(&state, &payload.owner, auth_user.telegram_user_id).await?;
+    let confidential_keys =
+        keys_from_signature(&payload.owner, &payload.mint, &payload.key_signature)?;
+
+    let apply_instructions = apply_pending_balance_with_keys(
+        state.rpc_client.clone(),
+        &payload.owner,
+        &payload.mint,
+        &confidential_keys,
+    )
+    .await?;
+
+    let response = unsigned_transactions(
+        &state,
+        &payload.owner,
+        vec![("Apply Pending Balance", apply_instructions)],
+    )
+    .await?;
+
+    Ok(ApiResponse::new(response))
+}
@@ -0,0 +1,59 @@
+use super::{UnsignedTransactionsResponse, unsigned_transactions};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::handlers::noncustodial::validate_external_wallet;
+use crate::solana::deposit::deposit_tokens;
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DepositRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub owner: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+// handler is at POST /api/tx/deposit, builds the deposit into the pending
+// balance. Follow up with /apply-pending once it landed.
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<DepositRequest>,
+) -> Result<ApiResponse<UnsignedTransactionsResponse>, AppError> {
+    validate_external_wallet(&state, &payload.owner, auth_user.telegram_user_id).await?;
+
+    if payload.amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Deposit amount must be greater than 0"
+        )));
+    }
+
+    let deposit_instructions = deposit_tokens(
+        state.rpc_client.clone(),
+        &payload.owner,
+        &payload.mint,
+        payload.decimals,
+        payload.amount,
+    )
+    .await?;
+
+    let response = unsigned_transactions(
+        &state,
+        &payload.owner,
+        vec![("Deposit", deposit_instructions)],
+    )
+    .await?;
+
+    Ok(ApiResponse::new(response))
+}
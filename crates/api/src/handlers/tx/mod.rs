@@ -0,0 +1,99 @@
+//! Unsigned transaction builders for wallets that sign on the client.
+//!
+//! Each builder returns the transactions of an operation in the order they
+//! must land. Server-side signers (proof context state accounts) have already
+//! signed; `missingSigners` names the slots the client still has to fill
+//! before handing the transactions to `POST /api/tx/submit`.
+
+use crate::AppState;
+use crate::handlers::AppError;
+use crate::solana::GeneratedInstructions;
+use crate::solana::transaction::{build_unsigned_transaction, encode_transaction, missing_signers};
+use axum::{Router, routing::post};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use std::sync::Arc;
+
+pub mod apply_pending;
+pub mod deposit;
+pub mod setup;
+pub mod submit;
+pub mod transfer;
+pub mod withdraw;
+
+/// nested within /tx prefix
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/setup", post(setup::handler))
+        .route("/deposit", post(deposit::handler))
+        .route("/apply-pending", post(apply_pending::handler))
+        .route("/withdraw", post(withdraw::handler))
+        .route("/transfer", post(transfer::handler))
+        .route("/submit", post(submit::handler))
+        .with_state(state)
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignerSlot {
+    /// Position in the transaction's signature list.
+    pub index: usize,
+    #[serde_as(as = "DisplayFromStr")]
+    pub pubkey: Pubkey,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedTransaction {
+    pub label: String,
+    /// Base64 encoded `VersionedTransaction`.
+    pub transaction: String,
+    pub missing_signers: Vec<SignerSlot>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedTransactionsResponse {
+    /// Transactions in the order they must be submitted.
+    pub transactions: Vec<UnsignedTransaction>,
+    /// Block height after which the transactions can no longer land.
+    pub last_valid_block_height: u64,
+}
+
+/// Build and encode the transactions of `steps`, all paid for by `fee_payer`
+/// and sharing one recent blockhash.
+pub async fn unsigned_transactions(
+    state: &AppState,
+    fee_payer: &Pubkey,
+    steps: Vec<(&str, GeneratedInstructions)>,
+) -> Result<UnsignedTransactionsResponse, AppError> {
+    let (recent_blockhash, last_valid_block_height) = state
+        .rpc_client
+        .get_latest_blockhash_with_commitment(state.rpc_client.commitment())
+        .await
+        .map_err(|e| {
+            AppError::internal_server_error(anyhow::anyhow!("Failed to get blockhash: {}", e))
+        })?;
+
+    let transactions = steps
+        .into_iter()
+        .map(|(label, generated)| {
+            let transaction = build_unsigned_transaction(fee_payer, recent_blockhash, generated)?;
+            Ok(UnsignedTransaction {
+                label: label.to_string(),
+                transaction: encode_transaction(&transaction)?,
+                missing_signers: missing_signers(&transaction)
+                    .into_iter()
+                    .map(|(index, pubkey)| SignerSlot { index, pubkey })
+                    .collect(),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(UnsignedTransactionsResponse {
+        transactions,
+        last_valid_block_height,
+    })
+}
@@ -0,0 +1,61 @@
+use super::{UnsignedTransactionsResponse, unsigned_transactions};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::handlers::noncustodial::{keys_from_signature, validate_external_wallet};
+use crate::handlers::transfers::validate_confidential_mint;
+use crate::solana::tokens::setup_token_account_with_keys;
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetupRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub owner: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    /// Owner's signature over the token account address.
+    #[serde_as(as = "DisplayFromStr")]
+    pub key_signature: Signature,
+}
+
+// handler is at POST /api/tx/setup, builds the transaction creating and
+// configuring the confidential token account, if one is needed
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<SetupRequest>,
+) -> Result<ApiResponse<UnsignedTransactionsResponse>, AppError> {
+    validate_external_wallet(&state, &payload.owner, auth_user.telegram_user_id).await?;
+    validate_confidential_mint(&state, &payload.mint).await?;
+    let confidential_keys =
+        keys_from_signature(&payload.owner, &payload.mint, &payload.key_signature)?;
+
+    let setup_instructions = setup_token_account_with_keys(
+        state.rpc_client.clone(),
+        &payload.owner,
+        &payload.owner,
+        &payload.mint,
+        &confidential_keys,
+    )
+    .await?;
+    if setup_instructions.instructions.is_empty() {
+        return Ok(ApiResponse::new(UnsignedTransactionsResponse::default()));
+    }
+
+    let response = unsigned_transactions(
+        &state,
+        &payload.owner,
+        vec![("Setup Token Account", setup_instructions)],
+    )
+    .await?;
+
+    Ok(ApiResponse::new(response))
+}
@@ -0,0 +1,110 @@
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::solana::transaction::{decode_transaction, missing_signers};
+use anyhow::Context;
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_signature::Signature;
+use std::sync::Arc;
+use tracing::info;
+
+/// Longest sequence the builders return is a confidential transfer (5).
+const MAX_SUBMITTED_TRANSACTIONS: usize = 8;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitTransactionsRequest {
+    /// Base64 encoded, fully signed transactions, sent in order.
+    pub transactions: Vec<String>,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitTransactionsResponse {
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub signatures: Vec<Signature>,
+}
+
+// handler is at POST /api/tx/submit, relays client-signed transactions one at
+// a time, waiting for each to confirm before the next
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<SubmitTransactionsRequest>,
+) -> Result<ApiResponse<SubmitTransactionsResponse>, AppError> {
+    if payload.transactions.is_empty() || payload.transactions.len() > MAX_SUBMITTED_TRANSACTIONS {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Expected between 1 and {} transactions",
+            MAX_SUBMITTED_TRANSACTIONS
+        )));
+    }
+
+    let user_wallets =
+        db::get_wallets_for_telegram_user(&state.db, auth_user.telegram_user_id).await?;
+
+    let transactions = payload
+        .transactions
+        .iter()
+        .map(|encoded| decode_transaction(encoded).map_err(AppError::bad_request))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // only relay transactions the user's own wallets pay for and that are
+    // completely signed, so the endpoint can't be used as a generic relayer
+    for (index, transaction) in transactions.iter().enumerate() {
+        let fee_payer = transaction.message.static_account_keys().first();
+        if !fee_payer.is_some_and(|fee_payer| user_wallets.contains(fee_payer)) {
+            return Err(AppError::bad_request(anyhow::anyhow!(
+                "Transaction {} is not paid for by one of your wallets",
+                index
+            )));
+        }
+
+        let missing = missing_signers(transaction);
+        if !missing.is_empty() {
+            return Err(AppError::bad_request(anyhow::anyhow!(
+                "Transaction {} is missing signatures from {}",
+                index,
+                missing
+                    .iter()
+                    .map(|(_, signer)| signer.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        if !transaction.verify_with_results().into_iter().all(|ok| ok) {
+            return Err(AppError::bad_request(anyhow::anyhow!(
+                "Transaction {} has an invalid signature",
+                index
+            )));
+        }
+    }
+
+    let mut signatures = Vec::with_capacity(transactions.len());
+    for transaction in transactions {
+        let signature = state
+            .rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .await
+            .with_context(|| {
+                anyhow::anyhow!(
+                    "Error sending transaction {} of {}",
+                    signatures.len() + 1,
+                    payload.transactions.len()
+                )
+            })
+            .map_err(AppError::from)?;
+        info!(
+            "submitted client-signed transaction signature={}",
+            signature
+        );
+        signatures.push(signature);
+    }
+
+    Ok(ApiResponse::new(SubmitTransactionsResponse { signatures }))
+}
@@ -0,0 +1,86 @@
+use super::{UnsignedTransactionsResponse, unsigned_transactions};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::handlers::noncustodial::{keys_from_signature, validate_external_wallet};
+use crate::handlers::transfers::{TRANSFER_TRANSACTION_LABELS, validate_confidential_mint};
+use crate::solana::tokens::{ata_has_confidential_transfer_extension, get_maybe_ata};
+use crate::solana::transfer::build_transfer_steps;
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use std::sync::Arc;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub owner: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub recipient: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    /// Sender's signature over their token account address.
+    #[serde_as(as = "DisplayFromStr")]
+    pub key_signature: Signature,
+}
+
+// handler is at POST /api/tx/transfer, builds the proof, transfer and cleanup
+// transactions of a confidential transfer
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<TransferRequest>,
+) -> Result<ApiResponse<UnsignedTransactionsResponse>, AppError> {
+    validate_external_wallet(&state, &payload.owner, auth_user.telegram_user_id).await?;
+    validate_confidential_mint(&state, &payload.mint).await?;
+    let confidential_keys =
+        keys_from_signature(&payload.owner, &payload.mint, &payload.key_signature)?;
+
+    if payload.amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Transfer amount must be greater than 0"
+        )));
+    }
+
+    // the recipient's keys are not ours to use, so their account must already
+    // be configured for confidential transfers
+    let (_, maybe_recipient_ata) =
+        get_maybe_ata(state.rpc_client.clone(), &payload.recipient, &payload.mint).await?;
+    if ata_has_confidential_transfer_extension(
+        maybe_recipient_ata,
+        &payload.recipient,
+        &payload.mint,
+    )? {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Recipient {} has no confidential token account for mint {}",
+            payload.recipient,
+            payload.mint
+        )));
+    }
+
+    let steps = build_transfer_steps(
+        state.rpc_client.clone(),
+        &payload.owner,
+        &payload.recipient,
+        payload.amount,
+        &payload.mint,
+        &confidential_keys,
+    )
+    .await?;
+
+    let response = unsigned_transactions(
+        &state,
+        &payload.owner,
+        TRANSFER_TRANSACTION_LABELS.into_iter().zip(steps).collect(),
+    )
+    .await?;
+
+    Ok(ApiResponse::new(response))
+}
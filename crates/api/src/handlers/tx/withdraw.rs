@@ -0,0 +1,77 @@
+use super::{UnsignedTransactionsResponse, unsigned_transactions};
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::handlers::noncustodial::{keys_from_signature, validate_external_wallet};
+use crate::solana::withdraw::build_withdraw_steps;
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use std::sync::Arc;
+
+const WITHDRAW_TRANSACTION_LABELS: [&str; 4] = [
+    "Create Proof Accounts",
+    "Verify Proof Accounts: Range",
+    "Verify Proof Accounts: Equality",
+    "Withdraw",
+];
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawRequest {
+    #[serde_as(as = "DisplayFromStr")]
+    pub owner: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub amount: u64,
+    pub decimals: u8,
+    /// Owner's signature over the token account address.
+    #[serde_as(as = "DisplayFromStr")]
+    pub key_signature: Signature,
+}
+
+// handler is at POST /api/tx/withdraw, builds the proof and withdraw
+// transactions from the available balance
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<WithdrawRequest>,
+) -> Result<ApiResponse<UnsignedTransactionsResponse>, AppError> {
+    validate_external_wallet(&state, &payload.owner, auth_user.telegram_user_id).await?;
+    let confidential_keys =
+        keys_from_signature(&payload.owner, &payload.mint, &payload.key_signature)?;
+
+    if payload.amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Withdraw amount must be greater than 0"
+        )));
+    }
+
+    let prepared = build_withdraw_steps(
+        state.rpc_client.clone(),
+        &payload.owner,
+        &payload.owner,
+        payload.amount,
+        &payload.mint,
+        payload.decimals,
+        &confidential_keys,
+    )
+    .await?;
+
+    let response = unsigned_transactions(
+        &state,
+        &payload.owner,
+        WITHDRAW_TRANSACTION_LABELS
+            .into_iter()
+            .zip(prepared.steps)
+            .collect(),
+    )
+    .await?;
+
+    Ok(ApiResponse::new(response))
+}
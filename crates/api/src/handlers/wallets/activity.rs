@@ -0,0 +1,262 @@
+use crate::AppState;
+use crate::auth::AuthUser;
+use crate::db;
+use crate::handlers::ApiResponse;
+use crate::handlers::AppError;
+use crate::handlers::noncustodial::keys_from_signature;
+use crate::models::{Wallet, WalletCustody};
+use crate::solana::confidential_keys::ConfidentialKeys;
+use crate::solana::history::{ActivityEntry, ActivityKind, MAX_PAGE_SIZE, rebuild_history};
+use crate::solana::memo::{ENCRYPTED_MEMO_PREFIX, decrypt_memo};
+use crate::solana::utils::confidential_keys_for_mint;
+use anyhow::Result;
+use axum::extract::Path;
+use axum::extract::{Query, State};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use spl_token_2022_interface::{extension::StateWithExtensionsOwned, state::Account};
+use std::sync::Arc;
+use tracing::warn;
+
+const DEFAULT_PAGE_SIZE: usize = 10;
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityPath {
+    #[serde_as(as = "DisplayFromStr")]
+    pub address: Pubkey,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityQuery {
+    #[serde_as(as = "DisplayFromStr")]
+    pub mint: Pubkey,
+    /// `nextCursor` from the previous page.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub cursor: Option<Signature>,
+    /// Number of transactions to scan; a transaction can yield several entries.
+    pub limit: Option<usize>,
+    /// Owner's signature over the token account address, required for
+    /// non-custodial wallets.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub key_signature: Option<Signature>,
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityItem {
+    #[serde_as(as = "DisplayFromStr")]
+    pub signature: Signature,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub kind: String,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub amount: Option<u64>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub available_balance: Option<u64>,
+    /// Token account on the other side of a transfer.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub counterparty: Option<Pubkey>,
+    /// Memo of a transfer, `None` for an encrypted one that couldn't be
+    /// decrypted.
+    pub memo: Option<String>,
+    pub memo_encrypted: bool,
+}
+
+impl From<ActivityEntry> for ActivityItem {
+    fn from(entry: ActivityEntry) -> Self {
+        let memo_encrypted = entry
+            .memo
+            .as_ref()
+            .is_some_and(|memo| memo.text.starts_with(ENCRYPTED_MEMO_PREFIX));
+        Self {
+            signature: entry.signature,
+            slot: entry.slot,
+            block_time: entry.block_time,
+            kind: entry.kind.as_str().to_string(),
+            amount: entry.amount,
+            available_balance: entry.available_balance,
+            counterparty: entry.counterparty,
+            memo: entry.memo.filter(|_| !memo_encrypted).map(|memo| memo.text),
+            memo_encrypted,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityResponse {
+    pub entries: Vec<ActivityItem>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub next_cursor: Option<Signature>,
+}
+
+// handler is at GET /api/wallets/{address}/activity, rebuilds the wallet's
+// confidential activity for a mint from chain, newest first. Non-custodial
+// wallets pass `keySignature` to decrypt it
+pub async fn handler(
+    State(state): State<Arc<AppState>>,
+    Path(path): Path<ActivityPath>,
+    Query(params): Query<ActivityQuery>,
+    auth_user: AuthUser,
+) -> Result<ApiResponse<ActivityResponse>, AppError> {
+    let Some(wallet) =
+        db::get_user_wallet_by_pubkey(&state.db, &path.address, auth_user.telegram_user_id).await?
+    else {
+        return Err(AppError::not_found(anyhow::anyhow!(
+            "Wallet not found or not authorized"
+        )));
+    };
+
+    let confidential_keys = match wallet.custody {
+        WalletCustody::Custodial => {
+            confidential_keys_for_mint(state.key_store.signer(wallet.id).await?, &params.mint)?
+        }
+        WalletCustody::External => {
+            external_wallet_keys(&wallet, &params.mint, params.key_signature.as_ref())?
+        }
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let page = rebuild_history(
+        state.rpc_client.clone(),
+        &wallet.pubkey,
+        &params.mint,
+        &confidential_keys,
+        params.cursor,
+        limit,
+    )
+    .await
+    .map_err(|e| {
+        AppError::internal_server_error(anyhow::anyhow!("Failed to rebuild history: {:?}", e))
+    })?;
+
+    let mut entries = Vec::with_capacity(page.entries.len());
+    for entry in page.entries {
+        let memo = match read_encrypted_memo(&state, &wallet, &entry).await {
+            Ok(memo) => memo,
+            Err(e) => {
+                warn!("failed to decrypt memo of {}: {:?}", entry.signature, e);
+                None
+            }
+        };
+        let mut item = ActivityItem::from(entry);
+        if memo.is_some() {
+            item.memo = memo;
+        }
+        entries.push(item);
+    }
+
+    Ok(ApiResponse::new(ActivityResponse {
+        entries,
+        next_cursor: page.next_before,
+    }))
+}
+
+/// Confidential keys of a non-custodial wallet, derived from the owner's
+/// signature since the API doesn't hold its keypair.
+fn external_wallet_keys(
+    wallet: &Wallet,
+    mint: &Pubkey,
+    key_signature: Option<&Signature>,
+) -> Result<ConfidentialKeys, AppError> {
+    let Some(key_signature) = key_signature else {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Wallet {} is non-custodial, pass keySignature to read its activity",
+            wallet.pubkey
+        )));
+    };
+    keys_from_signature(&wallet.pubkey, mint, key_signature)
+}
+
+/// Decrypt the encrypted memo of a transfer with the key the wallet shares
+/// with the wallet on the other side: the memo's signer for an incoming
+/// transfer, the owner of the destination account for an outgoing one. The
+/// memos of non-custodial wallets stay encrypted, their keypair is needed.
+async fn read_encrypted_memo(
+    state: &AppState,
+    wallet: &Wallet,
+    entry: &ActivityEntry,
+) -> Result<Option<String>> {
+    if wallet.custody == WalletCustody::External {
+        return Ok(None);
+    }
+    let Some(memo) = entry
+        .memo
+        .as_ref()
+        .filter(|memo| memo.text.starts_with(ENCRYPTED_MEMO_PREFIX))
+    else {
+        return Ok(None);
+    };
+
+    let peer = match (entry.kind, entry.counterparty) {
+        (ActivityKind::TransferIn, _) => memo.signer,
+        (ActivityKind::TransferOut, Some(destination)) => {
+            let account = state.rpc_client.get_account(&destination).await?;
+            Some(
+                StateWithExtensionsOwned::<Account>::unpack(account.data)?
+                    .base
+                    .owner,
+            )
+        }
+        _ => None,
+    };
+    let Some(peer) = peer else {
+        return Ok(None);
+    };
+
+    let key = state.key_store.memo_key(wallet.id, &peer).await?;
+    Ok(Some(decrypt_memo(&key, &memo.text)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use chrono::Utc;
+    use solana_keypair::Keypair;
+    use solana_signer::Signer;
+    use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+    #[test]
+    fn test_external_wallet_keys_need_the_owner_signature() {
+        let owner = Keypair::new();
+        let mint = Pubkey::new_unique();
+        let wallet = Wallet {
+            id: 1,
+            user_id: 1,
+            pubkey: owner.pubkey(),
+            custody: WalletCustody::External,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        let missing = external_wallet_keys(&wallet, &mint, None).unwrap_err();
+        assert_eq!(missing.into_response().status(), StatusCode::BAD_REQUEST);
+
+        let wrong = Keypair::new().sign_message(b"not the token account");
+        let wrong = external_wallet_keys(&wallet, &mint, Some(&wrong)).unwrap_err();
+        assert_eq!(wrong.into_response().status(), StatusCode::BAD_REQUEST);
+
+        let ata = get_associated_token_address_with_program_id(
+            &owner.pubkey(),
+            &mint,
+            &spl_token_2022::id(),
+        );
+        let key_signature = owner.sign_message(&ata.to_bytes());
+        assert!(external_wallet_keys(&wallet, &mint, Some(&key_signature)).is_ok());
+    }
+}
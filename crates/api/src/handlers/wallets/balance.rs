@@ -3,6 +3,7 @@ use crate::auth::AuthUser;
 use crate::db;
 use crate::handlers::ApiResponse;
 use crate::handlers::AppError;
+use crate::models::Wallet;
 use crate::solana::balance::get_confidential_balances_with_keys;
 use crate::solana::tokens::get_maybe_ata;
 use crate::solana::utils::confidential_keys_for_mint;
@@ -12,7 +13,6 @@ use axum::extract::{Query, State};
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
 use solana_pubkey::Pubkey;
-use solana_signer::Signer;
 use std::sync::Arc;
 
 #[serde_as]
@@ -83,14 +83,7 @@ pub async fn handler(
         )));
     }
 
-    let confidential_keys = confidential_keys_for_mint(Arc::new(wallet.keypair), &params.mint)?;
-    let (pending_balance, available_balance) = get_confidential_balances_with_keys(
-        state.rpc_client.clone(),
-        &path.address,
-        &params.mint,
-        &confidential_keys,
-    )
-    .await?;
+    let encrypted_balance = get_encrypted_balance(&state, &wallet, &params.mint).await?;
 
     let public_balance = state
         .rpc_client
@@ -111,13 +104,29 @@ pub async fn handler(
         mint: params.mint,
         token_account: ata,
         public_balance,
-        encrypted_balance: EncryptedBalance {
-            pending: pending_balance,
-            available: available_balance,
-        },
+        encrypted_balance,
     }))
 }
 
+/// Decrypt the confidential balance of a custodial wallet's token account for
+/// `mint`, which must exist.
+pub async fn get_encrypted_balance(
+    state: &AppState,
+    wallet: &Wallet,
+    mint: &Pubkey,
+) -> Result<EncryptedBalance, AppError> {
+    let confidential_keys =
+        confidential_keys_for_mint(state.key_store.signer(wallet.id).await?, mint)?;
+    let (pending, available) = get_confidential_balances_with_keys(
+        state.rpc_client.clone(),
+        &wallet.pubkey,
+        mint,
+        &confidential_keys,
+    )
+    .await?;
+    Ok(EncryptedBalance { pending, available })
+}
+
 #[serde_as]
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -140,8 +149,7 @@ pub async fn solana(
         )));
     };
 
-    let pubkey = wallet.keypair.pubkey();
-    if pubkey != path.address {
+    if wallet.pubkey != path.address {
         return Err(AppError::bad_request(anyhow::anyhow!(
             "Wallet address does not match provided address"
         )));
@@ -1,9 +1,9 @@
 use crate::AppState;
 use crate::auth::AuthUser;
 use crate::db;
+use crate::fees;
 use crate::handlers::ApiResponse;
 use crate::handlers::AppError;
-use crate::solana::airdrop::request_airdrop_and_confirm;
 use axum::body::Bytes;
 use axum::extract::State;
 use serde::{Deserialize, Serialize};
@@ -61,11 +61,12 @@ pub async fn handler(
     };
 
     let pubkey = keypair.pubkey();
-    request_airdrop_and_confirm(state.rpc_client.clone(), &pubkey, 1 * 10_u64.pow(9))
+    fees::fund_new_wallet(&state, &pubkey)
         .await
         .map_err(AppError::from)?;
 
-    db::create_wallet_for_telegram_user(&state.db, auth_user.telegram_user_id, &pubkey, &keypair)
+    let secret = state.key_store.seal(&keypair)?;
+    db::create_wallet_for_telegram_user(&state.db, auth_user.telegram_user_id, &pubkey, &secret)
         .await
         .map_err(AppError::from)?;
 
@@ -1,11 +1,10 @@
 use crate::AppState;
 use crate::auth::AuthUser;
 use crate::db;
+use crate::fees;
 use crate::handlers::ApiResponse;
 use crate::handlers::AppError;
-use crate::partial_sign::PartialSign;
 use crate::solana::balance::apply_pending_balance_with_keys;
-use crate::solana::transaction::build_transaction;
 use crate::solana::utils::confidential_keys_for_mint;
 use anyhow::Context;
 use axum::extract::Path;
@@ -75,7 +74,9 @@ pub async fn handler(
         )));
     }
 
-    let owner_kp: Arc<dyn Signer + Send + Sync> = Arc::new(wallet.keypair);
+    fees::ensure_budget(&state, wallet.id).await?;
+
+    let owner_kp = state.key_store.signer(wallet.id).await?;
     let confidential_keys = confidential_keys_for_mint(owner_kp.clone(), &payload.mint)?;
 
     let mut transactions: Vec<TransactionResult> = vec![];
@@ -95,22 +96,14 @@ pub async fn handler(
     )
     .await?;
 
-    let mut tx = build_transaction(
-        state.rpc_client.clone(),
-        None,
-        deposit_instructions.instructions,
+    let tx = fees::build_sponsored_transaction(
+        &state,
         owner_kp.clone(),
+        deposit_instructions.instructions,
         vec![],
     )
     .await?;
 
-    tx.partial_sign(&owner_kp).map_err(|e| {
-        AppError::internal_server_error(anyhow::anyhow!(
-            "Failed to partial sign transaction: {:?}",
-            e
-        ))
-    })?;
-
     let deposit_signature = state
         .rpc_client
         .clone()
@@ -118,6 +111,7 @@ pub async fn handler(
         .await
         .with_context(|| anyhow::anyhow!("error sending transaction"))
         .map_err(AppError::from)?;
+    fees::charge(&state, wallet.id, fees::transaction_fee(&tx)).await;
     transactions.push(TransactionResult {
         label: "Deposit".to_string(),
         signature: deposit_signature,
@@ -136,12 +130,11 @@ pub async fn handler(
     .await
     .map_err(AppError::from)?;
 
-    let transaction = build_transaction(
-        state.rpc_client.clone(),
-        None,
-        apply_instructions.instructions,
+    let transaction = fees::build_sponsored_transaction(
+        &state,
         owner_kp.clone(),
-        apply_instructions.additional_signers.into_iter().collect(),
+        apply_instructions.instructions,
+        apply_instructions.additional_signers,
     )
     .await
     .map_err(AppError::from)?;
@@ -153,6 +146,7 @@ pub async fn handler(
         .await
         .with_context(|| anyhow::anyhow!("Error sending transaction"))
         .map_err(AppError::from)?;
+    fees::charge(&state, wallet.id, fees::transaction_fee(&transaction)).await;
 
     transactions.push(TransactionResult {
         label: "Apply Pending Balance".to_string(),
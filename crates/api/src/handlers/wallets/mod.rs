@@ -1,11 +1,13 @@
 use axum::{
     Router,
+    middleware::from_fn_with_state,
     routing::{get, post},
 };
 use std::sync::Arc;
 
-use crate::AppState;
+use crate::{AppState, idempotency};
 
+pub mod activity;
 pub mod balance;
 pub mod create;
 pub mod deposit;
@@ -18,8 +20,17 @@ pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
         .route("/", get(list::handler))
         .route("/{address}/balance", get(balance::handler))
         .route("/{address}/balance/solana", get(balance::solana))
+        .route("/{address}/activity", get(activity::handler))
         .route("/", post(create::handler))
-        .route("/{address}/deposit", post(deposit::handler))
-        .route("/{address}/withdraw", post(withdraw::handler))
+        .route(
+            "/{address}/deposit",
+            post(deposit::handler)
+                .layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
+        .route(
+            "/{address}/withdraw",
+            post(withdraw::handler)
+                .layer(from_fn_with_state(state.clone(), idempotency::middleware)),
+        )
         .with_state(state)
 }
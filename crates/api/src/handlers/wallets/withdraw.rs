@@ -1,12 +1,13 @@
 use crate::AppState;
 use crate::auth::AuthUser;
 use crate::db;
+use crate::fees;
 use crate::handlers::ApiResponse;
 use crate::handlers::AppError;
-use crate::solana;
 use crate::solana::balance::apply_pending_balance_with_keys;
-use crate::solana::transaction::build_transaction;
+use crate::solana::submit::build_sequence;
 use crate::solana::utils::confidential_keys_for_mint;
+use crate::solana::withdraw::build_withdraw_steps;
 use anyhow::Context;
 use axum::extract::Path;
 use axum::{Json, extract::State};
@@ -16,15 +17,13 @@ use solana_keypair::Signature;
 use solana_pubkey::Pubkey;
 use solana_signer::Signer;
 use std::sync::Arc;
-use tokio::task;
 use tracing::info;
 
-const WITHDRAW_TRANSACTION_LABELS: [&str; 5] = [
-    "Create Equality Proof",
-    "Create Range Proof",
+const WITHDRAW_TRANSACTION_LABELS: [&str; 4] = [
+    "Create Proof Accounts",
+    "Verify Range Proof",
+    "Verify Equality Proof",
     "Withdraw",
-    "Close Equality Proof",
-    "Close Range Proof",
 ];
 
 #[serde_as]
@@ -84,7 +83,9 @@ pub async fn handler(
         )));
     }
 
-    let owner_kp: Arc<dyn Signer + Send + Sync> = Arc::new(wallet.keypair);
+    fees::ensure_budget(&state, wallet.id).await?;
+
+    let owner_kp = state.key_store.signer(wallet.id).await?;
     let confidential_keys = confidential_keys_for_mint(owner_kp.clone(), &payload.mint)?;
 
     // TODO: do this conditionally?
@@ -96,12 +97,11 @@ pub async fn handler(
     )
     .await?;
 
-    let transaction = build_transaction(
-        state.rpc_client.clone(),
-        None,
-        apply_instructions.instructions,
+    let transaction = fees::build_sponsored_transaction(
+        &state,
         owner_kp.clone(),
-        apply_instructions.additional_signers.into_iter().collect(),
+        apply_instructions.instructions,
+        apply_instructions.additional_signers,
     )
     .await?;
 
@@ -112,34 +112,62 @@ pub async fn handler(
         .await
         .with_context(|| anyhow::anyhow!("Error sending transaction"))
         .map_err(AppError::from)?;
+    fees::charge(&state, wallet.id, fees::transaction_fee(&transaction)).await;
     info!(
         "Withdraw [Apply Pending Balance] with signature={:?}",
         apply_signature
     );
 
-    // TODO: we can remove this after removing `ProgramRpcClientSendTransaction`
-    let withdraw_signatures: Vec<Signature> = task::spawn_blocking(move || {
-        let handle = tokio::runtime::Handle::current();
-        handle.block_on(solana::withdraw::withdraw_tokens(
-            state.rpc_client.clone(),
-            owner_kp.clone(),
-            payload.amount,
-            &payload.mint,
-            payload.decimals,
-        ))
-    })
-    .await
-    .with_context(|| anyhow::anyhow!("Failed to join withdraw task"))
-    .map_err(AppError::from)?
-    .with_context(|| anyhow::anyhow!("Failed to create withdraw"))
-    .map_err(AppError::from)?;
+    let prepared = build_withdraw_steps(
+        state.rpc_client.clone(),
+        &fees::payer(&state, &owner_kp).pubkey(),
+        &owner_kp.pubkey(),
+        payload.amount,
+        &payload.mint,
+        payload.decimals,
+        &confidential_keys,
+    )
+    .await?;
+    // kept until the withdraw lands, see `jobs::janitor`
+    let withdraw_proofs_id = db::create_withdraw_proofs(
+        &state.db,
+        wallet.id,
+        &prepared.equality_proof_account,
+        &prepared.range_proof_account,
+        &prepared.proofs.to_bytes(),
+    )
+    .await?;
+    let withdraw_transactions = build_sequence(
+        state.rpc_client.clone(),
+        state.submitter.as_ref(),
+        fees::payer(&state, &owner_kp),
+        vec![owner_kp],
+        prepared.steps,
+    )
+    .await?;
+    let withdraw_signatures = state
+        .submitter
+        .submit(&withdraw_transactions)
+        .await
+        .with_context(|| anyhow::anyhow!("Failed to create withdraw"))
+        .map_err(AppError::from)?;
+    db::delete_withdraw_proofs(&state.db, withdraw_proofs_id).await?;
+    fees::charge(
+        &state,
+        wallet.id,
+        withdraw_transactions
+            .iter()
+            .map(fees::transaction_fee)
+            .sum(),
+    )
+    .await;
 
     let transactions = WITHDRAW_TRANSACTION_LABELS
         .iter()
         .zip(withdraw_signatures.iter())
         .map(|(label, signature)| TransactionResult {
             label: label.to_string(),
-            signature: signature.clone(),
+            signature: *signature,
         })
         .collect();
 
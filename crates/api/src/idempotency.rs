@@ -0,0 +1,262 @@
+//! `Idempotency-Key` support for endpoints that move funds.
+//!
+//! Clients retry POSTs on flaky connections. When a request carries an
+//! `Idempotency-Key` header, the first response for that key (per user) is
+//! stored, and any retry with the same key and the same body gets that
+//! response back instead of running the handler again. Reusing a key with a
+//! different body, or while the first request is still running, is a 409.
+//!
+//! Server errors are not stored, the key is released so the client can retry,
+//! unless the handler recorded something before failing (see [`Recorded`]). A
+//! transfer whose job is left to the recovery loop is answered with a 202
+//! instead, which is stored like any other response.
+//! A key whose request never finished (a crash, a dropped connection) can be
+//! claimed again after [`LOCK_SECS`], and keys are forgotten after
+//! [`KEY_TTL_SECS`].
+//!
+//! Keys are scoped to the authenticated user, so a request sending the header
+//! must also be authenticated.
+
+use crate::auth::AuthUser;
+use crate::handlers::AppError;
+use crate::{AppState, db};
+use axum::{
+    body::{Body, Bytes, to_bytes},
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderValue, Method, StatusCode, header::CONTENT_TYPE},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{error, info};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long a request holds its key before a retry may run the handler
+/// again, well beyond what any handler takes.
+pub const LOCK_SECS: i64 = 15 * 60;
+pub const KEY_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// Response extension set on an error returned after the handler recorded
+/// something, like a transfer job, that a retry must not record again. The
+/// key is then kept and the error stored like any other response.
+#[derive(Debug, Clone, Copy)]
+pub struct Recorded;
+
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+// matches axum's default request body limit
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Middleware that makes the wrapped route idempotent for requests sending an
+/// `Idempotency-Key` header. Requests without the header pass straight through.
+pub async fn middleware(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN => key.to_string(),
+        _ => {
+            return AppError::bad_request(anyhow::anyhow!(
+                "Idempotency-Key must be between 1 and {} visible ASCII characters",
+                MAX_IDEMPOTENCY_KEY_LEN
+            ))
+            .into_response();
+        }
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let auth_user = match AuthUser::from_request_parts(&mut parts, &state).await {
+        Ok(auth_user) => auth_user,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            return AppError::bad_request(anyhow::anyhow!("Failed to read request body: {}", e))
+                .into_response();
+        }
+    };
+    let hash = request_hash(&parts.method, parts.uri.path(), &body);
+
+    match db::claim_idempotency_key(
+        &state.db,
+        auth_user.telegram_user_id,
+        &key,
+        &hash,
+        LOCK_SECS,
+        KEY_TTL_SECS,
+    )
+    .await
+    {
+        Ok(None) => {}
+        Ok(Some(existing)) => {
+            if existing.request_hash != hash {
+                return AppError::conflict(anyhow::anyhow!(
+                    "Idempotency-Key has already been used with a different request"
+                ))
+                .into_response();
+            }
+            let (Some(status), Some(body)) = (existing.response_status, existing.response_body)
+            else {
+                return AppError::conflict(anyhow::anyhow!(
+                    "A request with this Idempotency-Key is still being processed"
+                ))
+                .into_response();
+            };
+
+            info!(
+                telegram_user_id = auth_user.telegram_user_id,
+                "replaying response for idempotency key {}", key
+            );
+            return replay(status, existing.response_content_type.as_deref(), body);
+        }
+        Err(e) => return AppError::internal_server_error(e).into_response(),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    if releases_key(&response) {
+        if let Err(e) =
+            db::release_idempotency_key(&state.db, auth_user.telegram_user_id, &key).await
+        {
+            error!("failed to release idempotency key {}: {:?}", key, e);
+        }
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            return AppError::internal_server_error(anyhow::anyhow!(
+                "Failed to read response body: {}",
+                e
+            ))
+            .into_response();
+        }
+    };
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+
+    if let Err(e) = db::set_idempotency_key_response(
+        &state.db,
+        auth_user.telegram_user_id,
+        &key,
+        parts.status.as_u16(),
+        content_type,
+        &body,
+    )
+    .await
+    {
+        // the handler already ran, so still return its response; a retry will
+        // see the key as in progress until its lock runs out
+        error!(
+            "failed to store response for idempotency key {}: {:?}",
+            key, e
+        );
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Whether the handler failed before recording anything, so a retry may run
+/// it again.
+fn releases_key(response: &Response) -> bool {
+    response.status().is_server_error() && response.extensions().get::<Recorded>().is_none()
+}
+
+fn replay(status: i16, content_type: Option<&str>, body: Vec<u8>) -> Response {
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+    let mut response = (status, body).into_response();
+    let headers = response.headers_mut();
+    headers.remove(CONTENT_TYPE);
+    if let Some(content_type) = content_type.and_then(|value| HeaderValue::from_str(value).ok()) {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+/// Hash of everything that identifies a request, so a key reused for a
+/// different request can be detected.
+fn request_hash(method: &Method, path: &str, body: &Bytes) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str().as_bytes());
+    hasher.update(b"\n");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_hash_depends_on_path_and_body() {
+        let body = Bytes::from_static(br#"{"amount":"1"}"#);
+        let hash = request_hash(&Method::POST, "/api/transfers", &body);
+
+        assert_eq!(hash, request_hash(&Method::POST, "/api/transfers", &body));
+        assert_ne!(
+            hash,
+            request_hash(&Method::POST, "/api/transfers/telegram", &body)
+        );
+        assert_ne!(
+            hash,
+            request_hash(
+                &Method::POST,
+                "/api/transfers",
+                &Bytes::from_static(br#"{"amount":"2"}"#)
+            )
+        );
+    }
+
+    #[test]
+    fn test_server_errors_release_the_key_unless_recorded() {
+        assert!(releases_key(
+            &AppError::internal_server_error(anyhow::anyhow!("RPC error")).into_response()
+        ));
+        assert!(!releases_key(
+            &AppError::internal_server_error(anyhow::anyhow!("Transfer rolled back"))
+                .recorded()
+                .into_response()
+        ));
+        assert!(!releases_key(
+            &AppError::bad_request(anyhow::anyhow!("Invalid amount")).into_response()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_paused_transfer_job_keeps_the_key() {
+        let error = crate::handlers::transfers::finished_transfer(
+            7,
+            42,
+            Err(anyhow::anyhow!("RPC error while confirming")),
+        )
+        .unwrap_err();
+        let response = error.into_response();
+
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(!releases_key(&response));
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["transferId"], 7);
+        assert_eq!(body["data"]["jobId"], 42);
+        assert_eq!(body["data"]["status"], "pending");
+    }
+}
@@ -0,0 +1,326 @@
+//! Reclaiming rent from orphaned proof context state accounts.
+//!
+//! Transfers, withdraws and confidential mints allocate proof context state
+//! accounts and close them once the proofs are used. When a sequence fails
+//! part way, e.g. outside a transfer job that can roll itself back, those
+//! accounts stay on chain holding rent. The janitor started by
+//! [`spawn_janitor`] lists every verified proof context account whose
+//! authority is a custodial wallet or the global authority and closes it,
+//! refunding the rent to whoever pays for the authority's transactions.
+//!
+//! An account is only closed once it has been seen by two consecutive runs
+//! and no unfinished transfer job refers to it, so a sequence that is still
+//! in flight keeps its accounts. Every run is recorded in
+//! `proof_janitor_runs`.
+//!
+//! Accounts that were allocated but never verified carry no authority yet, so
+//! they can't be listed. Custodial withdraws store their proofs until they
+//! land; the janitor verifies the accounts of a withdraw that never did from
+//! those proofs, after which they are found and closed like any other.
+
+use crate::models::PendingWithdrawProofs;
+use crate::solana::transfer::{
+    ProofAccountState, build_close_proof_accounts_ixs, get_proof_account_state,
+};
+use crate::solana::withdraw::WithdrawProofs;
+use crate::solana::zk::{
+    ProofContextAccount, get_proof_context_accounts, get_zk_proof_verify_instruction,
+};
+use crate::{AppState, db, fees};
+use anyhow::{Context, Result};
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const RUN_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Proof accounts closed per transaction.
+const CLOSE_BATCH_SIZE: usize = 8;
+
+/// Stored withdraw proofs looked at per run.
+const MAX_WITHDRAW_RECOVERIES: i64 = 20;
+
+/// Outcome of a single janitor run.
+#[derive(Debug, Default)]
+struct JanitorRun {
+    /// Every account found this run, closed or not.
+    seen: HashSet<Pubkey>,
+    closed: usize,
+    /// Unverified withdraw proof accounts verified this run.
+    unverified: usize,
+    lamports_reclaimed: u64,
+    errors: Vec<String>,
+}
+
+/// Periodically close orphaned proof context state accounts.
+pub fn spawn_janitor(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RUN_INTERVAL);
+        let mut previously_seen = HashSet::new();
+        loop {
+            interval.tick().await;
+            match run(&state, &previously_seen).await {
+                Ok(seen) => previously_seen = seen,
+                Err(e) => error!("proof janitor run failed: {:?}", e),
+            }
+        }
+    });
+}
+
+/// Run the janitor once and record the run. Returns the accounts it found,
+/// which the next run may close.
+async fn run(state: &AppState, previously_seen: &HashSet<Pubkey>) -> Result<HashSet<Pubkey>> {
+    let run_id = db::start_proof_janitor_run(&state.db).await?;
+
+    let outcome = match sweep(state, previously_seen).await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            let error = format!("{:#}", e);
+            db::finish_proof_janitor_run(&state.db, run_id, 0, 0, 0, 0, Some(&error)).await?;
+            return Err(e);
+        }
+    };
+
+    let error = (!outcome.errors.is_empty()).then(|| outcome.errors.join("; "));
+    db::finish_proof_janitor_run(
+        &state.db,
+        run_id,
+        outcome.seen.len(),
+        outcome.closed,
+        outcome.unverified,
+        outcome.lamports_reclaimed,
+        error.as_deref(),
+    )
+    .await?;
+    info!(
+        run_id,
+        found = outcome.seen.len(),
+        closed = outcome.closed,
+        unverified = outcome.unverified,
+        lamports_reclaimed = outcome.lamports_reclaimed,
+        "proof janitor run finished"
+    );
+
+    Ok(outcome.seen)
+}
+
+async fn sweep(state: &AppState, previously_seen: &HashSet<Pubkey>) -> Result<JanitorRun> {
+    let mut unverified = 0;
+    let mut errors = Vec::new();
+    let stale_withdraws = db::get_stale_withdraw_proofs(
+        &state.db,
+        RUN_INTERVAL.as_secs_f64(),
+        MAX_WITHDRAW_RECOVERIES,
+    )
+    .await?;
+    for pending in stale_withdraws {
+        match verify_withdraw_proofs(state, &pending).await {
+            Ok(verified) => {
+                unverified += verified;
+                db::delete_withdraw_proofs(&state.db, pending.id).await?;
+            }
+            Err(e) => {
+                warn!(
+                    withdraw_proofs_id = pending.id,
+                    "proof janitor failed to verify withdraw proofs: {:?}", e
+                );
+                errors.push(format!("withdraw proofs {}: {:#}", pending.id, e));
+            }
+        }
+    }
+
+    // `None` stands for the global authority, everything else is a wallet id
+    let mut authorities: HashMap<Pubkey, Option<i64>> = db::list_custodial_wallets(&state.db)
+        .await?
+        .into_iter()
+        .map(|(wallet_id, pubkey)| (pubkey, Some(wallet_id)))
+        .collect();
+    authorities.insert(state.global_authority.pubkey(), None);
+
+    let accounts = get_proof_context_accounts(
+        state.rpc_client.clone(),
+        &authorities.keys().copied().collect(),
+    )
+    .await?;
+
+    let in_use: HashSet<Pubkey> = db::get_unfinished_transfer_jobs(&state.db)
+        .await?
+        .into_iter()
+        .filter_map(|job| job.proof_accounts)
+        .flat_map(|proof_accounts| proof_accounts.all())
+        .collect();
+
+    let mut outcome = JanitorRun {
+        seen: accounts.iter().map(|account| account.address).collect(),
+        unverified,
+        errors,
+        ..Default::default()
+    };
+
+    let mut by_authority: HashMap<Pubkey, Vec<ProofContextAccount>> = HashMap::new();
+    for account in select_orphans(accounts, previously_seen, &in_use) {
+        by_authority
+            .entry(account.authority)
+            .or_default()
+            .push(account);
+    }
+
+    for (authority, accounts) in by_authority {
+        let signer = match authorities.get(&authority).copied().flatten() {
+            Some(wallet_id) => match state.key_store.signer(wallet_id).await {
+                Ok(signer) => signer,
+                Err(e) => {
+                    warn!(%authority, "proof janitor can't sign for wallet: {:?}", e);
+                    outcome.errors.push(format!("{}: {:#}", authority, e));
+                    continue;
+                }
+            },
+            None => state.global_authority.clone(),
+        };
+
+        for batch in accounts.chunks(CLOSE_BATCH_SIZE) {
+            match close_batch(state, signer.clone(), batch).await {
+                Ok(()) => {
+                    outcome.closed += batch.len();
+                    outcome.lamports_reclaimed +=
+                        batch.iter().map(|account| account.lamports).sum::<u64>();
+                }
+                Err(e) => {
+                    warn!(%authority, "proof janitor failed to close accounts: {:?}", e);
+                    outcome.errors.push(format!("{}: {:#}", authority, e));
+                }
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// Accounts that were already found by the previous run and that no
+/// unfinished transfer job still needs.
+fn select_orphans(
+    accounts: Vec<ProofContextAccount>,
+    previously_seen: &HashSet<Pubkey>,
+    in_use: &HashSet<Pubkey>,
+) -> Vec<ProofContextAccount> {
+    accounts
+        .into_iter()
+        .filter(|account| {
+            previously_seen.contains(&account.address) && !in_use.contains(&account.address)
+        })
+        .collect()
+}
+
+/// Verify the proof accounts of a withdraw that never landed if they are still
+/// unverified, returning how many were. Accounts that are missing or already
+/// verified are left as they are.
+async fn verify_withdraw_proofs(
+    state: &AppState,
+    pending: &PendingWithdrawProofs,
+) -> Result<usize> {
+    let proofs = WithdrawProofs::from_bytes(&pending.proofs)?;
+    let owner = state.key_store.signer(pending.wallet_id).await?;
+
+    let mut instructions = Vec::new();
+    if get_proof_account_state(state.rpc_client.clone(), &pending.range_proof_account).await?
+        == ProofAccountState::Allocated
+    {
+        instructions.push(get_zk_proof_verify_instruction(
+            &pending.range_proof_account,
+            &owner.pubkey(),
+            &proofs.range_proof_data,
+        )?);
+    }
+    if get_proof_account_state(state.rpc_client.clone(), &pending.equality_proof_account).await?
+        == ProofAccountState::Allocated
+    {
+        instructions.push(get_zk_proof_verify_instruction(
+            &pending.equality_proof_account,
+            &owner.pubkey(),
+            &proofs.equality_proof_data,
+        )?);
+    }
+
+    let verified = instructions.len();
+    // a range proof fills most of a transaction, verify one proof per transaction
+    for instruction in instructions {
+        let transaction =
+            fees::build_sponsored_transaction(state, owner.clone(), vec![instruction], vec![])
+                .await?;
+        let signature = state
+            .rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .await
+            .context("Failed to send verify proof transaction")?;
+        fees::charge(
+            state,
+            pending.wallet_id,
+            fees::transaction_fee(&transaction),
+        )
+        .await;
+        info!(
+            wallet_id = pending.wallet_id,
+            "Janitor [verify withdraw proof] with signature={:?}", signature
+        );
+    }
+    Ok(verified)
+}
+
+async fn close_batch(
+    state: &AppState,
+    authority: Arc<dyn Signer + Send + Sync>,
+    accounts: &[ProofContextAccount],
+) -> Result<()> {
+    let addresses: Vec<Pubkey> = accounts.iter().map(|account| account.address).collect();
+    let instructions = build_close_proof_accounts_ixs(
+        &authority.pubkey(),
+        &fees::payer(state, &authority).pubkey(),
+        &addresses,
+    );
+    let transaction =
+        fees::build_sponsored_transaction(state, authority.clone(), instructions, vec![]).await?;
+    let signature = state
+        .rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .await
+        .context("Failed to send close proof accounts transaction")?;
+    info!(
+        authority = %authority.pubkey(),
+        accounts = ?addresses,
+        "Janitor [close proof accounts] with signature={:?}",
+        signature
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(authority: Pubkey) -> ProofContextAccount {
+        ProofContextAccount {
+            address: Pubkey::new_unique(),
+            authority,
+            lamports: 1_000,
+        }
+    }
+
+    #[test]
+    fn test_select_orphans() {
+        let authority = Pubkey::new_unique();
+        let orphan = account(authority);
+        let new = account(authority);
+        let in_flight = account(authority);
+
+        let previously_seen = HashSet::from([orphan.address, in_flight.address]);
+        let in_use = HashSet::from([in_flight.address]);
+
+        assert_eq!(
+            select_orphans(vec![orphan, new, in_flight], &previously_seen, &in_use),
+            vec![orphan]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Expiry of claim links.
+//!
+//! A claim link that nobody claimed before it expires (see
+//! `CLAIM_LINK_TTL_DAYS`) is refunded: the sweeper started by
+//! [`spawn_sweeper`] marks it as expired and sends the amount back from the
+//! link's escrow wallet to the wallet that funded it. Refunds are sent with
+//! [`super::refunds::send_refund`], so an interrupted refund is resumed by
+//! [`super::transfer::spawn_recovery`] and a failed one is retried on the next
+//! sweep.
+
+use super::refunds::{Refund, Refunded, send_refund};
+use crate::models::TransferJobStatus;
+use crate::{AppState, db};
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Refunds started per sweep, each one is five transactions.
+const MAX_REFUNDS_PER_SWEEP: i64 = 20;
+
+const REFUND_MEMO: &str = "Refund of unclaimed link";
+
+/// Periodically expire unclaimed links and refund them.
+pub fn spawn_sweeper(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweep(&state).await {
+                error!("claim link sweep failed: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn sweep(state: &AppState) -> Result<()> {
+    let expired = db::expire_claim_links(&state.db).await?;
+    if !expired.is_empty() {
+        info!(link_ids = ?expired, "claim links expired");
+    }
+
+    let refunds = db::get_pending_claim_link_refunds(&state.db, MAX_REFUNDS_PER_SWEEP).await?;
+    for refund in refunds {
+        let sent = send_refund(
+            state,
+            &Refund {
+                refunded: Refunded::ClaimLink(refund.link_id),
+                wallet_id: refund.escrow_wallet_id,
+                recipient: refund.sender,
+                mint: refund.mint,
+                amount: refund.amount,
+                decimals: refund.decimals,
+                memo: REFUND_MEMO,
+            },
+        )
+        .await;
+        match sent {
+            Ok(Some(TransferJobStatus::Completed)) => info!(
+                link_id = refund.link_id,
+                sender = %refund.sender,
+                "refunded claim link"
+            ),
+            Ok(Some(status)) => warn!(
+                link_id = refund.link_id,
+                status = status.as_str(),
+                "claim link refund did not complete"
+            ),
+            Ok(None) => {}
+            Err(e) => error!(
+                link_id = refund.link_id,
+                "failed to refund claim link: {:?}", e
+            ),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,10 @@
+//! Long-running and background work that outlives a single request.
+
+pub mod janitor;
+pub mod links;
+pub mod notifications;
+pub mod pots;
+pub mod refunds;
+pub mod reservations;
+pub mod scheduled;
+pub mod transfer;
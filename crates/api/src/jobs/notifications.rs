@@ -0,0 +1,149 @@
+//! Delivery of queued Telegram notifications.
+//!
+//! [`spawn_worker`] polls the `notifications` outbox for pending notifications
+//! that are due and whose user has a `telegram_user_id`, claims them with a
+//! lease so several API instances don't send the same one, and sends them with
+//! the bot. A failed delivery is retried with exponential backoff, up to
+//! [`MAX_ATTEMPTS`] times; errors retrying can't fix, like a user who blocked
+//! the bot, fail the notification right away. Notifications about transfers
+//! into a reserved wallet are cancelled when the wallet expires and they are
+//! refunded, see [`crate::jobs::reservations`].
+
+use crate::models::{Notification, NotificationStatus};
+use crate::telegram::api::BotApiError;
+use crate::{AppState, db};
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Notifications sent per poll.
+const MAX_NOTIFICATIONS_PER_POLL: i64 = 50;
+
+/// How long a claimed notification is left alone before it is retried, in
+/// case the process dies while sending it.
+const LEASE_SECS: i64 = 5 * 60;
+
+pub const MAX_ATTEMPTS: i32 = 8;
+
+/// Doubled after every failed attempt, so all of them span about an hour.
+const BASE_RETRY_DELAY_SECS: i64 = 30;
+
+/// Periodically send notifications that are due.
+pub fn spawn_worker(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = send_due(&state).await {
+                error!("notification poll failed: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn send_due(state: &AppState) -> Result<()> {
+    let due =
+        db::claim_due_notifications(&state.db, MAX_NOTIFICATIONS_PER_POLL, LEASE_SECS).await?;
+    for notification in due {
+        // the lease runs out and the notification is retried
+        if let Err(e) = send(state, &notification).await {
+            error!(
+                notification_id = notification.id,
+                "failed to record notification delivery: {:?}", e
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn send(state: &AppState, notification: &Notification) -> Result<()> {
+    if !notification.wanted {
+        return db::finish_notification(
+            &state.db,
+            notification.id,
+            NotificationStatus::Skipped,
+            None,
+        )
+        .await;
+    }
+
+    let error = match state
+        .telegram
+        .send_message(notification.telegram_user_id, &notification.text, None)
+        .await
+    {
+        Ok(_) => {
+            info!(
+                notification_id = notification.id,
+                kind = notification.kind.as_str(),
+                "notification sent"
+            );
+            return db::finish_notification(
+                &state.db,
+                notification.id,
+                NotificationStatus::Sent,
+                None,
+            )
+            .await;
+        }
+        Err(e) => e,
+    };
+
+    let permanent = error
+        .downcast_ref::<BotApiError>()
+        .is_some_and(BotApiError::is_permanent);
+    let message = format!("{:#}", error);
+    match retry_delay_secs(notification.attempts) {
+        Some(delay_secs) if !permanent => {
+            warn!(
+                notification_id = notification.id,
+                attempts = notification.attempts,
+                "notification failed, retrying in {}s: {}",
+                delay_secs,
+                message
+            );
+            db::retry_notification(&state.db, notification.id, &message, delay_secs).await
+        }
+        _ => {
+            warn!(
+                notification_id = notification.id,
+                attempts = notification.attempts,
+                "giving up on notification: {}",
+                message
+            );
+            db::finish_notification(
+                &state.db,
+                notification.id,
+                NotificationStatus::Failed,
+                Some(&message),
+            )
+            .await
+        }
+    }
+}
+
+/// Delay before the next attempt after `attempts` failed ones, `None` once
+/// there are no attempts left.
+fn retry_delay_secs(attempts: i32) -> Option<i64> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    Some(BASE_RETRY_DELAY_SECS << attempts.saturating_sub(1).max(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_secs() {
+        assert_eq!(retry_delay_secs(1), Some(30));
+        assert_eq!(retry_delay_secs(2), Some(60));
+        assert_eq!(retry_delay_secs(3), Some(120));
+        assert_eq!(retry_delay_secs(7), Some(1_920));
+        assert_eq!(retry_delay_secs(MAX_ATTEMPTS), None);
+    }
+}
@@ -0,0 +1,80 @@
+//! Expiry of group pots.
+//!
+//! A pot that wasn't closed by its creator before it expires is refunded: the
+//! sweeper started by [`spawn_sweeper`] marks it as expired and sends every
+//! contribution back from the pot's escrow wallet to the wallet it came from.
+//! Refunds are sent with [`super::refunds::send_refund`], so an interrupted
+//! refund is resumed by [`super::transfer::spawn_recovery`] and a failed one is
+//! retried on the next sweep. A contribution still in flight when the pot
+//! expires is refunded once it lands.
+
+use super::refunds::{Refund, Refunded, send_refund};
+use crate::models::TransferJobStatus;
+use crate::{AppState, db};
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Refunds started per sweep, each one is five transactions.
+const MAX_REFUNDS_PER_SWEEP: i64 = 20;
+
+const REFUND_MEMO: &str = "Refund of expired pot";
+
+/// Periodically expire pots past their expiry and refund their contributions.
+pub fn spawn_sweeper(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweep(&state).await {
+                error!("pot sweep failed: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn sweep(state: &AppState) -> Result<()> {
+    let expired = db::expire_pots(&state.db).await?;
+    if !expired.is_empty() {
+        info!(pot_ids = ?expired, "pots expired");
+    }
+
+    let refunds = db::get_pending_pot_refunds(&state.db, MAX_REFUNDS_PER_SWEEP).await?;
+    for refund in refunds {
+        let sent = send_refund(
+            state,
+            &Refund {
+                refunded: Refunded::PotContribution(refund.contribution_id),
+                wallet_id: refund.escrow_wallet_id,
+                recipient: refund.contributor,
+                mint: refund.mint,
+                amount: refund.amount,
+                decimals: refund.decimals,
+                memo: REFUND_MEMO,
+            },
+        )
+        .await;
+        match sent {
+            Ok(None) => {}
+            Ok(Some(TransferJobStatus::Completed)) => info!(
+                contribution_id = refund.contribution_id,
+                contributor = %refund.contributor,
+                "refunded pot contribution"
+            ),
+            Ok(Some(status)) => warn!(
+                contribution_id = refund.contribution_id,
+                status = status.as_str(),
+                "pot refund did not complete"
+            ),
+            Err(e) => error!(
+                contribution_id = refund.contribution_id,
+                "failed to refund pot contribution: {:?}", e
+            ),
+        }
+    }
+
+    Ok(())
+}
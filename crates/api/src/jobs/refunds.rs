@@ -0,0 +1,68 @@
+//! Refunds sent by the sweepers of expired pots, claim links and reserved
+//! wallets.
+//!
+//! A refund is an ordinary transfer job from the wallet holding the funds back
+//! to whoever paid them in. It is recorded in the same transaction as the
+//! claim on what it refunds, so two sweeps never refund the same thing, and
+//! one whose refund failed can be claimed again.
+
+use crate::models::TransferJobStatus;
+use crate::{AppState, db};
+use anyhow::{Context, Result};
+use solana_pubkey::Pubkey;
+
+/// What a refund gives back, claimed when the refund is recorded.
+#[derive(Debug, Clone, Copy)]
+pub enum Refunded {
+    PotContribution(i64),
+    ClaimLink(i64),
+    ReservedWalletCredit(i64),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Refund<'a> {
+    pub refunded: Refunded,
+    /// The wallet holding the funds, which sends the refund.
+    pub wallet_id: i64,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    pub memo: &'a str,
+}
+
+/// Claim `refund.refunded` and run its refund. Returns None if another sweep
+/// is already refunding it.
+pub async fn send_refund(
+    state: &AppState,
+    refund: &Refund<'_>,
+) -> Result<Option<TransferJobStatus>> {
+    let wallet = db::get_wallet_by_id(&state.db, refund.wallet_id)
+        .await?
+        .with_context(|| format!("refund wallet {} not found", refund.wallet_id))?;
+    let transfer = db::NewTransfer {
+        sender_wallet: &wallet,
+        recipient: &refund.recipient,
+        mint: &refund.mint,
+        amount: refund.amount,
+        decimals: refund.decimals,
+        onchain_memo: Some(refund.memo),
+        memo: Some(refund.memo),
+        memo_encrypted: false,
+        balance_funded: false,
+    };
+
+    let job_id = match refund.refunded {
+        Refunded::PotContribution(id) => db::start_pot_refund(&state.db, id, &transfer).await?,
+        Refunded::ClaimLink(id) => db::start_claim_link_refund(&state.db, id, &transfer).await?,
+        Refunded::ReservedWalletCredit(id) => {
+            db::start_reserved_wallet_refund(&state.db, id, &transfer).await?
+        }
+    };
+    let Some(job_id) = job_id else {
+        return Ok(None);
+    };
+
+    let job = super::transfer::run(state, job_id).await?;
+    Ok(Some(job.status))
+}
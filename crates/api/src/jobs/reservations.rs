@@ -0,0 +1,121 @@
+//! Expiry of reserved wallets.
+//!
+//! A transfer to a Telegram username that has never logged in lands in a
+//! reserved wallet, and every such transfer pushes the wallet's expiry back
+//! (see `RESERVED_WALLET_TTL_DAYS`). The sweeper started by [`spawn_sweeper`]
+//! marks reserved wallets that were not claimed in time as expired and sends
+//! each transfer they received back to its sender. Refunds are sent with
+//! [`super::refunds::send_refund`], so an interrupted refund is resumed by
+//! [`super::transfer::spawn_recovery`] or the next sweep. A failed refund is
+//! started again with exponential backoff, up to [`MAX_REFUND_ATTEMPTS`]
+//! times; after that the credit is left alone and listed for admins at
+//! `GET /api/admin/refunds`.
+
+use super::refunds::{Refund, Refunded, send_refund};
+use crate::models::{ReservedWalletRefund, TransferJobStatus};
+use crate::{AppState, db};
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Refunds started per sweep, each one is five transactions.
+const MAX_REFUNDS_PER_SWEEP: i64 = 20;
+
+const REFUND_MEMO: &str = "Refund of unclaimed transfer";
+
+pub const MAX_REFUND_ATTEMPTS: i32 = 5;
+
+/// Doubled after every failed refund, so all of them span about a day.
+const BASE_REFUND_RETRY_DELAY_SECS: i64 = 90 * 60;
+
+/// Periodically expire unclaimed reserved wallets and refund their balances.
+pub fn spawn_sweeper(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweep(&state).await {
+                error!("reserved wallet sweep failed: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn sweep(state: &AppState) -> Result<()> {
+    let expired = db::expire_reserved_wallets(&state.db).await?;
+    if !expired.is_empty() {
+        info!(wallet_ids = ?expired, "reserved wallets expired");
+    }
+
+    let refunds = db::get_pending_reserved_wallet_refunds(
+        &state.db,
+        MAX_REFUNDS_PER_SWEEP,
+        super::transfer::RECOVERY_IDLE_SECS,
+        MAX_REFUND_ATTEMPTS,
+        BASE_REFUND_RETRY_DELAY_SECS,
+    )
+    .await?;
+    for refund in refunds {
+        match refund_credit(state, &refund).await {
+            Ok(None) => {}
+            Ok(Some(TransferJobStatus::Completed)) => info!(
+                credit_id = refund.credit_id,
+                wallet_id = refund.wallet_id,
+                sender = %refund.sender,
+                "refunded reserved wallet transfer"
+            ),
+            Ok(Some(status)) => {
+                // a stalled job was counted when it was started
+                let attempts = refund.refund_attempts + i32::from(refund.stalled_job_id.is_none());
+                if status.is_terminal() && attempts >= MAX_REFUND_ATTEMPTS {
+                    error!(
+                        credit_id = refund.credit_id,
+                        attempts, "giving up on reserved wallet refund"
+                    );
+                } else {
+                    warn!(
+                        credit_id = refund.credit_id,
+                        attempts,
+                        status = status.as_str(),
+                        "reserved wallet refund did not complete"
+                    );
+                }
+            }
+            Err(e) => error!(
+                credit_id = refund.credit_id,
+                "failed to refund reserved wallet transfer: {:?}", e
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Drive a stalled refund of the credit again, or start one. Returns None if
+/// another sweep is already refunding the credit.
+async fn refund_credit(
+    state: &AppState,
+    refund: &ReservedWalletRefund,
+) -> Result<Option<TransferJobStatus>> {
+    if let Some(job_id) = refund.stalled_job_id {
+        let job = super::transfer::run(state, job_id).await?;
+        return Ok(Some(job.status));
+    }
+
+    send_refund(
+        state,
+        &Refund {
+            refunded: Refunded::ReservedWalletCredit(refund.credit_id),
+            wallet_id: refund.wallet_id,
+            recipient: refund.sender,
+            mint: refund.mint,
+            amount: refund.amount,
+            decimals: refund.decimals,
+            memo: REFUND_MEMO,
+        },
+    )
+    .await
+}
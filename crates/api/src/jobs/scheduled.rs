@@ -0,0 +1,174 @@
+//! Worker for scheduled transfers.
+//!
+//! [`spawn_worker`] polls for active schedules whose `next_run_at` has passed.
+//! Each due run is first claimed by moving the schedule on to its next run, so
+//! a run is attempted at most once even with several API instances, and then
+//! executed like any other transfer. Every run is recorded in
+//! `scheduled_transfer_runs`. A run that sends a transfer is recorded with it,
+//! as pending, and takes the outcome of the transfer's job once it ends, even
+//! if the recovery loop finishes the job later. A run that sends nothing is
+//! recorded as failed, or as skipped when the confidential balance can't
+//! cover it.
+
+use crate::handlers::transfers::{TransferMemo, finish_transfer, prepare_transfer};
+use crate::models::{ScheduledTransfer, ScheduledTransferRunStatus};
+use crate::solana::balance::get_confidential_balances;
+use crate::{AppState, db};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Runs started per poll, each transfer is five transactions.
+const MAX_RUNS_PER_POLL: i64 = 20;
+
+enum RunOutcome {
+    /// The transfer was recorded with the run, which follows its job.
+    Sent {
+        transfer_id: i64,
+        job_id: i64,
+    },
+    Skipped {
+        reason: String,
+    },
+}
+
+/// Periodically run scheduled transfers that are due.
+pub fn spawn_worker(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_due(&state).await {
+                error!("scheduled transfer poll failed: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn run_due(state: &AppState) -> Result<()> {
+    let due = db::get_due_scheduled_transfers(&state.db, MAX_RUNS_PER_POLL).await?;
+    for scheduled in due {
+        let due_at = scheduled.next_run_at;
+        let next_run_at = match scheduled
+            .schedule()
+            .and_then(|schedule| schedule.next_run(due_at, Utc::now()))
+        {
+            Ok(next_run_at) => next_run_at,
+            Err(e) => {
+                // schedules are validated on creation, so this only happens if
+                // the row was edited by hand; stop it rather than retry forever
+                warn!(
+                    scheduled_transfer_id = scheduled.id,
+                    "pausing scheduled transfer with invalid schedule: {:?}", e
+                );
+                db::pause_scheduled_transfer(&state.db, scheduled.id).await?;
+                db::record_scheduled_transfer_run(
+                    &state.db,
+                    scheduled.id,
+                    due_at,
+                    ScheduledTransferRunStatus::Failed,
+                    Some(&format!("Invalid schedule: {}", e)),
+                )
+                .await?;
+                continue;
+            }
+        };
+
+        if !db::claim_scheduled_transfer_run(&state.db, scheduled.id, due_at, next_run_at).await? {
+            continue;
+        }
+
+        let (status, reason) = match run(state, &scheduled, due_at).await {
+            Ok(RunOutcome::Sent {
+                transfer_id,
+                job_id,
+            }) => {
+                info!(
+                    scheduled_transfer_id = scheduled.id,
+                    transfer_id, job_id, "scheduled transfer sent"
+                );
+                continue;
+            }
+            Ok(RunOutcome::Skipped { reason }) => {
+                info!(
+                    scheduled_transfer_id = scheduled.id,
+                    "scheduled transfer skipped: {}", reason
+                );
+                (ScheduledTransferRunStatus::Skipped, reason)
+            }
+            Err(e) => {
+                error!(
+                    scheduled_transfer_id = scheduled.id,
+                    "scheduled transfer failed: {:?}", e
+                );
+                (ScheduledTransferRunStatus::Failed, e.to_string())
+            }
+        };
+        db::record_scheduled_transfer_run(&state.db, scheduled.id, due_at, status, Some(&reason))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Send the transfer of a due run, recording the run with it. Errors mean
+/// nothing was recorded, so the run is still to be recorded as failed.
+async fn run(
+    state: &AppState,
+    scheduled: &ScheduledTransfer,
+    due_at: DateTime<Utc>,
+) -> Result<RunOutcome> {
+    let wallet = db::get_wallet_by_id(&state.db, scheduled.wallet_id)
+        .await?
+        .with_context(|| format!("wallet {} not found", scheduled.wallet_id))?;
+
+    let signer = state.key_store.signer(wallet.id).await?;
+    let (pending, available) =
+        get_confidential_balances(state.rpc_client.clone(), signer, &scheduled.mint).await?;
+    if available.saturating_add(pending) < scheduled.amount {
+        return Ok(RunOutcome::Skipped {
+            reason: format!(
+                "Insufficient confidential balance: available {}, pending {}, amount {}",
+                available, pending, scheduled.amount
+            ),
+        });
+    }
+
+    let transfer = prepare_transfer(
+        state,
+        &wallet,
+        scheduled.recipient,
+        scheduled.amount,
+        scheduled.mint,
+        scheduled.decimals,
+        scheduled.memo.as_deref().map(|text| TransferMemo {
+            text,
+            encrypt: scheduled.encrypt_memo,
+        }),
+    )
+    .await?;
+    let (transfer_id, job_id) = db::create_scheduled_transfer_run(
+        &state.db,
+        scheduled.id,
+        due_at,
+        &transfer.ledger_entry(),
+    )
+    .await?;
+
+    // the run is settled with the job, here or by the recovery loop
+    if let Err(e) = finish_transfer(state, transfer_id, job_id).await {
+        warn!(
+            scheduled_transfer_id = scheduled.id,
+            transfer_id, "scheduled transfer did not complete yet or failed: {}", e
+        );
+    }
+
+    Ok(RunOutcome::Sent {
+        transfer_id,
+        job_id,
+    })
+}
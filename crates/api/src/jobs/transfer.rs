@@ -0,0 +1,594 @@
+//! Persistent state machine for confidential transfers.
+//!
+//! A confidential transfer is five transactions sent in sequence (allocate
+//! proof accounts, verify range proof, verify equality and validity proofs,
+//! transfer, close proof accounts). Every step is recorded in the
+//! `transfer_jobs` table before and after it is sent, so a job interrupted by
+//! a crash, a dropped request or an RPC error can be picked up again:
+//!
+//! - a step whose transaction landed is marked done and the job moves on,
+//! - a step whose transaction was dropped is rebuilt from the stored proof data,
+//! - a step that was rejected before the transfer landed rolls the job back by
+//!   verifying any half-initialized proof accounts and closing all of them,
+//! - a rejected close completes the job anyway, the transfer landed and the
+//!   janitor (see [`crate::jobs::janitor`]) reclaims the proof accounts.
+//!
+//! Whoever drives a job holds a lease on it, renewed before every step.
+//! [`spawn_recovery`] periodically resumes the unfinished jobs whose lease ran
+//! out, one sender at a time: a job whose sender has another job under a live
+//! lease waits for it, and [`run_in_order`] leases a whole sequence up front. A job that errors before anything was sent for it fails right away
+//! instead, so a transfer its caller saw fail doesn't go through later.
+//!
+//! With an atomic [`crate::solana::submit::Submitter`] the five transactions
+//! go out as one bundle from the `created` status instead. The bundle lands
+//! as a whole or not at all, so there is nothing to roll back: the job is
+//! either completed with every step's signature or rebuilt from scratch.
+//!
+//! Every transaction is paid for by the fee payer, and the sender only signs
+//! as the owner; fees are charged to the sender's budget (see
+//! [`crate::fees`]). A job whose sender has run out of budget fails before
+//! anything is sent.
+
+use crate::models::{TransferJob, TransferJobStatus};
+use crate::solana::confidential_keys::ConfidentialKeys;
+use crate::solana::memo::build_memo_ix;
+use crate::solana::submit::{BundleRejected, build_sequence};
+use crate::solana::transaction::{
+    TransactionOutcome, build_transaction, required_signers, wait_for_transaction_outcome,
+};
+use crate::solana::transfer::{
+    PreparedTransfer, ProofAccountState, ProofAccounts, TransferProofs,
+    build_allocate_proof_accounts_ixs, build_close_proof_accounts_ixs,
+    build_prepared_transfer_steps, build_transfer_ixs, build_verify_range_proof_ixs,
+    build_verify_remaining_proofs_ixs, ensure_confidential_balance, get_proof_account_state,
+    prepare_transfer,
+};
+use crate::solana::utils::confidential_keys_for_mint;
+use crate::solana::zk::get_zk_proof_verify_instruction;
+use crate::{AppState, db, fees};
+use anyhow::{Context, Result};
+use solana_instruction::Instruction;
+use solana_keypair::Signature;
+use solana_signer::Signer;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// How long a job is left to whoever drives it. Renewed before every step,
+/// and much longer than a step takes.
+const LEASE_SECS: i64 = 10 * 60;
+
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Jobs are only recovered once they haven't changed for this long, so a job
+/// that was just created is left to the request that created it.
+pub const RECOVERY_IDLE_SECS: i64 = 60;
+
+type SharedSigner = Arc<dyn Signer + Send + Sync>;
+
+/// Status a job reaches once the transaction for its current step lands.
+fn next_status(status: TransferJobStatus) -> Option<TransferJobStatus> {
+    match status {
+        TransferJobStatus::Created => Some(TransferJobStatus::ProofsAllocated),
+        TransferJobStatus::ProofsAllocated => Some(TransferJobStatus::RangeProofVerified),
+        TransferJobStatus::RangeProofVerified => Some(TransferJobStatus::ProofsVerified),
+        TransferJobStatus::ProofsVerified => Some(TransferJobStatus::Transferred),
+        TransferJobStatus::Transferred => Some(TransferJobStatus::Completed),
+        _ => None,
+    }
+}
+
+/// Drive a job until it reaches a terminal status.
+///
+/// Errors that leave the job's on-chain state unknown (RPC failures, a crash)
+/// are returned without changing the job's status, so it can be resumed later.
+/// Fails if someone else holds the job's lease.
+pub async fn run(state: &AppState, job_id: i64) -> Result<TransferJob> {
+    run_leased(state, job_id, Uuid::new_v4()).await
+}
+
+/// Run jobs of one sender one after the other. They are all leased up front
+/// and the lease on the ones still waiting is renewed before each job, so the
+/// recovery loop doesn't start a waiting job while an earlier one runs.
+pub async fn run_in_order(state: &AppState, job_ids: &[i64]) -> Vec<Result<TransferJob>> {
+    let lease_id = Uuid::new_v4();
+    let mut results = Vec::with_capacity(job_ids.len());
+    for (index, job_id) in job_ids.iter().enumerate() {
+        if let Err(e) =
+            db::lease_transfer_jobs(&state.db, &job_ids[index..], lease_id, LEASE_SECS).await
+        {
+            error!(job_id, "failed to lease waiting transfer jobs: {:?}", e);
+        }
+        results.push(run_leased(state, *job_id, lease_id).await);
+    }
+    results
+}
+
+async fn run_leased(state: &AppState, job_id: i64, lease_id: Uuid) -> Result<TransferJob> {
+    let result = drive(state, job_id, lease_id).await;
+    // a dropped future skips this, the lease then runs out on its own
+    if let Err(e) = db::release_transfer_job(&state.db, job_id, lease_id).await {
+        error!(job_id, "failed to release transfer job: {:?}", e);
+    }
+    result
+}
+
+async fn drive(state: &AppState, job_id: i64, lease_id: Uuid) -> Result<TransferJob> {
+    loop {
+        let job = db::get_transfer_job(&state.db, job_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Transfer job {} not found", job_id))?;
+
+        if job.status.is_terminal() {
+            db::sync_transfer_with_job(&state.db, &job).await?;
+            return Ok(job);
+        }
+
+        if !db::lease_transfer_job(&state.db, job_id, lease_id, LEASE_SECS).await? {
+            anyhow::bail!("Transfer job {} is being run elsewhere", job_id);
+        }
+
+        if let Err(e) = step(state, &job).await {
+            error!(
+                job_id,
+                status = job.status.as_str(),
+                "transfer job step failed: {:?}",
+                e
+            );
+            let message = format!("{:#}", e);
+            if job.status == TransferJobStatus::Created && !has_pending(state, job_id).await? {
+                // nothing was sent for the job, don't leave it to go through later
+                db::set_transfer_job_status(
+                    &state.db,
+                    job_id,
+                    TransferJobStatus::Failed,
+                    Some(&message),
+                )
+                .await?;
+                continue;
+            }
+            db::set_transfer_job_error(&state.db, job_id, &message).await?;
+            return Err(e);
+        }
+    }
+}
+
+/// Whether a transaction was sent for the job without its outcome being seen.
+async fn has_pending(state: &AppState, job_id: i64) -> Result<bool> {
+    let job = db::get_transfer_job(&state.db, job_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Transfer job {} not found", job_id))?;
+    Ok(job.pending_signature.is_some())
+}
+
+/// Periodically resume unfinished jobs nobody is driving: left behind by a
+/// previous run of the API, a dropped request or an error.
+pub fn spawn_recovery(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RECOVERY_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = recover_stalled(&state).await {
+                error!("failed to load stalled transfer jobs: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn recover_stalled(state: &AppState) -> Result<()> {
+    let jobs = db::get_stalled_transfer_jobs(&state.db, RECOVERY_IDLE_SECS).await?;
+    if jobs.is_empty() {
+        return Ok(());
+    }
+    info!("recovering {} stalled transfer jobs", jobs.len());
+
+    for job in jobs {
+        info!(
+            job_id = job.id,
+            status = job.status.as_str(),
+            "resuming transfer job"
+        );
+        match run(state, job.id).await {
+            Ok(job) => info!(
+                job_id = job.id,
+                status = job.status.as_str(),
+                "transfer job recovered"
+            ),
+            Err(e) => error!(job_id = job.id, "failed to recover transfer job: {:?}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Advance a job by exactly one step.
+async fn step(state: &AppState, job: &TransferJob) -> Result<()> {
+    let sender: SharedSigner = state.key_store.signer(job.sender_wallet_id).await?;
+
+    if let (Some(signature), Some(blockhash)) = (job.pending_signature, job.pending_blockhash) {
+        let outcome =
+            wait_for_transaction_outcome(state.rpc_client.clone(), &signature, &blockhash).await?;
+        match (outcome, &job.pending_bundle) {
+            (TransactionOutcome::Landed, Some(signatures)) => {
+                return complete_bundle(state, job, signatures).await;
+            }
+            (TransactionOutcome::Rejected(reason), Some(_)) => {
+                return db::set_transfer_job_status(
+                    &state.db,
+                    job.id,
+                    TransferJobStatus::Failed,
+                    Some(&reason),
+                )
+                .await;
+            }
+            (TransactionOutcome::Landed, None) => {
+                return complete_step(state, job, &signature).await;
+            }
+            (TransactionOutcome::Rejected(reason), None) => {
+                return reject_step(state, job, &reason).await;
+            }
+            (TransactionOutcome::Dropped, _) => {
+                warn!(
+                    job_id = job.id,
+                    "pending transaction dropped, rebuilding step"
+                );
+                db::clear_transfer_job_pending(&state.db, job.id).await?;
+            }
+        }
+    }
+
+    match job.status {
+        TransferJobStatus::Created => {
+            if let Err(exceeded) = fees::check(state, job.sender_wallet_id).await? {
+                return db::set_transfer_job_status(
+                    &state.db,
+                    job.id,
+                    TransferJobStatus::Failed,
+                    Some(&exceeded.to_string()),
+                )
+                .await;
+            }
+
+            if !job.balance_funded {
+                let balance_fees = ensure_confidential_balance(
+                    state.rpc_client.clone(),
+                    fees::payer(state, &sender).clone(),
+                    sender.clone(),
+                    &job.mint,
+                    job.decimals,
+                    job.amount,
+                )
+                .await?;
+                fees::charge(state, job.sender_wallet_id, balance_fees).await;
+            }
+
+            let keys = confidential_keys_for_mint(sender.clone(), &job.mint)?;
+            let prepared = prepare_transfer(
+                state.rpc_client.clone(),
+                &job.sender,
+                &job.recipient,
+                job.amount,
+                &job.mint,
+                &keys,
+            )
+            .await?;
+            db::set_transfer_job_proofs(
+                &state.db,
+                job.id,
+                &prepared.proof_accounts,
+                &prepared.proofs.to_bytes(),
+            )
+            .await?;
+
+            if state.submitter.is_atomic() {
+                return submit_bundle(state, job, sender, &keys, prepared).await;
+            }
+
+            let instructions = build_allocate_proof_accounts_ixs(
+                state.rpc_client.clone(),
+                &fees::payer(state, &sender).pubkey(),
+                &job.sender,
+                &prepared.proof_accounts,
+                &prepared.proofs,
+            )
+            .await?;
+            submit_step(
+                state,
+                job,
+                instructions,
+                sender,
+                prepared.proof_account_signers,
+            )
+            .await
+        }
+        TransferJobStatus::ProofsAllocated => {
+            let (proof_accounts, proofs) = stored_proofs(job)?;
+            let instructions = build_verify_range_proof_ixs(&job.sender, &proof_accounts, &proofs)?;
+            submit_step(state, job, instructions, sender, vec![]).await
+        }
+        TransferJobStatus::RangeProofVerified => {
+            let (proof_accounts, proofs) = stored_proofs(job)?;
+            let instructions =
+                build_verify_remaining_proofs_ixs(&job.sender, &proof_accounts, &proofs)?;
+            submit_step(state, job, instructions, sender, vec![]).await
+        }
+        TransferJobStatus::ProofsVerified => {
+            let (proof_accounts, proofs) = stored_proofs(job)?;
+            let keys = confidential_keys_for_mint(sender.clone(), &job.mint)?;
+            let mut instructions = build_transfer_ixs(
+                &job.sender,
+                &job.recipient,
+                &job.mint,
+                job.amount,
+                &proof_accounts,
+                &proofs,
+                &keys,
+            )?;
+            if let Some(memo) = &job.memo {
+                instructions.push(build_memo_ix(memo, &job.sender));
+            }
+            submit_step(state, job, instructions, sender, vec![]).await
+        }
+        TransferJobStatus::Transferred => {
+            let (proof_accounts, _) = stored_proofs(job)?;
+            let instructions = build_close_proof_accounts_ixs(
+                &job.sender,
+                &fees::payer(state, &sender).pubkey(),
+                &proof_accounts.all(),
+            );
+            submit_step(state, job, instructions, sender, vec![]).await
+        }
+        TransferJobStatus::RollingBack => roll_back(state, job, sender).await,
+        TransferJobStatus::Completed
+        | TransferJobStatus::RolledBack
+        | TransferJobStatus::Failed => Ok(()),
+    }
+}
+
+fn stored_proofs(job: &TransferJob) -> Result<(ProofAccounts, TransferProofs)> {
+    let proof_accounts = job
+        .proof_accounts
+        .context("Transfer job has no proof accounts")?;
+    let proofs = job
+        .proofs
+        .as_deref()
+        .context("Transfer job has no proof data")?;
+    Ok((proof_accounts, TransferProofs::from_bytes(proofs)?))
+}
+
+/// Send the transaction for the job's current step, recording it as pending
+/// first so a restart can tell whether it landed.
+async fn submit_step(
+    state: &AppState,
+    job: &TransferJob,
+    instructions: Vec<Instruction>,
+    sender: SharedSigner,
+    additional_signers: Vec<SharedSigner>,
+) -> Result<()> {
+    let blockhash = state.rpc_client.get_latest_blockhash().await?;
+    let payer = fees::payer(state, &sender);
+    let mut signers = additional_signers;
+    signers.push(sender);
+    let signers = required_signers(&instructions, &signers);
+    let transaction = build_transaction(
+        state.rpc_client.clone(),
+        Some(blockhash),
+        instructions,
+        payer,
+        signers,
+    )
+    .await?;
+    let signature = transaction.signatures[0];
+    db::set_transfer_job_pending(&state.db, job.id, &signature, &blockhash).await?;
+
+    match state
+        .rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .await
+    {
+        Ok(signature) => {
+            fees::charge(
+                state,
+                job.sender_wallet_id,
+                fees::transaction_fee(&transaction),
+            )
+            .await;
+            complete_step(state, job, &signature).await
+        }
+        Err(e) => match e.get_transaction_error() {
+            Some(reason) => reject_step(state, job, &reason.to_string()).await,
+            None => Err(e).context("Failed to send transfer transaction"),
+        },
+    }
+}
+
+/// Send every step of the job as one bundle, recording it as pending first so
+/// a restart can tell whether it landed.
+async fn submit_bundle(
+    state: &AppState,
+    job: &TransferJob,
+    sender: SharedSigner,
+    keys: &ConfidentialKeys,
+    prepared: PreparedTransfer,
+) -> Result<()> {
+    let mut steps = build_prepared_transfer_steps(
+        state.rpc_client.clone(),
+        &fees::payer(state, &sender).pubkey(),
+        &job.sender,
+        &job.recipient,
+        job.amount,
+        &job.mint,
+        prepared,
+        keys,
+    )
+    .await?;
+    if let Some(memo) = &job.memo {
+        steps[3].instructions.push(build_memo_ix(memo, &job.sender));
+    }
+
+    let transactions = build_sequence(
+        state.rpc_client.clone(),
+        state.submitter.as_ref(),
+        fees::payer(state, &sender).clone(),
+        vec![sender],
+        steps,
+    )
+    .await?;
+    let signatures: Vec<Signature> = transactions.iter().map(|tx| tx.signatures[0]).collect();
+    let blockhash = *transactions[0].message.recent_blockhash();
+    db::set_transfer_job_pending_bundle(&state.db, job.id, &signatures, &blockhash).await?;
+
+    match state.submitter.submit(&transactions).await {
+        Ok(signatures) => {
+            fees::charge(
+                state,
+                job.sender_wallet_id,
+                transactions.iter().map(fees::transaction_fee).sum(),
+            )
+            .await;
+            complete_bundle(state, job, &signatures).await
+        }
+        Err(e) => match e.downcast_ref::<BundleRejected>() {
+            Some(rejected) => {
+                warn!(job_id = job.id, "transfer bundle rejected: {}", rejected);
+                db::set_transfer_job_status(
+                    &state.db,
+                    job.id,
+                    TransferJobStatus::Failed,
+                    Some(&rejected.to_string()),
+                )
+                .await
+            }
+            None => Err(e).context("Failed to send transfer bundle"),
+        },
+    }
+}
+
+async fn complete_bundle(
+    state: &AppState,
+    job: &TransferJob,
+    signatures: &[Signature],
+) -> Result<()> {
+    info!(
+        job_id = job.id,
+        sender = job.sender.to_string(),
+        recipient = job.recipient.to_string(),
+        mint = job.mint.to_string(),
+        "Transfer [bundle] with signatures={:?}",
+        signatures
+    );
+    db::complete_transfer_job_bundle(&state.db, job.id, signatures).await
+}
+
+async fn complete_step(state: &AppState, job: &TransferJob, signature: &Signature) -> Result<()> {
+    let status = next_status(job.status)
+        .ok_or_else(|| anyhow::anyhow!("Transfer job status {:?} has no next step", job.status))?;
+    info!(
+        job_id = job.id,
+        sender = job.sender.to_string(),
+        recipient = job.recipient.to_string(),
+        mint = job.mint.to_string(),
+        "Transfer [{}] with signature={:?}",
+        status.as_str(),
+        signature
+    );
+    db::advance_transfer_job(&state.db, job.id, status, signature).await
+}
+
+/// Handle a step whose transaction was rejected. Nothing has moved until the
+/// transfer lands, so earlier steps roll back. A rejected close still
+/// completes the job, keeping the reason, and leaves the proof accounts to
+/// the janitor.
+async fn reject_step(state: &AppState, job: &TransferJob, reason: &str) -> Result<()> {
+    warn!(
+        job_id = job.id,
+        status = job.status.as_str(),
+        "transfer step rejected: {}",
+        reason
+    );
+    let status = match job.status {
+        TransferJobStatus::Created => TransferJobStatus::Failed,
+        TransferJobStatus::ProofsAllocated
+        | TransferJobStatus::RangeProofVerified
+        | TransferJobStatus::ProofsVerified => TransferJobStatus::RollingBack,
+        TransferJobStatus::Transferred => TransferJobStatus::Completed,
+        _ => TransferJobStatus::Failed,
+    };
+    db::set_transfer_job_status(&state.db, job.id, status, Some(reason)).await
+}
+
+/// Reclaim the proof accounts of a job whose transfer will never land.
+///
+/// Allocated accounts can only be closed once they hold a verified proof, so
+/// any account left half-initialized is verified with the stored proof data
+/// first. Every step reads on-chain state, so this is safe to repeat.
+async fn roll_back(state: &AppState, job: &TransferJob, sender: SharedSigner) -> Result<()> {
+    let Ok((proof_accounts, proofs)) = stored_proofs(job) else {
+        return db::set_transfer_job_status(&state.db, job.id, TransferJobStatus::RolledBack, None)
+            .await;
+    };
+
+    let mut verify_range_ixs = vec![];
+    let mut verify_remaining_ixs = vec![];
+    let mut to_close = vec![];
+    for account in proof_accounts.all() {
+        let account_state = get_proof_account_state(state.rpc_client.clone(), &account).await?;
+        if account_state == ProofAccountState::Missing {
+            continue;
+        }
+        to_close.push(account);
+        if account_state == ProofAccountState::Verified {
+            continue;
+        }
+
+        if account == proof_accounts.range {
+            verify_range_ixs.push(get_zk_proof_verify_instruction(
+                &account,
+                &job.sender,
+                &proofs.range_proof_data,
+            )?);
+        } else if account == proof_accounts.equality {
+            verify_remaining_ixs.push(get_zk_proof_verify_instruction(
+                &account,
+                &job.sender,
+                &proofs.equality_proof_data,
+            )?);
+        } else {
+            verify_remaining_ixs.push(get_zk_proof_verify_instruction(
+                &account,
+                &job.sender,
+                &proofs.ciphertext_validity_proof_data,
+            )?);
+        }
+    }
+
+    let close_ixs = build_close_proof_accounts_ixs(
+        &job.sender,
+        &fees::payer(state, &sender).pubkey(),
+        &to_close,
+    );
+    for instructions in [verify_range_ixs, verify_remaining_ixs, close_ixs] {
+        if instructions.is_empty() {
+            continue;
+        }
+        let transaction =
+            fees::build_sponsored_transaction(state, sender.clone(), instructions, vec![]).await?;
+        let signature = state
+            .rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .await
+            .context("Failed to send rollback transaction")?;
+        fees::charge(
+            state,
+            job.sender_wallet_id,
+            fees::transaction_fee(&transaction),
+        )
+        .await;
+        info!(
+            job_id = job.id,
+            "Transfer [rollback] with signature={:?}", signature
+        );
+    }
+
+    db::set_transfer_job_status(&state.db, job.id, TransferJobStatus::RolledBack, None).await
+}
@@ -0,0 +1,299 @@
+use super::{KeyStore, SealedSecret, open_wallet_secret};
+use crate::solana::memo::shared_memo_key;
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use sqlx::PgPool;
+use std::{collections::BTreeMap, sync::Arc};
+
+const ENVELOPE_PREFIX: &str = "envelope:";
+const NONCE_LEN: usize = 12;
+
+/// Versioned 256-bit key-encryption keys that wrap the per-wallet data keys.
+/// New secrets are wrapped with the current version; any version in the ring
+/// can unwrap, so old and new keys both work while a rotation is running.
+#[derive(Clone)]
+pub struct MasterKeyRing {
+    current: i32,
+    keys: BTreeMap<i32, Key<Aes256Gcm>>,
+}
+
+impl MasterKeyRing {
+    pub fn new(current: i32, keys: BTreeMap<i32, Key<Aes256Gcm>>) -> Result<Self> {
+        if !keys.contains_key(&current) {
+            anyhow::bail!("Master key version {} is not configured", current);
+        }
+        Ok(Self { current, keys })
+    }
+
+    /// Parse the master keys from `<version>:<hex key>` entries separated by
+    /// commas or newlines, or from a single hex key taken as version 1.
+    /// `current` picks the version used for new secrets and defaults to the
+    /// highest one.
+    pub fn parse(entries: &str, current: Option<i32>) -> Result<Self> {
+        let keys = if entries.contains(':') {
+            parse_master_keys(entries)?
+        } else {
+            BTreeMap::from([(1, parse_master_key(entries)?)])
+        };
+        let current =
+            current.unwrap_or_else(|| *keys.keys().next_back().expect("at least one master key"));
+        Self::new(current, keys)
+    }
+
+    fn key(&self, version: i32) -> Result<&Key<Aes256Gcm>> {
+        self.keys
+            .get(&version)
+            .ok_or_else(|| anyhow::anyhow!("Master key version {} is not configured", version))
+    }
+
+    /// Encrypt `secret` under a fresh data key and wrap the data key with the
+    /// current master key: `envelope:<wrapped data key>:<ciphertext>`, each
+    /// part being the hex of a nonce followed by AES-GCM output.
+    fn seal(&self, secret: &[u8]) -> Result<SealedSecret> {
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let ciphertext = encrypt(&data_key, secret)?;
+        let wrapped_key = encrypt(self.key(self.current)?, &data_key)?;
+        Ok(SealedSecret {
+            secret: format_envelope(&wrapped_key, &ciphertext),
+            kek_version: Some(self.current),
+        })
+    }
+
+    fn open(&self, sealed: &SealedSecret) -> Result<Vec<u8>> {
+        let (data_key, ciphertext) = self.unwrap_data_key(sealed)?;
+        decrypt(Key::<Aes256Gcm>::from_slice(&data_key), &ciphertext)
+    }
+
+    /// Re-wrap the data key of `sealed` under the current master key, leaving
+    /// the ciphertext as is. Returns `None` if it already uses the current key.
+    fn rewrap(&self, sealed: &SealedSecret) -> Result<Option<SealedSecret>> {
+        if sealed.kek_version == Some(self.current) {
+            return Ok(None);
+        }
+        let (data_key, ciphertext) = self.unwrap_data_key(sealed)?;
+        let wrapped_key = encrypt(self.key(self.current)?, &data_key)?;
+        Ok(Some(SealedSecret {
+            secret: format_envelope(&wrapped_key, &ciphertext),
+            kek_version: Some(self.current),
+        }))
+    }
+
+    fn unwrap_data_key(&self, sealed: &SealedSecret) -> Result<(Vec<u8>, Vec<u8>)> {
+        let version = sealed
+            .kek_version
+            .ok_or_else(|| anyhow::anyhow!("Wallet secret is not encrypted"))?;
+        let (wrapped_key, ciphertext) = sealed
+            .secret
+            .strip_prefix(ENVELOPE_PREFIX)
+            .and_then(|parts| parts.split_once(':'))
+            .ok_or_else(|| anyhow::anyhow!("Wallet secret is not an envelope"))?;
+        let data_key = decrypt(self.key(version)?, &hex::decode(wrapped_key)?)?;
+        Ok((data_key, hex::decode(ciphertext)?))
+    }
+}
+
+fn parse_master_keys(entries: &str) -> Result<BTreeMap<i32, Key<Aes256Gcm>>> {
+    let mut keys = BTreeMap::new();
+    for entry in entries
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (version, key) = entry
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Master key entries must be <version>:<hex key>"))?;
+        let version = version
+            .trim()
+            .parse::<i32>()
+            .map_err(|e| anyhow::anyhow!("Invalid master key version {}: {}", version, e))?;
+        if keys.insert(version, parse_master_key(key)?).is_some() {
+            anyhow::bail!("Master key version {} is configured twice", version);
+        }
+    }
+    if keys.is_empty() {
+        anyhow::bail!("No master keys configured");
+    }
+    Ok(keys)
+}
+
+fn parse_master_key(encoded: &str) -> Result<Key<Aes256Gcm>> {
+    let bytes = hex::decode(encoded.trim())
+        .map_err(|e| anyhow::anyhow!("Master key is not valid hex: {}", e))?;
+    if bytes.len() != 32 {
+        anyhow::bail!("Master key must be 32 bytes, got {}", bytes.len());
+    }
+    Ok(*Key::<Aes256Gcm>::from_slice(&bytes))
+}
+
+fn format_envelope(wrapped_key: &[u8], ciphertext: &[u8]) -> String {
+    format!(
+        "{}{}:{}",
+        ENVELOPE_PREFIX,
+        hex::encode(wrapped_key),
+        hex::encode(ciphertext)
+    )
+}
+
+fn encrypt(key: &Key<Aes256Gcm>, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(key)
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt wallet secret"))?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn decrypt(key: &Key<Aes256Gcm>, data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < NONCE_LEN {
+        anyhow::bail!("Encrypted wallet secret is truncated");
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    Aes256Gcm::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt wallet secret"))
+}
+
+/// Seals each keypair with its own AES-256-GCM data key, wrapped by a
+/// [`MasterKeyRing`] version.
+pub struct EnvelopeKeyStore {
+    db: PgPool,
+    master_keys: MasterKeyRing,
+}
+
+impl EnvelopeKeyStore {
+    pub fn new(db: PgPool, master_keys: MasterKeyRing) -> Self {
+        Self { db, master_keys }
+    }
+
+    /// Re-wrap a secret under the current master key version, `None` if it
+    /// already uses it.
+    pub fn rewrap(&self, sealed: &SealedSecret) -> Result<Option<SealedSecret>> {
+        self.master_keys.rewrap(sealed)
+    }
+}
+
+#[async_trait]
+impl KeyStore for EnvelopeKeyStore {
+    fn seal(&self, keypair: &Keypair) -> Result<SealedSecret> {
+        self.master_keys.seal(&keypair.to_bytes())
+    }
+
+    fn open(&self, sealed: &SealedSecret) -> Result<Keypair> {
+        let bytes = self.master_keys.open(sealed)?;
+        Keypair::try_from(bytes.as_slice()).map_err(|e| anyhow::anyhow!("Invalid keypair: {}", e))
+    }
+
+    async fn signer(&self, wallet_id: i64) -> Result<Arc<dyn Signer + Send + Sync>> {
+        Ok(Arc::new(
+            open_wallet_secret(self, &self.db, wallet_id).await?,
+        ))
+    }
+
+    async fn memo_key(&self, wallet_id: i64, peer: &Pubkey) -> Result<[u8; 32]> {
+        let keypair = open_wallet_secret(self, &self.db, wallet_id).await?;
+        shared_memo_key(&keypair, peer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(current: i32, versions: &[i32]) -> MasterKeyRing {
+        let keys = versions
+            .iter()
+            .map(|version| {
+                (
+                    *version,
+                    *Key::<Aes256Gcm>::from_slice(&[*version as u8; 32]),
+                )
+            })
+            .collect();
+        MasterKeyRing::new(current, keys).unwrap()
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let master_keys = ring(1, &[1]);
+        let keypair = Keypair::new();
+
+        let sealed = master_keys.seal(&keypair.to_bytes()).unwrap();
+        assert_eq!(sealed.kek_version, Some(1));
+        assert!(!sealed.secret.contains(&keypair.to_base58_string()));
+        assert_eq!(master_keys.open(&sealed).unwrap(), keypair.to_bytes());
+
+        // a fresh data key and nonces are used every time
+        assert_ne!(master_keys.seal(&keypair.to_bytes()).unwrap(), sealed);
+
+        // same version number, different key material
+        let other = MasterKeyRing::new(
+            1,
+            BTreeMap::from([(1, *Key::<Aes256Gcm>::from_slice(&[9; 32]))]),
+        )
+        .unwrap();
+        assert!(other.open(&sealed).is_err());
+
+        let mut tampered = sealed.clone();
+        let last = if tampered.secret.ends_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        tampered
+            .secret
+            .replace_range(tampered.secret.len() - 1.., last);
+        assert!(master_keys.open(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_rewrap_under_new_version() {
+        let keypair = Keypair::new();
+        let sealed = ring(1, &[1]).seal(&keypair.to_bytes()).unwrap();
+
+        // during the rollover both versions are configured, 2 is current
+        let rollover = ring(2, &[1, 2]);
+        assert_eq!(rollover.open(&sealed).unwrap(), keypair.to_bytes());
+        let rewrapped = rollover.rewrap(&sealed).unwrap().unwrap();
+        assert_eq!(rewrapped.kek_version, Some(2));
+        assert!(rollover.rewrap(&rewrapped).unwrap().is_none());
+
+        // only the data key is re-wrapped
+        let ciphertext =
+            |sealed: &SealedSecret| sealed.secret.split(':').nth(2).unwrap().to_string();
+        assert_eq!(ciphertext(&rewrapped), ciphertext(&sealed));
+
+        // once version 1 is retired, only rewrapped secrets open
+        let retired = ring(2, &[2]);
+        assert_eq!(retired.open(&rewrapped).unwrap(), keypair.to_bytes());
+        assert!(retired.open(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_parse_master_keys() {
+        let keys = parse_master_keys(&format!(
+            "1:{}, 2:{}\n",
+            hex::encode([1; 32]),
+            hex::encode([2; 32])
+        ))
+        .unwrap();
+        assert_eq!(keys.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+
+        assert!(parse_master_keys("").is_err());
+        assert!(parse_master_keys(&hex::encode([1; 32])).is_err());
+        assert!(parse_master_keys(&format!("1:{}", hex::encode([1; 16]))).is_err());
+        assert!(
+            parse_master_keys(&format!(
+                "1:{},1:{}",
+                hex::encode([1; 32]),
+                hex::encode([2; 32])
+            ))
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Custody of wallet signing keys.
+//!
+//! Nothing outside this module reads `wallets.keypair` directly: handlers and
+//! jobs ask the [`KeyStore`] for a signer by wallet id. The column holds
+//! whatever the configured store sealed, either the base58 keypair
+//! ([`PlaintextKeyStore`], for local development) or an AES-GCM envelope
+//! ([`EnvelopeKeyStore`]) whose master key version is kept in
+//! `wallets.kek_version`.
+
+mod envelope;
+mod plaintext;
+
+pub use envelope::{EnvelopeKeyStore, MasterKeyRing};
+pub use plaintext::PlaintextKeyStore;
+
+use crate::config::Config;
+use crate::db;
+use anyhow::Result;
+use async_trait::async_trait;
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::info;
+
+/// Number of wallets rewritten per batch by the maintenance commands.
+const REWRITE_BATCH_SIZE: i64 = 100;
+
+/// A wallet secret in the form stored on `wallets`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SealedSecret {
+    pub secret: String,
+    /// Version of the master key that wrapped `secret`, `None` if the secret
+    /// is not encrypted.
+    pub kek_version: Option<i32>,
+}
+
+#[async_trait]
+pub trait KeyStore: Send + Sync {
+    /// Seal a keypair for storage.
+    fn seal(&self, keypair: &Keypair) -> Result<SealedSecret>;
+
+    /// Recover a keypair from its stored form.
+    fn open(&self, sealed: &SealedSecret) -> Result<Keypair>;
+
+    /// Signer for the wallet with the given id.
+    async fn signer(&self, wallet_id: i64) -> Result<Arc<dyn Signer + Send + Sync>>;
+
+    /// Key the wallet with the given id shares with `peer` for encrypting
+    /// memos, see [`crate::solana::memo`].
+    async fn memo_key(&self, wallet_id: i64, peer: &Pubkey) -> Result<[u8; 32]>;
+}
+
+/// Which [`KeyStore`] seals wallet secrets, `KEY_STORE` in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStoreKind {
+    Plaintext,
+    Envelope,
+}
+
+impl KeyStoreKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyStoreKind::Plaintext => "plaintext",
+            KeyStoreKind::Envelope => "envelope",
+        }
+    }
+}
+
+impl FromStr for KeyStoreKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "plaintext" => Ok(KeyStoreKind::Plaintext),
+            "envelope" => Ok(KeyStoreKind::Envelope),
+            _ => anyhow::bail!("expected plaintext or envelope"),
+        }
+    }
+}
+
+/// Build the key store selected by the configuration.
+pub fn from_config(db: PgPool, config: &Config) -> Result<Arc<dyn KeyStore>> {
+    let store: Arc<dyn KeyStore> = match config.key_store {
+        KeyStoreKind::Plaintext => Arc::new(PlaintextKeyStore::new(db)),
+        KeyStoreKind::Envelope => Arc::new(EnvelopeKeyStore::new(db, master_keys(config)?)),
+    };
+    info!("using {} key store", config.key_store.as_str());
+    Ok(store)
+}
+
+/// The configured master keys, for the envelope key store and the commands
+/// that re-encrypt wallets.
+pub fn master_keys(config: &Config) -> Result<MasterKeyRing> {
+    config
+        .wallet_master_keys
+        .as_ref()
+        .map(|keys| keys.expose().clone())
+        .ok_or_else(|| anyhow::anyhow!("WALLET_MASTER_KEYS or WALLET_MASTER_KEY must be set"))
+}
+
+/// Load and open the secret of a wallet, checking it belongs to the wallet's
+/// pubkey so a row can't be made to sign with another wallet's key.
+async fn open_wallet_secret(store: &dyn KeyStore, db: &PgPool, wallet_id: i64) -> Result<Keypair> {
+    let (pubkey, sealed) = db::get_wallet_secret(db, wallet_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Wallet {} not found", wallet_id))?;
+    let keypair = store.open(&sealed)?;
+    if keypair.pubkey() != pubkey {
+        anyhow::bail!("Secret of wallet {} does not match its pubkey", wallet_id);
+    }
+    Ok(keypair)
+}
+
+/// Seal every plaintext wallet secret with `envelope`. Rows that are already
+/// encrypted are left alone, so the command can be re-run after an
+/// interruption. Returns the number of wallets re-encrypted.
+pub async fn reencrypt_wallets(db: &PgPool, envelope: &EnvelopeKeyStore) -> Result<u64> {
+    let plaintext = PlaintextKeyStore::new(db.clone());
+    rewrite_wallet_secrets(db, |wallet_id, stored| {
+        if stored.kek_version.is_some() {
+            return Ok(None);
+        }
+        let keypair = plaintext
+            .open(stored)
+            .map_err(|e| anyhow::anyhow!("Failed to read wallet {}: {}", wallet_id, e))?;
+        envelope.seal(&keypair).map(Some)
+    })
+    .await
+}
+
+/// Re-wrap every encrypted wallet secret under the current master key
+/// version. Only the per-wallet data keys are re-encrypted; servers keep
+/// working throughout as long as they are configured with both the old and
+/// the new version. Plaintext secrets are skipped, see [`reencrypt_wallets`].
+/// Returns the number of wallets re-wrapped.
+pub async fn rotate_master_key(db: &PgPool, envelope: &EnvelopeKeyStore) -> Result<u64> {
+    rewrite_wallet_secrets(db, |wallet_id, stored| {
+        if stored.kek_version.is_none() {
+            return Ok(None);
+        }
+        envelope
+            .rewrap(stored)
+            .map_err(|e| anyhow::anyhow!("Failed to re-wrap wallet {}: {}", wallet_id, e))
+    })
+    .await
+}
+
+/// Walk all wallets in batches and replace the secrets `rewrite` returns a new
+/// form for. A row changed concurrently is skipped rather than overwritten.
+async fn rewrite_wallet_secrets(
+    db: &PgPool,
+    rewrite: impl Fn(i64, &SealedSecret) -> Result<Option<SealedSecret>>,
+) -> Result<u64> {
+    let mut after_id = 0;
+    let mut rewritten = 0;
+    loop {
+        let batch = db::list_wallet_secrets(db, after_id, REWRITE_BATCH_SIZE).await?;
+        let Some((last_id, _)) = batch.last() else {
+            break;
+        };
+        after_id = *last_id;
+
+        for (wallet_id, stored) in batch {
+            let Some(replacement) = rewrite(wallet_id, &stored)? else {
+                continue;
+            };
+            if db::replace_wallet_secret(db, wallet_id, &stored.secret, &replacement).await? {
+                rewritten += 1;
+            }
+        }
+        info!(after_id, rewritten, "rewrote wallet secret batch");
+    }
+
+    Ok(rewritten)
+}
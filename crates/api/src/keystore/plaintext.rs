@@ -0,0 +1,51 @@
+use super::{KeyStore, SealedSecret, open_wallet_secret};
+use crate::solana::memo::shared_memo_key;
+use anyhow::Result;
+use async_trait::async_trait;
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Stores keypairs as base58 strings. Only meant for local development.
+pub struct PlaintextKeyStore {
+    db: PgPool,
+}
+
+impl PlaintextKeyStore {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl KeyStore for PlaintextKeyStore {
+    fn seal(&self, keypair: &Keypair) -> Result<SealedSecret> {
+        Ok(SealedSecret {
+            secret: keypair.to_base58_string(),
+            kek_version: None,
+        })
+    }
+
+    fn open(&self, sealed: &SealedSecret) -> Result<Keypair> {
+        if sealed.kek_version.is_some() {
+            anyhow::bail!("Wallet secret is encrypted, configure the envelope key store");
+        }
+        let bytes = bs58::decode(&sealed.secret)
+            .into_vec()
+            .map_err(|e| anyhow::anyhow!("Invalid base58 keypair: {}", e))?;
+        Keypair::try_from(bytes.as_slice()).map_err(|e| anyhow::anyhow!("Invalid keypair: {}", e))
+    }
+
+    async fn signer(&self, wallet_id: i64) -> Result<Arc<dyn Signer + Send + Sync>> {
+        Ok(Arc::new(
+            open_wallet_secret(self, &self.db, wallet_id).await?,
+        ))
+    }
+
+    async fn memo_key(&self, wallet_id: i64, peer: &Pubkey) -> Result<[u8; 32]> {
+        let keypair = open_wallet_secret(self, &self.db, wallet_id).await?;
+        shared_memo_key(&keypair, peer)
+    }
+}
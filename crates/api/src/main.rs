@@ -1,31 +1,49 @@
 mod auth;
+mod cluster;
+mod config;
 mod db;
+mod fees;
 mod handlers;
+mod idempotency;
+mod jobs;
+mod keystore;
 mod models;
+mod notifications;
 mod partial_sign;
 mod routes;
+mod schedule;
 mod solana;
+mod telegram;
 
 use crate::solana::airdrop::request_airdrop_and_confirm;
 use anyhow::Result;
-use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::CommitmentConfig};
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_keypair::Keypair;
 use solana_signer::Signer;
 use spl_token_2022::solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair};
 use sqlx::postgres::PgPoolOptions;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+const RPC_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub struct AppState {
     pub db: sqlx::PgPool,
-    pub dev_mode: bool,
+    pub config: config::Config,
     pub rpc_client: Arc<RpcClient>,
     pub elgamal_keypair: Arc<ElGamalKeypair>,
     pub supply_aes_key: Arc<AeKey>,
     pub global_authority: Arc<Keypair>,
-    pub telegram_bot_token: String,
-    pub jwt_secret: String,
+    /// Pays the fees and proof account rent of every transaction sent for a
+    /// custodial wallet, see [`fees`].
+    pub fee_payer: Arc<Keypair>,
+    pub key_store: Arc<dyn keystore::KeyStore>,
+    /// How multi-transaction sequences (transfer, withdraw, confidential
+    /// mint) are sent to the cluster.
+    pub submitter: Arc<dyn solana::submit::Submitter>,
+    pub telegram: telegram::api::BotApi,
 }
 
 // TODO: EOD
@@ -61,60 +79,85 @@ async fn main() -> Result<()> {
 
     dotenvy::dotenv().ok();
 
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let config = config::Config::load()?;
+    info!("loaded configuration: {:?}", config);
 
     let pool = PgPoolOptions::new()
         .max_connections(5)
-        .connect(&database_url)
+        .connect(config.database_url.expose())
         .await?;
 
-    let rpc_url = std::env::var("RPC_URL").expect("RPC_URL must be set");
-    info!("RPC client created for URL: {:?}", &rpc_url);
-    let rpc_client = Arc::new(RpcClient::new_with_commitment(
-        rpc_url,
-        CommitmentConfig::confirmed(),
+    if let Some(command) = std::env::args().nth(1) {
+        return run_command(&command, &config, &pool).await;
+    }
+
+    let key_store = keystore::from_config(pool.clone(), &config)?;
+
+    let cluster = &config.cluster;
+    info!("running against {} cluster", cluster.kind.as_str());
+
+    info!("RPC client created for URL: {:?}", &config.rpc_url);
+    let rpc_client = Arc::new(RpcClient::new_with_timeouts_and_commitment(
+        config.rpc_url.clone(),
+        RPC_REQUEST_TIMEOUT,
+        cluster.commitment,
+        cluster.confirmation_timeout,
     ));
+    let submitter = solana::submit::from_config(&config.submitter, rpc_client.clone())?;
 
-    let auditor_kp = std::env::var("AUDITOR_KP").expect("AUDITOR_KP must be set");
-    let auditor_kp = solana::utils::kp_from_base58_string(&auditor_kp);
-    let elgamal_keypair = solana::utils::el_gamal_deterministic(&auditor_kp)
+    let auditor_kp = config.auditor_keypair.expose();
+    let elgamal_keypair = solana::utils::el_gamal_deterministic(auditor_kp)
         .map_err(|e| anyhow::anyhow!("Failed to create ElGamal keypair: {}", e))?;
-    let supply_aes_key = solana::utils::ae_key_deterministic(&auditor_kp)
+    let supply_aes_key = solana::utils::ae_key_deterministic(auditor_kp)
         .map_err(|e| anyhow::anyhow!("Failed to derive supply AE key: {}", e))?;
 
-    let authority_kp = std::env::var("AUTHORITY_KP").expect("AUTHORITY_KP must be set");
-    let global_authority = solana::utils::kp_from_base58_string(&authority_kp);
-
-    request_airdrop_and_confirm(
-        rpc_client.clone(),
-        &global_authority.pubkey(),
-        10 * 10_u64.pow(9),
-    )
-    .await?;
-
-    let telegram_bot_token =
-        std::env::var("TELEGRAM_BOT_TOKEN").expect("TELEGRAM_BOT_TOKEN must be set");
-    let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+    let global_authority = config.authority_keypair.expose().insecure_clone();
 
+    if cluster.airdrops {
+        request_airdrop_and_confirm(
+            rpc_client.clone(),
+            &global_authority.pubkey(),
+            10 * 10_u64.pow(9),
+        )
+        .await?;
+    }
+
+    let fee_payer = match &config.fee_payer_keypair {
+        Some(fee_payer) => fee_payer.expose().insecure_clone(),
+        None => global_authority.insecure_clone(),
+    };
+    info!("sponsoring fees from {}", fee_payer.pubkey());
+    if cluster.airdrops && fee_payer.pubkey() != global_authority.pubkey() {
+        request_airdrop_and_confirm(rpc_client.clone(), &fee_payer.pubkey(), 10 * 10_u64.pow(9))
+            .await?;
+    }
+
+    let telegram =
+        telegram::api::BotApi::new(&config.telegram_api_url, config.telegram_bot_token.expose());
+    let port = config.port;
     let state = Arc::new(AppState {
-        dev_mode: std::env::var("DEV_MODE")
-            .map(|v| v == "true")
-            .unwrap_or(false),
         db: pool,
+        config,
         rpc_client: rpc_client.clone(),
         elgamal_keypair: Arc::new(elgamal_keypair),
         supply_aes_key: Arc::new(supply_aes_key),
         global_authority: Arc::new(global_authority),
-        telegram_bot_token,
-        jwt_secret,
+        fee_payer: Arc::new(fee_payer),
+        key_store,
+        submitter,
+        telegram,
     });
 
+    jobs::transfer::spawn_recovery(state.clone());
+    jobs::reservations::spawn_sweeper(state.clone());
+    jobs::scheduled::spawn_worker(state.clone());
+    jobs::janitor::spawn_janitor(state.clone());
+    jobs::links::spawn_sweeper(state.clone());
+    jobs::notifications::spawn_worker(state.clone());
+    jobs::pots::spawn_sweeper(state.clone());
+
     let app = routes::create_router(state);
 
-    let port: u16 = std::env::var("PORT")
-        .ok()
-        .and_then(|value| value.parse().ok())
-        .unwrap_or(6767);
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}")).await?;
     tracing::info!("Server listening on {}", listener.local_addr()?);
 
@@ -122,3 +165,24 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// One-off maintenance commands, run as `solana-api <command>`.
+async fn run_command(command: &str, config: &config::Config, pool: &sqlx::PgPool) -> Result<()> {
+    match command {
+        "reencrypt-wallets" => {
+            let envelope =
+                keystore::EnvelopeKeyStore::new(pool.clone(), keystore::master_keys(config)?);
+            let count = keystore::reencrypt_wallets(pool, &envelope).await?;
+            info!("re-encrypted {} wallets", count);
+            Ok(())
+        }
+        "rotate-master-key" => {
+            let envelope =
+                keystore::EnvelopeKeyStore::new(pool.clone(), keystore::master_keys(config)?);
+            let count = keystore::rotate_master_key(pool, &envelope).await?;
+            info!("re-wrapped {} wallets under the current master key", count);
+            Ok(())
+        }
+        _ => anyhow::bail!("Unknown command: {}", command),
+    }
+}
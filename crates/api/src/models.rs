@@ -1,10 +1,15 @@
-use std::sync::Arc;
+use std::str::FromStr;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use solana_keypair::Keypair;
+use solana_hash::Hash;
+use solana_keypair::Signature;
 use solana_pubkey::Pubkey;
 use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::schedule::Schedule;
+use crate::solana::transfer::ProofAccounts;
 
 #[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -21,7 +26,725 @@ pub struct Wallet {
     pub id: i64,
     pub user_id: i64,
     pub pubkey: Pubkey,
-    pub keypair: Arc<Keypair>,
+    pub custody: WalletCustody,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Who holds a wallet's keypair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletCustody {
+    /// The keypair is sealed in the key store and the API signs for the wallet.
+    Custodial,
+    /// The keypair stays with the user, who signs transactions built by the
+    /// non-custodial endpoints.
+    External,
+}
+
+impl WalletCustody {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Custodial => "custodial",
+            Self::External => "external",
+        }
+    }
+}
+
+impl FromStr for WalletCustody {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "custodial" => Self::Custodial,
+            "external" => Self::External,
+            _ => anyhow::bail!("Unknown wallet custody: {}", s),
+        })
+    }
+}
+
+/// Step a transfer job has reached. Jobs move forward through the transfer
+/// sequence one transaction at a time, or into `RollingBack` when a step is
+/// rejected and the proof accounts need to be reclaimed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferJobStatus {
+    Created,
+    ProofsAllocated,
+    RangeProofVerified,
+    ProofsVerified,
+    Transferred,
+    Completed,
+    RollingBack,
+    RolledBack,
+    Failed,
+}
+
+impl TransferJobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::ProofsAllocated => "proofs_allocated",
+            Self::RangeProofVerified => "range_proof_verified",
+            Self::ProofsVerified => "proofs_verified",
+            Self::Transferred => "transferred",
+            Self::Completed => "completed",
+            Self::RollingBack => "rolling_back",
+            Self::RolledBack => "rolled_back",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Completed | Self::RolledBack | Self::Failed)
+    }
+}
+
+impl FromStr for TransferJobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "created" => Self::Created,
+            "proofs_allocated" => Self::ProofsAllocated,
+            "range_proof_verified" => Self::RangeProofVerified,
+            "proofs_verified" => Self::ProofsVerified,
+            "transferred" => Self::Transferred,
+            "completed" => Self::Completed,
+            "rolling_back" => Self::RollingBack,
+            "rolled_back" => Self::RolledBack,
+            "failed" => Self::Failed,
+            _ => anyhow::bail!("Unknown transfer job status: {}", s),
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct TransferJob {
+    pub id: i64,
+    pub sender_wallet_id: i64,
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    /// Memo sent with the transfer instruction, encrypted if the sender asked for it.
+    pub memo: Option<String>,
+    pub status: TransferJobStatus,
+    pub proof_accounts: Option<ProofAccounts>,
+    pub proofs: Option<Vec<u8>>,
+    pub allocate_signature: Option<Signature>,
+    pub range_proof_signature: Option<Signature>,
+    pub remaining_proofs_signature: Option<Signature>,
+    pub transfer_signature: Option<Signature>,
+    pub close_signature: Option<Signature>,
+    pub pending_signature: Option<Signature>,
+    pub pending_blockhash: Option<Hash>,
+    /// Signatures of every step when the pending transaction is part of a bundle.
+    pub pending_bundle: Option<Vec<Signature>>,
+    pub error: Option<String>,
+    /// The sender's available balance was topped up before the job was
+    /// recorded (once for a whole batch), so the job doesn't do it itself.
+    pub balance_funded: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TransferJob {
+    /// Signatures of the steps that have landed, in transfer order.
+    pub fn signatures(&self) -> Vec<Signature> {
+        [
+            self.allocate_signature,
+            self.range_proof_signature,
+            self.remaining_proofs_signature,
+            self.transfer_signature,
+            self.close_signature,
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// Status of a transfer in the ledger, as shown to users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferStatus {
+    Pending,
+    Completed,
+    Failed,
+}
+
+impl TransferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+
+    /// Ledger status for a job. A job that fails after its transfer landed
+    /// (e.g. while closing proof accounts) still moved the funds.
+    pub fn from_job(job: &TransferJob) -> Self {
+        if job.transfer_signature.is_some() {
+            return Self::Completed;
+        }
+        match job.status {
+            TransferJobStatus::RolledBack | TransferJobStatus::Failed => Self::Failed,
+            _ => Self::Pending,
+        }
+    }
+}
+
+impl FromStr for TransferStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "pending" => Self::Pending,
+            "completed" => Self::Completed,
+            "failed" => Self::Failed,
+            _ => anyhow::bail!("Unknown transfer status: {}", s),
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub id: i64,
+    pub transfer_job_id: Option<i64>,
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    pub memo: Option<String>,
+    /// Whether the on-chain copy of `memo` is encrypted.
+    pub memo_encrypted: bool,
+    pub status: TransferStatus,
+    pub signatures: Vec<Signature>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+/// Status of a payment request. Requests start `Open` and end in exactly one
+/// of the other states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentRequestStatus {
+    Open,
+    Paid,
+    Expired,
+    Cancelled,
+}
+
+impl PaymentRequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Paid => "paid",
+            Self::Expired => "expired",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl FromStr for PaymentRequestStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "open" => Self::Open,
+            "paid" => Self::Paid,
+            "expired" => Self::Expired,
+            "cancelled" => Self::Cancelled,
+            _ => anyhow::bail!("Unknown payment request status: {}", s),
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct PaymentRequest {
+    pub id: i64,
+    pub wallet_id: i64,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub memo: Option<String>,
+    pub status: PaymentRequestStatus,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub payer: Option<Pubkey>,
+    pub transfer_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An entry of a batch payout, see [`TransferBatch`].
+#[derive(Debug, Clone)]
+pub struct TransferBatchEntry {
+    pub position: usize,
+    pub recipient: Option<Pubkey>,
+    pub telegram_username: Option<String>,
+    pub amount: u64,
+    pub transfer_id: Option<i64>,
+    pub job_id: Option<i64>,
+    /// Status of the entry's transfer, None if it couldn't be made.
+    pub status: Option<TransferStatus>,
+    pub signatures: Vec<Signature>,
+    /// Why the transfer couldn't be made.
+    pub error: Option<String>,
+}
+
+/// A batch payout whose transfers run in the background.
+#[derive(Debug, Clone)]
+pub struct TransferBatch {
+    pub id: i64,
+    pub mint: Pubkey,
+    pub total_amount: u64,
+    pub entries: Vec<TransferBatchEntry>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Status of a claim link. `Funding` and `Claiming` follow the transfer in
+/// flight: funding ends in `Open` or `Failed`, a claim in `Claimed` or back in
+/// `Open` so it can be retried. An `Open` link past its expiry is `Expired`
+/// until the refund to its sender lands and it is `Refunded`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimLinkStatus {
+    Funding,
+    Open,
+    Claiming,
+    Claimed,
+    Failed,
+    Expired,
+    Refunded,
+}
+
+impl ClaimLinkStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Funding => "funding",
+            Self::Open => "open",
+            Self::Claiming => "claiming",
+            Self::Claimed => "claimed",
+            Self::Failed => "failed",
+            Self::Expired => "expired",
+            Self::Refunded => "refunded",
+        }
+    }
+}
+
+impl FromStr for ClaimLinkStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "funding" => Self::Funding,
+            "open" => Self::Open,
+            "claiming" => Self::Claiming,
+            "claimed" => Self::Claimed,
+            "failed" => Self::Failed,
+            "expired" => Self::Expired,
+            "refunded" => Self::Refunded,
+            _ => anyhow::bail!("Unknown claim link status: {}", s),
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ClaimLink {
+    pub id: i64,
+    pub sender_wallet_id: i64,
+    pub escrow_wallet_id: i64,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub memo: Option<String>,
+    pub status: ClaimLinkStatus,
+    pub funding_transfer_id: Option<i64>,
+    pub claim_transfer_id: Option<i64>,
+    pub claimed_by_wallet_id: Option<i64>,
+    pub refund_transfer_id: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An expired claim link whose funds still have to go back to its sender.
+#[derive(Debug, Clone)]
+pub struct ClaimLinkRefund {
+    pub link_id: i64,
+    pub escrow_wallet_id: i64,
+    pub sender: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+/// A completed transfer into an expired reserved wallet that still has to be
+/// sent back to its sender.
+#[derive(Debug, Clone)]
+pub struct ReservedWalletRefund {
+    pub credit_id: i64,
+    pub wallet_id: i64,
+    pub sender: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    /// Refunds of the credit started so far.
+    pub refund_attempts: i32,
+    /// Job of a refund that was started but stalled before it finished, to be
+    /// driven again instead of starting another one.
+    pub stalled_job_id: Option<i64>,
+}
+
+/// A reserved wallet credit the sweeper gave up refunding, left for an admin
+/// to look into.
+#[derive(Debug, Clone)]
+pub struct AbandonedReservedWalletRefund {
+    pub credit_id: i64,
+    pub wallet_id: i64,
+    pub sender: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    pub attempts: i32,
+    /// Error of the last refund job.
+    pub error: Option<String>,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Proof accounts and proof data of a custodial withdraw that has not landed
+/// yet, see [`crate::solana::withdraw::WithdrawProofs`].
+#[derive(Debug, Clone)]
+pub struct PendingWithdrawProofs {
+    pub id: i64,
+    pub wallet_id: i64,
+    pub equality_proof_account: Pubkey,
+    pub range_proof_account: Pubkey,
+    pub proofs: Vec<u8>,
+}
+
+/// Status of a scheduled transfer. Only `Active` schedules run, `Cancelled`
+/// is final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduledTransferStatus {
+    Active,
+    Paused,
+    Cancelled,
+}
+
+impl ScheduledTransferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Paused => "paused",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl FromStr for ScheduledTransferStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "active" => Self::Active,
+            "paused" => Self::Paused,
+            "cancelled" => Self::Cancelled,
+            _ => anyhow::bail!("Unknown scheduled transfer status: {}", s),
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ScheduledTransfer {
+    pub id: i64,
+    pub wallet_id: i64,
+    pub sender: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+    pub memo: Option<String>,
+    pub encrypt_memo: bool,
+    pub interval_secs: Option<i64>,
+    pub cron: Option<String>,
+    pub status: ScheduledTransferStatus,
+    pub next_run_at: DateTime<Utc>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ScheduledTransfer {
+    pub fn schedule(&self) -> anyhow::Result<Schedule> {
+        match (self.interval_secs, self.cron.as_deref()) {
+            (Some(secs), None) => Schedule::interval(secs),
+            (None, Some(cron)) => Ok(Schedule::Cron(cron.parse()?)),
+            _ => anyhow::bail!("Scheduled transfer {} has no valid schedule", self.id),
+        }
+    }
+}
+
+/// Outcome of one run of a scheduled transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduledTransferRunStatus {
+    /// The run's transfer is still being sent.
+    Pending,
+    Completed,
+    Failed,
+    /// Not attempted, e.g. because the confidential balance was too low.
+    Skipped,
+}
+
+impl ScheduledTransferRunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+impl FromStr for ScheduledTransferRunStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "pending" => Self::Pending,
+            "completed" => Self::Completed,
+            "failed" => Self::Failed,
+            "skipped" => Self::Skipped,
+            _ => anyhow::bail!("Unknown scheduled transfer run status: {}", s),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduledTransferRun {
+    pub id: i64,
+    pub scheduled_for: DateTime<Utc>,
+    pub status: ScheduledTransferRunStatus,
+    pub transfer_id: Option<i64>,
+    pub job_id: Option<i64>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One run of the proof context account janitor, see [`crate::jobs::janitor`].
+#[derive(Debug, Clone)]
+pub struct ProofJanitorRun {
+    pub id: i64,
+    pub accounts_found: i32,
+    pub accounts_closed: i32,
+    /// Withdraw proof accounts left allocated but never verified, which the
+    /// run verified so a later run can close them.
+    pub accounts_unverified: i32,
+    pub lamports_reclaimed: u64,
+    /// Errors hit while closing accounts, the run still closes what it can.
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    /// Someone paid the user.
+    IncomingPayment,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::IncomingPayment => "incoming_payment",
+        }
+    }
+}
+
+impl FromStr for NotificationKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "incoming_payment" => Self::IncomingPayment,
+            _ => anyhow::bail!("Unknown notification kind: {}", s),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationStatus {
+    Sent,
+    /// Gave up, after too many attempts or an error retrying can't fix.
+    Failed,
+    /// Not sent because the user turned this kind of notification off.
+    Skipped,
+}
+
+impl NotificationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Failed => "failed",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+/// A notification claimed for delivery by the worker, see
+/// [`crate::jobs::notifications`].
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub id: i64,
+    pub kind: NotificationKind,
+    pub telegram_user_id: i64,
+    pub text: String,
+    /// Delivery attempts so far, this one included.
+    pub attempts: i32,
+    /// Whether the user still wants this kind of notification.
+    pub wanted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotificationPreferences {
+    pub incoming_payments: bool,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            incoming_payments: true,
+        }
+    }
+}
+
+/// Status of an inline payment. `Claiming` follows the transfer in flight and
+/// ends in `Claimed`, or back in `Open` so it can be claimed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlinePaymentStatus {
+    Open,
+    Claiming,
+    Claimed,
+}
+
+impl FromStr for InlinePaymentStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "open" => Self::Open,
+            "claiming" => Self::Claiming,
+            "claimed" => Self::Claimed,
+            _ => anyhow::bail!("Unknown inline payment status: {}", s),
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct InlinePayment {
+    pub id: i64,
+    pub token: String,
+    pub sender_wallet_id: i64,
+    pub sender_telegram_user_id: i64,
+    pub sender_name: String,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub inline_message_id: Option<String>,
+    pub status: InlinePaymentStatus,
+    pub claimed_by_telegram_user_id: Option<i64>,
+    pub transfer_id: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Status of a group pot. `Closing` follows the withdraw transfer in flight
+/// and ends in `Closed`, or back in `Open` so closing can be retried. The
+/// contributions of an `Expired` pot are refunded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PotStatus {
+    Open,
+    Closing,
+    Closed,
+    Expired,
+}
+
+impl PotStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Closing => "closing",
+            Self::Closed => "closed",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+impl FromStr for PotStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "open" => Self::Open,
+            "closing" => Self::Closing,
+            "closed" => Self::Closed,
+            "expired" => Self::Expired,
+            _ => anyhow::bail!("Unknown pot status: {}", s),
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Pot {
+    pub id: i64,
+    pub chat_id: i64,
+    pub creator_wallet_id: i64,
+    /// Telegram user who created the pot.
+    pub creator_telegram_user_id: Option<i64>,
+    pub escrow_wallet_id: i64,
+    pub title: String,
+    pub mint: Pubkey,
+    pub target_amount: u64,
+    /// Sum of the completed contributions.
+    pub collected_amount: u64,
+    pub contributor_count: i64,
+    pub status: PotStatus,
+    pub withdraw_transfer_id: Option<i64>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What one wallet has contributed to a pot, counting completed transfers.
+#[derive(Debug, Clone)]
+pub struct PotContributor {
+    pub wallet: Pubkey,
+    pub telegram_username: Option<String>,
+    pub amount: u64,
+}
+
+/// A completed contribution to an expired pot that still has to be sent back
+/// to its contributor.
+#[derive(Debug, Clone)]
+pub struct PotRefund {
+    pub contribution_id: i64,
+    pub escrow_wallet_id: i64,
+    pub contributor: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+/// An active login session, see [`crate::auth`].
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: Uuid,
+    pub telegram_user_id: i64,
+    pub username: Option<String>,
+    /// When the refresh token stops working.
+    pub expires_at: DateTime<Utc>,
+}
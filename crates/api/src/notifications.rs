@@ -0,0 +1,109 @@
+//! Telegram notifications to users.
+//!
+//! Notifications are written to the `notifications` outbox and sent with the
+//! bot by [`crate::jobs::notifications`], which retries failed deliveries with
+//! backoff. A notification to a reserved wallet's user waits in the outbox
+//! until they sign up and claim the wallet (see
+//! [`crate::db::upsert_telegram_user`]), which gives the worker a
+//! `telegram_user_id` to send it to. Users can turn notifications off in
+//! `notification_preferences`; notifications they don't want are skipped at
+//! delivery time.
+
+use crate::models::{NotificationKind, Wallet};
+use crate::solana::pay::format_ui_amount;
+use crate::{AppState, db};
+use solana_pubkey::Pubkey;
+use tracing::error;
+
+/// Tell the recipient of a completed transfer about it. The transfer already
+/// happened, so a failure to queue the notification is only logged.
+pub async fn notify_incoming_payment(
+    state: &AppState,
+    sender_wallet: &Wallet,
+    recipient_wallet: &Wallet,
+    transfer_id: i64,
+    amount: u64,
+    mint: &Pubkey,
+    decimals: u8,
+) {
+    let result = async {
+        let sender = db::get_telegram_username_for_wallet(&state.db, sender_wallet.id).await?;
+        let text = incoming_payment_text(
+            &format_ui_amount(amount, decimals),
+            &token_label(state, mint),
+            sender.as_deref(),
+            app_link(state, transfer_id).as_deref(),
+        );
+        db::enqueue_notification(
+            &state.db,
+            recipient_wallet.id,
+            NotificationKind::IncomingPayment,
+            &text,
+            Some(transfer_id),
+        )
+        .await
+    }
+    .await;
+
+    if let Err(e) = result {
+        error!(
+            transfer_id,
+            "Failed to queue incoming payment notification: {:?}", e
+        );
+    }
+}
+
+/// The bot's symbol for its mint, the address for any other.
+pub fn token_label(state: &AppState, mint: &Pubkey) -> String {
+    match &state.config.bot {
+        Some(bot) if bot.mint == *mint => bot.mint_symbol.clone(),
+        _ => mint.to_string(),
+    }
+}
+
+/// Link opening the mini app on a transfer.
+fn app_link(state: &AppState, transfer_id: i64) -> Option<String> {
+    let mut url = url::Url::parse(state.config.telegram_app_url.as_deref()?).ok()?;
+    url.query_pairs_mut()
+        .append_pair("startapp", &format!("transfer-{}", transfer_id));
+    Some(url.to_string())
+}
+
+fn incoming_payment_text(
+    amount: &str,
+    token: &str,
+    sender: Option<&str>,
+    link: Option<&str>,
+) -> String {
+    let mut text = match sender {
+        Some(sender) => format!("You received {} {} from @{}.", amount, token, sender),
+        None => format!("You received {} {}.", amount, token),
+    };
+    if let Some(link) = link {
+        text.push_str("\nOpen TeeGee Pay: ");
+        text.push_str(link);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoming_payment_text() {
+        assert_eq!(
+            incoming_payment_text(
+                "5",
+                "tgUSD",
+                Some("alice"),
+                Some("https://t.me/teegeepay_bot/app?startapp=transfer-7")
+            ),
+            "You received 5 tgUSD from @alice.\nOpen TeeGee Pay: https://t.me/teegeepay_bot/app?startapp=transfer-7"
+        );
+        assert_eq!(
+            incoming_payment_text("2.5", "tgUSD", None, None),
+            "You received 2.5 tgUSD."
+        );
+    }
+}
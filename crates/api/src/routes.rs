@@ -2,20 +2,48 @@ use crate::AppState;
 use crate::handlers;
 use axum::routing::post;
 use axum::{Router, routing::get};
+use handlers::admin::routes as admin_routes;
+use handlers::audit::routes as audit_routes;
+use handlers::noncustodial::routes as noncustodial_routes;
+use handlers::notifications::routes as notification_routes;
+use handlers::pots::routes as pot_routes;
+use handlers::requests::routes as request_routes;
+use handlers::schedules::routes as schedule_routes;
+use handlers::sessions::routes as session_routes;
 use handlers::telegram::routes as telegram_routes;
 use handlers::tokens::routes as token_routes;
 use handlers::transfers::routes as transfer_routes;
+use handlers::tx::routes as tx_routes;
 use handlers::wallets::routes as wallet_routes;
 use std::sync::Arc;
 
 // NOTE: none of these accounts have proper authentication or anything. For demo purposes only.
 pub fn create_router(state: Arc<AppState>) -> Router<()> {
-    Router::new()
+    let mut router = Router::new()
         .route("/api/health", get(handlers::health::handler))
+        .route("/api/cluster", get(handlers::cluster::handler))
         .nest("/api/auth/telegram", telegram_routes(state.clone()))
+        .nest("/api/auth", session_routes(state.clone()))
         .nest("/api/wallets", wallet_routes(state.clone()))
         .nest("/api/transfers", transfer_routes(state.clone()))
         .nest("/api/tokens", token_routes(state.clone()))
-        .route("/api/convert", post(crate::handlers::convert::handler))
-        .with_state(state.clone())
+        .nest("/api/audit", audit_routes(state.clone()))
+        .nest("/api/admin", admin_routes(state.clone()))
+        .nest("/api/noncustodial", noncustodial_routes(state.clone()))
+        .nest("/api/tx", tx_routes(state.clone()))
+        .nest("/api/requests", request_routes(state.clone()))
+        .nest("/api/schedules", schedule_routes(state.clone()))
+        .nest("/api/notifications", notification_routes(state.clone()))
+        .nest("/api/pots", pot_routes(state.clone()));
+    // logs keypairs, never served on mainnet (see `Cluster::validate`)
+    if state.config.cluster.convert_route {
+        router = router.route("/api/convert", post(crate::handlers::convert::handler));
+    }
+    if state.config.bot.is_some() {
+        router = router.route(
+            "/api/telegram/webhook",
+            post(crate::handlers::telegram::webhook::handler),
+        );
+    }
+    router.with_state(state.clone())
 }
@@ -0,0 +1,292 @@
+//! Schedules of recurring transfers.
+//!
+//! A schedule is either a fixed interval or a five field cron expression
+//! (`minute hour day-of-month month day-of-week`, evaluated in UTC). Cron
+//! fields accept `*`, single values, ranges (`1-5`), steps (`*/15`, `0-30/10`)
+//! and comma separated lists of those. As in cron, when both the day of month
+//! and the day of week are restricted a day matching either one is due.
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, DurationRound, TimeZone, Timelike, Utc};
+use std::str::FromStr;
+
+/// Shortest interval accepted, the worker only looks for due transfers about
+/// this often anyway.
+pub const MIN_INTERVAL_SECS: i64 = 60;
+
+/// Longest interval accepted, a year.
+pub const MAX_INTERVAL_SECS: i64 = 365 * 24 * 60 * 60;
+
+/// Give up looking for the next cron occurrence after this many years, for
+/// expressions like `0 0 30 2 *` that never match.
+const MAX_CRON_SEARCH_YEARS: i32 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Interval(Duration),
+    Cron(CronExpr),
+}
+
+impl Schedule {
+    pub fn interval(secs: i64) -> Result<Self> {
+        if !(MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(&secs) {
+            anyhow::bail!(
+                "Interval must be between {} and {} seconds",
+                MIN_INTERVAL_SECS,
+                MAX_INTERVAL_SECS
+            );
+        }
+        let interval =
+            Duration::try_seconds(secs).ok_or_else(|| anyhow::anyhow!("Interval out of range"))?;
+        Ok(Self::Interval(interval))
+    }
+
+    /// First run strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        match self {
+            Self::Interval(interval) => after
+                .checked_add_signed(*interval)
+                .ok_or_else(|| anyhow::anyhow!("Next run is out of range")),
+            Self::Cron(expr) => expr.next_after(after),
+        }
+    }
+
+    /// Run following one that was due at `previous`. Runs missed while the
+    /// worker was down or the schedule paused are not caught up on.
+    pub fn next_run(&self, previous: DateTime<Utc>, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        let next = self.next_after(previous)?;
+        if next > now {
+            return Ok(next);
+        }
+        self.next_after(now)
+    }
+}
+
+/// A parsed cron expression, each field kept as a bitset of allowed values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+impl FromStr for CronExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            anyhow::bail!("Cron expression must have 5 fields, got {}", fields.len());
+        };
+
+        let mut days_of_week = parse_field(day_of_week, 0, 7, "day of week")?;
+        // 7 is another name for sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days_of_month: parse_field(day_of_month, 1, 31, "day of month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            days_of_week,
+            day_of_month_restricted: day_of_month != "*",
+            day_of_week_restricted: day_of_week != "*",
+        })
+    }
+}
+
+impl CronExpr {
+    fn day_matches(&self, t: &DateTime<Utc>) -> bool {
+        let day_of_month = has(self.days_of_month, t.day());
+        let day_of_week = has(self.days_of_week, t.weekday().num_days_from_sunday());
+        match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            (true, false) => day_of_month,
+            (false, true) => day_of_week,
+            (false, false) => true,
+        }
+    }
+
+    fn next_after(&self, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        let mut t = after.duration_trunc(Duration::minutes(1))? + Duration::minutes(1);
+        let give_up_year = after.year() + MAX_CRON_SEARCH_YEARS;
+
+        while t.year() <= give_up_year {
+            if !has(self.months, t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = Utc
+                    .with_ymd_and_hms(year, month, 1, 0, 0, 0)
+                    .single()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid date while evaluating cron"))?;
+            } else if !self.day_matches(&t) {
+                t = t.duration_trunc(Duration::days(1))? + Duration::days(1);
+            } else if !has(self.hours, t.hour()) {
+                t = t.duration_trunc(Duration::hours(1))? + Duration::hours(1);
+            } else if !has(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else {
+                return Ok(t);
+            }
+        }
+
+        anyhow::bail!("Cron expression never matches")
+    }
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid {} step: {}", name, part))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, min, max, name)?,
+                parse_value(end, min, max, name)?,
+            )
+        } else {
+            let value = parse_value(range, min, max, name)?;
+            // `5/15` means every 15 starting at 5
+            (value, if step > 1 { max } else { value })
+        };
+        if start > end {
+            anyhow::bail!("Invalid {} range: {}", name, part);
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+fn parse_value(value: &str, min: u32, max: u32, name: &str) -> Result<u32> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| anyhow::anyhow!("Invalid {}: {} (expected {}-{})", name, value, min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn cron(s: &str) -> Schedule {
+        Schedule::Cron(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let start = at("2026-10-16T10:17:30Z");
+
+        assert_eq!(
+            cron("* * * * *").next_after(start).unwrap(),
+            at("2026-10-16T10:18:00Z")
+        );
+        assert_eq!(
+            cron("*/15 * * * *").next_after(start).unwrap(),
+            at("2026-10-16T10:30:00Z")
+        );
+        assert_eq!(
+            cron("0 9 * * *").next_after(start).unwrap(),
+            at("2026-10-17T09:00:00Z")
+        );
+        // the 16th is a friday, next monday
+        assert_eq!(
+            cron("0 9 * * 1").next_after(start).unwrap(),
+            at("2026-10-19T09:00:00Z")
+        );
+        assert_eq!(
+            cron("30 8 1 * *").next_after(start).unwrap(),
+            at("2026-11-01T08:30:00Z")
+        );
+        assert_eq!(
+            cron("0 0 1 1 *").next_after(start).unwrap(),
+            at("2027-01-01T00:00:00Z")
+        );
+        // sunday as 7, and either day field matching when both are set
+        assert_eq!(
+            cron("0 12 * * 7").next_after(start).unwrap(),
+            at("2026-10-18T12:00:00Z")
+        );
+        assert_eq!(
+            cron("0 12 20 * 0").next_after(start).unwrap(),
+            at("2026-10-18T12:00:00Z")
+        );
+        assert_eq!(
+            cron("0 0 29 2 *").next_after(start).unwrap(),
+            at("2028-02-29T00:00:00Z")
+        );
+        assert!(cron("0 0 30 2 *").next_after(start).is_err());
+    }
+
+    #[test]
+    fn test_cron_parse_errors() {
+        for expr in [
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "10-5 * * * *",
+            "a * * * *",
+        ] {
+            assert!(expr.parse::<CronExpr>().is_err(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_next_run_skips_missed_runs() {
+        let schedule = Schedule::interval(3600).unwrap();
+        let previous = at("2026-10-16T08:00:00Z");
+
+        assert_eq!(
+            schedule
+                .next_run(previous, at("2026-10-16T08:00:10Z"))
+                .unwrap(),
+            at("2026-10-16T09:00:00Z")
+        );
+        assert_eq!(
+            schedule
+                .next_run(previous, at("2026-10-16T12:30:00Z"))
+                .unwrap(),
+            at("2026-10-16T13:30:00Z")
+        );
+        assert!(Schedule::interval(59).is_err());
+        assert!(Schedule::interval(MAX_INTERVAL_SECS).is_ok());
+        assert!(Schedule::interval(MAX_INTERVAL_SECS + 1).is_err());
+        assert!(Schedule::interval(i64::MAX).is_err());
+    }
+}
@@ -0,0 +1,462 @@
+//! Auditor view of a confidential mint.
+//!
+//! Every confidential transfer, mint and burn carries its amount encrypted
+//! under the mint's auditor ElGamal key (split into low and high bits) directly
+//! in the instruction data. Scanning the mint account's signatures and
+//! decrypting those ciphertexts with the auditor secret gives a full record of
+//! confidential activity on the mint.
+//!
+//! Signatures come newest first, so a scan walks back from the newest one to
+//! where the last completed scan started. [`scan_mint`] covers at most
+//! [`SCAN_BATCH_SIZE`] signatures per call and returns an [`AuditScanCursor`]
+//! to carry on from, and decryption (a discrete log per amount) runs on the
+//! blocking pool.
+
+use crate::solana::history::fetch_transaction;
+use crate::solana::utils::decrypt_split_amount;
+use anyhow::Result;
+use futures::{StreamExt, TryStreamExt, stream};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+};
+use solana_message::compiled_instruction::CompiledInstruction;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use spl_token_2022::solana_zk_sdk::encryption::elgamal::ElGamalSecretKey;
+use spl_token_2022_interface::{
+    extension::{
+        confidential_mint_burn::instruction::{
+            BurnInstructionData, ConfidentialMintBurnInstruction, MintInstructionData,
+        },
+        confidential_transfer::instruction::{
+            ConfidentialTransferInstruction, TransferInstructionData,
+        },
+    },
+    instruction::{TokenInstruction, decode_instruction_data, decode_instruction_type},
+};
+use std::{fmt, str::FromStr, sync::Arc};
+
+/// Signatures scanned per call of [`scan_mint`].
+pub const SCAN_BATCH_SIZE: usize = 200;
+
+/// Transactions fetched at the same time while scanning.
+const FETCH_CONCURRENCY: usize = 5;
+
+/// How far the scan of a mint got.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditScanCursor {
+    /// Newest signature of the last completed scan, everything up to it is
+    /// stored.
+    pub scanned_until: Option<Signature>,
+    /// Scan still walking back to `scanned_until`: the newest signature it
+    /// started from and the oldest one it reached.
+    pub in_progress: Option<(Signature, Signature)>,
+}
+
+impl AuditScanCursor {
+    /// Cursor after scanning back from `newest` to `oldest`, `complete` when
+    /// that reached the previous scan.
+    fn advance(
+        &self,
+        newest: Option<Signature>,
+        oldest: Option<Signature>,
+        complete: bool,
+    ) -> Self {
+        match (complete, newest, oldest) {
+            (true, newest, _) => Self {
+                scanned_until: newest.or(self.scanned_until),
+                in_progress: None,
+            },
+            (false, Some(newest), Some(oldest)) => Self {
+                scanned_until: self.scanned_until,
+                in_progress: Some((newest, oldest)),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.in_progress.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditKind {
+    Transfer,
+    Mint,
+    Burn,
+}
+
+impl AuditKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Transfer => "transfer",
+            Self::Mint => "mint",
+            Self::Burn => "burn",
+        }
+    }
+}
+
+impl FromStr for AuditKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "transfer" => Self::Transfer,
+            "mint" => Self::Mint,
+            "burn" => Self::Burn,
+            _ => anyhow::bail!("Unknown audit kind: {}", s),
+        })
+    }
+}
+
+/// A confidential instruction on the mint with its auditor-decrypted amount.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub signature: Signature,
+    pub instruction_index: u8,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub kind: AuditKind,
+    /// Token account debited; `None` for mints.
+    pub source: Option<Pubkey>,
+    /// Token account credited; `None` for burns.
+    pub destination: Option<Pubkey>,
+    pub amount: Option<u64>,
+}
+
+impl AuditRecord {
+    pub fn position(&self) -> AuditPosition {
+        AuditPosition {
+            slot: self.slot,
+            signature: self.signature,
+            instruction_index: self.instruction_index,
+        }
+    }
+}
+
+/// Where a record sits in chain order, to page through stored records.
+/// Written as `slot:signature:instruction_index`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditPosition {
+    pub slot: u64,
+    pub signature: Signature,
+    pub instruction_index: u8,
+}
+
+impl fmt::Display for AuditPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.slot, self.signature, self.instruction_index
+        )
+    }
+}
+
+impl FromStr for AuditPosition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let (Some(slot), Some(signature), Some(instruction_index), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            anyhow::bail!("Invalid audit position: {}", s);
+        };
+        Ok(Self {
+            slot: slot.parse()?,
+            signature: signature.parse()?,
+            instruction_index: instruction_index.parse()?,
+        })
+    }
+}
+
+/// Scan up to [`SCAN_BATCH_SIZE`] signatures of `mint` from `cursor` and
+/// return the audit records found along with the cursor to continue from.
+pub async fn scan_mint(
+    rpc_client: Arc<RpcClient>,
+    mint: &Pubkey,
+    auditor_secret: &ElGamalSecretKey,
+    cursor: &AuditScanCursor,
+) -> Result<(Vec<AuditRecord>, AuditScanCursor)> {
+    let (newest, before) = match cursor.in_progress {
+        Some((newest, oldest)) => (Some(newest), Some(oldest)),
+        None => (None, None),
+    };
+    let statuses = rpc_client
+        .get_signatures_for_address_with_config(
+            mint,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: cursor.scanned_until,
+                limit: Some(SCAN_BATCH_SIZE),
+                commitment: Some(rpc_client.commitment()),
+            },
+        )
+        .await?;
+
+    let complete = statuses.len() < SCAN_BATCH_SIZE;
+    let first = statuses
+        .first()
+        .map(|status| Signature::from_str(&status.signature))
+        .transpose()?;
+    let last = statuses
+        .last()
+        .map(|status| Signature::from_str(&status.signature))
+        .transpose()?;
+    let next_cursor = cursor.advance(newest.or(first), last, complete);
+
+    let transactions: Vec<_> = stream::iter(statuses.into_iter().filter(|s| s.err.is_none()))
+        .map(|status| {
+            let rpc_client = rpc_client.clone();
+            async move {
+                let signature = Signature::from_str(&status.signature)?;
+                let transaction = fetch_transaction(rpc_client, &signature).await?;
+                anyhow::Ok((status, signature, transaction))
+            }
+        })
+        .buffered(FETCH_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let mint = *mint;
+    let auditor_secret = auditor_secret.clone();
+    let records = tokio::task::spawn_blocking(move || {
+        let mut records = vec![];
+        for (status, signature, transaction) in transactions {
+            let Some((account_keys, instructions)) = transaction else {
+                continue;
+            };
+            records.extend(
+                parse_audit_instructions(&mint, &account_keys, &instructions, &auditor_secret)
+                    .into_iter()
+                    .map(|parsed| AuditRecord {
+                        signature,
+                        instruction_index: parsed.instruction_index,
+                        slot: status.slot,
+                        block_time: status.block_time,
+                        kind: parsed.kind,
+                        source: parsed.source,
+                        destination: parsed.destination,
+                        amount: parsed.amount,
+                    }),
+            );
+        }
+        records
+    })
+    .await?;
+
+    Ok((records, next_cursor))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ParsedAuditInstruction {
+    instruction_index: u8,
+    kind: AuditKind,
+    source: Option<Pubkey>,
+    destination: Option<Pubkey>,
+    amount: Option<u64>,
+}
+
+/// Parse the confidential transfers, mints and burns on `mint` in a transaction.
+fn parse_audit_instructions(
+    mint: &Pubkey,
+    account_keys: &[Pubkey],
+    instructions: &[CompiledInstruction],
+    auditor_secret: &ElGamalSecretKey,
+) -> Vec<ParsedAuditInstruction> {
+    let account = |ix: &CompiledInstruction, position: usize| -> Option<Pubkey> {
+        ix.accounts
+            .get(position)
+            .and_then(|index| account_keys.get(*index as usize))
+            .copied()
+    };
+
+    let mut parsed = vec![];
+    for (ix_index, ix) in instructions.iter().enumerate() {
+        if account_keys.get(ix.program_id_index as usize) != Some(&spl_token_2022::id()) {
+            continue;
+        }
+        // the extension instruction starts after the token instruction byte
+        let Some(data) = ix.data.get(1..) else {
+            continue;
+        };
+
+        let entry = match TokenInstruction::unpack(&ix.data) {
+            Ok(TokenInstruction::ConfidentialTransferExtension) => {
+                if !matches!(
+                    decode_instruction_type(data),
+                    Ok(ConfidentialTransferInstruction::Transfer)
+                ) || account(ix, 1) != Some(*mint)
+                {
+                    continue;
+                }
+                let Ok(transfer) = decode_instruction_data::<TransferInstructionData>(data) else {
+                    continue;
+                };
+                ParsedAuditInstruction {
+                    instruction_index: ix_index as u8,
+                    kind: AuditKind::Transfer,
+                    source: account(ix, 0),
+                    destination: account(ix, 2),
+                    amount: decrypt_split_amount(
+                        auditor_secret,
+                        &transfer.transfer_amount_auditor_ciphertext_lo,
+                        &transfer.transfer_amount_auditor_ciphertext_hi,
+                    ),
+                }
+            }
+            Ok(TokenInstruction::ConfidentialMintBurnExtension) => {
+                if account(ix, 1) != Some(*mint) {
+                    continue;
+                }
+                match decode_instruction_type(data) {
+                    Ok(ConfidentialMintBurnInstruction::Mint) => {
+                        let Ok(mint_data) = decode_instruction_data::<MintInstructionData>(data)
+                        else {
+                            continue;
+                        };
+                        ParsedAuditInstruction {
+                            instruction_index: ix_index as u8,
+                            kind: AuditKind::Mint,
+                            source: None,
+                            destination: account(ix, 0),
+                            amount: decrypt_split_amount(
+                                auditor_secret,
+                                &mint_data.mint_amount_auditor_ciphertext_lo,
+                                &mint_data.mint_amount_auditor_ciphertext_hi,
+                            ),
+                        }
+                    }
+                    Ok(ConfidentialMintBurnInstruction::Burn) => {
+                        let Ok(burn) = decode_instruction_data::<BurnInstructionData>(data) else {
+                            continue;
+                        };
+                        ParsedAuditInstruction {
+                            instruction_index: ix_index as u8,
+                            kind: AuditKind::Burn,
+                            source: account(ix, 0),
+                            destination: None,
+                            amount: decrypt_split_amount(
+                                auditor_secret,
+                                &burn.burn_amount_auditor_ciphertext_lo,
+                                &burn.burn_amount_auditor_ciphertext_hi,
+                            ),
+                        }
+                    }
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+        parsed.push(entry);
+    }
+
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_cursor_advance() {
+        let [previous, newest, oldest] = [1u8, 2, 3].map(|b| Signature::from([b; 64]));
+        let cursor = AuditScanCursor {
+            scanned_until: Some(previous),
+            in_progress: None,
+        };
+
+        // a full batch leaves the scan in progress, from the newest signature
+        let partial = cursor.advance(Some(newest), Some(oldest), false);
+        assert_eq!(partial.scanned_until, Some(previous));
+        assert_eq!(partial.in_progress, Some((newest, oldest)));
+        assert!(!partial.is_complete());
+
+        // reaching the previous scan moves the cursor up to where this one started
+        let done = partial.advance(Some(newest), None, true);
+        assert_eq!(done.scanned_until, Some(newest));
+        assert!(done.is_complete());
+
+        // nothing new leaves the cursor as it was
+        assert_eq!(cursor.advance(None, None, true), cursor);
+    }
+
+    #[test]
+    fn test_audit_position_roundtrip() {
+        let position = AuditPosition {
+            slot: 42,
+            signature: Signature::from([7; 64]),
+            instruction_index: 3,
+        };
+        assert_eq!(
+            position.to_string().parse::<AuditPosition>().unwrap(),
+            position
+        );
+        assert!("42:abc".parse::<AuditPosition>().is_err());
+        assert!(format!("{}:1", position).parse::<AuditPosition>().is_err());
+    }
+    use solana_message::{Message, VersionedMessage};
+    use spl_token_2022::solana_zk_sdk::encryption::{
+        auth_encryption::AeKey, elgamal::ElGamalKeypair,
+    };
+    use spl_token_2022_interface::extension::confidential_transfer::instruction::transfer;
+    use spl_token_confidential_transfer_proof_extraction::instruction::ProofLocation;
+
+    #[test]
+    fn test_parse_transfer_decrypts_auditor_amount() {
+        let auditor = ElGamalKeypair::new_rand();
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+
+        // 70_000 = 4_464 + (1 << 16), split into low 16 bits and the rest
+        let ciphertext_lo = auditor.pubkey().encrypt(4_464_u64).into();
+        let ciphertext_hi = auditor.pubkey().encrypt(1_u64).into();
+        let instructions = transfer(
+            &spl_token_2022::id(),
+            &source,
+            &mint,
+            &destination,
+            &AeKey::new_rand().encrypt(0).into(),
+            &ciphertext_lo,
+            &ciphertext_hi,
+            &owner,
+            &[],
+            ProofLocation::ContextStateAccount(&Pubkey::new_unique()),
+            ProofLocation::ContextStateAccount(&Pubkey::new_unique()),
+            ProofLocation::ContextStateAccount(&Pubkey::new_unique()),
+        )
+        .unwrap();
+
+        let message = VersionedMessage::Legacy(Message::new(&instructions, Some(&owner)));
+        let parsed = parse_audit_instructions(
+            &mint,
+            message.static_account_keys(),
+            message.instructions(),
+            auditor.secret(),
+        );
+
+        assert_eq!(
+            parsed,
+            vec![ParsedAuditInstruction {
+                instruction_index: 0,
+                kind: AuditKind::Transfer,
+                source: Some(source),
+                destination: Some(destination),
+                amount: Some(70_000),
+            }]
+        );
+
+        // transfers on other mints are ignored
+        let parsed = parse_audit_instructions(
+            &Pubkey::new_unique(),
+            message.static_account_keys(),
+            message.instructions(),
+            auditor.secret(),
+        );
+        assert!(parsed.is_empty());
+    }
+}
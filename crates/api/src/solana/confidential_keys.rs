@@ -29,14 +29,11 @@ impl ConfidentialKeys {
         })
     }
 
-    #[allow(dead_code)]
     /// Derive keys from pre-computed signature bytes (non-custodial).
     ///
     /// The client signs a known seed (e.g. ATA address bytes) and sends the
     /// 64-byte signature. The server derives deterministic keypairs from it
     /// without needing the wallet's private key.
-    ///
-    /// NOTE: Unused today, but may be used in a non-custodial flow in the future.
     pub fn from_signature_bytes(
         wallet_pubkey: Pubkey,
         signature_bytes: &[u8; 64],
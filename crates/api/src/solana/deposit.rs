@@ -24,13 +24,13 @@ pub async fn deposit_tokens(
     amount: u64,
 ) -> Result<GeneratedInstructions> {
     let depositor_token_account =
-        get_associated_token_address_with_program_id(depositor, &mint, &spl_token_2022::id());
+        get_associated_token_address_with_program_id(depositor, mint, &spl_token_2022::id());
 
     // deposit from non-confidential balance to "pending" balance
     let deposit_instruction = deposit(
         &spl_token_2022::id(),
         &depositor_token_account,
-        &mint,
+        mint,
         amount,
         decimals,
         depositor,
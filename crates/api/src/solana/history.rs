@@ -0,0 +1,733 @@
+//! Rebuild a wallet's confidential activity from on-chain transactions.
+//!
+//! The transfer ledger only knows about transfers made through the API. This
+//! module walks the signatures of a wallet's confidential token account and
+//! parses every Token-2022 confidential transfer instruction that touched it
+//! (`Deposit`, `Withdraw`, `Transfer` and `ApplyPendingBalance`), decrypting
+//! amounts with the wallet's own confidential keys. Transfers also carry the
+//! memo written alongside them, as it is on chain; an encrypted one is left
+//! for the caller to decrypt, see [`crate::solana::memo`].
+//!
+//! Transfer amounts are not stored in the `Transfer` instruction itself; they
+//! live in the ciphertext validity proof. When the proof was verified inline,
+//! it is read from the same transaction. When it was verified into a context
+//! state account (as [`crate::solana::transfer`] does), the transaction that
+//! verified it is found through the context state account's own signatures.
+//!
+//! Recovering a transfer amount takes a discrete log, so it runs on the
+//! blocking pool, and a page scans at most [`MAX_PAGE_SIZE`] transactions.
+
+use crate::solana::confidential_keys::ConfidentialKeys;
+use crate::solana::utils::decrypt_split_amount;
+use anyhow::{Context, Result};
+use futures::{StreamExt, TryStreamExt, stream};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig,
+};
+use solana_message::compiled_instruction::CompiledInstruction;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_transaction_status::{UiLoadedAddresses, UiTransactionEncoding};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::solana_zk_sdk::{
+    encryption::{auth_encryption::AeCiphertext, pod::auth_encryption::PodAeCiphertext},
+    zk_elgamal_proof_program::{
+        self,
+        instruction::ProofInstruction,
+        proof_data::{
+            BatchedGroupedCiphertext3HandlesValidityProofContext,
+            BatchedGroupedCiphertext3HandlesValidityProofData,
+        },
+    },
+};
+use spl_token_2022_interface::{
+    extension::confidential_transfer::instruction::{
+        ApplyPendingBalanceData, ConfidentialTransferInstruction, DepositInstructionData,
+        TransferInstructionData, WithdrawInstructionData,
+    },
+    instruction::{TokenInstruction, decode_instruction_data, decode_instruction_type},
+};
+use std::{str::FromStr, sync::Arc};
+use tracing::warn;
+
+/// Most transactions scanned for one page.
+pub const MAX_PAGE_SIZE: usize = 25;
+
+/// Transactions fetched at the same time while scanning a page.
+const FETCH_CONCURRENCY: usize = 5;
+
+/// Index of each party's decrypt handle in a transfer amount ciphertext.
+const SOURCE_HANDLE_INDEX: usize = 0;
+const DESTINATION_HANDLE_INDEX: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityKind {
+    /// Public balance moved into the pending confidential balance.
+    Deposit,
+    /// Available confidential balance moved back to the public balance.
+    Withdraw,
+    /// Pending balance credited to the available balance.
+    ApplyPendingBalance,
+    TransferIn,
+    TransferOut,
+}
+
+impl ActivityKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Withdraw => "withdraw",
+            Self::ApplyPendingBalance => "apply_pending_balance",
+            Self::TransferIn => "transfer_in",
+            Self::TransferOut => "transfer_out",
+        }
+    }
+}
+
+/// A single confidential instruction that touched the wallet's token account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityEntry {
+    pub signature: Signature,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub kind: ActivityKind,
+    /// Amount moved, when it could be recovered.
+    pub amount: Option<u64>,
+    /// Decrypted available balance after the instruction, for instructions that
+    /// set a new decryptable available balance.
+    pub available_balance: Option<u64>,
+    /// Token account on the other side of a transfer.
+    pub counterparty: Option<Pubkey>,
+    /// Memo written with a transfer.
+    pub memo: Option<TransactionMemo>,
+}
+
+/// An SPL Memo instruction of a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionMemo {
+    pub text: String,
+    /// First account that signed the memo, the sender of a transfer.
+    pub signer: Option<Pubkey>,
+}
+
+/// One page of rebuilt activity.
+pub struct ActivityPage {
+    pub entries: Vec<ActivityEntry>,
+    /// Last signature scanned; pass as `before` to continue.
+    pub next_before: Option<Signature>,
+}
+
+/// Where the ciphertext validity proof of a `Transfer` instruction lives.
+enum ValidityProof {
+    /// Verified in the same transaction; holds the proof context.
+    Inline(Box<BatchedGroupedCiphertext3HandlesValidityProofContext>),
+    /// Verified into a context state account by an earlier transaction.
+    ContextStateAccount(Pubkey),
+    Unknown,
+}
+
+/// Instruction parsed from a transaction, before transfer amounts are resolved.
+struct ParsedInstruction {
+    kind: ActivityKind,
+    amount: Option<u64>,
+    available_balance: Option<u64>,
+    counterparty: Option<Pubkey>,
+    validity_proof: Option<ValidityProof>,
+}
+
+/// Rebuild the confidential activity of `owner`'s token account for `mint`,
+/// newest first, scanning at most `limit` (up to [`MAX_PAGE_SIZE`])
+/// transactions before `before`.
+pub async fn rebuild_history(
+    rpc_client: Arc<RpcClient>,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    confidential_keys: &ConfidentialKeys,
+    before: Option<Signature>,
+    limit: usize,
+) -> Result<ActivityPage> {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let token_account =
+        get_associated_token_address_with_program_id(owner, mint, &spl_token_2022::id());
+
+    let statuses = rpc_client
+        .get_signatures_for_address_with_config(
+            &token_account,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: None,
+                limit: Some(limit),
+                commitment: Some(rpc_client.commitment()),
+            },
+        )
+        .await?;
+    let next_before = match statuses.last() {
+        Some(status) if statuses.len() == limit => Some(Signature::from_str(&status.signature)?),
+        _ => None,
+    };
+
+    let transactions: Vec<_> = stream::iter(statuses.into_iter().filter(|s| s.err.is_none()))
+        .map(|status| {
+            let rpc_client = rpc_client.clone();
+            async move {
+                let signature = Signature::from_str(&status.signature)?;
+                let transaction = fetch_transaction(rpc_client, &signature).await?;
+                anyhow::Ok((status, signature, transaction))
+            }
+        })
+        .buffered(FETCH_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let mut entries = vec![];
+    for (status, signature, transaction) in transactions {
+        let Some((account_keys, instructions)) = transaction else {
+            continue;
+        };
+        let memo = parse_memo(&account_keys, &instructions);
+
+        for parsed in parse_transaction(
+            &token_account,
+            &account_keys,
+            &instructions,
+            confidential_keys,
+        ) {
+            let amount = match &parsed.validity_proof {
+                Some(proof) => {
+                    let handle_index = match parsed.kind {
+                        ActivityKind::TransferOut => SOURCE_HANDLE_INDEX,
+                        _ => DESTINATION_HANDLE_INDEX,
+                    };
+                    resolve_transfer_amount(
+                        rpc_client.clone(),
+                        proof,
+                        handle_index,
+                        confidential_keys,
+                    )
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!(
+                            "failed to resolve transfer amount for {}: {:?}",
+                            signature, e
+                        );
+                        None
+                    })
+                }
+                None => parsed.amount,
+            };
+
+            entries.push(ActivityEntry {
+                signature,
+                slot: status.slot,
+                block_time: status.block_time,
+                kind: parsed.kind,
+                amount,
+                available_balance: parsed.available_balance,
+                counterparty: parsed.counterparty,
+                memo: match parsed.kind {
+                    ActivityKind::TransferIn | ActivityKind::TransferOut => memo.clone(),
+                    _ => None,
+                },
+            });
+        }
+    }
+
+    Ok(ActivityPage {
+        entries,
+        next_before,
+    })
+}
+
+/// Fetch a transaction and return its full account key list (including keys
+/// loaded from lookup tables) and its top-level instructions.
+pub async fn fetch_transaction(
+    rpc_client: Arc<RpcClient>,
+    signature: &Signature,
+) -> Result<Option<(Vec<Pubkey>, Vec<CompiledInstruction>)>> {
+    let transaction = rpc_client
+        .get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(rpc_client.commitment()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await?;
+
+    let Some(decoded) = transaction.transaction.transaction.decode() else {
+        return Ok(None);
+    };
+
+    let mut account_keys = decoded.message.static_account_keys().to_vec();
+    let loaded_addresses = transaction
+        .transaction
+        .meta
+        .and_then(|meta| Option::<UiLoadedAddresses>::from(meta.loaded_addresses));
+    if let Some(loaded_addresses) = loaded_addresses {
+        for address in loaded_addresses
+            .writable
+            .iter()
+            .chain(loaded_addresses.readonly.iter())
+        {
+            account_keys.push(Pubkey::from_str(address)?);
+        }
+    }
+
+    Ok(Some((
+        account_keys,
+        decoded.message.instructions().to_vec(),
+    )))
+}
+
+/// Parse the confidential transfer instructions in a transaction that touch
+/// `token_account`. Instructions that fail to parse are skipped.
+fn parse_transaction(
+    token_account: &Pubkey,
+    account_keys: &[Pubkey],
+    instructions: &[CompiledInstruction],
+    confidential_keys: &ConfidentialKeys,
+) -> Vec<ParsedInstruction> {
+    let account = |ix: &CompiledInstruction, position: usize| -> Option<Pubkey> {
+        ix.accounts
+            .get(position)
+            .and_then(|index| account_keys.get(*index as usize))
+            .copied()
+    };
+
+    let mut parsed = vec![];
+    for (ix_index, ix) in instructions.iter().enumerate() {
+        if account_keys.get(ix.program_id_index as usize) != Some(&spl_token_2022::id()) {
+            continue;
+        }
+        if !matches!(
+            TokenInstruction::unpack(&ix.data),
+            Ok(TokenInstruction::ConfidentialTransferExtension)
+        ) {
+            continue;
+        }
+        // the extension instruction starts after the token instruction byte
+        let data = &ix.data[1..];
+        let Ok(instruction_type) = decode_instruction_type::<ConfidentialTransferInstruction>(data)
+        else {
+            continue;
+        };
+
+        match instruction_type {
+            ConfidentialTransferInstruction::Deposit => {
+                let Ok(deposit) = decode_instruction_data::<DepositInstructionData>(data) else {
+                    continue;
+                };
+                if account(ix, 0) != Some(*token_account) {
+                    continue;
+                }
+                parsed.push(ParsedInstruction {
+                    kind: ActivityKind::Deposit,
+                    amount: Some(deposit.amount.into()),
+                    available_balance: None,
+                    counterparty: None,
+                    validity_proof: None,
+                });
+            }
+            ConfidentialTransferInstruction::Withdraw => {
+                let Ok(withdraw) = decode_instruction_data::<WithdrawInstructionData>(data) else {
+                    continue;
+                };
+                if account(ix, 0) != Some(*token_account) {
+                    continue;
+                }
+                parsed.push(ParsedInstruction {
+                    kind: ActivityKind::Withdraw,
+                    amount: Some(withdraw.amount.into()),
+                    available_balance: decrypt_available_balance(
+                        &withdraw.new_decryptable_available_balance,
+                        confidential_keys,
+                    ),
+                    counterparty: None,
+                    validity_proof: None,
+                });
+            }
+            ConfidentialTransferInstruction::ApplyPendingBalance => {
+                let Ok(apply) = decode_instruction_data::<ApplyPendingBalanceData>(data) else {
+                    continue;
+                };
+                if account(ix, 0) != Some(*token_account) {
+                    continue;
+                }
+                parsed.push(ParsedInstruction {
+                    kind: ActivityKind::ApplyPendingBalance,
+                    amount: None,
+                    available_balance: decrypt_available_balance(
+                        &apply.new_decryptable_available_balance,
+                        confidential_keys,
+                    ),
+                    counterparty: None,
+                    validity_proof: None,
+                });
+            }
+            ConfidentialTransferInstruction::Transfer => {
+                let Ok(transfer) = decode_instruction_data::<TransferInstructionData>(data) else {
+                    continue;
+                };
+                let (Some(source), Some(destination)) = (account(ix, 0), account(ix, 2)) else {
+                    continue;
+                };
+                let (kind, counterparty, available_balance) = if source == *token_account {
+                    (
+                        ActivityKind::TransferOut,
+                        destination,
+                        decrypt_available_balance(
+                            &transfer.new_source_decryptable_available_balance,
+                            confidential_keys,
+                        ),
+                    )
+                } else if destination == *token_account {
+                    (ActivityKind::TransferIn, source, None)
+                } else {
+                    continue;
+                };
+
+                parsed.push(ParsedInstruction {
+                    kind,
+                    amount: None,
+                    available_balance,
+                    counterparty: Some(counterparty),
+                    validity_proof: Some(locate_validity_proof(
+                        transfer,
+                        ix,
+                        ix_index,
+                        instructions,
+                        &account,
+                    )),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    parsed
+}
+
+/// The first SPL Memo instruction of a transaction, if it is valid UTF-8.
+fn parse_memo(
+    account_keys: &[Pubkey],
+    instructions: &[CompiledInstruction],
+) -> Option<TransactionMemo> {
+    let ix = instructions.iter().find(|ix| {
+        account_keys.get(ix.program_id_index as usize) == Some(&spl_memo_interface::v3::id())
+    })?;
+    let text = String::from_utf8(ix.data.clone()).ok()?;
+    let signer = ix
+        .accounts
+        .first()
+        .and_then(|index| account_keys.get(*index as usize))
+        .copied();
+    Some(TransactionMemo { text, signer })
+}
+
+/// Find the ciphertext validity proof of a `Transfer` instruction, following
+/// the account layout documented on `ConfidentialTransferInstruction::Transfer`.
+fn locate_validity_proof(
+    transfer: &TransferInstructionData,
+    ix: &CompiledInstruction,
+    ix_index: usize,
+    instructions: &[CompiledInstruction],
+    account: &impl Fn(&CompiledInstruction, usize) -> Option<Pubkey>,
+) -> ValidityProof {
+    let validity_offset = transfer.ciphertext_validity_proof_instruction_offset;
+    if validity_offset != 0 {
+        return ix_index
+            .checked_add_signed(validity_offset as isize)
+            .and_then(|index| instructions.get(index))
+            .and_then(|proof_ix| validity_proof_context(&proof_ix.data))
+            .map(|context| ValidityProof::Inline(Box::new(context)))
+            .unwrap_or(ValidityProof::Unknown);
+    }
+
+    // source, mint and destination come first, then the instructions sysvar if
+    // any proof is inline, then the context state accounts in proof order
+    let mut position = 3;
+    if transfer.equality_proof_instruction_offset != 0
+        || transfer.range_proof_instruction_offset != 0
+    {
+        position += 1;
+    }
+    if transfer.equality_proof_instruction_offset == 0 {
+        position += 1;
+    }
+
+    account(ix, position)
+        .map(ValidityProof::ContextStateAccount)
+        .unwrap_or(ValidityProof::Unknown)
+}
+
+/// Context of a `VerifyBatchedGroupedCiphertext3HandlesValidity` instruction
+/// carrying its proof in instruction data.
+fn validity_proof_context(
+    data: &[u8],
+) -> Option<BatchedGroupedCiphertext3HandlesValidityProofContext> {
+    if ProofInstruction::instruction_type(data)
+        != Some(ProofInstruction::VerifyBatchedGroupedCiphertext3HandlesValidity)
+    {
+        return None;
+    }
+    ProofInstruction::proof_data::<
+        BatchedGroupedCiphertext3HandlesValidityProofData,
+        BatchedGroupedCiphertext3HandlesValidityProofContext,
+    >(data)
+    .map(|proof_data| proof_data.context)
+}
+
+async fn resolve_transfer_amount(
+    rpc_client: Arc<RpcClient>,
+    proof: &ValidityProof,
+    handle_index: usize,
+    confidential_keys: &ConfidentialKeys,
+) -> Result<Option<u64>> {
+    let context = match proof {
+        ValidityProof::Inline(context) => **context,
+        ValidityProof::ContextStateAccount(context_state_account) => {
+            match find_verified_validity_proof(rpc_client, context_state_account).await? {
+                Some(context) => context,
+                None => return Ok(None),
+            }
+        }
+        ValidityProof::Unknown => return Ok(None),
+    };
+
+    let confidential_keys = confidential_keys.clone();
+    let amount = tokio::task::spawn_blocking(move || {
+        decrypt_transfer_amount(&context, handle_index, &confidential_keys)
+    })
+    .await?;
+    Ok(amount)
+}
+
+/// Find the instruction that verified a ciphertext validity proof into
+/// `context_state_account`. The account is closed after the transfer, but its
+/// signature history remains.
+async fn find_verified_validity_proof(
+    rpc_client: Arc<RpcClient>,
+    context_state_account: &Pubkey,
+) -> Result<Option<BatchedGroupedCiphertext3HandlesValidityProofContext>> {
+    let statuses = rpc_client
+        .get_signatures_for_address_with_config(
+            context_state_account,
+            GetConfirmedSignaturesForAddress2Config {
+                commitment: Some(rpc_client.commitment()),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    for status in statuses.iter().filter(|status| status.err.is_none()) {
+        let signature = Signature::from_str(&status.signature)
+            .context("Failed to parse context state account signature")?;
+        let Some((account_keys, instructions)) =
+            fetch_transaction(rpc_client.clone(), &signature).await?
+        else {
+            continue;
+        };
+
+        let context = instructions.iter().find_map(|ix| {
+            let is_verify_into_account = account_keys.get(ix.program_id_index as usize)
+                == Some(&zk_elgamal_proof_program::id())
+                && ix
+                    .accounts
+                    .first()
+                    .and_then(|index| account_keys.get(*index as usize))
+                    == Some(context_state_account);
+            if !is_verify_into_account {
+                return None;
+            }
+            validity_proof_context(&ix.data)
+        });
+        if context.is_some() {
+            return Ok(context);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Decrypt a transfer amount from a ciphertext validity proof context using the
+/// decrypt handle at `handle_index` (source or destination).
+fn decrypt_transfer_amount(
+    context: &BatchedGroupedCiphertext3HandlesValidityProofContext,
+    handle_index: usize,
+    confidential_keys: &ConfidentialKeys,
+) -> Option<u64> {
+    let ciphertext_lo = context
+        .grouped_ciphertext_lo
+        .try_extract_ciphertext(handle_index)
+        .ok()?;
+    let ciphertext_hi = context
+        .grouped_ciphertext_hi
+        .try_extract_ciphertext(handle_index)
+        .ok()?;
+    decrypt_split_amount(
+        confidential_keys.elgamal_keypair.secret(),
+        &ciphertext_lo,
+        &ciphertext_hi,
+    )
+}
+
+fn decrypt_available_balance(
+    ciphertext: &PodAeCiphertext,
+    confidential_keys: &ConfidentialKeys,
+) -> Option<u64> {
+    let ciphertext: AeCiphertext = (*ciphertext).try_into().ok()?;
+    confidential_keys.ae_key.decrypt(&ciphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solana::utils::confidential_keys_for_mint;
+    use solana_keypair::Keypair;
+    use solana_message::{Message, VersionedMessage};
+    use solana_signer::Signer;
+    use spl_token_2022::{
+        extension::confidential_transfer::account_info::TransferAccountInfo,
+        solana_zk_sdk::encryption::elgamal::ElGamalKeypair,
+    };
+    use spl_token_2022_interface::extension::confidential_transfer::instruction::{
+        deposit, inner_withdraw,
+    };
+    use spl_token_confidential_transfer_proof_extraction::instruction::ProofLocation;
+    use spl_token_confidential_transfer_proof_generation::transfer::TransferProofData;
+
+    fn keys_for(owner: Arc<Keypair>, mint: &Pubkey) -> ConfidentialKeys {
+        confidential_keys_for_mint(owner, mint).unwrap()
+    }
+
+    #[test]
+    fn test_parse_deposit_and_withdraw() {
+        let owner = Arc::new(Keypair::new());
+        let mint = Pubkey::new_unique();
+        let keys = keys_for(owner.clone(), &mint);
+        let owner_pubkey = owner.pubkey();
+        let token_account = get_associated_token_address_with_program_id(
+            &owner_pubkey,
+            &mint,
+            &spl_token_2022::id(),
+        );
+
+        let deposit_ix = deposit(
+            &spl_token_2022::id(),
+            &token_account,
+            &mint,
+            1_000,
+            6,
+            &owner_pubkey,
+            &[],
+        )
+        .unwrap();
+        let withdraw_ix = inner_withdraw(
+            &spl_token_2022::id(),
+            &token_account,
+            &mint,
+            400,
+            6,
+            &keys.ae_key.encrypt(600).into(),
+            &owner_pubkey,
+            &[],
+            ProofLocation::ContextStateAccount(&Pubkey::new_unique()),
+            ProofLocation::ContextStateAccount(&Pubkey::new_unique()),
+        )
+        .unwrap();
+
+        let message = VersionedMessage::Legacy(Message::new(
+            &[deposit_ix, withdraw_ix],
+            Some(&owner_pubkey),
+        ));
+        let parsed = parse_transaction(
+            &token_account,
+            message.static_account_keys(),
+            message.instructions(),
+            &keys,
+        );
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].kind, ActivityKind::Deposit);
+        assert_eq!(parsed[0].amount, Some(1_000));
+        assert_eq!(parsed[1].kind, ActivityKind::Withdraw);
+        assert_eq!(parsed[1].amount, Some(400));
+        assert_eq!(parsed[1].available_balance, Some(600));
+
+        // instructions for another token account are ignored
+        let parsed = parse_transaction(
+            &Pubkey::new_unique(),
+            message.static_account_keys(),
+            message.instructions(),
+            &keys,
+        );
+        assert!(parsed.is_empty());
+    }
+
+    #[test]
+    fn test_parse_memo() {
+        let sender = Pubkey::new_unique();
+        let message = VersionedMessage::Legacy(Message::new(
+            &[crate::solana::memo::build_memo_ix("enc1:AAAA", &sender)],
+            Some(&sender),
+        ));
+        assert_eq!(
+            parse_memo(message.static_account_keys(), message.instructions()),
+            Some(TransactionMemo {
+                text: "enc1:AAAA".to_string(),
+                signer: Some(sender),
+            })
+        );
+
+        let message = VersionedMessage::Legacy(Message::new(&[], Some(&sender)));
+        assert_eq!(
+            parse_memo(message.static_account_keys(), message.instructions()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decrypt_transfer_amount_for_source_and_destination() {
+        let sender = Arc::new(Keypair::new());
+        let recipient = Arc::new(Keypair::new());
+        let mint = Pubkey::new_unique();
+        let sender_keys = keys_for(sender, &mint);
+        let recipient_keys = keys_for(recipient, &mint);
+        let auditor = ElGamalKeypair::new_rand();
+
+        let sender_transfer_account_info = TransferAccountInfo {
+            available_balance: sender_keys
+                .elgamal_keypair
+                .pubkey()
+                .encrypt(100_000_u64)
+                .into(),
+            decryptable_available_balance: sender_keys.ae_key.encrypt(100_000).into(),
+        };
+        let TransferProofData {
+            ciphertext_validity_proof_data_with_ciphertext,
+            ..
+        } = sender_transfer_account_info
+            .generate_split_transfer_proof_data(
+                70_000,
+                &sender_keys.elgamal_keypair,
+                &sender_keys.ae_key,
+                recipient_keys.elgamal_keypair.pubkey(),
+                Some(auditor.pubkey()),
+            )
+            .unwrap();
+        let context = ciphertext_validity_proof_data_with_ciphertext
+            .proof_data
+            .context;
+
+        assert_eq!(
+            decrypt_transfer_amount(&context, SOURCE_HANDLE_INDEX, &sender_keys),
+            Some(70_000)
+        );
+        assert_eq!(
+            decrypt_transfer_amount(&context, DESTINATION_HANDLE_INDEX, &recipient_keys),
+            Some(70_000)
+        );
+    }
+}
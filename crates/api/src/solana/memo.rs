@@ -0,0 +1,133 @@
+//! SPL Memo instructions attached to confidential transfers.
+//!
+//! A memo is either written as is, or encrypted so that only the sender and
+//! the recipient can read it. The encryption key is derived from an X25519
+//! exchange between the two wallets' ed25519 keys, so either side can recover
+//! it from its own secret key and the other side's address:
+//!
+//! ```text
+//! shared = X25519(sender secret, recipient pubkey) = X25519(recipient secret, sender pubkey)
+//! key    = HKDF-SHA256(ikm = shared, info = "teegeepay memo v1")
+//! memo   = "enc1:" || base64(nonce || AES-256-GCM(key, nonce, plaintext))
+//! ```
+
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+};
+use anyhow::Result;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256, Sha512};
+use solana_instruction::Instruction;
+use solana_keypair::Keypair;
+use solana_pubkey::Pubkey;
+
+/// Prefix of encrypted memos, so readers can tell them apart from plain ones.
+pub const ENCRYPTED_MEMO_PREFIX: &str = "enc1:";
+
+const MEMO_KEY_INFO: &[u8] = b"teegeepay memo v1";
+const NONCE_LEN: usize = 12;
+
+/// Memo instruction signed by `signer`.
+pub fn build_memo_ix(memo: &str, signer: &Pubkey) -> Instruction {
+    spl_memo_interface::instruction::build_memo(
+        &spl_memo_interface::v3::id(),
+        memo.as_bytes(),
+        &[signer],
+    )
+}
+
+/// Key shared between `keypair` and `peer` for encrypting memos.
+pub fn shared_memo_key(keypair: &Keypair, peer: &Pubkey) -> Result<[u8; 32]> {
+    let peer = CompressedEdwardsY(peer.to_bytes())
+        .decompress()
+        .ok_or_else(|| anyhow::anyhow!("{} is not a valid ed25519 public key", peer))?
+        .to_montgomery();
+
+    // the X25519 secret of an ed25519 keypair is the (clamped) first half of
+    // the hashed seed, the same scalar ed25519 signs with
+    let hashed_seed = Sha512::digest(keypair.secret_bytes());
+    let mut scalar = [0u8; 32];
+    scalar.copy_from_slice(&hashed_seed[..32]);
+    let shared = peer.mul_clamped(scalar);
+
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, shared.as_bytes())
+        .expand(MEMO_KEY_INFO, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive memo key: {}", e))?;
+    Ok(key)
+}
+
+/// Encrypt `memo` with a key from [`shared_memo_key`].
+pub fn encrypt_memo(key: &[u8; 32], memo: &str) -> Result<String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .encrypt(&nonce, memo.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt memo"))?;
+
+    Ok(format!(
+        "{}{}",
+        ENCRYPTED_MEMO_PREFIX,
+        BASE64_STANDARD.encode([nonce.as_slice(), &ciphertext].concat())
+    ))
+}
+
+/// Decrypt a memo produced by [`encrypt_memo`].
+pub fn decrypt_memo(key: &[u8; 32], memo: &str) -> Result<String> {
+    let encoded = memo
+        .strip_prefix(ENCRYPTED_MEMO_PREFIX)
+        .ok_or_else(|| anyhow::anyhow!("Memo is not encrypted"))?;
+    let data = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|e| anyhow::anyhow!("Invalid encrypted memo: {}", e))?;
+    if data.len() < NONCE_LEN {
+        anyhow::bail!("Encrypted memo is truncated");
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let plaintext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt memo"))?;
+    String::from_utf8(plaintext).map_err(|e| anyhow::anyhow!("Memo is not valid UTF-8: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_signer::Signer;
+
+    #[test]
+    fn test_shared_memo_key_is_symmetric() {
+        let sender = Keypair::new();
+        let recipient = Keypair::new();
+
+        let sender_key = shared_memo_key(&sender, &recipient.pubkey()).unwrap();
+        let recipient_key = shared_memo_key(&recipient, &sender.pubkey()).unwrap();
+        assert_eq!(sender_key, recipient_key);
+
+        let other_key = shared_memo_key(&sender, &Keypair::new().pubkey()).unwrap();
+        assert_ne!(sender_key, other_key);
+    }
+
+    #[test]
+    fn test_encrypt_memo_roundtrip() {
+        let sender = Keypair::new();
+        let recipient = Keypair::new();
+        let key = shared_memo_key(&sender, &recipient.pubkey()).unwrap();
+
+        let encrypted = encrypt_memo(&key, "rent for october 🏠").unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_MEMO_PREFIX));
+        assert!(!encrypted.contains("rent"));
+
+        let recipient_key = shared_memo_key(&recipient, &sender.pubkey()).unwrap();
+        assert_eq!(
+            decrypt_memo(&recipient_key, &encrypted).unwrap(),
+            "rent for october 🏠"
+        );
+
+        let wrong_key = shared_memo_key(&Keypair::new(), &sender.pubkey()).unwrap();
+        assert!(decrypt_memo(&wrong_key, &encrypted).is_err());
+    }
+}
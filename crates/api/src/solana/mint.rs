@@ -63,7 +63,7 @@ pub async fn build_standard_mint_instructions(
     }
 
     let receiving_token_account = get_associated_token_address_with_program_id(
-        &token_account_owner,
+        token_account_owner,
         mint,
         &spl_token_2022::id(),
     );
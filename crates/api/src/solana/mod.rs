@@ -1,9 +1,14 @@
 pub mod airdrop;
+pub mod audit;
 pub mod balance;
+pub mod confidential_keys;
 pub mod create;
 pub mod deposit;
+pub mod history;
+pub mod memo;
 pub mod mint;
-pub mod confidential_keys;
+pub mod pay;
+pub mod submit;
 pub mod supply;
 pub mod tokens;
 pub mod transaction;
@@ -0,0 +1,152 @@
+//! Solana Pay-style transfer request links.
+//!
+//! See https://docs.solanapay.com/spec#transfer-request for the URL format.
+
+use anyhow::Result;
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use solana_pubkey::Pubkey;
+use std::io::Cursor;
+
+const LINK_LABEL: &str = "TeeGee Pay";
+
+/// Build a `solana:` transfer request URL asking for `amount` base units of `mint`.
+pub fn transfer_request_url(
+    recipient: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+    decimals: u8,
+    message: Option<&str>,
+    memo: Option<&str>,
+) -> String {
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    query.append_pair("amount", &format_ui_amount(amount, decimals));
+    query.append_pair("spl-token", &mint.to_string());
+    query.append_pair("label", LINK_LABEL);
+    if let Some(message) = message {
+        query.append_pair("message", message);
+    }
+    if let Some(memo) = memo {
+        query.append_pair("memo", memo);
+    }
+
+    format!("solana:{}?{}", recipient, query.finish())
+}
+
+/// Decimal representation of a token amount, without trailing zeros.
+pub fn format_ui_amount(amount: u64, decimals: u8) -> String {
+    let divisor = 10u128.pow(decimals as u32);
+    let whole = amount as u128 / divisor;
+    let fraction = amount as u128 % divisor;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+
+    let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// Base units of a decimal token amount such as `2.5`, the inverse of
+/// [`format_ui_amount`]. More fractional digits than `decimals` are an error,
+/// not rounded away.
+pub fn parse_ui_amount(amount: &str, decimals: u8) -> Result<u64> {
+    let invalid = || anyhow::anyhow!("Invalid amount: {}", amount);
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+    if fraction.len() > decimals as usize {
+        anyhow::bail!("Amount {} has more than {} decimals", amount, decimals);
+    }
+
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| invalid())?
+    };
+    let fraction: u128 = format!("{:0<width$}", fraction, width = decimals as usize)
+        .parse()
+        .unwrap_or(0);
+    whole
+        .checked_mul(10u128.pow(decimals as u32))
+        .and_then(|whole| whole.checked_add(fraction))
+        .and_then(|amount| u64::try_from(amount).ok())
+        .ok_or_else(|| anyhow::anyhow!("Amount {} is too large", amount))
+}
+
+/// Render `data` as a QR code PNG.
+pub fn qr_code_png(data: &str) -> Result<Vec<u8>> {
+    let code = QrCode::new(data.as_bytes())?;
+    let image = code.render::<Luma<u8>>().min_dimensions(256, 256).build();
+
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_ui_amount() {
+        assert_eq!(format_ui_amount(0, 6), "0");
+        assert_eq!(format_ui_amount(1_500_000, 6), "1.5");
+        assert_eq!(format_ui_amount(1_000_001, 6), "1.000001");
+        assert_eq!(format_ui_amount(42, 0), "42");
+        assert_eq!(format_ui_amount(u64::MAX, 9), "18446744073.709551615");
+    }
+
+    #[test]
+    fn test_parse_ui_amount() {
+        assert_eq!(parse_ui_amount("5", 6).unwrap(), 5_000_000);
+        assert_eq!(parse_ui_amount("2.5", 6).unwrap(), 2_500_000);
+        assert_eq!(parse_ui_amount(".5", 2).unwrap(), 50);
+        assert_eq!(parse_ui_amount("1.000001", 6).unwrap(), 1_000_001);
+        assert_eq!(parse_ui_amount("42", 0).unwrap(), 42);
+        assert_eq!(
+            parse_ui_amount("18446744073.709551615", 9).unwrap(),
+            u64::MAX
+        );
+
+        assert!(parse_ui_amount("1.0000001", 6).is_err());
+        assert!(parse_ui_amount("18446744073.709551616", 9).is_err());
+        for invalid in ["", ".", "-1", "1,5", "1.2.3", "abc", " 1"] {
+            assert!(parse_ui_amount(invalid, 6).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_transfer_request_url() {
+        let recipient = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+
+        let url = transfer_request_url(
+            &recipient,
+            &mint,
+            2_500_000,
+            6,
+            Some("Request #7"),
+            Some("pizza & drinks"),
+        );
+
+        assert_eq!(
+            url,
+            format!(
+                "solana:{}?amount=2.5&spl-token={}&label=TeeGee+Pay&message=Request+%237&memo=pizza+%26+drinks",
+                recipient, mint
+            )
+        );
+    }
+
+    #[test]
+    fn test_qr_code_png() {
+        let png = qr_code_png("solana:11111111111111111111111111111111").unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+}
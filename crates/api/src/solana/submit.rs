@@ -0,0 +1,499 @@
+//! Submission backends for multi-transaction sequences.
+//!
+//! Confidential transfers, withdraws and mints are each a short sequence of
+//! transactions (allocate proof accounts, verify proofs, act, close). A
+//! [`Submitter`] decides how such a sequence reaches the cluster:
+//!
+//! - [`SequentialSubmitter`] sends and confirms one transaction at a time
+//!   through the RPC node, so a failure can leave earlier steps on chain,
+//! - [`BundleSubmitter`] sends the whole sequence to a Jito block engine as
+//!   one bundle, which lands atomically or not at all.
+//!
+//! Bundles are capped at [`MAX_BUNDLE_TRANSACTIONS`] and must pay a tip, which
+//! [`build_sequence`] appends to the last transaction of the sequence.
+
+use crate::solana::GeneratedInstructions;
+use crate::solana::transaction::{build_transaction, required_signers};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::Deserialize;
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_instruction::Instruction;
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
+use solana_signer::Signer;
+use solana_system_interface::instruction as system_instruction;
+use solana_transaction::versioned::VersionedTransaction;
+use std::{sync::Arc, time::Duration};
+use tracing::info;
+
+/// Most transactions a block engine accepts in one bundle.
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
+/// One of the block engine's tip accounts, used unless `JITO_TIP_ACCOUNT` is set.
+pub const DEFAULT_TIP_ACCOUNT: &str = "96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5";
+pub const DEFAULT_TIP_LAMPORTS: u64 = 10_000;
+
+const BUNDLE_STATUS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Roughly the lifetime of a blockhash.
+const BUNDLE_STATUS_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The block engine refused or dropped a bundle. Bundles are atomic, so none
+/// of its transactions landed.
+#[derive(Debug, thiserror::Error)]
+#[error("Bundle {bundle_id} was not landed: {reason}")]
+pub struct BundleRejected {
+    pub bundle_id: String,
+    pub reason: String,
+}
+
+#[async_trait]
+pub trait Submitter: Send + Sync {
+    /// Whether the sequence lands as a unit. When it does, callers can skip
+    /// tracking (and rolling back) the individual steps.
+    fn is_atomic(&self) -> bool;
+
+    /// Instruction that pays for inclusion, appended to the last transaction
+    /// of every sequence.
+    fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction>;
+
+    /// Send `transactions` in order and wait for all of them to land,
+    /// returning their signatures.
+    async fn submit(&self, transactions: &[VersionedTransaction]) -> Result<Vec<Signature>>;
+}
+
+/// Which submitter to use, from `SUBMITTER` (`sequential` or `bundle`,
+/// defaults to `sequential`). See [`crate::config`].
+#[derive(Debug)]
+pub enum SubmitterConfig {
+    Sequential,
+    /// Send bundles to `BLOCK_ENGINE_URL`, tipping `JITO_TIP_LAMPORTS` to
+    /// `JITO_TIP_ACCOUNT`.
+    Bundle {
+        block_engine_url: String,
+        tip_account: Pubkey,
+        tip_lamports: u64,
+    },
+}
+
+pub fn from_config(
+    config: &SubmitterConfig,
+    rpc_client: Arc<RpcClient>,
+) -> Result<Arc<dyn Submitter>> {
+    let submitter: Arc<dyn Submitter> = match config {
+        SubmitterConfig::Sequential => {
+            info!("using sequential submitter");
+            Arc::new(SequentialSubmitter::new(rpc_client))
+        }
+        SubmitterConfig::Bundle {
+            block_engine_url,
+            tip_account,
+            tip_lamports,
+        } => {
+            info!("using bundle submitter");
+            Arc::new(BundleSubmitter::new(
+                block_engine_url,
+                *tip_account,
+                *tip_lamports,
+            )?)
+        }
+    };
+    Ok(submitter)
+}
+
+/// Build the transactions of a sequence against a single blockhash, paid for
+/// by `fee_payer`, with the submitter's tip in the last one. Each of `signers`
+/// signs the transactions that need it, e.g. the owner of a sponsored wallet.
+pub async fn build_sequence(
+    rpc_client: Arc<RpcClient>,
+    submitter: &dyn Submitter,
+    fee_payer: Arc<dyn Signer + Send + Sync>,
+    signers: Vec<Arc<dyn Signer + Send + Sync>>,
+    mut steps: Vec<GeneratedInstructions>,
+) -> Result<Vec<VersionedTransaction>> {
+    if submitter.is_atomic() && steps.len() > MAX_BUNDLE_TRANSACTIONS {
+        anyhow::bail!(
+            "Sequence of {} transactions does not fit in a bundle of {}",
+            steps.len(),
+            MAX_BUNDLE_TRANSACTIONS
+        );
+    }
+    if let (Some(tip), Some(last)) = (
+        submitter.tip_instruction(&fee_payer.pubkey()),
+        steps.last_mut(),
+    ) {
+        last.instructions.push(tip);
+    }
+
+    let blockhash = rpc_client.get_latest_blockhash().await?;
+    let mut transactions = Vec::with_capacity(steps.len());
+    for mut step in steps {
+        step.additional_signers
+            .extend(required_signers(&step.instructions, &signers));
+        transactions.push(
+            build_transaction(
+                rpc_client.clone(),
+                Some(blockhash),
+                step.instructions,
+                fee_payer.clone(),
+                step.additional_signers,
+            )
+            .await?,
+        );
+    }
+    Ok(transactions)
+}
+
+/// Build and submit a sequence, see [`build_sequence`].
+pub async fn submit_sequence(
+    rpc_client: Arc<RpcClient>,
+    submitter: &dyn Submitter,
+    fee_payer: Arc<dyn Signer + Send + Sync>,
+    signers: Vec<Arc<dyn Signer + Send + Sync>>,
+    steps: Vec<GeneratedInstructions>,
+) -> Result<Vec<Signature>> {
+    let transactions = build_sequence(rpc_client, submitter, fee_payer, signers, steps).await?;
+    submitter.submit(&transactions).await
+}
+
+/// Sends each transaction through the RPC node and waits for it to confirm
+/// before sending the next.
+pub struct SequentialSubmitter {
+    rpc_client: Arc<RpcClient>,
+}
+
+impl SequentialSubmitter {
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        Self { rpc_client }
+    }
+}
+
+#[async_trait]
+impl Submitter for SequentialSubmitter {
+    fn is_atomic(&self) -> bool {
+        false
+    }
+
+    fn tip_instruction(&self, _payer: &Pubkey) -> Option<Instruction> {
+        None
+    }
+
+    async fn submit(&self, transactions: &[VersionedTransaction]) -> Result<Vec<Signature>> {
+        let mut signatures = Vec::with_capacity(transactions.len());
+        for transaction in transactions {
+            let signature = self
+                .rpc_client
+                .send_and_confirm_transaction(transaction)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to send transaction {} of {}",
+                        signatures.len() + 1,
+                        transactions.len()
+                    )
+                })?;
+            signatures.push(signature);
+        }
+        Ok(signatures)
+    }
+}
+
+/// Sends the whole sequence to a Jito block engine as one bundle and polls
+/// the block engine until it lands.
+pub struct BundleSubmitter {
+    http: reqwest::Client,
+    bundles_url: url::Url,
+    tip_account: Pubkey,
+    tip_lamports: u64,
+    poll_interval: Duration,
+}
+
+impl BundleSubmitter {
+    pub fn new(block_engine_url: &str, tip_account: Pubkey, tip_lamports: u64) -> Result<Self> {
+        let bundles_url = url::Url::parse(block_engine_url)
+            .and_then(|url| url.join("/api/v1/bundles"))
+            .map_err(|e| anyhow::anyhow!("Invalid BLOCK_ENGINE_URL {}: {}", block_engine_url, e))?;
+        Ok(Self {
+            http: reqwest::Client::new(),
+            bundles_url,
+            tip_account,
+            tip_lamports,
+            poll_interval: BUNDLE_STATUS_POLL_INTERVAL,
+        })
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T> {
+        let response: JsonRpcResponse<T> = self
+            .http
+            .post(self.bundles_url.clone())
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach block engine for {}", method))?
+            .json()
+            .await
+            .with_context(|| format!("Invalid block engine response to {}", method))?;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => anyhow::bail!("Block engine {} failed: {}", method, error.message),
+            (Some(result), None) => Ok(result),
+            (None, None) => anyhow::bail!("Block engine {} returned no result", method),
+        }
+    }
+
+    /// Send a bundle, returning the id the block engine assigned it.
+    pub async fn send_bundle(&self, transactions: &[VersionedTransaction]) -> Result<String> {
+        let encoded = transactions
+            .iter()
+            .map(|transaction| Ok(BASE64_STANDARD.encode(bincode::serialize(transaction)?)))
+            .collect::<Result<Vec<_>>>()?;
+        self.call("sendBundle", json!([encoded, { "encoding": "base64" }]))
+            .await
+    }
+
+    /// Status of a recently sent bundle.
+    pub async fn get_bundle_status(&self, bundle_id: &str) -> Result<BundleStatus> {
+        let statuses: InflightBundleStatuses = self
+            .call("getInflightBundleStatuses", json!([[bundle_id]]))
+            .await?;
+        Ok(statuses
+            .value
+            .into_iter()
+            .find(|status| status.bundle_id == bundle_id)
+            .map(|status| status.status)
+            .unwrap_or(BundleStatus::Unknown))
+    }
+}
+
+#[async_trait]
+impl Submitter for BundleSubmitter {
+    fn is_atomic(&self) -> bool {
+        true
+    }
+
+    fn tip_instruction(&self, payer: &Pubkey) -> Option<Instruction> {
+        Some(system_instruction::transfer(
+            payer,
+            &self.tip_account,
+            self.tip_lamports,
+        ))
+    }
+
+    async fn submit(&self, transactions: &[VersionedTransaction]) -> Result<Vec<Signature>> {
+        if transactions.is_empty() {
+            return Ok(vec![]);
+        }
+        if transactions.len() > MAX_BUNDLE_TRANSACTIONS {
+            anyhow::bail!(
+                "Bundle of {} transactions exceeds the limit of {}",
+                transactions.len(),
+                MAX_BUNDLE_TRANSACTIONS
+            );
+        }
+
+        let bundle_id = self.send_bundle(transactions).await?;
+        info!(
+            bundle_id,
+            "sent bundle of {} transactions",
+            transactions.len()
+        );
+
+        let started = tokio::time::Instant::now();
+        loop {
+            match self.get_bundle_status(&bundle_id).await? {
+                BundleStatus::Landed => break,
+                BundleStatus::Failed | BundleStatus::Invalid => {
+                    return Err(BundleRejected {
+                        bundle_id,
+                        reason: "failed or invalid".to_string(),
+                    }
+                    .into());
+                }
+                BundleStatus::Pending | BundleStatus::Unknown => {}
+            }
+            if started.elapsed() > BUNDLE_STATUS_TIMEOUT {
+                // the outcome is unknown, callers check the signatures on chain
+                anyhow::bail!("Timed out waiting for bundle {} to land", bundle_id);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+
+        info!(bundle_id, "bundle landed");
+        Ok(transactions
+            .iter()
+            .map(|transaction| transaction.signatures[0])
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BundleStatus {
+    Invalid,
+    Pending,
+    Failed,
+    Landed,
+    /// Not (yet) known to the block engine.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct InflightBundleStatuses {
+    value: Vec<InflightBundleStatus>,
+}
+
+#[derive(Deserialize)]
+struct InflightBundleStatus {
+    bundle_id: String,
+    status: BundleStatus,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, extract::State, routing::post};
+    use solana_hash::Hash;
+    use solana_keypair::Keypair;
+    use solana_message::{VersionedMessage, v0::Message};
+    use std::sync::Mutex;
+
+    /// Block engine stand-in that records every bundle it is sent and reports
+    /// them with a fixed status.
+    #[derive(Clone)]
+    struct MockBlockEngine {
+        status: &'static str,
+        bundles: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    async fn mock_rpc(
+        State(engine): State<MockBlockEngine>,
+        Json(request): Json<serde_json::Value>,
+    ) -> Json<serde_json::Value> {
+        let result = match request["method"].as_str() {
+            Some("sendBundle") => {
+                let transactions = request["params"][0]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|tx| tx.as_str().unwrap().to_string())
+                    .collect();
+                engine.bundles.lock().unwrap().push(transactions);
+                json!("bundle-1")
+            }
+            Some("getInflightBundleStatuses") => json!({
+                "context": { "slot": 1 },
+                "value": [{ "bundle_id": "bundle-1", "status": engine.status, "landed_slot": null }],
+            }),
+            _ => {
+                return Json(
+                    json!({ "jsonrpc": "2.0", "id": 1, "error": { "message": "unknown method" } }),
+                );
+            }
+        };
+        Json(json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+    }
+
+    async fn spawn_block_engine(status: &'static str) -> (String, MockBlockEngine) {
+        let engine = MockBlockEngine {
+            status,
+            bundles: Arc::default(),
+        };
+        let app = Router::new()
+            .route("/api/v1/bundles", post(mock_rpc))
+            .with_state(engine.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, engine)
+    }
+
+    fn transaction(payer: &Keypair, tip: Instruction) -> VersionedTransaction {
+        let message = VersionedMessage::V0(
+            Message::try_compile(&payer.pubkey(), &[tip], &[], Hash::new_unique()).unwrap(),
+        );
+        VersionedTransaction::try_new(message, &[payer]).unwrap()
+    }
+
+    fn submitter(url: &str) -> BundleSubmitter {
+        let mut submitter = BundleSubmitter::new(url, Pubkey::new_unique(), 1_000).unwrap();
+        submitter.poll_interval = Duration::from_millis(10);
+        submitter
+    }
+
+    #[tokio::test]
+    async fn test_bundle_submitter_sends_bundle_and_waits_for_landing() {
+        let (url, engine) = spawn_block_engine("Landed").await;
+        let submitter = submitter(&url);
+        let payer = Keypair::new();
+        let transactions: Vec<_> = (0..3)
+            .map(|_| transaction(&payer, submitter.tip_instruction(&payer.pubkey()).unwrap()))
+            .collect();
+
+        let signatures = submitter.submit(&transactions).await.unwrap();
+        assert_eq!(
+            signatures,
+            transactions
+                .iter()
+                .map(|tx| tx.signatures[0])
+                .collect::<Vec<_>>()
+        );
+
+        let bundles = engine.bundles.lock().unwrap();
+        assert_eq!(bundles.len(), 1);
+        let decoded: Vec<VersionedTransaction> = bundles[0]
+            .iter()
+            .map(|tx| bincode::deserialize(&BASE64_STANDARD.decode(tx).unwrap()).unwrap())
+            .collect();
+        assert_eq!(decoded, transactions);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_submitter_reports_rejected_bundle() {
+        let (url, _) = spawn_block_engine("Failed").await;
+        let submitter = submitter(&url);
+        let payer = Keypair::new();
+        let transactions = vec![transaction(
+            &payer,
+            submitter.tip_instruction(&payer.pubkey()).unwrap(),
+        )];
+
+        let err = submitter.submit(&transactions).await.unwrap_err();
+        let rejected = err.downcast_ref::<BundleRejected>().unwrap();
+        assert_eq!(rejected.bundle_id, "bundle-1");
+    }
+
+    #[tokio::test]
+    async fn test_bundle_submitter_rejects_oversized_bundle() {
+        let (url, engine) = spawn_block_engine("Landed").await;
+        let submitter = submitter(&url);
+        let payer = Keypair::new();
+        let transactions: Vec<_> = (0..MAX_BUNDLE_TRANSACTIONS + 1)
+            .map(|_| transaction(&payer, submitter.tip_instruction(&payer.pubkey()).unwrap()))
+            .collect();
+
+        assert!(submitter.submit(&transactions).await.is_err());
+        assert!(engine.bundles.lock().unwrap().is_empty());
+    }
+}
@@ -76,25 +76,33 @@ pub async fn get_maybe_ata(
     mint: &Pubkey,
 ) -> Result<(Pubkey, Option<solana_account::Account>)> {
     let ata = get_associated_token_address_with_program_id(
-        &owner, // Token account owner
-        &mint,  // Mint
+        owner, // Token account owner
+        mint,  // Mint
         &spl_token_2022::id(),
     );
 
-    let maybe_ata_account = match rpc_client.get_account(&ata).await {
-        Ok(account) => Some(account),
+    let maybe_ata_account = get_maybe_account(rpc_client, &ata).await?;
+
+    Ok((ata, maybe_ata_account))
+}
+
+/// Fetch an account, returning `None` instead of an error when it does not exist.
+pub async fn get_maybe_account(
+    rpc_client: Arc<RpcClient>,
+    address: &Pubkey,
+) -> Result<Option<solana_account::Account>> {
+    match rpc_client.get_account(address).await {
+        Ok(account) => Ok(Some(account)),
         Err(err) => {
             let is_missing = err.to_string().contains("AccountNotFound")
                 || err.to_string().contains("could not find account");
             if is_missing {
-                None
+                Ok(None)
             } else {
-                return Err(err.into());
+                Err(err.into())
             }
         }
-    };
-
-    Ok((ata, maybe_ata_account))
+    }
 }
 
 pub fn ata_has_confidential_transfer_extension(
@@ -1,14 +1,20 @@
 use crate::partial_sign::PartialSign;
+use crate::solana::GeneratedInstructions;
 use anyhow::Result;
-use solana_client::nonblocking::rpc_client::RpcClient;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::CommitmentConfig};
 use solana_hash::Hash;
 use solana_instruction::Instruction;
 use solana_message::{VersionedMessage, v0::Message};
+use solana_pubkey::Pubkey;
+use solana_signature::Signature;
 use solana_signer::Signer;
 use solana_transaction::versioned::VersionedTransaction;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tracing::error;
 
+const PENDING_TRANSACTION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
 pub async fn build_transaction(
     rpc_client: Arc<RpcClient>,
     recent_blockhash: Option<Hash>,
@@ -63,3 +69,180 @@ pub async fn build_transaction_with_signers(
 
     Ok(transaction)
 }
+
+/// Build a transaction paid for by an external `fee_payer`, signed only by the
+/// server-side `additional_signers` (e.g. proof context state accounts). The
+/// remaining signature slots are left empty for the client to fill.
+pub fn build_unsigned_transaction(
+    fee_payer: &Pubkey,
+    recent_blockhash: Hash,
+    generated: GeneratedInstructions,
+) -> Result<VersionedTransaction> {
+    let message = VersionedMessage::V0(Message::try_compile(
+        fee_payer,
+        &generated.instructions,
+        &[],
+        recent_blockhash,
+    )?);
+    let mut transaction = VersionedTransaction {
+        signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+        message,
+    };
+
+    for signer in generated.additional_signers {
+        transaction.partial_sign(&signer)?;
+    }
+
+    Ok(transaction)
+}
+
+/// Signer slots of `transaction` that have not been signed yet, as
+/// `(signature index, signer)` pairs.
+pub fn missing_signers(transaction: &VersionedTransaction) -> Vec<(usize, Pubkey)> {
+    let required_signature_count = transaction.message.header().num_required_signatures as usize;
+    transaction
+        .message
+        .static_account_keys()
+        .iter()
+        .take(required_signature_count)
+        .enumerate()
+        .filter(|(index, _)| {
+            transaction
+                .signatures
+                .get(*index)
+                .is_none_or(|signature| *signature == Signature::default())
+        })
+        .map(|(index, signer)| (index, *signer))
+        .collect()
+}
+
+/// The `signers` that one of `instructions` requires a signature from, for
+/// signing sponsored transactions where the owner is not always needed.
+pub fn required_signers(
+    instructions: &[Instruction],
+    signers: &[Arc<dyn Signer + Send + Sync>],
+) -> Vec<Arc<dyn Signer + Send + Sync>> {
+    signers
+        .iter()
+        .filter(|signer| {
+            let pubkey = signer.pubkey();
+            instructions.iter().any(|instruction| {
+                instruction
+                    .accounts
+                    .iter()
+                    .any(|account| account.is_signer && account.pubkey == pubkey)
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Serialize a transaction in the base64 wire format wallets accept.
+pub fn encode_transaction(transaction: &VersionedTransaction) -> Result<String> {
+    Ok(BASE64_STANDARD.encode(bincode::serialize(transaction)?))
+}
+
+pub fn decode_transaction(encoded: &str) -> Result<VersionedTransaction> {
+    let bytes = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|e| anyhow::anyhow!("Transaction is not valid base64: {}", e))?;
+    bincode::deserialize(&bytes).map_err(|e| anyhow::anyhow!("Invalid transaction: {}", e))
+}
+
+/// Final outcome of a transaction whose result was not observed when it was sent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionOutcome {
+    /// The transaction landed and succeeded.
+    Landed,
+    /// The transaction landed (or failed preflight) with an error.
+    Rejected(String),
+    /// The blockhash expired without the transaction landing; it can never land.
+    Dropped,
+}
+
+/// Wait until a previously sent transaction either shows up on chain or its
+/// blockhash expires.
+pub async fn wait_for_transaction_outcome(
+    rpc_client: Arc<RpcClient>,
+    signature: &Signature,
+    recent_blockhash: &Hash,
+) -> Result<TransactionOutcome> {
+    loop {
+        if let Some(status) = rpc_client.get_signature_status(signature).await? {
+            return Ok(match status {
+                Ok(()) => TransactionOutcome::Landed,
+                Err(e) => TransactionOutcome::Rejected(e.to_string()),
+            });
+        }
+
+        let blockhash_valid = rpc_client
+            .is_blockhash_valid(recent_blockhash, CommitmentConfig::processed())
+            .await?;
+        if !blockhash_valid {
+            // one last look, the transaction may have landed right before expiry
+            return Ok(match rpc_client.get_signature_status(signature).await? {
+                Some(Ok(())) => TransactionOutcome::Landed,
+                Some(Err(e)) => TransactionOutcome::Rejected(e.to_string()),
+                None => TransactionOutcome::Dropped,
+            });
+        }
+
+        tokio::time::sleep(PENDING_TRANSACTION_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_unsigned_transaction, decode_transaction, encode_transaction, missing_signers,
+        required_signers,
+    };
+    use crate::solana::GeneratedInstructions;
+    use solana_hash::Hash;
+    use solana_keypair::Keypair;
+    use solana_pubkey::Pubkey;
+    use solana_signer::Signer;
+    use solana_system_interface::instruction as system_instruction;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_unsigned_transaction_reports_missing_signers() {
+        let owner = Pubkey::new_unique();
+        let proof_account = Arc::new(Keypair::new());
+        let instructions = vec![
+            system_instruction::transfer(&owner, &proof_account.pubkey(), 1),
+            system_instruction::transfer(&proof_account.pubkey(), &owner, 1),
+        ];
+
+        let transaction = build_unsigned_transaction(
+            &owner,
+            Hash::new_unique(),
+            GeneratedInstructions {
+                instructions,
+                additional_signers: vec![proof_account.clone()],
+            },
+        )
+        .unwrap();
+
+        assert_eq!(missing_signers(&transaction), vec![(0, owner)]);
+
+        let decoded = decode_transaction(&encode_transaction(&transaction).unwrap()).unwrap();
+        assert_eq!(decoded, transaction);
+        assert_eq!(missing_signers(&decoded), vec![(0, owner)]);
+    }
+
+    #[test]
+    fn test_required_signers() {
+        let owner: Arc<dyn Signer + Send + Sync> = Arc::new(Keypair::new());
+        let other: Arc<dyn Signer + Send + Sync> = Arc::new(Keypair::new());
+        let instructions = vec![system_instruction::transfer(
+            &owner.pubkey(),
+            &other.pubkey(),
+            1,
+        )];
+
+        let required = required_signers(&instructions, &[owner.clone(), other]);
+        assert_eq!(required.len(), 1);
+        assert_eq!(required[0].pubkey(), owner.pubkey());
+    }
+}
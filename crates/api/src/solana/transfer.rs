@@ -1,16 +1,20 @@
 //! Confidential token transfers using SPL Token-2022 split proofs.
 //!
-//! Orchestrates the full confidential transfer flow: ensuring the sender
-//! has sufficient confidential balance (depositing and applying pending
-//! balance if needed), generating ZK proof context accounts (equality,
-//! ciphertext-validity, and range proofs) across multiple transactions,
-//! executing the confidential transfer, and closing proof accounts to
-//! reclaim rent.
+//! Provides the building blocks of the confidential transfer flow: ensuring
+//! the sender has sufficient confidential balance (depositing and applying
+//! pending balance if needed), generating ZK proof data (equality,
+//! ciphertext-validity, and range proofs), building the instructions that
+//! allocate and verify the proof context accounts, executing the
+//! confidential transfer, and closing proof accounts to reclaim rent.
+//!
+//! Each step is built independently so that the sequence can be driven (and
+//! resumed after a crash) by [`crate::jobs::transfer`].
 
 use anyhow::Result;
+use bytemuck::{Pod, bytes_of, pod_read_unaligned};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_instruction::Instruction;
-use solana_keypair::{Keypair, Signature};
+use solana_keypair::Keypair;
 use solana_pubkey::Pubkey;
 use solana_signer::Signer;
 use solana_transaction::Transaction;
@@ -20,263 +24,143 @@ use spl_token_2022::{
         BaseStateWithExtensions, StateWithExtensionsOwned,
         confidential_transfer::{
             ConfidentialTransferAccount, ConfidentialTransferMint,
-            account_info::TransferAccountInfo,
+            account_info::TransferAccountInfo, instruction::transfer,
         },
     },
     solana_zk_sdk::{
-        encryption::{elgamal, pod::elgamal::PodElGamalPubkey},
-        zk_elgamal_proof_program::instruction::{ContextStateInfo, close_context_state},
+        encryption::{
+            elgamal,
+            pod::elgamal::{PodElGamalCiphertext, PodElGamalPubkey},
+        },
+        zk_elgamal_proof_program::{
+            self,
+            instruction::{ContextStateInfo, close_context_state},
+            proof_data::{
+                BatchedGroupedCiphertext3HandlesValidityProofData, BatchedRangeProofU128Data,
+                CiphertextCommitmentEqualityProofData, ProofType,
+            },
+            state::ProofContextStateMeta,
+        },
     },
     state::{Account, Mint},
 };
-use spl_token_client::{
-    client::{ProgramRpcClient, ProgramRpcClientSendTransaction},
-    token::{ProofAccountWithCiphertext, Token},
-};
+use spl_token_confidential_transfer_proof_extraction::instruction::ProofLocation;
 use spl_token_confidential_transfer_proof_generation::transfer::TransferProofData;
-use std::sync::Arc;
+use std::{collections::HashSet, mem::size_of, sync::Arc};
 use tracing::info;
 
+use crate::fees::LAMPORTS_PER_SIGNATURE;
+use crate::solana::GeneratedInstructions;
 use crate::solana::balance::{apply_pending_balance, get_confidential_balances};
 use crate::solana::confidential_keys::ConfidentialKeys;
 use crate::solana::deposit::deposit_tokens;
-use crate::solana::zk::get_zk_proof_context_state_account_creation_instructions;
-use crate::{handlers::AppError, solana::utils::confidential_keys_for_mint};
+use crate::solana::tokens::get_maybe_account;
+use crate::solana::transaction::required_signers;
+use crate::solana::zk::{
+    get_zk_proof_context_state_account_creation_instructions, get_zk_proof_verify_instruction,
+};
 
-/// State carried between the proof-setup phase and the transfer/cleanup phases.
-///
-/// After `execute_proof_transactions_with_keys` creates the three proof context
-/// state accounts on-chain, this struct bundles everything the subsequent
-/// `execute_transfer` and `build_close_proof_accounts_ixs` calls need:
-///   - Pubkeys of the three proof accounts (so the transfer instruction can
-///     reference them, and the close instructions can reclaim their rent).
-///   - The split ciphertexts (lo/hi) attached to the ciphertext validity proof.
-///   - Sender/recipient addressing and cryptographic keys.
-struct TransferContext {
+/// Pubkeys of the three proof context state accounts used by a single transfer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProofAccounts {
     /// On-chain account holding the verified equality proof.
-    equality_proof_pubkey: Pubkey,
+    pub equality: Pubkey,
     /// On-chain account holding the verified ciphertext validity proof.
-    ciphertext_validity_proof_pubkey: Pubkey,
+    pub ciphertext_validity: Pubkey,
     /// On-chain account holding the verified range proof (Bulletproofs).
-    range_proof_pubkey: Pubkey,
-    /// Ciphertext validity proof account bundled with the split ciphertexts
-    /// (`ciphertext_lo` and `ciphertext_hi`) that encode the transfer amount.
-    ciphertext_validity_proof_account_with_ciphertext: ProofAccountWithCiphertext,
-    sender_associated_token_address: Pubkey,
-    #[allow(dead_code)]
-    recipient: Pubkey,
-    recipient_associated_token_address: Pubkey,
-    /// Snapshot of the sender's confidential transfer extension state, used to
-    /// provide the encrypted available balance to the transfer instruction.
-    sender_transfer_account_info: TransferAccountInfo,
-    /// Sender's ElGamal keypair and AE key, needed to decrypt/re-encrypt balances.
-    sender_confidential_keys: ConfidentialKeys,
-    /// Recipient's ElGamal public key -- transfer ciphertexts are encrypted under
-    /// this key so only the recipient can decrypt the received amount.
-    recipient_elgamal_pubkey: elgamal::ElGamalPubkey,
-    /// Optional auditor ElGamal public key from the mint. When set, transfer
-    /// ciphertexts are also encrypted under this key for compliance auditing.
-    auditor_elgamal_pubkey: elgamal::ElGamalPubkey,
+    pub range: Pubkey,
 }
 
-// entrypoint for the confidential transfer process
-pub async fn invoke_confidential_transfer(
-    rpc_client: Arc<RpcClient>,
-    sender: Arc<Keypair>,
-    recipient: &Pubkey,
-    confidential_transfer_amount: u64,
-    mint: &Pubkey,
-    decimals: u8,
-) -> Result<Vec<Signature>> {
-    // TODO: add these transactions?
-    ensure_confidential_balance(
-        rpc_client.clone(),
-        sender.clone(),
-        mint,
-        decimals,
-        confidential_transfer_amount,
-    )
-    .await?;
-
-    let (transactions, ctx) = execute_proof_transactions(
-        rpc_client.clone(),
-        sender.clone(),
-        recipient,
-        confidential_transfer_amount,
-        mint,
-        decimals,
-    )
-    .await
-    .map_err(|e| {
-        AppError::internal_server_error(anyhow::anyhow!(
-            "Failed to prepare proof transactions: {:?}",
-            e
-        ))
-    })?;
-
-    // 3 proof setup TXs, executed sequentially
-    // followed by, transfer
-    // followed by, close proof accounts
-    let signature_1 = rpc_client
-        .send_and_confirm_transaction(&transactions[0])
-        .await?;
-    info!(
-        sender = sender.pubkey().to_string(),
-        recipient = recipient.to_string(),
-        mint = mint.to_string(),
-        "Transfer [Allocate Proof Accounts] with signature={:?}",
-        signature_1
-    );
-
-    let signature_2 = rpc_client
-        .send_and_confirm_transaction(&transactions[1])
-        .await?;
-    info!(
-        sender = sender.pubkey().to_string(),
-        recipient = recipient.to_string(),
-        mint = mint.to_string(),
-        "Transfer [Encode Range Proof] with signature={:?}",
-        signature_2
-    );
-
-    let signature_3 = rpc_client
-        .send_and_confirm_transaction(&transactions[2])
-        .await?;
-    info!(
-        sender = sender.pubkey().to_string(),
-        recipient = recipient.to_string(),
-        mint = mint.to_string(),
-        "Transfer [Encode Remaining Proofs] with signature={:?}",
-        signature_3
-    );
-
-    let transfer_signature = execute_transfer(
-        rpc_client.clone(),
-        sender.clone(),
-        &ctx,
-        confidential_transfer_amount,
-        mint,
-        decimals,
-    )
-    .await
-    .map_err(|e| {
-        AppError::internal_server_error(anyhow::anyhow!("Failed to execute transfer: {:?}", e))
-    })?;
-
-    let close_ixs = build_close_proof_accounts_ixs(rpc_client.clone(), sender.clone(), &ctx)?;
-    let close_tx = Transaction::new_signed_with_payer(
-        &close_ixs,
-        Some(&sender.pubkey()),
-        &[&sender],
-        rpc_client.clone().get_latest_blockhash().await?,
-    );
-    let signature_4 = rpc_client
-        .clone()
-        .send_and_confirm_transaction(&close_tx)
-        .await?;
-    info!(
-        sender = sender.pubkey().to_string(),
-        recipient = recipient.to_string(),
-        mint = mint.to_string(),
-        "Transfer [Close Proof Accounts] with signature={:?}",
-        signature_4
-    );
-
-    Ok(vec![
-        signature_1,
-        signature_2,
-        signature_3,
-        transfer_signature,
-        signature_4,
-    ])
+impl ProofAccounts {
+    pub fn all(&self) -> [Pubkey; 3] {
+        [self.equality, self.ciphertext_validity, self.range]
+    }
 }
 
-async fn execute_transfer(
-    rpc_client: Arc<RpcClient>,
-    sender: Arc<dyn Signer + Send + Sync>,
-    ctx: &TransferContext,
-    confidential_transfer_amount: u64,
-    mint: &Pubkey,
-    decimals: u8,
-) -> Result<Signature> {
-    let token = {
-        let program_client: ProgramRpcClient<ProgramRpcClientSendTransaction> =
-            ProgramRpcClient::new(rpc_client, ProgramRpcClientSendTransaction);
-        Token::new(
-            Arc::new(program_client),
-            &spl_token_2022::id(),
-            mint,
-            Some(decimals),
-            sender.clone(),
-        )
-    };
-
-    // TODO: break this out to add a memo ix for funsies
-    let response = token
-        .confidential_transfer_transfer(
-            &ctx.sender_associated_token_address,
-            &ctx.recipient_associated_token_address,
-            &sender.pubkey(),
-            Some(&ctx.equality_proof_pubkey),
-            Some(&ctx.ciphertext_validity_proof_account_with_ciphertext),
-            Some(&ctx.range_proof_pubkey),
-            confidential_transfer_amount,
-            Some(ctx.sender_transfer_account_info.clone()),
-            &ctx.sender_confidential_keys.elgamal_keypair,
-            &ctx.sender_confidential_keys.ae_key,
-            &ctx.recipient_elgamal_pubkey,
-            Some(&ctx.auditor_elgamal_pubkey),
-            &[&sender],
-        )
-        .await?;
+/// Proof data generated for a transfer, plus the sender balance snapshot it was
+/// generated against.
+///
+/// Everything in here is plain old data, so it can be persisted as bytes and
+/// used to rebuild the verify and transfer instructions after a restart.
+#[derive(Clone)]
+pub struct TransferProofs {
+    pub equality_proof_data: CiphertextCommitmentEqualityProofData,
+    pub ciphertext_validity_proof_data: BatchedGroupedCiphertext3HandlesValidityProofData,
+    /// Encryption of the low 16 bits of the transfer amount under the auditor key.
+    pub ciphertext_lo: PodElGamalCiphertext,
+    /// Encryption of the remaining high bits of the transfer amount under the auditor key.
+    pub ciphertext_hi: PodElGamalCiphertext,
+    pub range_proof_data: BatchedRangeProofU128Data,
+    /// Snapshot of the sender's confidential transfer extension state, used to
+    /// provide the encrypted available balance to the transfer instruction.
+    pub sender_transfer_account_info: TransferAccountInfo,
+}
 
-    match response {
-        spl_token_client::client::RpcClientResponse::Signature(sig) => Ok(sig),
-        _ => Err(anyhow::anyhow!("Expected signature response from transfer")),
+impl TransferProofs {
+    const SERIALIZED_LEN: usize = size_of::<CiphertextCommitmentEqualityProofData>()
+        + size_of::<BatchedGroupedCiphertext3HandlesValidityProofData>()
+        + 2 * size_of::<PodElGamalCiphertext>()
+        + size_of::<BatchedRangeProofU128Data>()
+        + size_of::<TransferAccountInfo>();
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SERIALIZED_LEN);
+        bytes.extend_from_slice(bytes_of(&self.equality_proof_data));
+        bytes.extend_from_slice(bytes_of(&self.ciphertext_validity_proof_data));
+        bytes.extend_from_slice(bytes_of(&self.ciphertext_lo));
+        bytes.extend_from_slice(bytes_of(&self.ciphertext_hi));
+        bytes.extend_from_slice(bytes_of(&self.range_proof_data));
+        bytes.extend_from_slice(bytes_of(&self.sender_transfer_account_info));
+        bytes
     }
-}
 
-fn build_close_proof_accounts_ixs(
-    _rpc_client: Arc<RpcClient>,
-    sender: Arc<dyn Signer + Send + Sync>,
-    ctx: &TransferContext,
-) -> Result<Vec<Instruction>> {
-    let context_state_authority_pubkey = sender.pubkey();
-    let destination_account = &sender.pubkey();
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::SERIALIZED_LEN {
+            anyhow::bail!(
+                "Invalid transfer proof length: expected {}, received {}",
+                Self::SERIALIZED_LEN,
+                bytes.len()
+            );
+        }
 
-    let close_equality_proof_instruction = close_context_state(
-        ContextStateInfo {
-            context_state_account: &ctx.equality_proof_pubkey,
-            context_state_authority: &context_state_authority_pubkey,
-        },
-        &destination_account,
-    );
+        fn take<T: Pod>(bytes: &mut &[u8]) -> T {
+            let (head, tail) = bytes.split_at(size_of::<T>());
+            *bytes = tail;
+            pod_read_unaligned(head)
+        }
 
-    let close_ciphertext_validity_proof_instruction = close_context_state(
-        ContextStateInfo {
-            context_state_account: &ctx.ciphertext_validity_proof_pubkey,
-            context_state_authority: &context_state_authority_pubkey,
-        },
-        &destination_account,
-    );
+        let mut cursor = bytes;
+        Ok(Self {
+            equality_proof_data: take(&mut cursor),
+            ciphertext_validity_proof_data: take(&mut cursor),
+            ciphertext_lo: take(&mut cursor),
+            ciphertext_hi: take(&mut cursor),
+            range_proof_data: take(&mut cursor),
+            sender_transfer_account_info: take(&mut cursor),
+        })
+    }
+}
 
-    let close_range_proof_instruction = close_context_state(
-        ContextStateInfo {
-            context_state_account: &ctx.range_proof_pubkey,
-            context_state_authority: &context_state_authority_pubkey,
-        },
-        &destination_account,
-    );
+/// Output of [`prepare_transfer`]: the proof data and the fresh context state
+/// account keypairs that must sign the allocation transaction.
+pub struct PreparedTransfer {
+    pub proof_accounts: ProofAccounts,
+    pub proof_account_signers: Vec<Arc<dyn Signer + Send + Sync>>,
+    pub proofs: TransferProofs,
+}
 
-    // sender is signer as context state authority (and fee payer, if designated)
-    Ok(vec![
-        close_equality_proof_instruction,
-        close_ciphertext_validity_proof_instruction,
-        close_range_proof_instruction,
-    ])
+/// On-chain state of a proof context state account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofAccountState {
+    /// The account does not exist (never allocated, or already closed).
+    Missing,
+    /// The account is allocated but no proof has been verified into it yet.
+    Allocated,
+    /// The account holds a verified proof and can be closed by its authority.
+    Verified,
 }
 
-/// Generates the three ZK proof-setup transactions required before a confidential transfer.
+/// Generates the proof data required before a confidential transfer.
 ///
 /// # Background: Why ZK proofs are needed
 ///
@@ -313,111 +197,81 @@ fn build_close_proof_accounts_ixs(
 /// # Why three separate transactions?
 ///
 /// These proofs are too large to fit in a single Solana transaction. The "split proof"
-/// approach stores each proof in its own on-chain **context state account**. This
-/// function produces three transactions:
+/// approach stores each proof in its own on-chain **context state account**, which
+/// takes three transactions to set up:
 ///
-/// - **tx1**: Allocate all three context state accounts (equality, ciphertext validity,
-///   range proof). Each new account keypair must sign this transaction.
-/// - **tx2**: Submit and verify the range proof (largest proof, needs its own tx).
-/// - **tx3**: Submit and verify the equality proof and ciphertext validity proof
-///   (small enough to share a tx).
+/// - **tx1** ([`build_allocate_proof_accounts_ixs`]): Allocate all three context state
+///   accounts (equality, ciphertext validity, range proof). Each new account keypair
+///   must sign this transaction.
+/// - **tx2** ([`build_verify_range_proof_ixs`]): Submit and verify the range proof
+///   (largest proof, needs its own tx).
+/// - **tx3** ([`build_verify_remaining_proofs_ixs`]): Submit and verify the equality
+///   proof and ciphertext validity proof (small enough to share a tx).
 ///
-/// After these three transactions confirm, the caller invokes the actual
-/// `confidential_transfer_transfer` instruction, which references the proof accounts
-/// by pubkey. Once the transfer is done, the proof accounts are closed to reclaim rent.
+/// After these three transactions confirm, the caller sends the actual transfer
+/// instruction ([`build_transfer_ixs`]), which references the proof accounts by
+/// pubkey. Once the transfer is done, the proof accounts are closed to reclaim rent
+/// ([`build_close_proof_accounts_ixs`]).
 ///
 /// # Arguments
-/// * `rpc_client` - Solana RPC client for fetching on-chain state and blockhashes
-/// * `sender` - Signing keypair that owns the source token account and pays fees
+/// * `rpc_client` - Solana RPC client for fetching on-chain state
+/// * `sender` - Public key of the wallet that owns the source token account
 /// * `recipient` - Public key of the destination wallet
 /// * `confidential_transfer_amount` - Raw token amount to transfer (before decimals)
 /// * `mint` - SPL Token-2022 mint address (must have confidential transfer extension)
-/// * `decimals` - Mint decimal precision
 /// * `sender_confidential_keys` - Pre-derived ElGamal keypair (for encryption/decryption)
 ///   and authenticated encryption (AE) key (for local balance decryption)
-///
-/// # Returns
-/// A tuple of `(transactions, TransferContext)` where `transactions` is `[tx1, tx2, tx3]`
-/// and `TransferContext` carries all the pubkeys and proof data needed by the subsequent
-/// transfer and close-account steps.
-async fn execute_proof_transactions_with_keys(
+pub async fn prepare_transfer(
     rpc_client: Arc<RpcClient>,
-    sender: Arc<dyn Signer + Send + Sync>,
+    sender: &Pubkey,
     recipient: &Pubkey,
     confidential_transfer_amount: u64,
     mint: &Pubkey,
-    decimals: u8,
     sender_confidential_keys: &ConfidentialKeys,
-) -> Result<(Vec<Transaction>, TransferContext)> {
-    // ---------------------------------------------------------------------------
-    // 1. Derive associated token addresses (ATAs) for sender and recipient
-    // ---------------------------------------------------------------------------
-    let sender_associated_token_address: Pubkey =
-        get_associated_token_address_with_program_id(&sender.pubkey(), mint, &spl_token_2022::id());
-
-    let token = {
-        let program_client: ProgramRpcClient<ProgramRpcClientSendTransaction> =
-            ProgramRpcClient::new(rpc_client.clone(), ProgramRpcClientSendTransaction);
-
-        Token::new(
-            Arc::new(program_client),
-            &spl_token_2022::id(),
-            mint,
-            Some(decimals),
-            sender.clone(),
-        )
-    };
+) -> Result<PreparedTransfer> {
+    let sender_associated_token_address =
+        get_associated_token_address_with_program_id(sender, mint, &spl_token_2022::id());
     let recipient_associated_token_address =
         get_associated_token_address_with_program_id(recipient, mint, &spl_token_2022::id());
 
-    // The sender is the authority that can later close the proof context state accounts
-    // (reclaiming their rent).
-    let context_state_authority = &sender;
-
     // ---------------------------------------------------------------------------
-    // 2. Create fresh keypairs for the three proof context state accounts
+    // 1. Create fresh keypairs for the three proof context state accounts
     //
     //    Each ZK proof is stored in its own on-chain account. We generate ephemeral
     //    keypairs here; their pubkeys become the account addresses. These accounts
     //    are created in tx1, populated in tx2/tx3, referenced during the transfer,
     //    and finally closed to reclaim rent.
     // ---------------------------------------------------------------------------
-    let equality_proof_context_state_account = Keypair::new();
-    let equality_proof_pubkey = equality_proof_context_state_account.pubkey();
-
-    let ciphertext_validity_proof_context_state_account = Keypair::new();
-    let ciphertext_validity_proof_pubkey = ciphertext_validity_proof_context_state_account.pubkey();
-
-    let range_proof_context_state_account = Keypair::new();
-    let range_proof_pubkey = range_proof_context_state_account.pubkey();
+    let equality_proof_context_state_account = Arc::new(Keypair::new());
+    let ciphertext_validity_proof_context_state_account = Arc::new(Keypair::new());
+    let range_proof_context_state_account = Arc::new(Keypair::new());
 
     // ---------------------------------------------------------------------------
-    // 3. Fetch sender's on-chain confidential transfer state
+    // 2. Fetch sender's on-chain confidential transfer state
     //
     //    The ConfidentialTransferAccount extension on the sender's token account
     //    holds the encrypted available balance and pending balance. We wrap it in
     //    TransferAccountInfo which provides helper methods for proof generation.
     // ---------------------------------------------------------------------------
-    let sender_token_account_info = token
-        .get_account_info(&sender_associated_token_address)
+    let sender_token_account = rpc_client
+        .get_account(&sender_associated_token_address)
         .await?;
-
-    let sender_account_extension_data =
-        sender_token_account_info.get_extension::<ConfidentialTransferAccount>()?;
-
-    let sender_transfer_account_info = TransferAccountInfo::new(sender_account_extension_data);
+    let sender_token_account =
+        StateWithExtensionsOwned::<Account>::unpack(sender_token_account.data)?;
+    let sender_transfer_account_info = TransferAccountInfo::new(
+        sender_token_account.get_extension::<ConfidentialTransferAccount>()?,
+    );
 
     // ---------------------------------------------------------------------------
-    // 4. Fetch the recipient's ElGamal public key
+    // 3. Fetch the recipient's ElGamal public key
     //
     //    The recipient's token account also has a ConfidentialTransferAccount extension
     //    containing their ElGamal public key. We need this key to encrypt the transfer
     //    amount so that only the recipient can decrypt it.
     // ---------------------------------------------------------------------------
-    let recipient_account = token
-        .get_account(recipient_associated_token_address)
+    let recipient_account = rpc_client
+        .get_account(&recipient_associated_token_address)
         .await?;
-
     let recipient_elgamal_pubkey: elgamal::ElGamalPubkey =
         StateWithExtensionsOwned::<Account>::unpack(recipient_account.data)?
             .get_extension::<ConfidentialTransferAccount>()?
@@ -425,27 +279,25 @@ async fn execute_proof_transactions_with_keys(
             .try_into()?;
 
     // ---------------------------------------------------------------------------
-    // 5. Fetch the auditor's ElGamal public key from the mint
+    // 4. Fetch the auditor's ElGamal public key from the mint
     //
     //    The mint's ConfidentialTransferMint extension can specify an optional auditor
     //    public key. When present, transfer ciphertexts are also encrypted under this
     //    key, allowing a designated auditor to decrypt all transfers for compliance
     //    purposes without needing the sender's or recipient's private keys.
     // ---------------------------------------------------------------------------
-    let mint_account = token.get_account(*mint).await?;
-
+    let mint_account = rpc_client.get_account(mint).await?;
     let auditor_elgamal_pubkey_option = Option::<PodElGamalPubkey>::from(
         StateWithExtensionsOwned::<Mint>::unpack(mint_account.data)?
             .get_extension::<ConfidentialTransferMint>()?
             .auditor_elgamal_pubkey,
     );
-
     let auditor_elgamal_pubkey: elgamal::ElGamalPubkey = auditor_elgamal_pubkey_option
         .ok_or(anyhow::anyhow!("No Auditor ElGamal pubkey"))?
         .try_into()?;
 
     // ---------------------------------------------------------------------------
-    // 6. Generate the three split transfer proofs (client-side cryptography)
+    // 5. Generate the three split transfer proofs (client-side cryptography)
     //
     //    `generate_split_transfer_proof_data` does the heavy lifting:
     //      - Decrypts the sender's current encrypted balance using the ElGamal keypair
@@ -460,178 +312,341 @@ async fn execute_proof_transactions_with_keys(
     //          * ciphertext_validity   -- proves ciphertext_lo/hi are well-formed
     //                                     under the recipient/auditor keys
     //          * range_proof_data      -- proves all committed values are non-negative
+    //
+    //    Generating the range proof takes long enough to stall the async runtime,
+    //    so it runs on the blocking pool.
     // ---------------------------------------------------------------------------
+    let keys = sender_confidential_keys.clone();
     let TransferProofData {
         equality_proof_data,
         ciphertext_validity_proof_data_with_ciphertext,
         range_proof_data,
-    } = sender_transfer_account_info.generate_split_transfer_proof_data(
-        confidential_transfer_amount,
-        &sender_confidential_keys.elgamal_keypair,
-        &sender_confidential_keys.ae_key,
-        &recipient_elgamal_pubkey,
-        Some(&auditor_elgamal_pubkey),
-    )?;
-
-    // ---------------------------------------------------------------------------
-    // 7. Build create + verify instruction pairs for each proof
-    //
-    //    Each proof needs two instructions:
-    //      - A "create" instruction that allocates the context state account and
-    //        writes the proof data into it.
-    //      - A "verify" instruction that invokes the ZK ElGamal proof program to
-    //        cryptographically verify the proof on-chain.
-    //
-    //    The create and verify steps are split across transactions because the
-    //    account must exist before it can be verified, and transaction size limits
-    //    prevent bundling everything into one tx.
-    // ---------------------------------------------------------------------------
-    let (range_create_ix, range_verify_ix) =
-        get_zk_proof_context_state_account_creation_instructions(
-            rpc_client.clone(),
-            &sender.pubkey(),
-            &range_proof_context_state_account.pubkey(),
-            &context_state_authority.pubkey(),
-            &range_proof_data,
+    } = tokio::task::spawn_blocking(move || {
+        sender_transfer_account_info.generate_split_transfer_proof_data(
+            confidential_transfer_amount,
+            &keys.elgamal_keypair,
+            &keys.ae_key,
+            &recipient_elgamal_pubkey,
+            Some(&auditor_elgamal_pubkey),
         )
-        .await?;
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to join proof generation task: {:?}", e))??;
 
-    let (equality_create_ix, equality_verify_ix) =
-        get_zk_proof_context_state_account_creation_instructions(
-            rpc_client.clone(),
-            &sender.pubkey(),
-            &equality_proof_context_state_account.pubkey(),
-            &context_state_authority.pubkey(),
-            &equality_proof_data,
-        )
-        .await?;
+    Ok(PreparedTransfer {
+        proof_accounts: ProofAccounts {
+            equality: equality_proof_context_state_account.pubkey(),
+            ciphertext_validity: ciphertext_validity_proof_context_state_account.pubkey(),
+            range: range_proof_context_state_account.pubkey(),
+        },
+        proof_account_signers: vec![
+            range_proof_context_state_account,
+            equality_proof_context_state_account,
+            ciphertext_validity_proof_context_state_account,
+        ],
+        proofs: TransferProofs {
+            equality_proof_data,
+            ciphertext_validity_proof_data: ciphertext_validity_proof_data_with_ciphertext
+                .proof_data,
+            ciphertext_lo: ciphertext_validity_proof_data_with_ciphertext.ciphertext_lo,
+            ciphertext_hi: ciphertext_validity_proof_data_with_ciphertext.ciphertext_hi,
+            range_proof_data,
+            sender_transfer_account_info,
+        },
+    })
+}
 
-    let (cv_create_ix, cv_verify_ix) = get_zk_proof_context_state_account_creation_instructions(
+/// tx1 - Allocate all three context state accounts. Each account keypair must sign
+/// because Solana requires the private key of a new account to authorize its creation.
+pub async fn build_allocate_proof_accounts_ixs(
+    rpc_client: Arc<RpcClient>,
+    fee_payer: &Pubkey,
+    context_state_authority: &Pubkey,
+    proof_accounts: &ProofAccounts,
+    proofs: &TransferProofs,
+) -> Result<Vec<Instruction>> {
+    let (range_create_ix, _) = get_zk_proof_context_state_account_creation_instructions(
         rpc_client.clone(),
-        &sender.pubkey(),
-        &ciphertext_validity_proof_context_state_account.pubkey(),
-        &context_state_authority.pubkey(),
-        &ciphertext_validity_proof_data_with_ciphertext.proof_data,
+        fee_payer,
+        &proof_accounts.range,
+        context_state_authority,
+        &proofs.range_proof_data,
     )
     .await?;
 
-    // ---------------------------------------------------------------------------
-    // 8. Bundle instructions into three transactions
-    //
-    //    tx1 - Allocate all three context state accounts. Each account keypair
-    //          must sign because Solana requires the private key of a new account
-    //          to authorize its creation.
-    //
-    //    tx2 - Verify the range proof (Bulletproofs). This is the largest proof
-    //          and needs a dedicated transaction due to size constraints.
-    //
-    //    tx3 - Verify the equality proof and ciphertext validity proof. These are
-    //          smaller sigma-protocol proofs that fit together in one transaction.
-    // ---------------------------------------------------------------------------
-    let tx1 = Transaction::new_signed_with_payer(
-        &[
-            range_create_ix.clone(),
-            equality_create_ix.clone(),
-            cv_create_ix.clone(),
-        ],
-        Some(&sender.pubkey()),
-        &[
-            &sender,
-            &range_proof_context_state_account as &dyn Signer,
-            &equality_proof_context_state_account as &dyn Signer,
-            &ciphertext_validity_proof_context_state_account as &dyn Signer,
-        ],
-        rpc_client.get_latest_blockhash().await?,
-    );
+    let (equality_create_ix, _) = get_zk_proof_context_state_account_creation_instructions(
+        rpc_client.clone(),
+        fee_payer,
+        &proof_accounts.equality,
+        context_state_authority,
+        &proofs.equality_proof_data,
+    )
+    .await?;
 
-    let tx2 = Transaction::new_signed_with_payer(
-        &[range_verify_ix],
-        Some(&sender.pubkey()),
-        &[&sender],
-        rpc_client.get_latest_blockhash().await?,
-    );
+    let (cv_create_ix, _) = get_zk_proof_context_state_account_creation_instructions(
+        rpc_client,
+        fee_payer,
+        &proof_accounts.ciphertext_validity,
+        context_state_authority,
+        &proofs.ciphertext_validity_proof_data,
+    )
+    .await?;
 
-    let tx3 = Transaction::new_signed_with_payer(
-        &[equality_verify_ix, cv_verify_ix],
-        Some(&sender.pubkey()),
-        &[&sender],
-        rpc_client.get_latest_blockhash().await?,
-    );
+    Ok(vec![range_create_ix, equality_create_ix, cv_create_ix])
+}
 
-    // ---------------------------------------------------------------------------
-    // 9. Package proof metadata for the transfer step
-    //
-    //    The ciphertext validity proof account carries extra data alongside its
-    //    pubkey: the split ciphertexts (lo/hi). The transfer instruction needs these
-    //    to reconstruct the full encrypted transfer amount on-chain.
-    //      ciphertext_lo = encryption of the low 16 bits of the transfer amount
-    //      ciphertext_hi = encryption of the remaining high bits
-    //    Full encrypted amount = ciphertext_lo + 2^16 * ciphertext_hi
-    // ---------------------------------------------------------------------------
-    let ciphertext_validity_proof_account_with_ciphertext = ProofAccountWithCiphertext {
-        context_state_account: ciphertext_validity_proof_pubkey,
-        ciphertext_lo: ciphertext_validity_proof_data_with_ciphertext.ciphertext_lo,
-        ciphertext_hi: ciphertext_validity_proof_data_with_ciphertext.ciphertext_hi,
-    };
+/// tx2 - Verify the range proof (Bulletproofs). This is the largest proof and needs a
+/// dedicated transaction due to size constraints.
+pub fn build_verify_range_proof_ixs(
+    context_state_authority: &Pubkey,
+    proof_accounts: &ProofAccounts,
+    proofs: &TransferProofs,
+) -> Result<Vec<Instruction>> {
+    Ok(vec![get_zk_proof_verify_instruction(
+        &proof_accounts.range,
+        context_state_authority,
+        &proofs.range_proof_data,
+    )?])
+}
+
+/// tx3 - Verify the equality proof and ciphertext validity proof. These are smaller
+/// sigma-protocol proofs that fit together in one transaction.
+pub fn build_verify_remaining_proofs_ixs(
+    context_state_authority: &Pubkey,
+    proof_accounts: &ProofAccounts,
+    proofs: &TransferProofs,
+) -> Result<Vec<Instruction>> {
+    Ok(vec![
+        get_zk_proof_verify_instruction(
+            &proof_accounts.equality,
+            context_state_authority,
+            &proofs.equality_proof_data,
+        )?,
+        get_zk_proof_verify_instruction(
+            &proof_accounts.ciphertext_validity,
+            context_state_authority,
+            &proofs.ciphertext_validity_proof_data,
+        )?,
+    ])
+}
+
+/// Build the confidential transfer instruction referencing the verified proof accounts.
+///
+/// The ciphertext validity proof account carries extra data alongside its pubkey: the
+/// split ciphertexts (lo/hi). The transfer instruction needs these to reconstruct the
+/// full encrypted transfer amount on-chain.
+///   ciphertext_lo = encryption of the low 16 bits of the transfer amount
+///   ciphertext_hi = encryption of the remaining high bits
+///   Full encrypted amount = ciphertext_lo + 2^16 * ciphertext_hi
+pub fn build_transfer_ixs(
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    mint: &Pubkey,
+    confidential_transfer_amount: u64,
+    proof_accounts: &ProofAccounts,
+    proofs: &TransferProofs,
+    sender_confidential_keys: &ConfidentialKeys,
+) -> Result<Vec<Instruction>> {
+    let sender_associated_token_address =
+        get_associated_token_address_with_program_id(sender, mint, &spl_token_2022::id());
+    let recipient_associated_token_address =
+        get_associated_token_address_with_program_id(recipient, mint, &spl_token_2022::id());
+
+    let new_decryptable_available_balance = proofs
+        .sender_transfer_account_info
+        .new_decryptable_available_balance(
+            confidential_transfer_amount,
+            &sender_confidential_keys.ae_key,
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to compute new available balance: {:?}", e))?
+        .into();
+
+    let instructions = transfer(
+        &spl_token_2022::id(),
+        &sender_associated_token_address,
+        mint,
+        &recipient_associated_token_address,
+        &new_decryptable_available_balance,
+        &proofs.ciphertext_lo,
+        &proofs.ciphertext_hi,
+        sender,
+        &[],
+        ProofLocation::ContextStateAccount(&proof_accounts.equality),
+        ProofLocation::ContextStateAccount(&proof_accounts.ciphertext_validity),
+        ProofLocation::ContextStateAccount(&proof_accounts.range),
+    )?;
+
+    Ok(instructions)
+}
+
+/// Close the given proof context state accounts, refunding rent to
+/// `destination`, whoever paid for them.
+pub fn build_close_proof_accounts_ixs(
+    context_state_authority: &Pubkey,
+    destination: &Pubkey,
+    proof_accounts: &[Pubkey],
+) -> Vec<Instruction> {
+    proof_accounts
+        .iter()
+        .map(|context_state_account| {
+            close_context_state(
+                ContextStateInfo {
+                    context_state_account,
+                    context_state_authority,
+                },
+                destination,
+            )
+        })
+        .collect()
+}
 
-    let ctx = TransferContext {
-        equality_proof_pubkey,
-        ciphertext_validity_proof_pubkey,
-        range_proof_pubkey,
-        ciphertext_validity_proof_account_with_ciphertext,
-        sender_associated_token_address,
-        recipient: *recipient,
-        recipient_associated_token_address,
-        sender_transfer_account_info,
-        sender_confidential_keys: sender_confidential_keys.clone(),
-        recipient_elgamal_pubkey,
-        auditor_elgamal_pubkey,
+/// Look up whether a proof context state account exists and holds a verified proof.
+pub async fn get_proof_account_state(
+    rpc_client: Arc<RpcClient>,
+    proof_account: &Pubkey,
+) -> Result<ProofAccountState> {
+    let Some(account) = get_maybe_account(rpc_client, proof_account).await? else {
+        return Ok(ProofAccountState::Missing);
     };
 
-    Ok((vec![tx1, tx2, tx3], ctx))
+    if account.owner != zk_elgamal_proof_program::id() {
+        anyhow::bail!(
+            "Account {} is not owned by the ZK ElGamal proof program",
+            proof_account
+        );
+    }
+
+    let meta = ProofContextStateMeta::try_from_bytes(&account.data)
+        .map_err(|e| anyhow::anyhow!("Failed to parse proof context state: {:?}", e))?;
+    if meta.proof_type == ProofType::Uninitialized.into() {
+        return Ok(ProofAccountState::Allocated);
+    }
+
+    Ok(ProofAccountState::Verified)
 }
 
-/// Execute proof transactions - derives confidential keys from the sender signer
-/// In a non-custodial flow, we cannot execute the transactions server side
-async fn execute_proof_transactions(
+/// Build every transaction of a confidential transfer without signing for the
+/// sender, in the order they must land. This is the same sequence
+/// [`crate::jobs::transfer`] drives for custodial wallets, for wallets that
+/// sign on the client: the sender pays fees and is the proof account authority.
+pub async fn build_transfer_steps(
     rpc_client: Arc<RpcClient>,
-    sender: Arc<dyn Signer + Send + Sync>,
+    sender: &Pubkey,
     recipient: &Pubkey,
     confidential_transfer_amount: u64,
     mint: &Pubkey,
-    decimals: u8,
-) -> Result<(Vec<Transaction>, TransferContext)> {
-    let sender_confidential_keys = confidential_keys_for_mint(sender.clone(), mint)?;
-    execute_proof_transactions_with_keys(
+    sender_confidential_keys: &ConfidentialKeys,
+) -> Result<Vec<GeneratedInstructions>> {
+    let prepared = prepare_transfer(
+        rpc_client.clone(),
+        sender,
+        recipient,
+        confidential_transfer_amount,
+        mint,
+        sender_confidential_keys,
+    )
+    .await?;
+
+    build_prepared_transfer_steps(
         rpc_client,
         sender,
+        sender,
         recipient,
         confidential_transfer_amount,
         mint,
-        decimals,
-        &sender_confidential_keys,
+        prepared,
+        sender_confidential_keys,
     )
     .await
 }
 
-async fn ensure_confidential_balance(
+/// Build the five transactions of an already prepared transfer, from
+/// allocating the proof accounts to closing them. `fee_payer` funds the
+/// proof accounts and gets their rent back.
+#[allow(clippy::too_many_arguments)]
+pub async fn build_prepared_transfer_steps(
+    rpc_client: Arc<RpcClient>,
+    fee_payer: &Pubkey,
+    sender: &Pubkey,
+    recipient: &Pubkey,
+    confidential_transfer_amount: u64,
+    mint: &Pubkey,
+    prepared: PreparedTransfer,
+    sender_confidential_keys: &ConfidentialKeys,
+) -> Result<Vec<GeneratedInstructions>> {
+    let PreparedTransfer {
+        proof_accounts,
+        proof_account_signers,
+        proofs,
+    } = prepared;
+
+    let step = |instructions| GeneratedInstructions {
+        instructions,
+        additional_signers: vec![],
+    };
+
+    Ok(vec![
+        GeneratedInstructions {
+            instructions: build_allocate_proof_accounts_ixs(
+                rpc_client,
+                fee_payer,
+                sender,
+                &proof_accounts,
+                &proofs,
+            )
+            .await?,
+            additional_signers: proof_account_signers,
+        },
+        step(build_verify_range_proof_ixs(
+            sender,
+            &proof_accounts,
+            &proofs,
+        )?),
+        step(build_verify_remaining_proofs_ixs(
+            sender,
+            &proof_accounts,
+            &proofs,
+        )?),
+        step(build_transfer_ixs(
+            sender,
+            recipient,
+            mint,
+            confidential_transfer_amount,
+            &proof_accounts,
+            &proofs,
+            sender_confidential_keys,
+        )?),
+        step(build_close_proof_accounts_ixs(
+            sender,
+            fee_payer,
+            &proof_accounts.all(),
+        )),
+    ])
+}
+
+/// Make sure `sender` has at least `amount` available, depositing from the
+/// public balance and applying the pending balance if needed. `fee_payer`
+/// pays for the transactions, and the fees paid are returned.
+pub async fn ensure_confidential_balance(
     rpc_client: Arc<RpcClient>,
+    fee_payer: Arc<dyn Signer + Send + Sync>,
     sender: Arc<dyn Signer + Send + Sync>,
     mint: &Pubkey,
     decimals: u8,
     amount: u64,
-) -> Result<()> {
+) -> Result<u64> {
     let (pending_balance, available_balance) =
         get_confidential_balances(rpc_client.clone(), sender.clone(), mint).await?;
 
     if available_balance >= amount {
-        return Ok(());
+        return Ok(0);
     }
 
+    let mut fees = 0;
     if pending_balance == 0 {
         let deposit_amount = amount.saturating_sub(available_balance);
         if deposit_amount == 0 {
-            return Ok(());
+            return Ok(0);
         }
 
         let deposit_instructions = deposit_tokens(
@@ -642,22 +657,15 @@ async fn ensure_confidential_balance(
             deposit_amount,
         )
         .await?;
-        let mut deposit_signers: Vec<&dyn Signer> = deposit_instructions
-            .additional_signers
-            .iter()
-            .map(|signer| signer.as_ref() as &dyn Signer)
-            .collect();
-        if deposit_signers.is_empty() {
-            deposit_signers.push(sender.as_ref());
-        }
-
-        let deposit_tx = Transaction::new_signed_with_payer(
-            &deposit_instructions.instructions,
-            Some(&sender.pubkey()),
-            &deposit_signers,
-            rpc_client.get_latest_blockhash().await?,
-        );
+        let deposit_tx = sign_with_payer(
+            rpc_client.clone(),
+            fee_payer.clone(),
+            sender.clone(),
+            deposit_instructions,
+        )
+        .await?;
         let deposit_signature = rpc_client.send_and_confirm_transaction(&deposit_tx).await?;
+        fees += deposit_tx.signatures.len() as u64 * LAMPORTS_PER_SIGNATURE;
         info!(
             "Transfer [Deposit Confidential Pending Balance] with signature={:?}",
             deposit_signature
@@ -672,22 +680,15 @@ async fn ensure_confidential_balance(
         decimals,
     )
     .await?;
-    let mut apply_signers: Vec<&dyn Signer> = apply_instructions
-        .additional_signers
-        .iter()
-        .map(|signer| signer.as_ref() as &dyn Signer)
-        .collect();
-    if apply_signers.is_empty() {
-        apply_signers.push(sender.as_ref());
-    }
-
-    let apply_tx = Transaction::new_signed_with_payer(
-        &apply_instructions.instructions,
-        Some(&sender.pubkey()),
-        &apply_signers,
-        rpc_client.get_latest_blockhash().await?,
-    );
+    let apply_tx = sign_with_payer(
+        rpc_client.clone(),
+        fee_payer,
+        sender.clone(),
+        apply_instructions,
+    )
+    .await?;
     let apply_signature = rpc_client.send_and_confirm_transaction(&apply_tx).await?;
+    fees += apply_tx.signatures.len() as u64 * LAMPORTS_PER_SIGNATURE;
     info!(
         "Transfer [Apply Pending Balance] with signature={:?}",
         apply_signature
@@ -696,7 +697,7 @@ async fn ensure_confidential_balance(
     let (pending_after, available_after) =
         get_confidential_balances(rpc_client, sender, mint).await?;
     if available_after >= amount {
-        return Ok(());
+        return Ok(fees);
     }
 
     Err(anyhow::anyhow!(
@@ -706,3 +707,88 @@ async fn ensure_confidential_balance(
         amount
     ))
 }
+
+/// Sign `generated` with the fee payer and whichever of its own signers and
+/// the sender the instructions need.
+async fn sign_with_payer(
+    rpc_client: Arc<RpcClient>,
+    fee_payer: Arc<dyn Signer + Send + Sync>,
+    sender: Arc<dyn Signer + Send + Sync>,
+    generated: GeneratedInstructions,
+) -> Result<Transaction> {
+    let recent_blockhash = rpc_client.get_latest_blockhash().await?;
+    let mut candidates = generated.additional_signers;
+    candidates.push(sender);
+    let required = required_signers(&generated.instructions, &candidates);
+
+    let mut signers: Vec<&(dyn Signer + Send + Sync)> = vec![fee_payer.as_ref()];
+    signers.extend(required.iter().map(|signer| signer.as_ref()));
+    // the fee payer may also be the sender
+    let mut seen = HashSet::new();
+    signers.retain(|signer| seen.insert(signer.pubkey()));
+
+    Ok(Transaction::new_signed_with_payer(
+        &generated.instructions,
+        Some(&fee_payer.pubkey()),
+        &signers,
+        recent_blockhash,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solana::utils::confidential_keys_for_mint;
+    use spl_token_2022::solana_zk_sdk::encryption::elgamal::ElGamalKeypair;
+
+    #[test]
+    fn test_transfer_proofs_roundtrip() {
+        let sender = Arc::new(Keypair::new());
+        let mint = Pubkey::new_unique();
+        let keys = confidential_keys_for_mint(sender, &mint).unwrap();
+        let recipient = ElGamalKeypair::new_rand();
+        let auditor = ElGamalKeypair::new_rand();
+
+        let sender_transfer_account_info = TransferAccountInfo {
+            available_balance: keys.elgamal_keypair.pubkey().encrypt(100_u64).into(),
+            decryptable_available_balance: keys.ae_key.encrypt(100).into(),
+        };
+
+        let TransferProofData {
+            equality_proof_data,
+            ciphertext_validity_proof_data_with_ciphertext,
+            range_proof_data,
+        } = sender_transfer_account_info
+            .generate_split_transfer_proof_data(
+                42,
+                &keys.elgamal_keypair,
+                &keys.ae_key,
+                recipient.pubkey(),
+                Some(auditor.pubkey()),
+            )
+            .unwrap();
+
+        let proofs = TransferProofs {
+            equality_proof_data,
+            ciphertext_validity_proof_data: ciphertext_validity_proof_data_with_ciphertext
+                .proof_data,
+            ciphertext_lo: ciphertext_validity_proof_data_with_ciphertext.ciphertext_lo,
+            ciphertext_hi: ciphertext_validity_proof_data_with_ciphertext.ciphertext_hi,
+            range_proof_data,
+            sender_transfer_account_info,
+        };
+
+        let bytes = proofs.to_bytes();
+        let decoded = TransferProofs::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(
+            decoded.sender_transfer_account_info,
+            proofs.sender_transfer_account_info
+        );
+    }
+
+    #[test]
+    fn test_transfer_proofs_rejects_bad_length() {
+        assert!(TransferProofs::from_bytes(&[0u8; 12]).is_err());
+    }
+}
@@ -3,14 +3,30 @@ use std::sync::Arc;
 use anyhow::Result;
 use solana_keypair::Keypair;
 use solana_pubkey::Pubkey;
+use solana_signature::Signature;
 use solana_signer::Signer;
 use spl_associated_token_account::get_associated_token_address_with_program_id;
-use spl_token_2022::solana_zk_sdk::encryption::{auth_encryption::AeKey, elgamal::ElGamalKeypair};
+use spl_token_2022::solana_zk_sdk::encryption::{
+    auth_encryption::AeKey,
+    elgamal::{ElGamalCiphertext, ElGamalKeypair, ElGamalSecretKey},
+    pod::elgamal::PodElGamalCiphertext,
+};
+use spl_token_2022_interface::extension::confidential_transfer::PENDING_BALANCE_LO_BIT_LENGTH;
 
 use crate::solana::confidential_keys::ConfidentialKeys;
 
-pub fn kp_from_base58_string(kp: &str) -> Keypair {
-    Keypair::from_base58_string(kp)
+/// Decode a base58 encoded 64 byte keypair. Errors never include the input,
+/// which is a secret.
+pub fn kp_from_base58_string(kp: &str) -> Result<Keypair> {
+    let bytes = bs58::decode(kp)
+        .into_vec()
+        .map_err(|_| anyhow::anyhow!("Keypair is not valid base58"))?;
+    Keypair::try_from(bytes.as_slice()).map_err(|_| {
+        anyhow::anyhow!(
+            "Keypair must be 64 bytes of secret and public key, got {} bytes",
+            bytes.len()
+        )
+    })
 }
 
 pub fn el_gamal_deterministic(kp: &dyn Signer) -> Result<ElGamalKeypair> {
@@ -37,3 +53,74 @@ pub fn confidential_keys_for_mint(
     ConfidentialKeys::from_signer(owner.as_ref(), &ata.to_bytes())
         .map_err(|e| anyhow::anyhow!("Failed to derive confidential keys: {}", e))
 }
+
+/// Derive confidential keys for a given mint from the owner's signature over
+/// the ATA address (non-custodial).
+///
+/// The signature is checked first: a signature over anything else would still
+/// derive keys, just not the ones the owner's token account is configured with.
+pub fn confidential_keys_from_signature(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    signature: &Signature,
+) -> Result<ConfidentialKeys> {
+    let ata = get_associated_token_address_with_program_id(owner, mint, &spl_token_2022::id());
+    if !signature.verify(owner.as_ref(), &ata.to_bytes()) {
+        anyhow::bail!("Signature is not the owner's signature over the token account address");
+    }
+    ConfidentialKeys::from_signature_bytes(*owner, signature.as_array(), &ata.to_bytes())
+        .map_err(|e| anyhow::anyhow!("Failed to derive confidential keys: {}", e))
+}
+
+/// Decrypt an amount split into low 16 bits and high bits, as confidential
+/// transfer, mint and burn amounts are.
+pub fn decrypt_split_amount(
+    secret: &ElGamalSecretKey,
+    ciphertext_lo: &PodElGamalCiphertext,
+    ciphertext_hi: &PodElGamalCiphertext,
+) -> Option<u64> {
+    let ciphertext_lo: ElGamalCiphertext = (*ciphertext_lo).try_into().ok()?;
+    let ciphertext_hi: ElGamalCiphertext = (*ciphertext_hi).try_into().ok()?;
+    let amount_lo = secret.decrypt_u32(&ciphertext_lo)?;
+    let amount_hi = secret.decrypt_u32(&ciphertext_hi)?;
+    amount_hi
+        .checked_shl(PENDING_BALANCE_LO_BIT_LENGTH)
+        .and_then(|hi| hi.checked_add(amount_lo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kp_from_base58_string() {
+        let keypair = Keypair::new();
+        let decoded = kp_from_base58_string(&keypair.to_base58_string()).unwrap();
+        assert_eq!(decoded.pubkey(), keypair.pubkey());
+
+        assert!(kp_from_base58_string("not base58!").is_err());
+        assert!(kp_from_base58_string(&keypair.pubkey().to_string()).is_err());
+    }
+
+    #[test]
+    fn test_confidential_keys_from_signature_checks_seed() {
+        let owner = Keypair::new();
+        let mint = Pubkey::new_unique();
+        let ata = get_associated_token_address_with_program_id(
+            &owner.pubkey(),
+            &mint,
+            &spl_token_2022::id(),
+        );
+
+        let signature = owner.sign_message(&ata.to_bytes());
+        assert!(confidential_keys_from_signature(&owner.pubkey(), &mint, &signature).is_ok());
+
+        // signed by someone else, or over another mint's token account
+        let other = Keypair::new().sign_message(&ata.to_bytes());
+        assert!(confidential_keys_from_signature(&owner.pubkey(), &mint, &other).is_err());
+        assert!(
+            confidential_keys_from_signature(&owner.pubkey(), &Pubkey::new_unique(), &signature)
+                .is_err()
+        );
+    }
+}
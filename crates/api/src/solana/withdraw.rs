@@ -3,217 +3,203 @@
 //! Generates the required ZK proofs (equality and range), creates and
 //! verifies proof context state accounts, executes the confidential
 //! withdraw instruction, and closes proof accounts to reclaim rent.
-//! Two variants are provided: [`withdraw_tokens_with_keys`] for pre-derived
-//! keys (browser wallet flows) and [`withdraw_tokens`] as a convenience
-//! wrapper that derives keys from a [`Signer`].
+//! [`build_withdraw_steps`] only builds the transactions: custodial wallets
+//! send them through a [`crate::solana::submit::Submitter`], wallets that
+//! sign on the client submit them one by one.
 
 use anyhow::Result;
+use bytemuck::{bytes_of, pod_read_unaligned};
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_keypair::{Keypair, Signature};
+use solana_keypair::Keypair;
 use solana_pubkey::Pubkey;
 use solana_signer::Signer;
 use spl_associated_token_account::get_associated_token_address_with_program_id;
-use spl_token_2022::extension::{
-    BaseStateWithExtensions,
-    confidential_transfer::{ConfidentialTransferAccount, account_info::WithdrawAccountInfo},
+use spl_token_2022::solana_zk_sdk::zk_elgamal_proof_program::proof_data::{
+    BatchedRangeProofU64Data, CiphertextCommitmentEqualityProofData,
 };
-use spl_token_client::{
-    client::{ProgramRpcClient, ProgramRpcClientSendTransaction},
-    token::Token,
+use spl_token_2022::{
+    extension::{
+        BaseStateWithExtensions, StateWithExtensionsOwned,
+        confidential_transfer::{
+            ConfidentialTransferAccount, account_info::WithdrawAccountInfo, instruction::withdraw,
+        },
+    },
+    state::Account,
 };
+use spl_token_confidential_transfer_proof_extraction::instruction::ProofLocation;
 use spl_token_confidential_transfer_proof_generation::withdraw::WithdrawProofData;
+use std::mem::size_of;
 use std::sync::Arc;
-use tracing::{info, warn};
 
-use crate::solana::{confidential_keys::ConfidentialKeys, utils::confidential_keys_for_mint};
-
-/// Withdraw tokens using pre-derived confidential keys.
-///
-/// # Arguments
-/// * `rpc_client` - RPC client
-/// * `withdrawer` - Withdrawer signer (for transaction signing)
-/// * `amount` - Amount to withdraw
-/// * `mint` - Mint address
-/// * `decimals` - Token decimals
-/// * `confidential_keys` - Pre-derived ElGamal and AE keypairs
-///
-/// # Browser Wallet Flow
-/// ```ignore
-/// let ata = get_associated_token_address_with_program_id(&wallet_pubkey, &mint, &spl_token_2022::id());
-/// let keys = ConfidentialKeys::from_signature(wallet_pubkey, signature, &ata.to_bytes())?;
-/// withdraw_tokens_with_keys(rpc_client, withdrawer, amount, mint, decimals, keys).await?;
-/// ```
-pub async fn withdraw_tokens_with_keys(
-    rpc_client: Arc<RpcClient>,
-    withdrawer: Arc<dyn Signer + Send + Sync>,
-    amount: u64,
-    mint: &Pubkey,
-    decimals: u8,
-    confidential_keys: &ConfidentialKeys,
-) -> Result<Vec<Signature>> {
-    let recipient_associated_token_address = get_associated_token_address_with_program_id(
-        &withdrawer.pubkey(),
-        mint,
-        &spl_token_2022::id(),
-    );
-
-    // Create a "token" client, to use various helper functions for Token Extensions
-    // Requires block-on in an async context
-    let token = {
-        let program_client = ProgramRpcClient::new(rpc_client, ProgramRpcClientSendTransaction);
-        Token::new(
-            Arc::new(program_client),
-            &spl_token_2022::id(),
-            mint,
-            Some(decimals),
-            withdrawer.clone(),
-        )
-    };
-
-    let token_account = token
-        .get_account_info(&recipient_associated_token_address)
-        .await?;
-
-    // Unpack the ConfidentialTransferAccount extension portion of the token account data
-    let extension_data = token_account.get_extension::<ConfidentialTransferAccount>()?;
-
-    // Confidential Transfer extension information needed to construct a `Withdraw` instruction.
-    let withdraw_account_info = WithdrawAccountInfo::new(extension_data);
-
-    // Authority for the withdraw proof account (to close the account)
-    let context_state_authority = withdrawer.clone();
-
-    let equality_proof_context_state_keypair = Keypair::new();
-    let equality_proof_context_state_pubkey = equality_proof_context_state_keypair.pubkey();
-    let range_proof_context_state_keypair = Keypair::new();
-    let range_proof_context_state_pubkey = range_proof_context_state_keypair.pubkey();
-
-    let WithdrawProofData {
-        equality_proof_data,
-        range_proof_data,
-    } = withdraw_account_info.generate_proof_data(
-        amount,
-        &confidential_keys.elgamal_keypair,
-        &confidential_keys.ae_key,
-    )?;
-
-    // Generate withdrawal proof accounts
-    let context_state_authority_pubkey = context_state_authority.pubkey();
-    let create_equality_proof_signer = &[&equality_proof_context_state_keypair];
-    let create_range_proof_signer = &[&range_proof_context_state_keypair];
-
-    let mut signatures = vec![];
-
-    info!("create equality proof");
-    let equality_response = token
-        .confidential_transfer_create_context_state_account(
-            &equality_proof_context_state_pubkey,
-            &context_state_authority_pubkey,
-            &equality_proof_data,
-            false,
-            create_equality_proof_signer,
-        )
-        .await?;
-    if let Some(s) = get_maybe_signature(equality_response, &withdrawer.pubkey())? {
-        signatures.push(s.clone());
-    }
-
-    info!("create range proof");
-    let range_response = token
-        .confidential_transfer_create_context_state_account(
-            &range_proof_context_state_pubkey,
-            &context_state_authority_pubkey,
-            &range_proof_data,
-            true,
-            create_range_proof_signer,
-        )
-        .await?;
-    if let Some(s) = get_maybe_signature(range_response, &withdrawer.pubkey())? {
-        signatures.push(s.clone());
-    }
+use crate::solana::{
+    GeneratedInstructions,
+    confidential_keys::ConfidentialKeys,
+    transfer::build_close_proof_accounts_ixs,
+    zk::{
+        get_zk_proof_context_state_account_creation_instructions, get_zk_proof_verify_instruction,
+    },
+};
 
-    info!("creating withdraw");
-    let withdraw_response = token
-        .confidential_transfer_withdraw(
-            &recipient_associated_token_address,
-            &withdrawer.pubkey(),
-            Some(&equality_proof_context_state_pubkey),
-            Some(&range_proof_context_state_pubkey),
-            amount,
-            decimals,
-            Some(withdraw_account_info),
-            &confidential_keys.elgamal_keypair,
-            &confidential_keys.ae_key,
-            &[&withdrawer],
-        )
-        .await?;
-    if let Some(s) = get_maybe_signature(withdraw_response, &withdrawer.pubkey())? {
-        signatures.push(s.clone());
-    }
+/// Proof data of a withdraw. Custodial withdraws persist it with the proof
+/// account addresses until the sequence lands, so the janitor can still
+/// verify and close an account whose verify transaction never made it.
+#[derive(Clone)]
+pub struct WithdrawProofs {
+    pub equality_proof_data: CiphertextCommitmentEqualityProofData,
+    pub range_proof_data: BatchedRangeProofU64Data,
+}
 
-    let close_context_state_signer = &[&context_state_authority];
+impl WithdrawProofs {
+    const SERIALIZED_LEN: usize =
+        size_of::<CiphertextCommitmentEqualityProofData>() + size_of::<BatchedRangeProofU64Data>();
 
-    info!("closing equality proof");
-    let close_equality_response = token
-        .confidential_transfer_close_context_state_account(
-            &equality_proof_context_state_pubkey,
-            &recipient_associated_token_address,
-            &context_state_authority_pubkey,
-            close_context_state_signer,
-        )
-        .await?;
-    if let Some(s) = get_maybe_signature(close_equality_response, &withdrawer.pubkey())? {
-        signatures.push(s.clone());
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SERIALIZED_LEN);
+        bytes.extend_from_slice(bytes_of(&self.equality_proof_data));
+        bytes.extend_from_slice(bytes_of(&self.range_proof_data));
+        bytes
     }
 
-    info!("closing range proof");
-    let close_range_response = token
-        .confidential_transfer_close_context_state_account(
-            &range_proof_context_state_pubkey,
-            &recipient_associated_token_address,
-            &context_state_authority_pubkey,
-            close_context_state_signer,
-        )
-        .await?;
-    if let Some(s) = get_maybe_signature(close_range_response, &withdrawer.pubkey())? {
-        signatures.push(s.clone());
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != Self::SERIALIZED_LEN {
+            anyhow::bail!(
+                "Invalid withdraw proof length: expected {}, received {}",
+                Self::SERIALIZED_LEN,
+                bytes.len()
+            );
+        }
+        let (equality, range) = bytes.split_at(size_of::<CiphertextCommitmentEqualityProofData>());
+        Ok(Self {
+            equality_proof_data: pod_read_unaligned(equality),
+            range_proof_data: pod_read_unaligned(range),
+        })
     }
+}
 
-    Ok(signatures)
+/// Output of [`build_withdraw_steps`].
+pub struct PreparedWithdraw {
+    pub steps: Vec<GeneratedInstructions>,
+    pub equality_proof_account: Pubkey,
+    pub range_proof_account: Pubkey,
+    pub proofs: WithdrawProofs,
 }
 
-/// Withdraw tokens - convenience wrapper that derives keys from the withdrawer signer.
-pub async fn withdraw_tokens(
+/// Build the transactions of a withdraw without signing for the owner, in the
+/// order they must land:
+///
+/// 1. allocate the equality and range proof context state accounts
+/// 2. verify the range proof
+/// 3. verify the equality proof
+/// 4. withdraw, then close both proof accounts
+///
+/// Each step carries the proof account keypairs that must sign it; the owner
+/// is the authority of everything else. `fee_payer` funds the proof accounts
+/// and gets their rent back.
+pub async fn build_withdraw_steps(
     rpc_client: Arc<RpcClient>,
-    withdrawer: Arc<dyn Signer + Send + Sync>,
+    fee_payer: &Pubkey,
+    owner: &Pubkey,
     amount: u64,
     mint: &Pubkey,
     decimals: u8,
-) -> Result<Vec<Signature>> {
-    let confidential_keys = confidential_keys_for_mint(withdrawer.clone(), mint)?;
-    withdraw_tokens_with_keys(
+    confidential_keys: &ConfidentialKeys,
+) -> Result<PreparedWithdraw> {
+    let token_account_pubkey =
+        get_associated_token_address_with_program_id(owner, mint, &spl_token_2022::id());
+    let token_account = rpc_client.get_account(&token_account_pubkey).await?;
+    let token_account = StateWithExtensionsOwned::<Account>::unpack(token_account.data)?;
+    let withdraw_account_info =
+        WithdrawAccountInfo::new(token_account.get_extension::<ConfidentialTransferAccount>()?);
+
+    // proof generation is CPU heavy, keep it off the async runtime
+    let keys = confidential_keys.clone();
+    let WithdrawProofData {
+        equality_proof_data,
+        range_proof_data,
+    } = tokio::task::spawn_blocking(move || {
+        withdraw_account_info.generate_proof_data(amount, &keys.elgamal_keypair, &keys.ae_key)
+    })
+    .await
+    .map_err(|e| anyhow::anyhow!("Failed to join proof generation task: {:?}", e))??;
+    let new_decryptable_available_balance = withdraw_account_info
+        .new_decryptable_available_balance(amount, &confidential_keys.ae_key)?
+        .into();
+
+    let equality_proof_account = Arc::new(Keypair::new());
+    let range_proof_account = Arc::new(Keypair::new());
+
+    let (equality_create_ix, _) = get_zk_proof_context_state_account_creation_instructions(
+        rpc_client.clone(),
+        fee_payer,
+        &equality_proof_account.pubkey(),
+        owner,
+        &equality_proof_data,
+    )
+    .await?;
+    let (range_create_ix, _) = get_zk_proof_context_state_account_creation_instructions(
         rpc_client,
-        withdrawer,
-        amount,
-        mint,
-        decimals,
-        &confidential_keys,
+        fee_payer,
+        &range_proof_account.pubkey(),
+        owner,
+        &range_proof_data,
     )
-    .await
-}
+    .await?;
 
-fn get_maybe_signature(
-    response: spl_token_client::client::RpcClientResponse,
-    withdrawer: &Pubkey,
-) -> Result<Option<Signature>> {
-    match response {
-        spl_token_client::client::RpcClientResponse::Signature(s) => Ok(Some(s)),
-        _ => {
-            warn!(
-                "Expected signature response from create equality proof from {:?}",
-                withdrawer
-            );
-            Ok(None)
-        }
-    }
+    let mut withdraw_ixs = withdraw(
+        &spl_token_2022::id(),
+        &token_account_pubkey,
+        mint,
+        amount,
+        decimals,
+        &new_decryptable_available_balance,
+        owner,
+        &[],
+        ProofLocation::ContextStateAccount(&equality_proof_account.pubkey()),
+        ProofLocation::ContextStateAccount(&range_proof_account.pubkey()),
+    )?;
+    withdraw_ixs.extend(build_close_proof_accounts_ixs(
+        owner,
+        fee_payer,
+        &[
+            equality_proof_account.pubkey(),
+            range_proof_account.pubkey(),
+        ],
+    ));
+
+    let steps = vec![
+        GeneratedInstructions {
+            instructions: vec![equality_create_ix, range_create_ix],
+            additional_signers: vec![equality_proof_account.clone(), range_proof_account.clone()],
+        },
+        GeneratedInstructions {
+            instructions: vec![get_zk_proof_verify_instruction(
+                &range_proof_account.pubkey(),
+                owner,
+                &range_proof_data,
+            )?],
+            additional_signers: vec![],
+        },
+        GeneratedInstructions {
+            instructions: vec![get_zk_proof_verify_instruction(
+                &equality_proof_account.pubkey(),
+                owner,
+                &equality_proof_data,
+            )?],
+            additional_signers: vec![],
+        },
+        GeneratedInstructions {
+            instructions: withdraw_ixs,
+            additional_signers: vec![],
+        },
+    ];
+
+    Ok(PreparedWithdraw {
+        steps,
+        equality_proof_account: equality_proof_account.pubkey(),
+        range_proof_account: range_proof_account.pubkey(),
+        proofs: WithdrawProofs {
+            equality_proof_data,
+            range_proof_data,
+        },
+    })
 }
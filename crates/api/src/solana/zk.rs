@@ -1,16 +1,109 @@
 use {
-    anyhow::Result,
+    anyhow::{Context, Result},
     bytemuck::Pod,
-    solana_client::nonblocking::rpc_client::RpcClient,
+    futures::{StreamExt, TryStreamExt, stream},
+    solana_account::Account,
+    solana_client::{
+        nonblocking::rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, UiAccountEncoding},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
     solana_instruction::Instruction,
     solana_pubkey::Pubkey,
     spl_token_2022::solana_zk_sdk::zk_elgamal_proof_program::{
-        self, instruction::ContextStateInfo, proof_data::ZkProofData, state::ProofContextState,
+        self,
+        instruction::ContextStateInfo,
+        proof_data::{ProofType, ZkProofData},
+        state::{ProofContextState, ProofContextStateMeta},
     },
     spl_token_confidential_transfer_proof_extraction::instruction::zk_proof_type_to_instruction,
-    std::{mem::size_of, sync::Arc},
+    std::{collections::HashSet, mem::size_of, sync::Arc},
 };
 
+/// A proof context state account holding a verified proof, which its
+/// authority can close to reclaim the rent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofContextAccount {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    pub lamports: u64,
+}
+
+impl ProofContextAccount {
+    /// Parse a ZK ElGamal proof program account, `None` if it is not a
+    /// verified proof context owned by one of `authorities`.
+    pub fn parse(
+        address: Pubkey,
+        account: &Account,
+        authorities: &HashSet<Pubkey>,
+    ) -> Option<Self> {
+        if account.owner != zk_elgamal_proof_program::id() {
+            return None;
+        }
+        let meta = ProofContextStateMeta::try_from_bytes(&account.data).ok()?;
+        if meta.proof_type == ProofType::Uninitialized.into()
+            || !authorities.contains(&meta.context_state_authority)
+        {
+            return None;
+        }
+        Some(Self {
+            address,
+            authority: meta.context_state_authority,
+            lamports: account.lamports,
+        })
+    }
+}
+
+/// Concurrent `getProgramAccounts` requests, one per authority.
+const FETCH_CONCURRENCY: usize = 5;
+
+/// Every verified proof context state account whose authority is one of
+/// `authorities`.
+///
+/// The proof program holds every proof account on the cluster, so each
+/// authority gets its own request filtered on the authority at the start of
+/// the account data instead of listing the whole program. Accounts that were
+/// allocated but never verified have no authority yet and are not found,
+/// see [`crate::jobs::janitor`].
+pub async fn get_proof_context_accounts(
+    rpc_client: Arc<RpcClient>,
+    authorities: &HashSet<Pubkey>,
+) -> Result<Vec<ProofContextAccount>> {
+    let accounts: Vec<Vec<ProofContextAccount>> = stream::iter(authorities.iter().copied())
+        .map(|authority| {
+            let rpc_client = rpc_client.clone();
+            async move {
+                let config = RpcProgramAccountsConfig {
+                    filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                        0,
+                        authority.as_ref(),
+                    ))]),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        ..RpcAccountInfoConfig::default()
+                    },
+                    ..RpcProgramAccountsConfig::default()
+                };
+                let accounts = rpc_client
+                    .get_program_ui_accounts_with_config(&zk_elgamal_proof_program::id(), config)
+                    .await
+                    .with_context(|| format!("Failed to list proof accounts of {}", authority))?;
+                Ok::<_, anyhow::Error>(
+                    accounts
+                        .into_iter()
+                        .filter_map(|(address, account)| {
+                            ProofContextAccount::parse(address, &account.decode()?, authorities)
+                        })
+                        .collect(),
+                )
+            }
+        })
+        .buffer_unordered(FETCH_CONCURRENCY)
+        .try_collect()
+        .await?;
+    Ok(accounts.into_iter().flatten().collect())
+}
+
 /// Build create + verify instructions for a proof context state account.
 pub async fn get_zk_proof_context_state_account_creation_instructions<
     ZK: Pod + ZkProofData<U>,
@@ -28,13 +121,6 @@ pub async fn get_zk_proof_context_state_account_creation_instructions<
         .await
         .map_err(|_| anyhow::anyhow!("Failed to get minimum balance for rent exemption"))?;
 
-    let context_state_info = ContextStateInfo {
-        context_state_account: context_state_account_pubkey,
-        context_state_authority: context_state_authority_pubkey,
-    };
-
-    let instruction_type = zk_proof_type_to_instruction(ZK::PROOF_TYPE)?;
-
     let create_account_ix = solana_system_interface::instruction::create_account(
         fee_payer_pubkey,
         context_state_account_pubkey,
@@ -43,8 +129,27 @@ pub async fn get_zk_proof_context_state_account_creation_instructions<
         &zk_elgamal_proof_program::id(),
     );
 
-    let verify_proof_ix =
-        instruction_type.encode_verify_proof(Some(context_state_info), proof_data);
+    let verify_proof_ix = get_zk_proof_verify_instruction(
+        context_state_account_pubkey,
+        context_state_authority_pubkey,
+        proof_data,
+    )?;
 
     Ok((create_account_ix, verify_proof_ix))
 }
+
+/// Build the verify instruction for an already allocated proof context state account.
+pub fn get_zk_proof_verify_instruction<ZK: Pod + ZkProofData<U>, U: Pod>(
+    context_state_account_pubkey: &Pubkey,
+    context_state_authority_pubkey: &Pubkey,
+    proof_data: &ZK,
+) -> Result<Instruction> {
+    let context_state_info = ContextStateInfo {
+        context_state_account: context_state_account_pubkey,
+        context_state_authority: context_state_authority_pubkey,
+    };
+
+    let instruction_type = zk_proof_type_to_instruction(ZK::PROOF_TYPE)?;
+
+    Ok(instruction_type.encode_verify_proof(Some(context_state_info), proof_data))
+}
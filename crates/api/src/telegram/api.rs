@@ -0,0 +1,448 @@
+//! Minimal Telegram Bot API client.
+//!
+//! Only the methods and update fields the bot uses are modelled. Requests go
+//! to `{TELEGRAM_API_URL}/bot{token}/{method}`, so tests can point the client
+//! at a mock server.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::json;
+
+/// An incoming update, as delivered to the webhook.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<Message>,
+    pub inline_query: Option<InlineQuery>,
+    pub chosen_inline_result: Option<ChosenInlineResult>,
+    pub callback_query: Option<CallbackQuery>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Message {
+    pub message_id: i64,
+    pub from: Option<User>,
+    pub chat: Chat,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    pub id: i64,
+    #[serde(default)]
+    pub first_name: String,
+    pub username: Option<String>,
+}
+
+impl User {
+    /// `@username`, or the first name of users without one.
+    pub fn display_name(&self) -> String {
+        match &self.username {
+            Some(username) => format!("@{}", username),
+            None => self.first_name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Chat {
+    pub id: i64,
+    /// `private`, `group`, `supergroup` or `channel`.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl Chat {
+    pub fn is_private(&self) -> bool {
+        self.kind == "private"
+    }
+}
+
+/// Text typed after the bot's username in any chat, e.g. `@teegeepay_bot 5`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InlineQuery {
+    pub id: String,
+    pub from: User,
+    pub query: String,
+}
+
+/// An inline result the user picked and sent. Only delivered with inline
+/// feedback turned on for the bot.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChosenInlineResult {
+    pub result_id: String,
+    pub from: User,
+    /// Set when the result has a keyboard, to edit the sent message later.
+    pub inline_message_id: Option<String>,
+    pub query: String,
+}
+
+/// A tap on an inline keyboard button.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CallbackQuery {
+    pub id: String,
+    pub from: User,
+    pub data: Option<String>,
+}
+
+/// A user's membership of a chat.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatMember {
+    /// `creator`, `administrator`, `member`, `restricted`, `left` or `kicked`.
+    pub status: String,
+    /// Whether a `restricted` user is still in the chat.
+    #[serde(default)]
+    pub is_member: bool,
+}
+
+impl ChatMember {
+    pub fn is_in_chat(&self) -> bool {
+        match self.status.as_str() {
+            "creator" | "administrator" | "member" => true,
+            "restricted" => self.is_member,
+            _ => false,
+        }
+    }
+}
+
+/// A button under a message that sends `callback_data` back to the bot.
+pub fn callback_keyboard(text: &str, callback_data: &str) -> serde_json::Value {
+    json!({ "inline_keyboard": [[{ "text": text, "callback_data": callback_data }]] })
+}
+
+/// The Bot API refused a request.
+#[derive(Debug, thiserror::Error)]
+#[error("Bot API {method} failed: {description}")]
+pub struct BotApiError {
+    pub method: String,
+    pub error_code: Option<i64>,
+    pub description: String,
+}
+
+impl BotApiError {
+    /// Whether sending again can't help, e.g. the user blocked the bot or
+    /// never started a chat with it.
+    pub fn is_permanent(&self) -> bool {
+        matches!(self.error_code, Some(400 | 403))
+    }
+}
+
+#[derive(Clone)]
+pub struct BotApi {
+    http: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+impl BotApi {
+    pub fn new(base_url: &str, token: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T> {
+        let response: BotApiResponse<T> = self
+            .http
+            .post(format!("{}/bot{}/{}", self.base_url, self.token, method))
+            .json(&params)
+            .send()
+            .await
+            // the URL carries the bot token
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("Failed to reach the Bot API for {}", method))?
+            .json()
+            .await
+            .map_err(reqwest::Error::without_url)
+            .with_context(|| format!("Invalid Bot API response to {}", method))?;
+
+        match (response.ok, response.result) {
+            (true, Some(result)) => Ok(result),
+            (true, None) => anyhow::bail!("Bot API {} returned no result", method),
+            (false, _) => Err(BotApiError {
+                method: method.to_string(),
+                error_code: response.error_code,
+                description: response
+                    .description
+                    .unwrap_or_else(|| "unknown error".to_string()),
+            }
+            .into()),
+        }
+    }
+
+    /// Send a plain text message, as a reply to `reply_to` in the same chat
+    /// if given.
+    pub async fn send_message(
+        &self,
+        chat_id: i64,
+        text: &str,
+        reply_to: Option<i64>,
+    ) -> Result<Message> {
+        let mut params = json!({ "chat_id": chat_id, "text": text });
+        if let Some(message_id) = reply_to {
+            params["reply_parameters"] = json!({
+                "message_id": message_id,
+                "allow_sending_without_reply": true,
+            });
+        }
+        self.call("sendMessage", params).await
+    }
+
+    /// Look up a user's membership of a chat the bot is in.
+    pub async fn get_chat_member(&self, chat_id: i64, user_id: i64) -> Result<ChatMember> {
+        self.call(
+            "getChatMember",
+            json!({ "chat_id": chat_id, "user_id": user_id }),
+        )
+        .await
+    }
+
+    /// Answer an inline query with `results`, never cached since they depend
+    /// on the user's wallet. `button` is shown above the results and opens a
+    /// private chat with the bot, which gets `/start {start_parameter}`.
+    pub async fn answer_inline_query(
+        &self,
+        inline_query_id: &str,
+        results: serde_json::Value,
+        button: Option<(&str, &str)>,
+    ) -> Result<()> {
+        let mut params = json!({
+            "inline_query_id": inline_query_id,
+            "results": results,
+            "cache_time": 0,
+            "is_personal": true,
+        });
+        if let Some((text, start_parameter)) = button {
+            params["button"] = json!({ "text": text, "start_parameter": start_parameter });
+        }
+        self.call::<bool>("answerInlineQuery", params).await?;
+        Ok(())
+    }
+
+    /// Stop the loading indicator of a button tap, showing `text` to the user
+    /// in an alert if `show_alert` and as a notification otherwise.
+    pub async fn answer_callback_query(
+        &self,
+        callback_query_id: &str,
+        text: &str,
+        show_alert: bool,
+    ) -> Result<()> {
+        let params = json!({
+            "callback_query_id": callback_query_id,
+            "text": text,
+            "show_alert": show_alert,
+        });
+        self.call::<bool>("answerCallbackQuery", params).await?;
+        Ok(())
+    }
+
+    /// Replace the text of a message sent through inline mode, and its
+    /// keyboard with `reply_markup` (removed if `None`).
+    pub async fn edit_inline_message_text(
+        &self,
+        inline_message_id: &str,
+        text: &str,
+        reply_markup: Option<serde_json::Value>,
+    ) -> Result<()> {
+        let mut params = json!({ "inline_message_id": inline_message_id, "text": text });
+        if let Some(reply_markup) = reply_markup {
+            params["reply_markup"] = reply_markup;
+        }
+        self.call::<bool>("editMessageText", params).await?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct BotApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    error_code: Option<i64>,
+    description: Option<String>,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use axum::{Json, Router, extract::Path, extract::State, routing::post};
+    use std::sync::{Arc, Mutex};
+
+    /// Bot API stand-in that records every `sendMessage` it gets, and every
+    /// call to the other methods the bot uses along with its method name.
+    #[derive(Clone, Default)]
+    pub(crate) struct MockBotApi {
+        pub(crate) sent: Arc<Mutex<Vec<serde_json::Value>>>,
+        pub(crate) calls: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+    }
+
+    async fn mock_method(
+        State(api): State<MockBotApi>,
+        Path((token, method)): Path<(String, String)>,
+        Json(params): Json<serde_json::Value>,
+    ) -> Json<serde_json::Value> {
+        if token != "bottest-token" {
+            return Json(json!({ "ok": false, "error_code": 404, "description": "Not Found" }));
+        }
+        if matches!(
+            method.as_str(),
+            "answerInlineQuery" | "answerCallbackQuery" | "editMessageText"
+        ) {
+            api.calls.lock().unwrap().push((method, params));
+            return Json(json!({ "ok": true, "result": true }));
+        }
+        if method != "sendMessage" {
+            return Json(json!({ "ok": false, "error_code": 404, "description": "Not Found" }));
+        }
+        api.sent.lock().unwrap().push(params.clone());
+        Json(json!({
+            "ok": true,
+            "result": {
+                "message_id": 1,
+                "chat": { "id": params["chat_id"], "type": "private" },
+                "text": params["text"],
+            },
+        }))
+    }
+
+    pub(crate) async fn spawn_bot_api() -> (BotApi, MockBotApi) {
+        let api = MockBotApi::default();
+        let app = Router::new()
+            .route("/{token}/{method}", post(mock_method))
+            .with_state(api.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (BotApi::new(&url, "test-token"), api)
+    }
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let (bot_api, mock) = spawn_bot_api().await;
+
+        let message = bot_api.send_message(42, "hello", Some(7)).await.unwrap();
+        assert_eq!(message.chat.id, 42);
+        assert_eq!(message.text.as_deref(), Some("hello"));
+
+        let sent = mock.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["chat_id"], 42);
+        assert_eq!(sent[0]["reply_parameters"]["message_id"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_reports_bot_api_errors_without_token() {
+        let (bot_api, _) = spawn_bot_api().await;
+        let wrong_token = BotApi::new(&bot_api.base_url, "other-token");
+
+        let error = wrong_token
+            .send_message(42, "hello", None)
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<BotApiError>().unwrap();
+        assert_eq!(error.error_code, Some(404));
+        assert_eq!(error.description, "Not Found");
+        assert!(!error.is_permanent());
+
+        let unreachable = BotApi::new("http://127.0.0.1:1", "secret-token");
+        let error = format!(
+            "{:#}",
+            unreachable
+                .send_message(42, "hello", None)
+                .await
+                .unwrap_err()
+        );
+        assert!(!error.contains("secret-token"), "{}", error);
+    }
+
+    #[test]
+    fn test_parses_update() {
+        let update: Update = serde_json::from_value(json!({
+            "update_id": 10,
+            "message": {
+                "message_id": 3,
+                "date": 1700000000,
+                "from": { "id": 99, "is_bot": false, "first_name": "Alice", "username": "alice" },
+                "chat": { "id": 99, "type": "private", "first_name": "Alice" },
+                "text": "/balance",
+            },
+        }))
+        .unwrap();
+        let message = update.message.unwrap();
+        assert_eq!(message.from.unwrap().username.as_deref(), Some("alice"));
+        assert!(message.chat.is_private());
+        assert_eq!(message.text.as_deref(), Some("/balance"));
+
+        let update: Update =
+            serde_json::from_value(json!({ "update_id": 11, "edited_message": {} })).unwrap();
+        assert!(update.message.is_none());
+    }
+
+    #[test]
+    fn test_parses_inline_updates() {
+        let from = json!({ "id": 99, "is_bot": false, "first_name": "Alice" });
+        let update: Update = serde_json::from_value(json!({
+            "update_id": 12,
+            "inline_query": { "id": "q1", "from": from, "query": "5 tgUSD", "offset": "" },
+        }))
+        .unwrap();
+        let query = update.inline_query.unwrap();
+        assert_eq!(query.query, "5 tgUSD");
+        assert_eq!(query.from.display_name(), "Alice");
+
+        let update: Update = serde_json::from_value(json!({
+            "update_id": 13,
+            "callback_query": {
+                "id": "c1",
+                "from": { "id": 7, "is_bot": false, "first_name": "Bob", "username": "bob" },
+                "inline_message_id": "m1",
+                "chat_instance": "1",
+                "data": "claim:abc",
+            },
+        }))
+        .unwrap();
+        let callback = update.callback_query.unwrap();
+        assert_eq!(callback.from.display_name(), "@bob");
+        assert_eq!(callback.data.as_deref(), Some("claim:abc"));
+    }
+
+    #[test]
+    fn test_chat_member() {
+        let member = |value| serde_json::from_value::<ChatMember>(value).unwrap();
+        assert!(member(json!({ "status": "creator", "user": {} })).is_in_chat());
+        assert!(member(json!({ "status": "member" })).is_in_chat());
+        assert!(member(json!({ "status": "restricted", "is_member": true })).is_in_chat());
+        assert!(!member(json!({ "status": "restricted", "is_member": false })).is_in_chat());
+        assert!(!member(json!({ "status": "left" })).is_in_chat());
+        assert!(!member(json!({ "status": "kicked" })).is_in_chat());
+    }
+
+    #[tokio::test]
+    async fn test_inline_methods() {
+        let (bot_api, mock) = spawn_bot_api().await;
+
+        bot_api
+            .answer_inline_query("q1", json!([]), Some(("Create a wallet", "inline")))
+            .await
+            .unwrap();
+        bot_api
+            .edit_inline_message_text("m1", "done", Some(callback_keyboard("Claim", "claim:abc")))
+            .await
+            .unwrap();
+
+        let calls = mock.calls.lock().unwrap();
+        assert_eq!(calls[0].0, "answerInlineQuery");
+        assert_eq!(calls[0].1["button"]["start_parameter"], "inline");
+        assert_eq!(calls[0].1["is_personal"], true);
+        assert_eq!(calls[1].0, "editMessageText");
+        assert_eq!(
+            calls[1].1["reply_markup"]["inline_keyboard"][0][0]["callback_data"],
+            "claim:abc"
+        );
+    }
+}
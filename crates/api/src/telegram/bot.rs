@@ -0,0 +1,367 @@
+//! Chat commands of the Telegram bot.
+//!
+//! Updates arrive through the webhook (see
+//! [`crate::handlers::telegram::webhook`]) and are answered with a reply in
+//! the same chat:
+//!
+//! - `/balance` shows the confidential balance of the sender's wallet, only in
+//!   a private chat with the bot,
+//! - `/send @username 5 tgUSD` transfers to a Telegram user, into a reserved
+//!   wallet if they haven't signed up yet,
+//! - `/request 10 tgUSD` creates a payment request into the sender's wallet.
+//!
+//! Commands act on the sender's oldest custodial wallet and on the mint set
+//! by `TELEGRAM_BOT_MINT`, whose symbol is optional in commands. Inline
+//! queries and claim button taps are handed to [`super::inline`].
+
+use super::api::{Message, Update, User};
+use super::inline;
+use crate::config::BotConfig;
+use crate::handlers::AppError;
+use crate::handlers::transfers::telegram::send_to_username;
+use crate::handlers::transfers::{get_mint_decimals, validate_confidential_mint};
+use crate::handlers::wallets::balance::get_encrypted_balance;
+use crate::models::Wallet;
+use crate::solana::pay::{format_ui_amount, parse_ui_amount};
+use crate::solana::tokens::get_maybe_ata;
+use crate::{AppState, db};
+use anyhow::Result;
+use tracing::{error, info};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Balance,
+    Send { username: String, amount: String },
+    Request { amount: String },
+}
+
+fn usage(symbol: &str) -> String {
+    format!(
+        "/balance - your {symbol} balance\n\
+         /send @username 5 {symbol} - send {symbol} to a Telegram user\n\
+         /request 10 {symbol} - ask for a payment"
+    )
+}
+
+/// Parse a chat message. `None` if it isn't a command for this bot, the error
+/// is the reply for a malformed one. Groups share commands between their
+/// bots, so unknown commands are only answered in a private chat.
+pub fn parse_command(
+    text: &str,
+    bot_username: &str,
+    symbol: &str,
+    private: bool,
+) -> Option<Result<Command, String>> {
+    let mut words = text.split_whitespace();
+    let name = words.next()?.strip_prefix('/')?;
+    // `/balance@teegeepay_bot` in groups
+    let (name, addressee) = name.split_once('@').unwrap_or((name, ""));
+    if !addressee.is_empty() && !addressee.eq_ignore_ascii_case(bot_username) {
+        return None;
+    }
+    let name = name.to_lowercase();
+    let args: Vec<&str> = words.collect();
+
+    Some(match name.as_str() {
+        "start" | "help" => Ok(Command::Help),
+        "balance" => Ok(Command::Balance),
+        "send" => match args.split_first() {
+            Some((given, rest)) => {
+                let username = given.strip_prefix('@').unwrap_or(given);
+                if username.is_empty()
+                    || !username
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                {
+                    Err(format!("{} is not a Telegram username.", given))
+                } else {
+                    parse_amount_args(rest, symbol).map(|amount| Command::Send {
+                        username: username.to_string(),
+                        amount,
+                    })
+                }
+            }
+            None => Err(usage(symbol)),
+        },
+        "request" => parse_amount_args(&args, symbol).map(|amount| Command::Request { amount }),
+        other if private => Err(format!("Unknown command /{}.\n\n{}", other, usage(symbol))),
+        _ => return None,
+    })
+}
+
+/// An amount with an optional symbol, which has to be the bot's if given.
+pub(super) fn parse_amount_args(args: &[&str], symbol: &str) -> Result<String, String> {
+    match args {
+        [amount] => Ok(amount.to_string()),
+        [amount, given] if given.eq_ignore_ascii_case(symbol) => Ok(amount.to_string()),
+        [_, given] => Err(format!("I can only move {}, not {}.", symbol, given)),
+        _ => Err(usage(symbol)),
+    }
+}
+
+/// Answer a webhook update. Anything that isn't a command, an inline query or
+/// a claim button tap is ignored.
+pub async fn handle_update(state: &AppState, update: Update) -> Result<()> {
+    let Some(bot) = &state.config.bot else {
+        return Ok(());
+    };
+    if let Some(query) = update.inline_query {
+        return inline::answer_query(state, bot, update.update_id, query).await;
+    }
+    if let Some(chosen) = update.chosen_inline_result {
+        return inline::record_chosen_result(state, bot, chosen).await;
+    }
+    if let Some(callback) = update.callback_query {
+        return inline::handle_callback(state, update.update_id, callback).await;
+    }
+    let Some(message) = update.message else {
+        return Ok(());
+    };
+    let (Some(from), Some(text)) = (&message.from, &message.text) else {
+        return Ok(());
+    };
+    let Some(command) = parse_command(
+        text,
+        &bot.username,
+        &bot.mint_symbol,
+        message.chat.is_private(),
+    ) else {
+        return Ok(());
+    };
+    info!(
+        update_id = update.update_id,
+        telegram_user_id = from.id,
+        "bot command: {:?}",
+        command
+    );
+
+    let reply = match command {
+        Ok(command) => run_command(state, bot, &message, from, command)
+            .await
+            .unwrap_or_else(|e| error_reply(update.update_id, e)),
+        Err(reply) => reply,
+    };
+    state
+        .telegram
+        .send_message(message.chat.id, &reply, Some(message.message_id))
+        .await?;
+    Ok(())
+}
+
+/// What to tell the user about a failed command. Server errors are logged
+/// and not shown.
+pub(super) fn error_reply(update_id: i64, error: AppError) -> String {
+    if error.status().is_server_error() {
+        error!(update_id, "bot command failed: {:?}", error);
+        return "Something went wrong, please try again later.".to_string();
+    }
+    error.to_string()
+}
+
+async fn run_command(
+    state: &AppState,
+    bot: &BotConfig,
+    message: &Message,
+    from: &User,
+    command: Command,
+) -> Result<String, AppError> {
+    let symbol = &bot.mint_symbol;
+    match command {
+        Command::Help => Ok(usage(symbol)),
+        Command::Balance => {
+            if !message.chat.is_private() {
+                return Ok("Ask me in a private chat, so your balance stays confidential.".into());
+            }
+            let wallet = sender_wallet(state, from).await?;
+            let decimals = get_mint_decimals(state, &bot.mint).await?;
+            let (_, token_account) =
+                get_maybe_ata(state.rpc_client.clone(), &wallet.pubkey, &bot.mint).await?;
+            if token_account.is_none() {
+                return Ok(format!("Balance: 0 {}", symbol));
+            }
+
+            let balance = get_encrypted_balance(state, &wallet, &bot.mint).await?;
+            let mut reply = format!(
+                "Balance: {} {}",
+                format_ui_amount(balance.available, decimals),
+                symbol
+            );
+            if balance.pending > 0 {
+                reply.push_str(&format!(
+                    "\nPending: {} {}",
+                    format_ui_amount(balance.pending, decimals),
+                    symbol
+                ));
+            }
+            Ok(reply)
+        }
+        Command::Send { username, amount } => {
+            let wallet = sender_wallet(state, from).await?;
+            if from
+                .username
+                .as_deref()
+                .is_some_and(|own| own.eq_ignore_ascii_case(&username))
+            {
+                return Err(AppError::bad_request(anyhow::anyhow!(
+                    "You can't send to yourself."
+                )));
+            }
+            validate_confidential_mint(state, &bot.mint).await?;
+            let decimals = get_mint_decimals(state, &bot.mint).await?;
+            let amount = parse_amount(&amount, decimals)?;
+
+            let ui_amount = format_ui_amount(amount, decimals);
+            // a transfer takes a while, let the sender know it started
+            if let Err(e) = state
+                .telegram
+                .send_message(
+                    message.chat.id,
+                    &format!("Sending {} {} to @{}…", ui_amount, symbol, username),
+                    Some(message.message_id),
+                )
+                .await
+            {
+                error!("failed to acknowledge bot transfer: {:?}", e);
+            }
+
+            let (_, job, recipient) =
+                send_to_username(state, &wallet, &username, amount, bot.mint, decimals, None)
+                    .await?;
+
+            let mut reply = format!("Sent {} {} to @{}.", ui_amount, symbol, username);
+            if recipient.was_new_wallet {
+                reply.push_str(" They can claim it by opening the app.");
+            }
+            if let Some(signature) = job.transfer_signature {
+                reply.push('\n');
+                reply.push_str(&state.config.cluster.explorer_tx_url(&signature.to_string()));
+            }
+            Ok(reply)
+        }
+        Command::Request { amount } => {
+            let wallet = sender_wallet(state, from).await?;
+            validate_confidential_mint(state, &bot.mint).await?;
+            let decimals = get_mint_decimals(state, &bot.mint).await?;
+            let amount = parse_amount(&amount, decimals)?;
+
+            let request =
+                db::create_payment_request(&state.db, &wallet, &bot.mint, amount, None, None)
+                    .await?;
+            let response = crate::handlers::requests::to_response(request, decimals)?;
+            Ok(format!(
+                "Payment request #{} for {} {}.\nPay it in the app or with any Solana Pay wallet:\n{}",
+                response.id,
+                format_ui_amount(amount, decimals),
+                symbol,
+                response.link
+            ))
+        }
+    }
+}
+
+pub(super) async fn sender_wallet(state: &AppState, from: &User) -> Result<Wallet, AppError> {
+    db::get_custodial_wallet_for_telegram_user(&state.db, from.id)
+        .await?
+        .ok_or_else(|| {
+            AppError::not_found(anyhow::anyhow!(
+                "You don't have a wallet yet, open the app to create one."
+            ))
+        })
+}
+
+pub(super) fn parse_amount(amount: &str, decimals: u8) -> Result<u64, AppError> {
+    let amount = parse_ui_amount(amount, decimals).map_err(AppError::bad_request)?;
+    if amount == 0 {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Amount must be greater than 0"
+        )));
+    }
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Option<Result<Command, String>> {
+        parse_command(text, "teegeepay_bot", "tgUSD", true)
+    }
+
+    fn parse_in_group(text: &str) -> Option<Result<Command, String>> {
+        parse_command(text, "teegeepay_bot", "tgUSD", false)
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse("hello there"), None);
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("/start"), Some(Ok(Command::Help)));
+        assert_eq!(parse("/balance"), Some(Ok(Command::Balance)));
+        assert_eq!(parse("/Balance@teegeepay_bot"), Some(Ok(Command::Balance)));
+
+        let send = Command::Send {
+            username: "alice_1".to_string(),
+            amount: "5".to_string(),
+        };
+        assert_eq!(parse("/send @alice_1 5 tgUSD"), Some(Ok(send.clone())));
+        assert_eq!(parse("/send alice_1 5 tgusd"), Some(Ok(send.clone())));
+        assert_eq!(parse("/send@teegeepay_bot  @alice_1   5"), Some(Ok(send)));
+
+        assert_eq!(
+            parse("/request 10.5 tgUSD"),
+            Some(Ok(Command::Request {
+                amount: "10.5".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_command_errors() {
+        let usage = usage("tgUSD");
+        assert_eq!(parse("/send"), Some(Err(usage.clone())));
+        assert_eq!(parse("/send @alice"), Some(Err(usage.clone())));
+        assert_eq!(parse("/request 1 2 3"), Some(Err(usage.clone())));
+        assert_eq!(
+            parse("/send @alice 5 USDC"),
+            Some(Err("I can only move tgUSD, not USDC.".to_string()))
+        );
+        assert_eq!(
+            parse("/send @ 5"),
+            Some(Err("@ is not a Telegram username.".to_string()))
+        );
+        assert!(matches!(
+            parse("/withdraw 5"),
+            Some(Err(reply)) if reply.starts_with("Unknown command /withdraw.")
+        ));
+    }
+
+    #[test]
+    fn test_parse_command_in_group() {
+        assert_eq!(
+            parse_in_group("/balance@TeeGeePay_bot"),
+            Some(Ok(Command::Balance))
+        );
+        assert_eq!(parse_in_group("/balance@otherbot"), None);
+        assert_eq!(parse_in_group("/send@otherbot @alice 5"), None);
+        assert_eq!(parse_in_group("/withdraw 5"), None);
+        assert_eq!(parse_in_group("/withdraw@teegeepay_bot 5"), None);
+        assert_eq!(
+            parse_in_group("/send @alice 5 USDC"),
+            Some(Err("I can only move tgUSD, not USDC.".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("2.5", 6).unwrap(), 2_500_000);
+        assert_eq!(
+            parse_amount("0", 6).unwrap_err().status(),
+            reqwest::StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            parse_amount("1.1234567", 6).unwrap_err().status(),
+            reqwest::StatusCode::BAD_REQUEST
+        );
+    }
+}
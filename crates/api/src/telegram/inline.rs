@@ -0,0 +1,378 @@
+//! Payments from any chat through the bot's inline mode.
+//!
+//! Typing `@teegeepay_bot 5 tgUSD` in a chat offers a single result, which
+//! posts a message with a claim button. Telegram reports the sent result as a
+//! `chosen_inline_result` (inline feedback has to be turned on for the bot
+//! with @BotFather), and the payment is recorded in `inline_payments`.
+//!
+//! Whoever taps the button first, other than the sender, gets the amount
+//! transferred from the sender's wallet to the wallet of their username, a
+//! reserved one if they haven't signed up yet. Nothing moves before the
+//! claim, so an offer the sender can no longer cover only fails then, and an
+//! unclaimed one simply expires after [`INLINE_PAYMENT_TTL_SECS`].
+
+use super::api::{CallbackQuery, ChosenInlineResult, InlineQuery, User, callback_keyboard};
+use super::bot::{error_reply, parse_amount, parse_amount_args, sender_wallet};
+use crate::config::BotConfig;
+use crate::handlers::AppError;
+use crate::handlers::transfers::telegram::start_send_to_username;
+use crate::handlers::transfers::{finish_transfer, get_mint_decimals, validate_confidential_mint};
+use crate::models::{InlinePayment, InlinePaymentStatus, Wallet};
+use crate::notifications::{notify_incoming_payment, token_label};
+use crate::solana::pay::format_ui_amount;
+use crate::{AppState, db};
+use anyhow::Result;
+use chrono::Utc;
+use rand::RngCore;
+use serde_json::json;
+use tracing::{error, info, warn};
+
+pub const INLINE_PAYMENT_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Callback data of claim buttons, followed by the payment's token.
+const CLAIM_PREFIX: &str = "claim:";
+
+/// The payment an inline query describes.
+struct Offer {
+    wallet: Wallet,
+    amount: u64,
+    decimals: u8,
+}
+
+async fn parse_offer(
+    state: &AppState,
+    bot: &BotConfig,
+    from: &User,
+    query: &str,
+) -> Result<Offer, AppError> {
+    let args: Vec<&str> = query.split_whitespace().collect();
+    let amount = parse_amount_args(&args, &bot.mint_symbol).map_err(|_| {
+        AppError::bad_request(anyhow::anyhow!(
+            "Type an amount to send, e.g. 5 {}",
+            bot.mint_symbol
+        ))
+    })?;
+    let wallet = sender_wallet(state, from).await?;
+    let decimals = get_mint_decimals(state, &bot.mint).await?;
+    let amount = parse_amount(&amount, decimals)?;
+    Ok(Offer {
+        wallet,
+        amount,
+        decimals,
+    })
+}
+
+/// Offer the payment typed after the bot's username. A query that doesn't
+/// describe one gets no results, with a button explaining why.
+pub async fn answer_query(
+    state: &AppState,
+    bot: &BotConfig,
+    update_id: i64,
+    query: InlineQuery,
+) -> Result<()> {
+    let (results, hint) = match parse_offer(state, bot, &query.from, &query.query).await {
+        Ok(offer) => {
+            let mut token = [0u8; 16];
+            rand::rngs::OsRng.fill_bytes(&mut token);
+            let token = hex::encode(token);
+            let ui_amount = format_ui_amount(offer.amount, offer.decimals);
+            let result = json!({
+                "type": "article",
+                "id": token,
+                "title": format!("Send {} {}", ui_amount, bot.mint_symbol),
+                "description": "The first to tap Claim in the chat gets it",
+                "input_message_content": {
+                    "message_text": offer_text(&query.from.display_name(), &ui_amount, &bot.mint_symbol),
+                },
+                "reply_markup": claim_keyboard(&token, &ui_amount, &bot.mint_symbol),
+            });
+            (json!([result]), None)
+        }
+        Err(e) => (json!([]), Some(error_reply(update_id, e))),
+    };
+
+    state
+        .telegram
+        .answer_inline_query(
+            &query.id,
+            results,
+            hint.as_deref().map(|text| (text, "inline")),
+        )
+        .await
+}
+
+/// Record the payment once the sender has posted the offer.
+pub async fn record_chosen_result(
+    state: &AppState,
+    bot: &BotConfig,
+    chosen: ChosenInlineResult,
+) -> Result<()> {
+    let offer = match parse_offer(state, bot, &chosen.from, &chosen.query).await {
+        Ok(offer) => offer,
+        Err(e) => {
+            warn!(
+                telegram_user_id = chosen.from.id,
+                "ignoring inline result {}: {:?}", chosen.result_id, e
+            );
+            return Ok(());
+        }
+    };
+    info!(
+        telegram_user_id = chosen.from.id,
+        amount = offer.amount,
+        "inline payment offered"
+    );
+    db::create_inline_payment(
+        &state.db,
+        &chosen.result_id,
+        &offer.wallet,
+        chosen.from.id,
+        &chosen.from.display_name(),
+        &bot.mint,
+        offer.amount,
+        chosen.inline_message_id.as_deref(),
+        INLINE_PAYMENT_TTL_SECS,
+    )
+    .await
+}
+
+/// Pay out the payment behind a claim button to whoever tapped it, and tell
+/// them how it went.
+pub async fn handle_callback(
+    state: &AppState,
+    update_id: i64,
+    callback: CallbackQuery,
+) -> Result<()> {
+    let Some(token) = callback
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(CLAIM_PREFIX))
+    else {
+        return Ok(());
+    };
+    info!(
+        update_id,
+        telegram_user_id = callback.from.id,
+        "inline payment claim"
+    );
+
+    let (text, show_alert) = match claim(state, &callback.from, token).await {
+        Ok(text) => (text, false),
+        Err(e) => (error_reply(update_id, e), true),
+    };
+    state
+        .telegram
+        .answer_callback_query(&callback.id, &text, show_alert)
+        .await
+}
+
+async fn claim(state: &AppState, from: &User, token: &str) -> Result<String, AppError> {
+    // the claim can arrive before Telegram reports the posted result
+    let payment = db::get_inline_payment_by_token(&state.db, token)
+        .await?
+        .ok_or_else(|| {
+            AppError::not_found(anyhow::anyhow!(
+                "This payment isn't ready yet, try again in a moment."
+            ))
+        })?;
+    match payment.status {
+        InlinePaymentStatus::Open => {}
+        InlinePaymentStatus::Claiming => {
+            return Err(AppError::conflict(anyhow::anyhow!(
+                "Someone is already claiming this payment."
+            )));
+        }
+        InlinePaymentStatus::Claimed => {
+            return Err(AppError::conflict(anyhow::anyhow!(
+                "This payment has already been claimed."
+            )));
+        }
+    }
+    if payment.expires_at <= Utc::now() {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "This payment has expired."
+        )));
+    }
+    if from.id == payment.sender_telegram_user_id {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "You can't claim your own payment."
+        )));
+    }
+    // the payment goes to the wallet of the claimer's username
+    let Some(username) = &from.username else {
+        return Err(AppError::bad_request(anyhow::anyhow!(
+            "Set a Telegram username to claim payments."
+        )));
+    };
+
+    validate_confidential_mint(state, &payment.mint).await?;
+    let decimals = get_mint_decimals(state, &payment.mint).await?;
+    let sender_wallet = db::get_wallet_by_id(&state.db, payment.sender_wallet_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Wallet {} not found", payment.sender_wallet_id))?;
+
+    if !db::reserve_inline_payment(&state.db, payment.id, from.id).await? {
+        return Err(AppError::conflict(anyhow::anyhow!(
+            "Someone is already claiming this payment."
+        )));
+    }
+
+    let ui_amount = format_ui_amount(payment.amount, decimals);
+    let symbol = token_label(state, &payment.mint);
+    edit_message(
+        state,
+        &payment,
+        &format!(
+            "{} sent {} {}, claiming it for @{}…",
+            payment.sender_name, ui_amount, symbol, username
+        ),
+        None,
+    )
+    .await;
+
+    match pay_out(state, &payment, &sender_wallet, username, decimals).await {
+        Ok(was_new_wallet) => {
+            edit_message(
+                state,
+                &payment,
+                &format!(
+                    "{} sent {} {} to @{}.",
+                    payment.sender_name, ui_amount, symbol, username
+                ),
+                None,
+            )
+            .await;
+            let mut text = format!("You received {} {}.", ui_amount, symbol);
+            if was_new_wallet {
+                text.push_str(" Open the app to use it.");
+            }
+            Ok(text)
+        }
+        Err(e) => {
+            // put the offer back up, unless its transfer is still unresolved
+            let reopened = db::get_inline_payment_by_token(&state.db, token)
+                .await
+                .ok()
+                .flatten()
+                .is_some_and(|payment| payment.status == InlinePaymentStatus::Open);
+            if reopened {
+                edit_message(
+                    state,
+                    &payment,
+                    &offer_text(&payment.sender_name, &ui_amount, &symbol),
+                    Some(claim_keyboard(token, &ui_amount, &symbol)),
+                )
+                .await;
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Transfer the payment to `username`. Returns whether their wallet was
+/// created for it.
+async fn pay_out(
+    state: &AppState,
+    payment: &InlinePayment,
+    sender_wallet: &Wallet,
+    username: &str,
+    decimals: u8,
+) -> Result<bool, AppError> {
+    let (transfer_id, job_id, recipient) = match start_send_to_username(
+        state,
+        sender_wallet,
+        username,
+        payment.amount,
+        payment.mint,
+        decimals,
+        None,
+    )
+    .await
+    {
+        Ok(started) => started,
+        Err(e) => {
+            if let Err(release_error) = db::release_inline_payment(&state.db, payment.id).await {
+                error!(
+                    "failed to release inline payment {}: {:?}",
+                    payment.id, release_error
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    // the payment is marked claimed once the transfer lands, or opened up
+    // again if it fails (see `db::sync_transfer_with_job`)
+    db::set_inline_payment_transfer(&state.db, payment.id, transfer_id).await?;
+    finish_transfer(state, transfer_id, job_id).await?;
+    notify_incoming_payment(
+        state,
+        sender_wallet,
+        &recipient.wallet,
+        transfer_id,
+        payment.amount,
+        &payment.mint,
+        decimals,
+    )
+    .await;
+
+    Ok(recipient.was_new_wallet)
+}
+
+/// Best effort, the payment itself doesn't depend on the message.
+async fn edit_message(
+    state: &AppState,
+    payment: &InlinePayment,
+    text: &str,
+    reply_markup: Option<serde_json::Value>,
+) {
+    let Some(inline_message_id) = &payment.inline_message_id else {
+        return;
+    };
+    if let Err(e) = state
+        .telegram
+        .edit_inline_message_text(inline_message_id, text, reply_markup)
+        .await
+    {
+        error!(
+            "failed to update inline payment {} message: {:?}",
+            payment.id, e
+        );
+    }
+}
+
+fn offer_text(sender: &str, amount: &str, symbol: &str) -> String {
+    format!(
+        "{} is sending {} {}. The first to tap Claim gets it.",
+        sender, amount, symbol
+    )
+}
+
+fn claim_keyboard(token: &str, amount: &str, symbol: &str) -> serde_json::Value {
+    callback_keyboard(
+        &format!("Claim {} {}", amount, symbol),
+        &format!("{}{}", CLAIM_PREFIX, token),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offer_message() {
+        assert_eq!(
+            offer_text("@alice", "5", "tgUSD"),
+            "@alice is sending 5 tgUSD. The first to tap Claim gets it."
+        );
+
+        // callback data is limited to 64 bytes
+        let token = hex::encode([0xab; 16]);
+        let keyboard = claim_keyboard(&token, "5", "tgUSD");
+        let button = &keyboard["inline_keyboard"][0][0];
+        assert_eq!(button["text"], "Claim 5 tgUSD");
+        let data = button["callback_data"].as_str().unwrap();
+        assert!(data.len() <= 64);
+        assert_eq!(data.strip_prefix(CLAIM_PREFIX), Some(token.as_str()));
+    }
+}
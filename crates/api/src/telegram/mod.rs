@@ -0,0 +1,6 @@
+//! The Telegram bot: a Bot API client, the chat commands it answers and the
+//! payments it offers through inline mode.
+
+pub mod api;
+pub mod bot;
+pub mod inline;
@@ -2,20 +2,27 @@
 
 import {
     createContext,
+    useCallback,
     useContext,
     useEffect,
     useRef,
     useState,
     type ReactNode,
 } from "react";
+import { refreshSession, type RefreshSessionResponse } from "./telegram";
 
 export const DEV_MODE = process.env.NEXT_PUBLIC_DEV_MODE === "true";
 
 const TOKEN_STORAGE_KEY = "tg_auth_token";
 const USER_STORAGE_KEY = "tg_auth_user";
 const EXPIRES_STORAGE_KEY = "tg_auth_expires";
+const REFRESH_TOKEN_STORAGE_KEY = "tg_auth_refresh_token";
+const REFRESH_EXPIRES_STORAGE_KEY = "tg_auth_refresh_expires";
 const HAS_RESERVED_WALLET_KEY = "tg_has_reserved_wallet";
 
+// Access tokens only last 15 minutes, refresh them a bit before they expire
+const TOKEN_REFRESH_BUFFER_MS = 60 * 1000;
+
 export type TelegramUser = {
     telegramUserId: number;
     username?: string;
@@ -34,6 +41,10 @@ interface AuthContextType {
     hasReservedWallet: boolean;
     clearReservedWalletFlag: () => void;
     logout: () => void;
+    // An access token that isn't about to expire, refreshed if needed
+    getToken: () => Promise<string | null>;
+    // Swap the refresh token for a new access token, e.g. after a 401
+    refreshAuth: () => Promise<string | null>;
 }
 
 const AuthContext = createContext<AuthContextType | undefined>(undefined);
@@ -47,6 +58,22 @@ const DEV_MOCK_USER: TelegramUser = {
 };
 const DEV_MOCK_TOKEN = "dev_mock_token_for_local_testing";
 
+function storeSession(session: RefreshSessionResponse) {
+    localStorage.setItem(TOKEN_STORAGE_KEY, session.token);
+    localStorage.setItem(EXPIRES_STORAGE_KEY, session.expiresAt);
+    localStorage.setItem(REFRESH_TOKEN_STORAGE_KEY, session.refreshToken);
+    localStorage.setItem(REFRESH_EXPIRES_STORAGE_KEY, session.refreshExpiresAt);
+}
+
+function clearStoredAuth() {
+    localStorage.removeItem(TOKEN_STORAGE_KEY);
+    localStorage.removeItem(USER_STORAGE_KEY);
+    localStorage.removeItem(EXPIRES_STORAGE_KEY);
+    localStorage.removeItem(REFRESH_TOKEN_STORAGE_KEY);
+    localStorage.removeItem(REFRESH_EXPIRES_STORAGE_KEY);
+    localStorage.removeItem(HAS_RESERVED_WALLET_KEY);
+}
+
 export function SimpleAuthProvider({ children }: { children: ReactNode }) {
     const [status, setStatus] = useState<AuthStatus>("loading");
     const [token, setToken] = useState<string | null>(null);
@@ -54,6 +81,7 @@ export function SimpleAuthProvider({ children }: { children: ReactNode }) {
     const [error, setError] = useState<string | null>(null);
     const [hasReservedWallet, setHasReservedWallet] = useState(false);
     const didInit = useRef(false);
+    const refreshRef = useRef<Promise<string | null> | null>(null);
 
     useEffect(() => {
         if (didInit.current) {
@@ -63,10 +91,7 @@ export function SimpleAuthProvider({ children }: { children: ReactNode }) {
 
         const doInit = async () => {
             // Clear any stale auth data on fresh load to ensure we always check for reserved wallets
-            localStorage.removeItem(TOKEN_STORAGE_KEY);
-            localStorage.removeItem(USER_STORAGE_KEY);
-            localStorage.removeItem(EXPIRES_STORAGE_KEY);
-            localStorage.removeItem(HAS_RESERVED_WALLET_KEY);
+            clearStoredAuth();
 
             // DEV MODE - still call backend to check for reserved wallet
             if (DEV_MODE) {
@@ -145,19 +170,25 @@ export function SimpleAuthProvider({ children }: { children: ReactNode }) {
 
                     if (res.ok) {
                         const authData = await res.json();
-                        // Response is wrapped: { data: { token, user, expiresAt, hasReservedWallet } }
+                        // Response is wrapped: { data: { token, user, expiresAt, refreshToken, refreshExpiresAt, hasReservedWallet } }
                         const {
                             token: newToken,
                             user: newUser,
                             expiresAt,
+                            refreshToken,
+                            refreshExpiresAt,
                             hasReservedWallet: reservedWallet,
                         } = authData.data;
-                        localStorage.setItem(TOKEN_STORAGE_KEY, newToken);
+                        storeSession({
+                            token: newToken,
+                            expiresAt,
+                            refreshToken,
+                            refreshExpiresAt,
+                        });
                         localStorage.setItem(
                             USER_STORAGE_KEY,
                             JSON.stringify(newUser),
                         );
-                        localStorage.setItem(EXPIRES_STORAGE_KEY, expiresAt);
                         if (reservedWallet) {
                             localStorage.setItem(
                                 HAS_RESERVED_WALLET_KEY,
@@ -196,16 +227,68 @@ export function SimpleAuthProvider({ children }: { children: ReactNode }) {
         setHasReservedWallet(false);
     };
 
-    const logout = () => {
-        localStorage.removeItem(TOKEN_STORAGE_KEY);
-        localStorage.removeItem(USER_STORAGE_KEY);
-        localStorage.removeItem(EXPIRES_STORAGE_KEY);
-        localStorage.removeItem(HAS_RESERVED_WALLET_KEY);
+    const logout = useCallback(() => {
+        clearStoredAuth();
         setToken(null);
         setUser(null);
         setHasReservedWallet(false);
         setStatus("unauthenticated");
-    };
+    }, []);
+
+    const refreshAuth = useCallback((): Promise<string | null> => {
+        // A refresh token works once, and using it twice revokes the session,
+        // so concurrent callers share a single refresh
+        if (refreshRef.current) {
+            return refreshRef.current;
+        }
+
+        const doRefresh = async () => {
+            const refreshToken = localStorage.getItem(
+                REFRESH_TOKEN_STORAGE_KEY,
+            );
+            if (!refreshToken) {
+                return null;
+            }
+
+            try {
+                const session = await refreshSession(refreshToken);
+                if (!session) {
+                    console.error("[SIMPLE_AUTH] Session expired");
+                    logout();
+                    return null;
+                }
+                storeSession(session);
+                setToken(session.token);
+                return session.token;
+            } catch (err) {
+                console.error("[SIMPLE_AUTH] Refresh failed:", err);
+                return null;
+            }
+        };
+
+        refreshRef.current = doRefresh().finally(() => {
+            refreshRef.current = null;
+        });
+        return refreshRef.current;
+    }, [logout]);
+
+    const getToken = useCallback(async (): Promise<string | null> => {
+        // Stored tokens are newer than state right after a refresh
+        const currentToken = localStorage.getItem(TOKEN_STORAGE_KEY) ?? token;
+        const expiresAt = localStorage.getItem(EXPIRES_STORAGE_KEY);
+        const expiringSoon =
+            expiresAt !== null &&
+            new Date(expiresAt).getTime() - Date.now() <
+                TOKEN_REFRESH_BUFFER_MS;
+
+        if (
+            expiringSoon &&
+            localStorage.getItem(REFRESH_TOKEN_STORAGE_KEY) !== null
+        ) {
+            return (await refreshAuth()) ?? currentToken;
+        }
+        return currentToken;
+    }, [token, refreshAuth]);
 
     return (
         <AuthContext.Provider
@@ -217,6 +300,8 @@ export function SimpleAuthProvider({ children }: { children: ReactNode }) {
                 hasReservedWallet,
                 clearReservedWalletFlag,
                 logout,
+                getToken,
+                refreshAuth,
             }}
         >
             {children}
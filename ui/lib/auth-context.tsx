@@ -12,6 +12,7 @@ import {
 import {
     initTelegramWebApp,
     isTelegramEnvironment,
+    refreshSession,
     telegramAuth,
     type TelegramUser,
     type TelegramWebAppAuthResponse,
@@ -52,6 +53,8 @@ const AuthContext = createContext<AuthContextType | undefined>(undefined);
 const TOKEN_STORAGE_KEY = "tg_auth_token";
 const USER_STORAGE_KEY = "tg_auth_user";
 const EXPIRES_STORAGE_KEY = "tg_auth_expires";
+const REFRESH_TOKEN_STORAGE_KEY = "tg_auth_refresh_token";
+const REFRESH_EXPIRES_STORAGE_KEY = "tg_auth_refresh_expires";
 
 function getStoredAuth(): {
     token: string;
@@ -90,12 +93,52 @@ function storeAuth(auth: TelegramWebAppAuthResponse) {
     localStorage.setItem(TOKEN_STORAGE_KEY, auth.token);
     localStorage.setItem(USER_STORAGE_KEY, JSON.stringify(auth.user));
     localStorage.setItem(EXPIRES_STORAGE_KEY, auth.expiresAt);
+    localStorage.setItem(REFRESH_TOKEN_STORAGE_KEY, auth.refreshToken);
+    localStorage.setItem(REFRESH_EXPIRES_STORAGE_KEY, auth.refreshExpiresAt);
 }
 
 function clearStoredAuth() {
     localStorage.removeItem(TOKEN_STORAGE_KEY);
     localStorage.removeItem(USER_STORAGE_KEY);
     localStorage.removeItem(EXPIRES_STORAGE_KEY);
+    localStorage.removeItem(REFRESH_TOKEN_STORAGE_KEY);
+    localStorage.removeItem(REFRESH_EXPIRES_STORAGE_KEY);
+}
+
+let refreshInFlight: Promise<string | null> | null = null;
+
+// Swap the stored refresh token for new tokens, null if there is none or the
+// session is over. A refresh token works once, so concurrent callers share a
+// single refresh.
+function refreshStoredAuth(): Promise<string | null> {
+    if (refreshInFlight) {
+        return refreshInFlight;
+    }
+
+    const doRefresh = async () => {
+        const refreshToken = localStorage.getItem(REFRESH_TOKEN_STORAGE_KEY);
+        if (!refreshToken) {
+            return null;
+        }
+
+        const session = await refreshSession(refreshToken).catch(() => null);
+        if (!session) {
+            return null;
+        }
+        localStorage.setItem(TOKEN_STORAGE_KEY, session.token);
+        localStorage.setItem(EXPIRES_STORAGE_KEY, session.expiresAt);
+        localStorage.setItem(REFRESH_TOKEN_STORAGE_KEY, session.refreshToken);
+        localStorage.setItem(
+            REFRESH_EXPIRES_STORAGE_KEY,
+            session.refreshExpiresAt,
+        );
+        return session.token;
+    };
+
+    refreshInFlight = doRefresh().finally(() => {
+        refreshInFlight = null;
+    });
+    return refreshInFlight;
 }
 
 const TOKEN_EXPIRY_BUFFER_MS = 5 * 60 * 1000; // Refresh if expiring within 5 minutes
@@ -126,6 +169,8 @@ export function AuthProvider({ children }: { children: ReactNode }) {
                         token: DEV_MOCK_TOKEN,
                         user: DEV_MOCK_USER,
                         expiresAt: DEV_MOCK_EXPIRES_AT,
+                        refreshToken: "",
+                        refreshExpiresAt: DEV_MOCK_EXPIRES_AT,
                     };
                     storeAuth(authResponse);
                     setToken(authResponse.token);
@@ -186,19 +231,31 @@ export function AuthProvider({ children }: { children: ReactNode }) {
         return expiringSoon;
     }, [expiresAt]);
 
+    // Use the refresh token, and only log in with initData again once the
+    // session is over
+    const renewToken = useCallback(async (): Promise<string | null> => {
+        const refreshed = await refreshStoredAuth();
+        if (refreshed) {
+            setToken(refreshed);
+            setExpiresAt(localStorage.getItem(EXPIRES_STORAGE_KEY));
+            return refreshed;
+        }
+        if (!isTelegram) {
+            return null;
+        }
+        const authResponse = await authenticate();
+        return authResponse.token;
+    }, [isTelegram, authenticate]);
+
     const authFetch = useCallback(
         async <T,>(path: string, init?: RequestInit): Promise<T> => {
             // Always try to get token from localStorage as fallback (state might be stale)
             let currentToken = token ?? localStorage.getItem(TOKEN_STORAGE_KEY);
             const expiringSoon = isTokenExpiringSoon();
 
-            if (expiringSoon && isTelegram && !isAuthenticatingRef.current) {
+            if (expiringSoon && !isAuthenticatingRef.current) {
                 try {
-                    const refreshed = await authenticate();
-                    currentToken =
-                        refreshed?.token ??
-                        localStorage.getItem(TOKEN_STORAGE_KEY) ??
-                        null;
+                    currentToken = (await renewToken()) ?? currentToken;
                 } catch (err) {
                     // currentToken already set above, no need to reassign
                 }
@@ -218,17 +275,13 @@ export function AuthProvider({ children }: { children: ReactNode }) {
             });
 
             // If 401, try to refresh and retry once (but not if already authenticating)
-            if (
-                res.status === 401 &&
-                isTelegram &&
-                !isAuthenticatingRef.current
-            ) {
+            if (res.status === 401 && !isAuthenticatingRef.current) {
+                let newToken: string | null;
                 try {
-                    await authenticate();
+                    newToken = await renewToken();
                 } catch (err) {
                     throw new Error("Authentication failed");
                 }
-                const newToken = localStorage.getItem(TOKEN_STORAGE_KEY);
                 if (!newToken) {
                     throw new Error("Failed to refresh authentication");
                 }
@@ -257,7 +310,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
 
             return (await res.json()) as T;
         },
-        [token, isTelegram, isTokenExpiringSoon, authenticate],
+        [token, isTokenExpiringSoon, renewToken],
     );
 
     useEffect(() => {
@@ -279,6 +332,8 @@ export function AuthProvider({ children }: { children: ReactNode }) {
                         token: DEV_MOCK_TOKEN,
                         user: DEV_MOCK_USER,
                         expiresAt: DEV_MOCK_EXPIRES_AT,
+                        refreshToken: "",
+                        refreshExpiresAt: DEV_MOCK_EXPIRES_AT,
                     });
                     setToken(DEV_MOCK_TOKEN);
                     setUser(DEV_MOCK_USER);
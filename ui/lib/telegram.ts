@@ -42,6 +42,16 @@ export type TelegramWebAppAuthResponse = {
     token: string;
     user: TelegramUser;
     expiresAt: string;
+    refreshToken: string;
+    refreshExpiresAt: string;
+};
+
+export type RefreshSessionResponse = {
+    token: string;
+    expiresAt: string;
+    // The refresh token sent stops working, keep this one instead
+    refreshToken: string;
+    refreshExpiresAt: string;
 };
 
 export function getTelegramWebApp() {
@@ -122,6 +132,33 @@ export async function telegramAuth(): Promise<TelegramWebAppAuthResponse> {
     return authResponse;
 }
 
+// Exchange a refresh token for a new access token and refresh token.
+// Resolves to null when the session has expired or was revoked, the user has
+// to log in again then.
+export async function refreshSession(
+    refreshToken: string,
+): Promise<RefreshSessionResponse | null> {
+    const res = await fetch("/api/auth/refresh", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ refreshToken }),
+    });
+
+    if (res.status === 401) {
+        return null;
+    }
+    if (!res.ok) {
+        const text = await res.text().catch(() => "");
+        throw new Error(`Refresh failed (${res.status}): ${text}`);
+    }
+
+    const refreshResponse = (await res.json()) as {
+        data: RefreshSessionResponse;
+    };
+
+    return refreshResponse.data;
+}
+
 export async function apiFetch<TResponse>(
     path: string,
     token: string,
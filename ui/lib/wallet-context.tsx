@@ -168,8 +168,11 @@ export type MintResponse = {
     signature: string;
 };
 
+// A transfer the server is still sending is answered with a 202 and no
+// transactions yet
 export type TransferResponse = {
-    transactions: TransactionResult[];
+    transactions?: TransactionResult[];
+    status?: "pending";
 };
 
 export function WalletProvider({ children }: { children: ReactNode }) {
@@ -187,29 +190,45 @@ export function WalletProvider({ children }: { children: ReactNode }) {
     const [conversion, setConversionData] =
         useState<ConversionData>(initialConversion);
 
-    const { token, status, hasReservedWallet, clearReservedWalletFlag } =
-        useSimpleAuth();
+    const {
+        status,
+        hasReservedWallet,
+        clearReservedWalletFlag,
+        getToken,
+        refreshAuth,
+    } = useSimpleAuth();
 
-    // Simple authenticated fetch - always check localStorage as fallback
+    // Authenticated fetch, refreshing the short-lived access token before it
+    // expires and once more if it is rejected anyway
     const authFetch = async <T,>(
         path: string,
         init?: RequestInit
     ): Promise<T> => {
-        // Get token from context or localStorage (context might be stale)
-        const currentToken = token ?? localStorage.getItem("tg_auth_token");
+        const currentToken = await getToken();
 
         if (!currentToken) {
             throw new Error("Not authenticated");
         }
 
-        const res = await fetch(path, {
-            ...init,
-            headers: {
-                ...init?.headers,
-                "Content-Type": "application/json",
-                Authorization: `Bearer ${currentToken}`,
-            },
-        });
+        const send = (accessToken: string) =>
+            fetch(path, {
+                ...init,
+                headers: {
+                    ...init?.headers,
+                    "Content-Type": "application/json",
+                    Authorization: `Bearer ${accessToken}`,
+                },
+            });
+
+        let res = await send(currentToken);
+
+        // Rejected before the handler ran, so retrying can't repeat anything
+        if (res.status === 401) {
+            const refreshedToken = await refreshAuth();
+            if (refreshedToken) {
+                res = await send(refreshedToken);
+            }
+        }
 
         if (!res.ok) {
             const text = await res.text().catch(() => "");
@@ -412,7 +431,7 @@ export function WalletProvider({ children }: { children: ReactNode }) {
 
         console.log("Transfer", response.data);
 
-        return response.data.transactions;
+        return response.data.transactions ?? [];
     };
 
     const transferByTelegram = async (username: string, amount: string) => {
@@ -432,7 +451,7 @@ export function WalletProvider({ children }: { children: ReactNode }) {
 
         console.log("Telegram transfer", response.data);
 
-        return response.data.transactions;
+        return response.data.transactions ?? [];
     };
 
     const setTransaction = (data: Partial<TransactionData>) => {